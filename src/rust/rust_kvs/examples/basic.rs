@@ -76,18 +76,7 @@ fn main() -> Result<(), ErrorCode> {
 
             for key in kvs.get_all_keys()? {
                 let value = kvs.get_value(&key)?;
-                let value_type = match value {
-                    KvsValue::I32(_) => "I32",
-                    KvsValue::U32(_) => "U32",
-                    KvsValue::I64(_) => "I64",
-                    KvsValue::U64(_) => "U64",
-                    KvsValue::F64(_) => "F64",
-                    KvsValue::Boolean(_) => "Boolean",
-                    KvsValue::String(_) => "String",
-                    KvsValue::Null => "Null",
-                    KvsValue::Array(_) => "Array",
-                    KvsValue::Object(_) => "Object",
-                };
+                let value_type = value.type_name();
                 println!("{key:?} = {value:?} ({value_type:?})");
             }
 
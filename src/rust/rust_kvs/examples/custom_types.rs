@@ -95,7 +95,7 @@ impl KvsDeserialize for IpAddrWrapper {
 
 /// Main example struct.
 /// - Types defined by `KvsValue`.
-/// - `u8` - additional type not defined by `KvsValue`.
+/// - `u8` - small integer type, stored without widening.
 /// - `nested` - nested serializable object.
 /// - `ip` - external type serialized to `KvsValue`.
 #[derive(Debug)]
@@ -60,12 +60,14 @@ fn main() -> Result<(), ErrorCode> {
         let counter_key = "counter";
         for index in 0..max_count {
             kvs.set_value(counter_key, index)?;
-            kvs.flush()?;
+            let snapshot_id = kvs.flush_with_id()?;
+            println!("{counter_key} = {index} written to snapshot {snapshot_id}");
         }
 
         // Print current counter value, then restore oldest snapshot.
+        // Each flush rotates previously written snapshots by one.
         println!("{counter_key} = {:?}", kvs.get_value(counter_key)?);
-        kvs.snapshot_restore(SnapshotId(2))?;
+        kvs.snapshot_restore(SnapshotId(max_count as usize - 1))?;
         println!("{counter_key} = {:?}", kvs.get_value(counter_key)?);
 
         println!();
@@ -0,0 +1,604 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+use crate::error_code::ErrorCode;
+use crate::kvs_api::{InstanceId, SnapshotId};
+use crate::kvs_backend::{BackendCapabilities, KvsBackend};
+use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use tinyjson::JsonValue;
+
+/// Builder for `AppendLogBackend`.
+pub struct AppendLogBackendBuilder {
+    inner: Box<dyn KvsBackend>,
+    compact_threshold: usize,
+}
+
+impl AppendLogBackendBuilder {
+    /// Create `AppendLogBackendBuilder`.
+    ///
+    /// Default values:
+    /// - `compact_threshold` - 1000 records.
+    ///
+    /// # Parameters
+    ///   * `inner`: Backend storing base snapshots, log is stored in its working directory
+    pub fn new(inner: Box<dyn KvsBackend>) -> Self {
+        Self {
+            inner,
+            compact_threshold: 1000,
+        }
+    }
+
+    /// Set number of log records after which log is compacted into a base snapshot.
+    ///
+    /// # Parameters
+    ///   * `compact_threshold`: Max number of records in log
+    pub fn compact_threshold(mut self, compact_threshold: usize) -> Self {
+        self.compact_threshold = compact_threshold;
+        self
+    }
+
+    /// Finalize the builder and create append log backend.
+    pub fn build(self) -> AppendLogBackend {
+        AppendLogBackend {
+            inner: self.inner,
+            compact_threshold: self.compact_threshold,
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Change record stored in the log.
+#[derive(Clone, Debug, PartialEq)]
+enum LogRecord {
+    /// Key set to value.
+    Set(String, KvsValue),
+
+    /// Key removed.
+    Remove(String),
+}
+
+/// Current state of an instance, as known to the backend.
+struct LogState {
+    kvs_map: KvsMap,
+    record_count: usize,
+}
+
+/// KVS backend appending per-key changes to a log instead of rewriting whole content.
+///
+/// Each flush appends `set` and `remove` records of keys changed since the previous flush to
+/// `kvs_<instance_id>.log`, one record per line, prefixed with Adler32 hash of the record.
+/// Current KVS is the base snapshot of the inner backend with the log replayed on top of it.
+///
+/// Once the log exceeds the compact threshold, current state is flushed to the inner backend as
+/// new base snapshot and the log is truncated. Snapshots are therefore created on compaction only.
+/// Records are idempotent, replaying a log not yet truncated over the new base yields same state.
+/// Key expiry is not persisted.
+pub struct AppendLogBackend {
+    inner: Box<dyn KvsBackend>,
+    compact_threshold: usize,
+    state: Mutex<HashMap<InstanceId, LogState>>,
+}
+
+impl PartialEq for AppendLogBackend {
+    fn eq(&self, other: &Self) -> bool {
+        self.compact_threshold == other.compact_threshold && self.inner.dyn_eq(other.inner.as_any())
+    }
+}
+
+impl AppendLogBackend {
+    /// Path of the log file of the instance.
+    fn log_file_path(&self, instance_id: InstanceId) -> PathBuf {
+        let file_name = format!("kvs_{instance_id}.log");
+        match self.inner.working_dir() {
+            Some(working_dir) => working_dir.join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    }
+
+    /// Encode record as a single log line, including hash and trailing newline.
+    fn encode_record(record: &LogRecord) -> Result<String, ErrorCode> {
+        let mut obj = HashMap::new();
+        match record {
+            LogRecord::Set(key, value) => {
+                obj.insert("op".to_string(), JsonValue::String("set".to_string()));
+                obj.insert("key".to_string(), JsonValue::String(key.clone()));
+                obj.insert("value".to_string(), JsonValue::from(value.clone()));
+            },
+            LogRecord::Remove(key) => {
+                obj.insert("op".to_string(), JsonValue::String("remove".to_string()));
+                obj.insert("key".to_string(), JsonValue::String(key.clone()));
+            },
+        }
+
+        let json_str = JsonValue::Object(obj).stringify()?;
+        let hash = adler32::RollingAdler32::from_buffer(json_str.as_bytes()).hash();
+        Ok(format!("{hash:08x} {json_str}\n"))
+    }
+
+    /// Decode single log line, without trailing newline.
+    fn decode_record(line: &str) -> Result<LogRecord, ErrorCode> {
+        let (hash_str, json_str) = line.split_once(' ').ok_or_else(|| {
+            eprintln!("error: log record without hash");
+            ErrorCode::InvalidStorageFormat
+        })?;
+        let hash = adler32::RollingAdler32::from_buffer(json_str.as_bytes()).hash();
+        if u32::from_str_radix(hash_str, 16) != Ok(hash) {
+            eprintln!("error: log record hash mismatch");
+            return Err(ErrorCode::ValidationFailed);
+        }
+
+        let mut obj = match json_str.parse::<JsonValue>()? {
+            JsonValue::Object(obj) => obj,
+            _ => {
+                eprintln!("error: log record is not an object");
+                return Err(ErrorCode::InvalidStorageFormat);
+            },
+        };
+        let key = match obj.remove("key") {
+            Some(JsonValue::String(key)) => key,
+            _ => {
+                eprintln!("error: log record without key");
+                return Err(ErrorCode::InvalidStorageFormat);
+            },
+        };
+        match (obj.remove("op"), obj.remove("value")) {
+            (Some(JsonValue::String(op)), Some(value)) if op == "set" => Ok(LogRecord::Set(key, KvsValue::from(value))),
+            (Some(JsonValue::String(op)), None) if op == "remove" => Ok(LogRecord::Remove(key)),
+            _ => {
+                eprintln!("error: invalid log record for key {key:?}");
+                Err(ErrorCode::InvalidStorageFormat)
+            },
+        }
+    }
+
+    /// Read records from log file, together with length of complete records in bytes.
+    /// Incomplete last line, e.g. left by interrupted append, is skipped.
+    fn read_log(log_path: &Path) -> Result<(Vec<LogRecord>, usize), ErrorCode> {
+        let content = match fs::read_to_string(log_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+            Err(e) => return Err(e.into()),
+        };
+
+        let complete_len = content.rfind('\n').map_or(0, |idx| idx + 1);
+        if complete_len < content.len() {
+            eprintln!("warn: incomplete log record skipped");
+        }
+        let records = content[..complete_len]
+            .lines()
+            .map(Self::decode_record)
+            .collect::<Result<_, _>>()?;
+        Ok((records, complete_len))
+    }
+
+    /// Apply records to map in order.
+    fn replay(kvs_map: &mut KvsMap, records: Vec<LogRecord>) {
+        for record in records {
+            match record {
+                LogRecord::Set(key, value) => {
+                    kvs_map.insert(key, value);
+                },
+                LogRecord::Remove(key) => {
+                    kvs_map.remove(&key);
+                },
+            }
+        }
+    }
+
+    /// Records changing `old` into `new`, ordered by key.
+    fn diff(old: &KvsMap, new: &KvsMap) -> Vec<LogRecord> {
+        let mut records: Vec<LogRecord> = new
+            .iter()
+            .filter(|(key, value)| old.get(*key) != Some(*value))
+            .map(|(key, value)| LogRecord::Set(key.clone(), value.clone()))
+            .chain(
+                old.keys()
+                    .filter(|key| !new.contains_key(*key))
+                    .map(|key| LogRecord::Remove(key.clone())),
+            )
+            .collect();
+        records.sort_by(|left, right| Self::record_key(left).cmp(Self::record_key(right)));
+        records
+    }
+
+    fn record_key(record: &LogRecord) -> &str {
+        match record {
+            LogRecord::Set(key, _) | LogRecord::Remove(key) => key,
+        }
+    }
+
+    /// Load base snapshot and replay log.
+    fn load_current(&self, instance_id: InstanceId) -> Result<LogState, ErrorCode> {
+        let mut kvs_map = match self.inner.load_kvs(instance_id, SnapshotId(0)) {
+            Ok(kvs_map) => kvs_map,
+            Err(ErrorCode::FileNotFound) => KvsMap::new(),
+            Err(e) => return Err(e),
+        };
+        let log_path = self.log_file_path(instance_id);
+        let (records, complete_len) = Self::read_log(&log_path)?;
+
+        // Drop incomplete record, subsequent records are appended after last complete one.
+        if complete_len < fs::metadata(&log_path).map_or(0, |m| m.len() as usize) {
+            OpenOptions::new()
+                .write(true)
+                .open(&log_path)?
+                .set_len(complete_len as u64)?;
+        }
+
+        let record_count = records.len();
+        Self::replay(&mut kvs_map, records);
+        Ok(LogState { kvs_map, record_count })
+    }
+
+    /// Append records to log file.
+    fn append(&self, instance_id: InstanceId, records: &[LogRecord]) -> Result<(), ErrorCode> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut content = String::new();
+        for record in records {
+            content.push_str(&Self::encode_record(record)?);
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_file_path(instance_id))?;
+        file.write_all(content.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Flush state to inner backend as new base snapshot and truncate log.
+    fn compact_state(&self, instance_id: InstanceId, state: &mut LogState) -> Result<(), ErrorCode> {
+        self.inner.flush(instance_id, &state.kvs_map)?;
+        fs::write(self.log_file_path(instance_id), b"")?;
+        state.record_count = 0;
+        Ok(())
+    }
+
+    /// Compact log of the instance into a base snapshot
+    ///
+    /// Performed automatically on flush once log exceeds the compact threshold.
+    ///
+    /// # Parameters
+    ///   * `instance_id`: Instance ID
+    ///
+    /// # Return Values
+    ///   * Ok: Log compacted
+    ///   * `ErrorCode::ValidationFailed`: Log record hash mismatch
+    ///   * `ErrorCode::InvalidStorageFormat`: Invalid log record
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    pub fn compact(&self, instance_id: InstanceId) -> Result<(), ErrorCode> {
+        let mut states = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut state = match states.remove(&instance_id) {
+            Some(state) => state,
+            None => self.load_current(instance_id)?,
+        };
+        self.compact_state(instance_id, &mut state)?;
+        states.insert(instance_id, state);
+        Ok(())
+    }
+
+    /// Number of records currently stored in log of the instance.
+    pub fn log_record_count(&self, instance_id: InstanceId) -> Result<usize, ErrorCode> {
+        Ok(Self::read_log(&self.log_file_path(instance_id))?.0.len())
+    }
+}
+
+impl KvsBackend for AppendLogBackend {
+    fn name(&self) -> &str {
+        "appendlog"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn load_kvs(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        if snapshot_id != SnapshotId(0) {
+            return self.inner.load_kvs(instance_id, snapshot_id);
+        }
+
+        let state = self.load_current(instance_id)?;
+        let kvs_map = state.kvs_map.clone();
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(instance_id, state);
+        Ok(kvs_map)
+    }
+
+    fn load_defaults(&self, instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
+        self.inner.load_defaults(instance_id)
+    }
+
+    fn flush(&self, instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+        let mut states = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut state = match states.remove(&instance_id) {
+            Some(state) => state,
+            None => self.load_current(instance_id)?,
+        };
+
+        // Records are appended before compaction, log stays consistent with new base.
+        let records = Self::diff(&state.kvs_map, kvs_map);
+        self.append(instance_id, &records)?;
+        state.kvs_map = kvs_map.clone();
+        state.record_count += records.len();
+
+        if state.record_count > self.compact_threshold {
+            self.compact_state(instance_id, &mut state)?;
+        }
+        states.insert(instance_id, state);
+        Ok(())
+    }
+
+    fn snapshot_count(&self, instance_id: InstanceId) -> usize {
+        self.inner.snapshot_count(instance_id)
+    }
+
+    fn snapshots(&self, instance_id: InstanceId) -> Vec<SnapshotId> {
+        self.inner.snapshots(instance_id)
+    }
+
+    fn snapshot_max_count(&self) -> usize {
+        self.inner.snapshot_max_count()
+    }
+
+    fn set_snapshot_max_count(&self, snapshot_max_count: usize) -> Result<(), ErrorCode> {
+        self.inner.set_snapshot_max_count(snapshot_max_count)
+    }
+
+    fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        self.inner.snapshot_restore(instance_id, snapshot_id)
+    }
+
+    fn snapshot_restore_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        self.inner.snapshot_restore_with_expiry(instance_id, snapshot_id)
+    }
+
+    fn working_dir(&self) -> Option<&Path> {
+        self.inner.working_dir()
+    }
+
+    fn snapshot_paths(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Option<(PathBuf, PathBuf)> {
+        // Current KVS is not stored in a single file, log must be replayed over base snapshot.
+        if snapshot_id == SnapshotId(0) {
+            return None;
+        }
+        self.inner.snapshot_paths(instance_id, snapshot_id)
+    }
+}
+
+#[cfg(test)]
+mod append_log_backend_tests {
+    use crate::append_log_backend::{AppendLogBackend, AppendLogBackendBuilder, LogRecord};
+    use crate::error_code::ErrorCode;
+    use crate::json_backend::JsonBackendBuilder;
+    use crate::kvs_api::{InstanceId, SnapshotId};
+    use crate::kvs_backend::KvsBackend;
+    use crate::kvs_value::{KvsMap, KvsValue};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn get_backend(working_dir: &Path, compact_threshold: usize) -> AppendLogBackend {
+        let inner = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
+        AppendLogBackendBuilder::new(Box::new(inner))
+            .compact_threshold(compact_threshold)
+            .build()
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        for record in [
+            LogRecord::Set(
+                "key".to_string(),
+                KvsValue::from(vec![KvsValue::from(1.5), KvsValue::Null]),
+            ),
+            LogRecord::Remove("key with\nnewline".to_string()),
+        ] {
+            let line = AppendLogBackend::encode_record(&record).unwrap();
+            assert_eq!(line.matches('\n').count(), 1);
+            assert_eq!(
+                AppendLogBackend::decode_record(line.trim_end_matches('\n')).unwrap(),
+                record
+            );
+        }
+    }
+
+    #[test]
+    fn test_flush_appends_changes() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+        let instance_id = InstanceId(1);
+
+        let mut kvs_map = KvsMap::from([
+            ("a".to_string(), KvsValue::from(1)),
+            ("b".to_string(), KvsValue::from("value")),
+        ]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 2);
+
+        // Only changed keys are appended.
+        kvs_map.insert("a".to_string(), KvsValue::from(2));
+        kvs_map.remove("b");
+        backend.flush(instance_id, &kvs_map).unwrap();
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 4);
+
+        // Replay with new backend instance.
+        let backend = get_backend(dir.path(), 100);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+        assert_eq!(backend.snapshot_count(instance_id), 0);
+    }
+
+    #[test]
+    fn test_compaction_equivalent() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 3);
+        let instance_id = InstanceId(1);
+
+        let mut kvs_map = KvsMap::new();
+        for i in 0..3 {
+            kvs_map.insert(format!("key{i}"), KvsValue::from(i));
+            kvs_map.remove(&format!("key{}", i + 10));
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+        kvs_map.remove("key0");
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Fourth record exceeded threshold, log was compacted into base snapshot.
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 0);
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+        assert_eq!(backend.snapshot_paths(instance_id, SnapshotId(0)), None);
+        assert!(backend.snapshot_paths(instance_id, SnapshotId(1)).is_some());
+        let base = JsonBackendBuilder::new()
+            .working_dir(dir.path().to_path_buf())
+            .build()
+            .load_kvs(instance_id, SnapshotId(0))
+            .unwrap();
+        assert_eq!(base, kvs_map);
+        assert_eq!(
+            get_backend(dir.path(), 3).load_kvs(instance_id, SnapshotId(0)).unwrap(),
+            kvs_map
+        );
+    }
+
+    #[test]
+    fn test_compact_ok() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("a".to_string(), KvsValue::from(1))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        backend.compact(instance_id).unwrap();
+
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 0);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+
+        // Changes after compaction are appended on top of base snapshot.
+        let kvs_map = KvsMap::new();
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 1);
+        assert_eq!(
+            get_backend(dir.path(), 100)
+                .load_kvs(instance_id, SnapshotId(0))
+                .unwrap(),
+            kvs_map
+        );
+    }
+
+    #[test]
+    fn test_replay_not_truncated_log() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("a".to_string(), KvsValue::from(1))]);
+        backend
+            .flush(instance_id, &[("b".to_string(), KvsValue::from(2))].into())
+            .unwrap();
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Base written, but log not truncated, e.g. compaction interrupted.
+        let log = std::fs::read(backend.log_file_path(instance_id)).unwrap();
+        backend.compact(instance_id).unwrap();
+        std::fs::write(backend.log_file_path(instance_id), log).unwrap();
+
+        assert_eq!(
+            get_backend(dir.path(), 100)
+                .load_kvs(instance_id, SnapshotId(0))
+                .unwrap(),
+            kvs_map
+        );
+    }
+
+    #[test]
+    fn test_load_incomplete_record_skipped() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("a".to_string(), KvsValue::from(1))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        let log_path = backend.log_file_path(instance_id);
+        let mut log = std::fs::read_to_string(&log_path).unwrap();
+        log.push_str("0000 {\"op\":");
+        std::fs::write(&log_path, log).unwrap();
+
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+
+        // Incomplete record is dropped, next record is appended after last complete one.
+        let kvs_map = KvsMap::new();
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.log_record_count(instance_id).unwrap(), 2);
+        assert_eq!(
+            get_backend(dir.path(), 100)
+                .load_kvs(instance_id, SnapshotId(0))
+                .unwrap(),
+            kvs_map
+        );
+    }
+
+    #[test]
+    fn test_load_hash_mismatch() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+        let instance_id = InstanceId(1);
+
+        backend
+            .flush(instance_id, &KvsMap::from([("a".to_string(), KvsValue::from(1))]))
+            .unwrap();
+
+        let log_path = backend.log_file_path(instance_id);
+        let log = std::fs::read_to_string(&log_path).unwrap().replace("\"a\"", "\"b\"");
+        std::fs::write(&log_path, log).unwrap();
+
+        assert!(backend
+            .load_kvs(instance_id, SnapshotId(0))
+            .is_err_and(|e| e == ErrorCode::ValidationFailed));
+    }
+
+    #[test]
+    fn test_load_defaults_inner() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+
+        assert!(backend
+            .load_defaults(InstanceId(1))
+            .is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert_eq!(backend.name(), "appendlog");
+        assert_eq!(backend.working_dir(), Some(dir.path()));
+    }
+
+    #[test]
+    fn test_eq() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), 100);
+
+        assert!(backend == get_backend(dir.path(), 100));
+        assert!(backend != get_backend(dir.path(), 10));
+        assert!(backend != get_backend(Path::new("other"), 100));
+    }
+}
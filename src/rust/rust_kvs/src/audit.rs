@@ -0,0 +1,205 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+use crate::error_code::ErrorCode;
+use crate::kvs_value::KvsValue;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tinyjson::JsonValue;
+
+/// Audited operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOperation {
+    /// Value set with `set_value`, `set_value_with_ttl`, `compare_and_set`, `swap`, `rename_key`,
+    /// `materialize_default`, `merge` or `Kvs::import`.
+    Set,
+
+    /// Key removed with `remove_key`, `remove_prefix`, `rename_key` or `compact`.
+    Remove,
+
+    /// All stored values removed with `reset`.
+    Reset,
+
+    /// Key reset to its default with `reset_key` or `reset_to_defaults`.
+    ResetKey,
+
+    /// Storage flushed with `flush`, `flush_with_id`, `Kvs::flush_async`, completed
+    /// `flush_chunked`, `compact`, `snapshot_restore_and_flush` or `Kvs::swap_backend`.
+    Flush,
+
+    /// Stored values replaced with `snapshot_restore`, `snapshot_restore_and_flush` or
+    /// `snapshot_unrestore`.
+    Restore,
+}
+
+impl AuditOperation {
+    /// Name of the operation used in audit records.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditOperation::Set => "set",
+            AuditOperation::Remove => "remove",
+            AuditOperation::Reset => "reset",
+            AuditOperation::ResetKey => "reset_key",
+            AuditOperation::Flush => "flush",
+            AuditOperation::Restore => "restore",
+        }
+    }
+}
+
+/// Record of a successful mutation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    /// Time of the operation, taken from instance clock.
+    pub timestamp: SystemTime,
+
+    /// Operation.
+    pub operation: AuditOperation,
+
+    /// Affected key, `None` for operations on the whole storage.
+    pub key: Option<String>,
+
+    /// Stored value before the operation, `None` if there was none.
+    pub old: Option<KvsValue>,
+
+    /// Stored value after the operation, `None` if there is none.
+    pub new: Option<KvsValue>,
+}
+
+/// Receiver of audit events.
+///
+/// Events are recorded after the operation succeeded and the data lock is released, in order of
+/// operations of a single thread.
+pub trait AuditSink: Send + Sync {
+    /// Record an event.
+    ///
+    /// # Parameters
+    ///   * `event`: Recorded event
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Audit sink appending events to a file as JSON lines.
+///
+/// Each line is an object with `ts` (milliseconds since UNIX epoch), `op`, `key`, `old` and `new`.
+/// Values are stored t-tagged same as in KVS files, absent key or values are `null`.
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    /// Open audit file for appending, file is created if missing.
+    ///
+    /// # Parameters
+    ///   * `path`: Path to audit file
+    ///
+    /// # Return Values
+    ///   * Ok: Audit sink
+    ///   * `ErrorCode::FileNotFound`: Parent directory not found
+    ///   * `ErrorCode::PermissionDenied`: File cannot be opened for writing
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    pub fn new(path: &Path) -> Result<Self, ErrorCode> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    /// Format event as a single JSON line, without line break.
+    fn to_json_line(event: &AuditEvent) -> Result<String, ErrorCode> {
+        let millis = event.timestamp.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        let value = |v: &Option<KvsValue>| v.clone().map_or(JsonValue::Null, JsonValue::from);
+        let record = JsonValue::Object(HashMap::from([
+            ("ts".to_string(), JsonValue::Number(millis as f64)),
+            ("op".to_string(), JsonValue::String(event.operation.name().to_string())),
+            (
+                "key".to_string(),
+                event.key.clone().map_or(JsonValue::Null, JsonValue::String),
+            ),
+            ("old".to_string(), value(&event.old)),
+            ("new".to_string(), value(&event.new)),
+        ]));
+        Ok(record.stringify()?)
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        let line = match Self::to_json_line(event) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("error: failed to format audit event: {e}");
+                return;
+            },
+        };
+
+        // Each line is written at once, file cannot be left inconsistent.
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = writeln!(file, "{line}") {
+            eprintln!("error: failed to write audit event: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use crate::audit::{AuditEvent, AuditOperation, AuditSink, FileAuditSink};
+    use crate::kvs_value::KvsValue;
+    use core::time::Duration;
+    use std::time::UNIX_EPOCH;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_file_audit_sink() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let sink = FileAuditSink::new(&path).unwrap();
+
+        sink.record(&AuditEvent {
+            timestamp: UNIX_EPOCH + Duration::from_millis(1500),
+            operation: AuditOperation::Set,
+            key: Some("key".to_string()),
+            old: None,
+            new: Some(KvsValue::from(1)),
+        });
+        sink.record(&AuditEvent {
+            timestamp: UNIX_EPOCH + Duration::from_millis(2000),
+            operation: AuditOperation::Flush,
+            key: None,
+            old: None,
+            new: None,
+        });
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<tinyjson::JsonValue> = content.lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["ts"], tinyjson::JsonValue::Number(1500.0));
+        assert_eq!(lines[0]["op"], tinyjson::JsonValue::String("set".to_string()));
+        assert_eq!(lines[0]["key"], tinyjson::JsonValue::String("key".to_string()));
+        assert_eq!(lines[0]["old"], tinyjson::JsonValue::Null);
+        assert_eq!(lines[0]["new"], tinyjson::JsonValue::from(KvsValue::from(1)));
+        assert_eq!(lines[1]["op"], tinyjson::JsonValue::String("flush".to_string()));
+        assert_eq!(lines[1]["key"], tinyjson::JsonValue::Null);
+
+        // Existing file is appended to.
+        drop(sink);
+        let sink = FileAuditSink::new(&path).unwrap();
+        sink.record(&AuditEvent {
+            timestamp: UNIX_EPOCH,
+            operation: AuditOperation::Reset,
+            key: None,
+            old: None,
+            new: None,
+        });
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    }
+}
@@ -0,0 +1,105 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+use core::time::Duration;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Source of wall-clock time.
+///
+/// All time-dependent logic (value expiry, snapshot timestamps) reads time through a clock, so
+/// it can be controlled in tests.
+pub trait Clock: Send + Sync {
+    /// Current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock reporting system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock reporting manually set time.
+/// Clones share time, so it can be changed after clock is passed to a builder.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    /// Create clock reporting given time.
+    ///
+    /// # Parameters
+    ///   * `now`: Initial time
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Set reported time.
+    ///
+    /// # Parameters
+    ///   * `now`: New time, may be earlier than current one
+    pub fn set(&self, now: SystemTime) {
+        // Lock guards plain value, it cannot be left inconsistent.
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    /// Move reported time forward.
+    ///
+    /// # Parameters
+    ///   * `duration`: Time to advance by
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use crate::clock::{Clock, MockClock, SystemClock};
+    use core::time::Duration;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_system_clock() {
+        let before = SystemTime::now();
+        let now = SystemClock.now();
+        assert!(now >= before && now <= SystemTime::now());
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        assert_eq!(clock.now(), UNIX_EPOCH);
+
+        // Time is shared between clones.
+        let clone = clock.clone();
+        clone.advance(Duration::from_secs(10));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(10));
+
+        clock.set(UNIX_EPOCH + Duration::from_secs(5));
+        assert_eq!(clone.now(), UNIX_EPOCH + Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,341 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+use crate::error_code::ErrorCode;
+use crate::json_backend::JsonBackend;
+use crate::kvs_api::{InstanceId, SnapshotId};
+use crate::kvs_backend::{BackendCapabilities, KvsBackend};
+use crate::kvs_value::{KvsExpiryMap, KvsMap};
+use crate::storage_layer::{decrypt, encrypt};
+use std::path::{Path, PathBuf};
+
+/// Builder for `EncryptedBackend`.
+pub struct EncryptedBackendBuilder {
+    inner: Box<dyn KvsBackend>,
+    key: [u8; 32],
+}
+
+impl EncryptedBackendBuilder {
+    /// Create `EncryptedBackendBuilder`.
+    ///
+    /// # Parameters
+    ///   * `inner`: Backend storing encrypted content, must support raw content
+    ///   * `key`: AES-256 key
+    pub fn new(inner: Box<dyn KvsBackend>, key: [u8; 32]) -> Self {
+        Self { inner, key }
+    }
+
+    /// Finalize the builder and create encrypted backend.
+    pub fn build(self) -> EncryptedBackend {
+        EncryptedBackend {
+            inner: self.inner,
+            key: self.key,
+        }
+    }
+}
+
+/// KVS backend encrypting stored data with AES-256-GCM.
+///
+/// KVS content is serialized to JSON and encrypted before being passed to the inner backend,
+/// which only ever receives ciphertext. Defaults are not encrypted and are loaded by the inner
+/// backend directly.
+pub struct EncryptedBackend {
+    inner: Box<dyn KvsBackend>,
+    key: [u8; 32],
+}
+
+impl PartialEq for EncryptedBackend {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.inner.dyn_eq(other.inner.as_any())
+    }
+}
+
+impl EncryptedBackend {
+    /// Encrypt content, random nonce is prepended to the ciphertext.
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+        encrypt(&self.key, plaintext)
+    }
+
+    /// Check snapshot with given ID exists and can be restored.
+    fn check_restore_id(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
+        // fail if the snapshot ID is the current KVS
+        if snapshot_id == SnapshotId(0) {
+            eprintln!("error: tried to restore current KVS as snapshot");
+            return Err(ErrorCode::InvalidSnapshotId);
+        }
+
+        if self.snapshot_count(instance_id) < snapshot_id.0 {
+            eprintln!("error: tried to restore a non-existing snapshot");
+            return Err(ErrorCode::InvalidSnapshotId);
+        }
+        Ok(())
+    }
+
+    /// Decrypt content produced by `encrypt`.
+    fn decrypt(&self, content: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+        decrypt(&self.key, content)
+    }
+}
+
+impl KvsBackend for EncryptedBackend {
+    fn name(&self) -> &str {
+        "encrypted"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        // Content is authenticated on decryption.
+        BackendCapabilities {
+            integrity: true,
+            ..self.inner.capabilities()
+        }
+    }
+
+    fn load_kvs(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        let bytes = self.load_kvs_bytes(instance_id, snapshot_id)?;
+        let json_str = String::from_utf8(bytes)?;
+        JsonBackend::from_json_str(&json_str)
+    }
+
+    fn load_defaults(&self, instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
+        self.inner.load_defaults(instance_id)
+    }
+
+    fn flush(&self, instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+        let json_str = JsonBackend::to_json_string(kvs_map)?;
+        self.flush_bytes(instance_id, json_str.as_bytes())
+    }
+
+    fn load_kvs_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        let bytes = self.load_kvs_bytes(instance_id, snapshot_id)?;
+        let json_str = String::from_utf8(bytes)?;
+        JsonBackend::from_json_str_with_expiry(&json_str)
+    }
+
+    fn flush_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+    ) -> Result<(), ErrorCode> {
+        let json_str = JsonBackend::to_json_string_with_expiry(kvs_map, expiry_map, false)?;
+        self.flush_bytes(instance_id, json_str.as_bytes())
+    }
+
+    fn load_kvs_bytes(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<Vec<u8>, ErrorCode> {
+        let content = self.inner.load_kvs_bytes(instance_id, snapshot_id)?;
+        self.decrypt(&content)
+    }
+
+    fn flush_bytes(&self, instance_id: InstanceId, bytes: &[u8]) -> Result<(), ErrorCode> {
+        let content = self.encrypt(bytes)?;
+        self.inner.flush_bytes(instance_id, &content)
+    }
+
+    fn snapshot_count(&self, instance_id: InstanceId) -> usize {
+        self.inner.snapshot_count(instance_id)
+    }
+
+    fn snapshots(&self, instance_id: InstanceId) -> Vec<SnapshotId> {
+        self.inner.snapshots(instance_id)
+    }
+
+    fn snapshot_max_count(&self) -> usize {
+        self.inner.snapshot_max_count()
+    }
+
+    fn set_snapshot_max_count(&self, snapshot_max_count: usize) -> Result<(), ErrorCode> {
+        self.inner.set_snapshot_max_count(snapshot_max_count)
+    }
+
+    fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        self.check_restore_id(instance_id, snapshot_id)?;
+        self.load_kvs(instance_id, snapshot_id)
+    }
+
+    fn snapshot_restore_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        self.check_restore_id(instance_id, snapshot_id)?;
+        self.load_kvs_with_expiry(instance_id, snapshot_id)
+    }
+
+    fn working_dir(&self) -> Option<&Path> {
+        self.inner.working_dir()
+    }
+
+    fn snapshot_paths(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Option<(PathBuf, PathBuf)> {
+        self.inner.snapshot_paths(instance_id, snapshot_id)
+    }
+}
+
+#[cfg(test)]
+mod encrypted_backend_tests {
+    use crate::encrypted_backend::{EncryptedBackend, EncryptedBackendBuilder};
+    use crate::error_code::ErrorCode;
+    use crate::json_backend::{JsonBackend, JsonBackendBuilder};
+    use crate::kvs_api::{InstanceId, SnapshotId};
+    use crate::kvs_backend::{BackendCapabilities, KvsBackend};
+    use crate::kvs_value::{KvsMap, KvsValue};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    const KEY: [u8; 32] = [0x42; 32];
+
+    fn get_backend(working_dir: &Path, key: [u8; 32]) -> EncryptedBackend {
+        let inner = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
+        EncryptedBackendBuilder::new(Box::new(inner), key).build()
+    }
+
+    fn get_kvs_map() -> KvsMap {
+        KvsMap::from([
+            ("token".to_string(), KvsValue::from("secret_token_value")),
+            ("counter".to_string(), KvsValue::from(123i32)),
+        ])
+    }
+
+    #[test]
+    fn test_flush_load_ok() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &get_kvs_map()).unwrap();
+
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), get_kvs_map());
+    }
+
+    #[test]
+    fn test_file_not_plaintext() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &get_kvs_map()).unwrap();
+
+        // Stored file must not contain values, nor be readable by plain JSON backend.
+        let plain_backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let kvs_path = plain_backend.kvs_file_path(instance_id, SnapshotId(0));
+        let content = std::fs::read(kvs_path).unwrap();
+        assert!(!content
+            .windows(b"secret_token_value".len())
+            .any(|w| w == b"secret_token_value"));
+        assert!(JsonBackend::from_json_str(&String::from_utf8_lossy(&content)).is_err());
+        assert!(plain_backend.load_kvs(instance_id, SnapshotId(0)).is_err());
+    }
+
+    #[test]
+    fn test_load_wrong_key() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &get_kvs_map()).unwrap();
+
+        let wrong_key_backend = get_backend(dir.path(), [0x24; 32]);
+        assert!(wrong_key_backend
+            .load_kvs(instance_id, SnapshotId(0))
+            .is_err_and(|e| e == ErrorCode::EncryptionFailed));
+    }
+
+    #[test]
+    fn test_load_content_too_short() {
+        let backend = get_backend(Path::new(""), KEY);
+        assert!(backend
+            .decrypt(&[0x00; 4])
+            .is_err_and(|e| e == ErrorCode::EncryptionFailed));
+    }
+
+    #[test]
+    fn test_load_defaults_plain() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        // Defaults are stored unencrypted.
+        let plain_backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let defaults_map = KvsMap::from([("k1".to_string(), KvsValue::from("v1"))]);
+        JsonBackend::save(
+            &defaults_map,
+            &plain_backend.defaults_file_path(instance_id),
+            &plain_backend.defaults_hash_file_path(instance_id),
+        )
+        .unwrap();
+
+        assert_eq!(backend.load_defaults(instance_id).unwrap(), defaults_map);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+
+        assert_eq!(
+            backend.capabilities(),
+            BackendCapabilities {
+                snapshots: true,
+                persistence: true,
+                defaults: true,
+                integrity: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_snapshot_restore_ok() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        for i in 1..=3 {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(i))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        assert_eq!(backend.snapshot_count(instance_id), 3);
+        assert_eq!(
+            backend.snapshot_restore(instance_id, SnapshotId(2)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+    }
+
+    #[test]
+    fn test_snapshot_restore_invalid_id() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &get_kvs_map()).unwrap();
+
+        assert!(backend
+            .snapshot_restore(instance_id, SnapshotId(0))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+        assert!(backend
+            .snapshot_restore(instance_id, SnapshotId(2))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+    }
+
+    #[test]
+    fn test_eq() {
+        let dir = tempdir().unwrap();
+        let backend = get_backend(dir.path(), KEY);
+
+        assert!(backend == get_backend(dir.path(), KEY));
+        assert!(backend != get_backend(dir.path(), [0x24; 32]));
+        assert!(backend != get_backend(Path::new("other"), KEY));
+    }
+}
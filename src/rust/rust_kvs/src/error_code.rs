@@ -14,9 +14,10 @@ extern crate alloc;
 
 use alloc::string::FromUtf8Error;
 use core::array::TryFromSliceError;
+use core::fmt;
 
 /// Runtime Error Codes
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ErrorCode {
     /// Error that was not yet mapped
     UnmappedError,
@@ -86,13 +87,94 @@ pub enum ErrorCode {
 
     /// Instance parameters mismatch
     InstanceParametersMismatch,
+
+    /// Value type doesn't match expected type
+    SchemaMismatch,
+
+    /// KVS file exists, but its hash file is missing
+    HashFileNotFound,
+
+    /// Key rejected by key validator
+    InvalidKey,
+
+    /// Permission denied by file system
+    PermissionDenied,
+
+    /// File already exists
+    FileAlreadyExists,
+
+    /// Valid JSON, but top-level value is not an object
+    InvalidStorageFormat,
+
+    /// Key is locked and cannot be set
+    KeyLocked,
+
+    /// Key already has a stored value
+    KeyAlreadyExists,
+
+    /// Path resolves outside of confining base directory
+    PathOutsideBase,
+
+    /// File path has extension not matching its content type
+    InvalidFileExtension,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCode::UnmappedError => write!(f, "unmapped error"),
+            ErrorCode::FileNotFound => write!(f, "file not found"),
+            ErrorCode::KvsFileReadError => write!(f, "KVS file read error"),
+            ErrorCode::KvsHashFileReadError => write!(f, "KVS hash file read error"),
+            ErrorCode::JsonParserError => write!(f, "JSON parser error"),
+            ErrorCode::JsonGeneratorError => write!(f, "JSON generator error"),
+            ErrorCode::PhysicalStorageFailure => write!(f, "physical storage failure"),
+            ErrorCode::IntegrityCorrupted => write!(f, "integrity corrupted"),
+            ErrorCode::ValidationFailed => write!(f, "validation failed"),
+            ErrorCode::EncryptionFailed => write!(f, "encryption failed"),
+            ErrorCode::ResourceBusy => write!(f, "resource is busy"),
+            ErrorCode::OutOfStorageSpace => write!(f, "out of storage space"),
+            ErrorCode::QuotaExceeded => write!(f, "quota exceeded"),
+            ErrorCode::AuthenticationFailed => write!(f, "authentication failed"),
+            ErrorCode::KeyNotFound => write!(f, "key not found"),
+            ErrorCode::KeyDefaultNotFound => write!(f, "key has no default value"),
+            ErrorCode::SerializationFailed(msg) => write!(f, "serialization failed: {msg}"),
+            ErrorCode::DeserializationFailed(msg) => write!(f, "deserialization failed: {msg}"),
+            ErrorCode::InvalidSnapshotId => write!(f, "invalid snapshot ID"),
+            ErrorCode::InvalidInstanceId => write!(f, "invalid instance ID"),
+            ErrorCode::ConversionFailed => write!(f, "conversion failed"),
+            ErrorCode::MutexLockFailed => write!(f, "mutex locking failed"),
+            ErrorCode::InstanceParametersMismatch => write!(f, "instance parameters mismatch"),
+            ErrorCode::SchemaMismatch => write!(f, "schema mismatch"),
+            ErrorCode::HashFileNotFound => write!(f, "hash file not found"),
+            ErrorCode::InvalidKey => write!(f, "invalid key"),
+            ErrorCode::PermissionDenied => write!(f, "permission denied"),
+            ErrorCode::FileAlreadyExists => write!(f, "file already exists"),
+            ErrorCode::InvalidStorageFormat => write!(f, "invalid storage format"),
+            ErrorCode::KeyLocked => write!(f, "key is locked"),
+            ErrorCode::KeyAlreadyExists => write!(f, "key already exists"),
+            ErrorCode::PathOutsideBase => write!(f, "path outside base directory"),
+            ErrorCode::InvalidFileExtension => write!(f, "invalid file extension"),
+        }
+    }
 }
 
+impl core::error::Error for ErrorCode {}
+
 impl From<std::io::Error> for ErrorCode {
     fn from(cause: std::io::Error) -> Self {
         let kind = cause.kind();
         match kind {
             std::io::ErrorKind::NotFound => ErrorCode::FileNotFound,
+            std::io::ErrorKind::PermissionDenied => {
+                eprintln!("error: permission denied: {cause}");
+                ErrorCode::PermissionDenied
+            },
+            std::io::ErrorKind::AlreadyExists => ErrorCode::FileAlreadyExists,
+            std::io::ErrorKind::StorageFull | std::io::ErrorKind::WriteZero => {
+                eprintln!("error: out of storage space: {kind}");
+                ErrorCode::OutOfStorageSpace
+            },
             _ => {
                 eprintln!("error: unmapped error: {kind}");
                 ErrorCode::UnmappedError
@@ -127,12 +209,68 @@ mod error_code_tests {
     use crate::error_code::ErrorCode;
     use std::io::{Error, ErrorKind};
 
+    #[test]
+    fn test_display() {
+        assert_eq!(ErrorCode::KeyNotFound.to_string(), "key not found");
+        assert_eq!(ErrorCode::ValidationFailed.to_string(), "validation failed");
+        assert_eq!(ErrorCode::SchemaMismatch.to_string(), "schema mismatch");
+        assert_eq!(ErrorCode::HashFileNotFound.to_string(), "hash file not found");
+        assert_eq!(ErrorCode::InvalidKey.to_string(), "invalid key");
+        assert_eq!(ErrorCode::PermissionDenied.to_string(), "permission denied");
+        assert_eq!(ErrorCode::FileAlreadyExists.to_string(), "file already exists");
+        assert_eq!(ErrorCode::InvalidStorageFormat.to_string(), "invalid storage format");
+        assert_eq!(ErrorCode::KeyLocked.to_string(), "key is locked");
+        assert_eq!(ErrorCode::KeyAlreadyExists.to_string(), "key already exists");
+        assert_eq!(ErrorCode::PathOutsideBase.to_string(), "path outside base directory");
+        assert_eq!(ErrorCode::InvalidFileExtension.to_string(), "invalid file extension");
+        assert_eq!(
+            ErrorCode::SerializationFailed("bad value".to_string()).to_string(),
+            "serialization failed: bad value"
+        );
+        assert_eq!(
+            ErrorCode::DeserializationFailed("bad input".to_string()).to_string(),
+            "deserialization failed: bad input"
+        );
+    }
+
+    #[test]
+    fn test_boxed_error() {
+        fn fail() -> Result<(), Box<dyn core::error::Error>> {
+            Err(ErrorCode::InvalidSnapshotId)?;
+            Ok(())
+        }
+
+        let error = fail().unwrap_err();
+        assert_eq!(error.to_string(), "invalid snapshot ID");
+        assert_eq!(error.downcast_ref::<ErrorCode>(), Some(&ErrorCode::InvalidSnapshotId));
+    }
+
     #[test]
     fn test_from_io_error_to_file_not_found() {
         let error = Error::new(ErrorKind::NotFound, "File not found");
         assert_eq!(ErrorCode::from(error), ErrorCode::FileNotFound);
     }
 
+    #[test]
+    fn test_from_io_error_to_permission_denied() {
+        let error = Error::new(ErrorKind::PermissionDenied, "Permission denied");
+        assert_eq!(ErrorCode::from(error), ErrorCode::PermissionDenied);
+    }
+
+    #[test]
+    fn test_from_io_error_to_file_already_exists() {
+        let error = Error::new(ErrorKind::AlreadyExists, "File exists");
+        assert_eq!(ErrorCode::from(error), ErrorCode::FileAlreadyExists);
+    }
+
+    #[test]
+    fn test_from_io_error_to_out_of_storage_space() {
+        let error = Error::new(ErrorKind::StorageFull, "No space left on device");
+        assert_eq!(ErrorCode::from(error), ErrorCode::OutOfStorageSpace);
+        let error = Error::new(ErrorKind::WriteZero, "Failed to write whole buffer");
+        assert_eq!(ErrorCode::from(error), ErrorCode::OutOfStorageSpace);
+    }
+
     #[test]
     fn test_from_io_error_to_unmapped_error() {
         let error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid input provided");
@@ -10,25 +10,88 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
+use crate::clock::{Clock, SystemClock};
 use crate::error_code::ErrorCode;
 use crate::kvs_api::{InstanceId, SnapshotId};
-use crate::kvs_backend::KvsBackend;
-use crate::kvs_value::{KvsMap, KvsValue};
-use std::collections::HashMap;
+use crate::kvs_backend::{BackendCapabilities, ChunkedFlush, FlushProgress, KvsBackend};
+use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue};
+use crate::storage_layer::{FileLayer, GzipLayer, StorageLayer};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::{BufReader, Bytes, Read};
 use std::path::{Path, PathBuf};
-use tinyjson::{JsonGenerateError, JsonParseError, JsonValue};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tinyjson::{JsonGenerateError, JsonGenerator, JsonParseError, JsonParser, JsonValue};
+
+/// Enter span of backend operation until end of scope, no-op without `tracing` feature.
+/// Byte count is recorded on the span with `record_bytes!`.
+macro_rules! span {
+    ($name:literal, $instance_id:expr $(, $field:ident = $value:expr)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            $name,
+            instance_id = %$instance_id,
+            $($field = %$value,)*
+            bytes = tracing::field::Empty
+        )
+        .entered();
+    };
+}
+
+/// Record byte count of backend IO on current span, no-op without `tracing` feature.
+macro_rules! record_bytes {
+    ($bytes:expr) => {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("bytes", $bytes as u64);
+    };
+}
 
 // Example of how KvsValue is stored in the JSON file (t-tagged format):
 // {
 //   "my_int": { "t": "i32", "v": 42 },
+//   "my_byte": { "t": "u8", "v": 255 },
 //   "my_float": { "t": "f64", "v": 3.1415 },
+//   "my_single": { "t": "f32", "v": 0.1 },
 //   "my_bool": { "t": "bool", "v": true },
 //   "my_string": { "t": "str", "v": "hello" },
 //   "my_array": { "t": "arr", "v": [ ... ] },
 //   "my_object": { "t": "obj", "v": { ... } },
 //   "my_null": { "t": "null", "v": null }
 // }
+//
+// Top-level entries with expiry additionally store expiry instant as milliseconds since UNIX epoch:
+//   "my_cached": { "t": "i32", "v": 42, "exp": 1767225600000 }
+//
+// Non-finite floats are not representable by JSON numbers and are stored as strings instead:
+//   "my_nan": { "t": "f64", "v": "NaN" }, "my_inf": { "t": "f32", "v": "-Infinity" }
+//
+// Object keys are written in sorted order, so equal content always produces equal files and hashes.
+
+/// Encode float as JSON number, non-finite values are encoded as strings.
+fn float_to_json(value: f64) -> JsonValue {
+    if value.is_nan() {
+        JsonValue::String("NaN".to_string())
+    } else if value == f64::INFINITY {
+        JsonValue::String("Infinity".to_string())
+    } else if value == f64::NEG_INFINITY {
+        JsonValue::String("-Infinity".to_string())
+    } else {
+        JsonValue::Number(value)
+    }
+}
+
+/// Decode non-finite float encoded as string by `float_to_json`.
+fn non_finite_from_str(value: &str) -> Option<f64> {
+    match value {
+        "NaN" => Some(f64::NAN),
+        "Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
 
 /// Backend-specific JsonValue -> KvsValue conversion.
 impl From<JsonValue> for KvsValue {
@@ -38,11 +101,20 @@ impl From<JsonValue> for KvsValue {
                 // Type-tagged: { "t": ..., "v": ... }
                 if let (Some(JsonValue::String(type_str)), Some(value)) = (obj.remove("t"), obj.remove("v")) {
                     return match (type_str.as_str(), value) {
+                        ("i8", JsonValue::Number(v)) => KvsValue::I8(v as i8),
+                        ("u8", JsonValue::Number(v)) => KvsValue::U8(v as u8),
+                        ("i16", JsonValue::Number(v)) => KvsValue::I16(v as i16),
+                        ("u16", JsonValue::Number(v)) => KvsValue::U16(v as u16),
                         ("i32", JsonValue::Number(v)) => KvsValue::I32(v as i32),
                         ("u32", JsonValue::Number(v)) => KvsValue::U32(v as u32),
                         ("i64", JsonValue::Number(v)) => KvsValue::I64(v as i64),
                         ("u64", JsonValue::Number(v)) => KvsValue::U64(v as u64),
+                        ("f32", JsonValue::Number(v)) => KvsValue::F32(v as f32),
                         ("f64", JsonValue::Number(v)) => KvsValue::F64(v),
+                        ("f32", JsonValue::String(v)) => {
+                            non_finite_from_str(&v).map_or(KvsValue::Null, |v| KvsValue::F32(v as f32))
+                        },
+                        ("f64", JsonValue::String(v)) => non_finite_from_str(&v).map_or(KvsValue::Null, KvsValue::F64),
                         ("bool", JsonValue::Boolean(v)) => KvsValue::Boolean(v),
                         ("str", JsonValue::String(v)) => KvsValue::String(v),
                         ("null", JsonValue::Null) => KvsValue::Null,
@@ -64,59 +136,38 @@ impl From<JsonValue> for KvsValue {
     }
 }
 
+/// Create t-tagged JSON object.
+/// Object is preallocated for both entries, TinyJSON object keys are owned strings.
+fn tagged(tag: &str, value: JsonValue) -> JsonValue {
+    let mut obj = HashMap::with_capacity(2);
+    obj.insert("t".to_string(), JsonValue::String(tag.to_string()));
+    obj.insert("v".to_string(), value);
+    JsonValue::Object(obj)
+}
+
 /// Backend-specific KvsValue -> JsonValue conversion.
 impl From<KvsValue> for JsonValue {
     fn from(val: KvsValue) -> JsonValue {
-        let mut obj = HashMap::new();
         match val {
-            KvsValue::I32(n) => {
-                obj.insert("t".to_string(), JsonValue::String("i32".to_string()));
-                obj.insert("v".to_string(), JsonValue::Number(n as f64));
-            },
-            KvsValue::U32(n) => {
-                obj.insert("t".to_string(), JsonValue::String("u32".to_string()));
-                obj.insert("v".to_string(), JsonValue::Number(n as f64));
-            },
-            KvsValue::I64(n) => {
-                obj.insert("t".to_string(), JsonValue::String("i64".to_string()));
-                obj.insert("v".to_string(), JsonValue::Number(n as f64));
-            },
-            KvsValue::U64(n) => {
-                obj.insert("t".to_string(), JsonValue::String("u64".to_string()));
-                obj.insert("v".to_string(), JsonValue::Number(n as f64));
-            },
-            KvsValue::F64(n) => {
-                obj.insert("t".to_string(), JsonValue::String("f64".to_string()));
-                obj.insert("v".to_string(), JsonValue::Number(n));
-            },
-            KvsValue::Boolean(b) => {
-                obj.insert("t".to_string(), JsonValue::String("bool".to_string()));
-                obj.insert("v".to_string(), JsonValue::Boolean(b));
-            },
-            KvsValue::String(s) => {
-                obj.insert("t".to_string(), JsonValue::String("str".to_string()));
-                obj.insert("v".to_string(), JsonValue::String(s));
-            },
-            KvsValue::Null => {
-                obj.insert("t".to_string(), JsonValue::String("null".to_string()));
-                obj.insert("v".to_string(), JsonValue::Null);
-            },
-            KvsValue::Array(arr) => {
-                obj.insert("t".to_string(), JsonValue::String("arr".to_string()));
-                obj.insert(
-                    "v".to_string(),
-                    JsonValue::Array(arr.into_iter().map(JsonValue::from).collect()),
-                );
-            },
-            KvsValue::Object(map) => {
-                obj.insert("t".to_string(), JsonValue::String("obj".to_string()));
-                obj.insert(
-                    "v".to_string(),
-                    JsonValue::Object(map.into_iter().map(|(k, v)| (k, JsonValue::from(v))).collect()),
-                );
-            },
+            KvsValue::I8(n) => tagged("i8", JsonValue::Number(n as f64)),
+            KvsValue::U8(n) => tagged("u8", JsonValue::Number(n as f64)),
+            KvsValue::I16(n) => tagged("i16", JsonValue::Number(n as f64)),
+            KvsValue::U16(n) => tagged("u16", JsonValue::Number(n as f64)),
+            KvsValue::I32(n) => tagged("i32", JsonValue::Number(n as f64)),
+            KvsValue::U32(n) => tagged("u32", JsonValue::Number(n as f64)),
+            KvsValue::I64(n) => tagged("i64", JsonValue::Number(n as f64)),
+            KvsValue::U64(n) => tagged("u64", JsonValue::Number(n as f64)),
+            KvsValue::F32(n) => tagged("f32", float_to_json(n as f64)),
+            KvsValue::F64(n) => tagged("f64", float_to_json(n)),
+            KvsValue::Boolean(b) => tagged("bool", JsonValue::Boolean(b)),
+            KvsValue::String(s) => tagged("str", JsonValue::String(s)),
+            KvsValue::Null => tagged("null", JsonValue::Null),
+            KvsValue::Array(arr) => tagged("arr", JsonValue::Array(arr.into_iter().map(JsonValue::from).collect())),
+            KvsValue::Object(map) => tagged(
+                "obj",
+                JsonValue::Object(map.into_iter().map(|(k, v)| (k, JsonValue::from(v))).collect()),
+            ),
         }
-        JsonValue::Object(obj)
     }
 }
 
@@ -140,10 +191,266 @@ impl From<JsonGenerateError> for ErrorCode {
     }
 }
 
+/// Characters decoded from UTF-8 byte stream, Adler32 hash is computed over read bytes.
+/// Iteration ends on read or decoding error, error is stored for later inspection.
+struct HashingChars<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    hash: adler32::RollingAdler32,
+    error: Option<ErrorCode>,
+}
+
+impl<R: Read> HashingChars<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            bytes: BufReader::new(reader).bytes(),
+            hash: adler32::RollingAdler32::new(),
+            error: None,
+        }
+    }
+
+    /// Read next byte and include it in hash.
+    fn next_byte(&mut self) -> Option<u8> {
+        match self.bytes.next()? {
+            Ok(byte) => {
+                self.hash.update(byte);
+                Some(byte)
+            },
+            Err(e) => {
+                self.error = Some(e.into());
+                None
+            },
+        }
+    }
+
+    /// Read remaining bytes and return hash of the whole stream.
+    fn finish(mut self) -> Result<u32, ErrorCode> {
+        while self.error.is_none() && self.next_byte().is_some() {}
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.hash.hash()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for HashingChars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let first = self.next_byte()?;
+        let len = match first {
+            0x00..=0x7F => return Some(first as char),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 0,
+        };
+
+        let mut buf = [first, 0, 0, 0];
+        for byte in buf.iter_mut().take(len).skip(1) {
+            *byte = self.next_byte()?;
+        }
+        match core::str::from_utf8(&buf[..len]).ok().and_then(|s| s.chars().next()) {
+            Some(c) => Some(c),
+            None => {
+                eprintln!("error: UTF-8 conversion failed");
+                self.error = Some(ErrorCode::ConversionFailed);
+                None
+            },
+        }
+    }
+}
+
+/// Container open in `DuplicateKeyCheck`.
+enum JsonContainer {
+    /// Object with keys seen so far, `expect_key` is set if next string is a key.
+    Object { keys: HashSet<String>, expect_key: bool },
+
+    /// Array.
+    Array,
+}
+
+/// Characters passed through to parser, checked for duplicate keys within objects.
+///
+/// TinyJSON keeps last of duplicate keys, so a malformed document could load different values
+/// than other parsers see. Iteration ends on first duplicate key, so parser fails early.
+/// Escaped keys are compared after unescaping.
+struct DuplicateKeyCheck<I: Iterator<Item = char>> {
+    chars: I,
+    stack: Vec<JsonContainer>,
+    in_string: bool,
+    escape: bool,
+    key: Option<String>,
+    duplicate: Option<String>,
+}
+
+impl<I: Iterator<Item = char>> DuplicateKeyCheck<I> {
+    fn new(chars: I) -> Self {
+        Self {
+            chars,
+            stack: Vec::new(),
+            in_string: false,
+            escape: false,
+            key: None,
+            duplicate: None,
+        }
+    }
+
+    /// Result of the check.
+    ///
+    /// # Return Values
+    ///   * Ok: No duplicate key found in read characters
+    ///   * `ErrorCode::JsonParserError`: Duplicate key found
+    fn finish(self) -> Result<(), ErrorCode> {
+        match self.duplicate {
+            Some(key) => {
+                eprintln!("error: JSON parser error: duplicate key: {key:?}");
+                Err(ErrorCode::JsonParserError)
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Handle character inside a string.
+    fn string_char(&mut self, c: char) {
+        if self.escape {
+            self.escape = false;
+        } else if c == '\\' {
+            self.escape = true;
+        } else if c == '"' {
+            self.in_string = false;
+            if let Some(raw) = self.key.take() {
+                self.end_key(raw);
+            }
+            return;
+        }
+
+        if let Some(key) = &mut self.key {
+            key.push(c);
+        }
+    }
+
+    /// Record completed key of innermost object.
+    fn end_key(&mut self, raw: String) {
+        let key = if raw.contains('\\') {
+            // Invalid escape sequences are rejected by parser anyway.
+            match format!("\"{raw}\"").parse() {
+                Ok(JsonValue::String(key)) => key,
+                _ => raw,
+            }
+        } else {
+            raw
+        };
+
+        if let Some(JsonContainer::Object { keys, expect_key }) = self.stack.last_mut() {
+            *expect_key = false;
+            if !keys.insert(key.clone()) {
+                self.duplicate = Some(key);
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for DuplicateKeyCheck<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.duplicate.is_some() {
+            return None;
+        }
+
+        let c = self.chars.next()?;
+        if self.in_string {
+            self.string_char(c);
+        } else {
+            match c {
+                '{' => self.stack.push(JsonContainer::Object {
+                    keys: HashSet::new(),
+                    expect_key: true,
+                }),
+                '[' => self.stack.push(JsonContainer::Array),
+                '}' | ']' => {
+                    let _ = self.stack.pop();
+                },
+                ',' => {
+                    if let Some(JsonContainer::Object { expect_key, .. }) = self.stack.last_mut() {
+                        *expect_key = true;
+                    }
+                },
+                '"' => {
+                    self.in_string = true;
+                    let is_key = matches!(self.stack.last(), Some(JsonContainer::Object { expect_key: true, .. }));
+                    self.key = is_key.then(String::new);
+                },
+                _ => {},
+            }
+        }
+
+        if self.duplicate.is_some() {
+            None
+        } else {
+            Some(c)
+        }
+    }
+}
+
+/// Compression applied to KVS snapshot files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain JSON files (`.json`).
+    None,
+
+    /// Gzip-compressed JSON files (`.json.gz`).
+    Gzip,
+}
+
+/// Naming and rotation scheme of KVS snapshot files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationStrategy {
+    /// Files are named by snapshot ID (`kvs_{id}_{snapshot_id}.json`), existing files are renamed
+    /// on each flush.
+    Shift,
+
+    /// Files are named by creation time in milliseconds since UNIX epoch
+    /// (`kvs_{id}_{unixmillis}.json`). Each flush writes a new file and removes the oldest ones
+    /// beyond `snapshot_max_count`, existing files are never renamed. Snapshot IDs are assigned
+    /// by age, `SnapshotId(0)` is the newest file.
+    Timestamped,
+}
+
+/// Durability of written KVS snapshot files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Files are not synced, written data may be lost on power loss.
+    None,
+
+    /// KVS and hash files are synced after write.
+    Data,
+
+    /// KVS and hash files are synced, working directory is synced too, so created and renamed
+    /// files are durable. Directory sync is supported on Unix-like systems.
+    DataAndDir,
+}
+
 /// Builder for `JsonBackend`.
 pub struct JsonBackendBuilder {
     working_dir: PathBuf,
+    base_dir: Option<PathBuf>,
+    confine: bool,
     snapshot_max_count: usize,
+    rotation_strategy: RotationStrategy,
+    compression: Compression,
+    sync_policy: SyncPolicy,
+    pretty: bool,
+    require_hash: bool,
+    prune_excess_snapshots: bool,
+    max_value_bytes: Option<usize>,
+    max_store_bytes: Option<usize>,
+    storage_layer: Option<Box<dyn StorageLayer>>,
+    clock: Option<Box<dyn Clock>>,
 }
 
 impl JsonBackendBuilder {
@@ -151,11 +458,35 @@ impl JsonBackendBuilder {
     ///
     /// Defaults:
     /// - `working_dir` - empty `PathBuf`, CWD is used.
+    /// - `base_dir` - `None`, relative `working_dir` is resolved against CWD.
+    /// - `confine` - `false`, `working_dir` may point outside of `base_dir`.
     /// - `snapshot_max_count` - 3 snapshots.
+    /// - `rotation_strategy` - [`RotationStrategy::Shift`].
+    /// - `compression` - [`Compression::None`].
+    /// - `sync_policy` - [`SyncPolicy::None`].
+    /// - `pretty` - `false`, compact JSON.
+    /// - `require_hash` - `true`, missing hash file is an error.
+    /// - `prune_excess_snapshots` - `false`, excess snapshot files are only reported.
+    /// - `max_value_bytes` - `None`, value size is not limited.
+    /// - `max_store_bytes` - `None`, store size is not limited.
+    /// - `storage_layer` - [`FileLayer`], plain files.
+    /// - `clock` - [`SystemClock`].
     pub fn new() -> Self {
         Self {
             working_dir: PathBuf::new(),
+            base_dir: None,
+            confine: false,
             snapshot_max_count: 3,
+            rotation_strategy: RotationStrategy::Shift,
+            compression: Compression::None,
+            sync_policy: SyncPolicy::None,
+            pretty: false,
+            require_hash: true,
+            prune_excess_snapshots: false,
+            max_value_bytes: None,
+            max_store_bytes: None,
+            storage_layer: None,
+            clock: None,
         }
     }
 
@@ -165,17 +496,173 @@ impl JsonBackendBuilder {
         self
     }
 
+    /// Set base directory relative `working_dir` is resolved against.
+    /// Absolute `working_dir` is used as is.
+    pub fn base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = Some(base_dir);
+        self
+    }
+
+    /// Set whether `working_dir` must resolve inside of `base_dir`, e.g. when it comes from
+    /// untrusted configuration. Checked by [`JsonBackendBuilder::try_build`], ignored if
+    /// `base_dir` is not set.
+    pub fn confine(mut self, confine: bool) -> Self {
+        self.confine = confine;
+        self
+    }
+
     /// Set max number of snapshots.
+    /// With 0 snapshots are not written, `flush` succeeds without creating files.
     pub fn snapshot_max_count(mut self, snapshot_max_count: usize) -> Self {
         self.snapshot_max_count = snapshot_max_count;
         self
     }
 
+    /// Set naming and rotation scheme of KVS snapshot files.
+    /// Files stored with the other strategy are not migrated.
+    pub fn rotation_strategy(mut self, rotation_strategy: RotationStrategy) -> Self {
+        self.rotation_strategy = rotation_strategy;
+        self
+    }
+
+    /// Set compression of KVS snapshot files.
+    /// Defaults files are always stored uncompressed.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set durability of written KVS snapshot files.
+    /// Syncing makes flush durable at the cost of its performance. KVS file is synced at its path,
+    /// so storage layer must store content in that file.
+    pub fn sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    /// Set human-readable (indented) output of KVS snapshot files.
+    /// Hash is computed over the written form, loading accepts both forms.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Set whether hash file must exist when loading.
+    /// If not required, missing hash file is reported with a warning and content is loaded
+    /// without integrity verification. Present but mismatching hash file is always an error.
+    pub fn require_hash(mut self, require_hash: bool) -> Self {
+        self.require_hash = require_hash;
+        self
+    }
+
+    /// Set whether snapshot files beyond `snapshot_max_count` are removed.
+    /// Such files remain after `snapshot_max_count` was reduced, rotation never touches them.
+    /// Files are checked on first flush of each instance, if not removed they are reported with
+    /// a warning. Files beyond count reduced with `KvsBackend::set_snapshot_max_count` are
+    /// always removed on next flush.
+    pub fn prune_excess_snapshots(mut self, prune_excess_snapshots: bool) -> Self {
+        self.prune_excess_snapshots = prune_excess_snapshots;
+        self
+    }
+
+    /// Set max size of a single value, measured as compact t-tagged JSON of the value.
+    /// Limit is enforced on flush, since only then values are serialized. Flush containing an
+    /// oversized value is rejected as a whole with `ErrorCode::QuotaExceeded`, before snapshots
+    /// are rotated. Raw bytes flushed with `flush_bytes` are not checked.
+    pub fn max_value_bytes(mut self, max_value_bytes: usize) -> Self {
+        self.max_value_bytes = Some(max_value_bytes);
+        self
+    }
+
+    /// Set max size of whole KVS snapshot, measured as JSON passed to storage layer (before
+    /// compression). Flush exceeding the limit is rejected with `ErrorCode::QuotaExceeded`, before
+    /// snapshots are rotated, so existing snapshots are left intact.
+    pub fn max_store_bytes(mut self, max_store_bytes: usize) -> Self {
+        self.max_store_bytes = Some(max_store_bytes);
+        self
+    }
+
+    /// Set storage layer used to read and write KVS snapshot files.
+    /// If compression is enabled, [`GzipLayer`] is added on top of provided layer.
+    /// Defaults files are always read from plain files.
+    pub fn storage_layer(mut self, storage_layer: Box<dyn StorageLayer>) -> Self {
+        self.storage_layer = Some(storage_layer);
+        self
+    }
+
+    /// Set clock used for timestamps of snapshots with [`RotationStrategy::Timestamped`].
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Working directory joined onto base directory, if set.
+    fn resolved_working_dir(&self) -> PathBuf {
+        match &self.base_dir {
+            Some(base_dir) if self.working_dir.as_os_str().is_empty() => base_dir.clone(),
+            Some(base_dir) => base_dir.join(&self.working_dir),
+            None => self.working_dir.clone(),
+        }
+    }
+
+    /// Finalize the builder and create JSON backend, resolving and checking working directory.
+    ///
+    /// If `base_dir` is set, working directory is joined onto it and canonicalized, so both
+    /// directories must exist. With `confine` set, working directory resolving outside of base
+    /// directory (via `..` or symbolic links) is rejected.
+    ///
+    /// # Return Values
+    ///   * Ok: JSON backend
+    ///   * `ErrorCode::FileNotFound`: Base or working directory not found
+    ///   * `ErrorCode::PathOutsideBase`: Working directory outside of confining base directory
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    pub fn try_build(mut self) -> Result<JsonBackend, ErrorCode> {
+        if let Some(base_dir) = &self.base_dir {
+            let base_dir = base_dir.canonicalize()?;
+            let working_dir = self.resolved_working_dir().canonicalize()?;
+            if self.confine && !working_dir.starts_with(&base_dir) {
+                eprintln!(
+                    "error: working directory {} is outside of base directory {}",
+                    working_dir.display(),
+                    base_dir.display()
+                );
+                return Err(ErrorCode::PathOutsideBase);
+            }
+            self.working_dir = working_dir;
+            self.base_dir = None;
+        }
+        Ok(self.build())
+    }
+
     /// Finalize the builder and create JSON backend.
+    /// Working directory is joined onto `base_dir` without canonicalization, `confine` is not
+    /// checked, use [`JsonBackendBuilder::try_build`] for that.
     pub fn build(self) -> JsonBackend {
+        let working_dir = self.resolved_working_dir();
+        let storage_layer = self.storage_layer.unwrap_or_else(|| Box::new(FileLayer));
+        let storage_layer: Box<dyn StorageLayer> = match self.compression {
+            Compression::None => storage_layer,
+            Compression::Gzip => Box::new(GzipLayer::new(storage_layer)),
+        };
+
         JsonBackend {
-            working_dir: self.working_dir,
-            snapshot_max_count: self.snapshot_max_count,
+            working_dir,
+            snapshot_max_count: Arc::new(AtomicUsize::new(self.snapshot_max_count)),
+            rotation_strategy: self.rotation_strategy,
+            compression: self.compression,
+            sync_policy: self.sync_policy,
+            pretty: self.pretty,
+            require_hash: self.require_hash,
+            prune_excess_snapshots: self.prune_excess_snapshots,
+            max_value_bytes: self.max_value_bytes,
+            max_store_bytes: self.max_store_bytes,
+            storage_layer: Arc::from(storage_layer),
+            clock: self
+                .clock
+                .map_or_else(|| Arc::new(SystemClock) as Arc<dyn Clock>, Arc::from),
+            checked_instances: Arc::new(Mutex::new(HashSet::new())),
+            flush_hashes: Arc::new(Mutex::new(HashMap::new())),
+            retention_reduced: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -187,19 +674,146 @@ impl Default for JsonBackendBuilder {
 }
 
 /// KVS backend implementation based on TinyJSON.
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct JsonBackend {
     working_dir: PathBuf,
-    snapshot_max_count: usize,
+    rotation_strategy: RotationStrategy,
+    compression: Compression,
+    sync_policy: SyncPolicy,
+    pretty: bool,
+    require_hash: bool,
+    prune_excess_snapshots: bool,
+    max_value_bytes: Option<usize>,
+    max_store_bytes: Option<usize>,
+    storage_layer: Arc<dyn StorageLayer>,
+    clock: Arc<dyn Clock>,
+
+    /// Max number of snapshots, can be changed at runtime with `set_snapshot_max_count`.
+    snapshot_max_count: Arc<AtomicUsize>,
+
+    /// Snapshot max count was reduced at runtime, excess snapshots are removed regardless of
+    /// `prune_excess_snapshots`.
+    retention_reduced: Arc<AtomicBool>,
+
+    /// Instances already checked for excess snapshot files.
+    checked_instances: Arc<Mutex<HashSet<InstanceId>>>,
+
+    /// Hash of content last flushed per instance.
+    flush_hashes: Arc<Mutex<HashMap<InstanceId, u32>>>,
+}
+
+impl PartialEq for JsonBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Self::same_dir(&self.working_dir, &other.working_dir)
+            && self.snapshot_max_count() == other.snapshot_max_count()
+            && self.rotation_strategy == other.rotation_strategy
+            && self.compression == other.compression
+            && self.sync_policy == other.sync_policy
+            && self.pretty == other.pretty
+            && self.require_hash == other.require_hash
+            && self.prune_excess_snapshots == other.prune_excess_snapshots
+            && self.max_value_bytes == other.max_value_bytes
+            && self.max_store_bytes == other.max_store_bytes
+            && self.storage_layer.dyn_eq(other.storage_layer.as_any())
+    }
 }
 
 impl JsonBackend {
+    /// Directory used for `working_dir`, empty path refers to CWD.
+    fn dir_path(working_dir: &Path) -> &Path {
+        if working_dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            working_dir
+        }
+    }
+
+    /// Check if working directories refer to same directory.
+    /// Existing directories are compared canonicalized, e.g. relative path and its absolute form.
+    fn same_dir(left: &Path, right: &Path) -> bool {
+        if left == right {
+            return true;
+        }
+
+        match (
+            fs::canonicalize(Self::dir_path(left)),
+            fs::canonicalize(Self::dir_path(right)),
+        ) {
+            (Ok(left), Ok(right)) => left == right,
+            _ => false,
+        }
+    }
+
+    /// Parse JSON string, duplicate keys within an object are rejected.
     fn parse(s: &str) -> Result<JsonValue, ErrorCode> {
-        s.parse().map_err(ErrorCode::from)
+        let mut chars = DuplicateKeyCheck::new(s.chars());
+        let parse_result = JsonParser::new(&mut chars).parse();
+        chars.finish()?;
+        Ok(parse_result?)
+    }
+
+    fn stringify(val: &JsonValue, pretty: bool) -> Result<String, ErrorCode> {
+        let mut out = Vec::new();
+        Self::write_sorted(val, pretty.then_some("  "), 0, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Append JSON text of scalar value, generated by TinyJSON directly into output buffer.
+    fn write_scalar(val: &JsonValue, out: &mut Vec<u8>) -> Result<(), ErrorCode> {
+        JsonGenerator::new(out).generate(val).map_err(|e| {
+            eprintln!("error: JSON generator error: msg = {e}");
+            ErrorCode::JsonGeneratorError
+        })
+    }
+
+    /// Append indentation of given level.
+    fn write_indent(indent: &str, level: usize, out: &mut Vec<u8>) {
+        out.push(b'\n');
+        for _ in 0..level {
+            out.extend_from_slice(indent.as_bytes());
+        }
     }
 
-    fn stringify(val: &JsonValue) -> Result<String, ErrorCode> {
-        val.stringify().map_err(ErrorCode::from)
+    /// Append JSON text of value with object keys sorted recursively.
+    /// Layout matches TinyJSON, scalars are generated by TinyJSON. Pretty output is produced if
+    /// `indent` is set.
+    fn write_sorted(val: &JsonValue, indent: Option<&str>, level: usize, out: &mut Vec<u8>) -> Result<(), ErrorCode> {
+        let (open, close, items) = match val {
+            JsonValue::Array(array) => (b'[', b']', array.iter().map(|v| (None, v)).collect::<Vec<_>>()),
+            JsonValue::Object(map) => {
+                let mut entries: Vec<_> = map.iter().map(|(k, v)| (Some(k), v)).collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                (b'{', b'}', entries)
+            },
+            _ => return Self::write_scalar(val, out),
+        };
+
+        out.push(open);
+        if items.is_empty() {
+            out.push(close);
+            return Ok(());
+        }
+
+        for (idx, (key, value)) in items.into_iter().enumerate() {
+            if idx > 0 {
+                out.push(b',');
+            }
+            if let Some(indent) = indent {
+                Self::write_indent(indent, level + 1, out);
+            }
+            if let Some(key) = key {
+                // Key is quoted and escaped by TinyJSON, which only accepts it as a value.
+                Self::write_scalar(&JsonValue::String(key.clone()), out)?;
+                out.extend_from_slice(if indent.is_some() { b": " } else { b":" });
+            }
+            Self::write_sorted(value, indent, level + 1, out)?;
+        }
+
+        if let Some(indent) = indent {
+            Self::write_indent(indent, level, out);
+        }
+        out.push(close);
+        Ok(())
     }
 
     /// Rotate snapshots
@@ -211,120 +825,760 @@ impl JsonBackend {
     ///   * Ok: Rotation successful, also if no rotation was needed
     ///   * `ErrorCode::UnmappedError`: Unmapped error
     fn snapshot_rotate(&self, instance_id: InstanceId) -> Result<(), ErrorCode> {
-        for idx in (1..self.snapshot_max_count()).rev() {
-            let old_snapshot_id = SnapshotId(idx - 1);
-            let new_snapshot_id = SnapshotId(idx);
+        // Timestamped files are never renamed.
+        if self.rotation_strategy == RotationStrategy::Timestamped {
+            return Ok(());
+        }
 
-            // Old paths.
-            let hash_path_old = self.hash_file_path(instance_id, old_snapshot_id);
-            let snap_name_old = Self::kvs_file_name(instance_id, old_snapshot_id);
-            let snap_path_old = self.kvs_file_path(instance_id, old_snapshot_id);
+        for idx in (1..self.snapshot_max_count()).rev() {
+            self.snapshot_rotate_single(instance_id, idx)?;
+        }
 
-            // Check snapshot and hash files exist.
-            let snap_old_exists = snap_path_old.exists();
-            let hash_old_exists = hash_path_old.exists();
+        Ok(())
+    }
 
-            // Both files must exist to rotate.
-            // If neither exist - continue.
-            if !snap_old_exists && !hash_old_exists {
-                continue;
-            }
-            // In other case - this is erroneous scenario.
-            // Either snapshot or hash file got removed.
-            else if !snap_old_exists || !hash_old_exists {
-                return Err(ErrorCode::IntegrityCorrupted);
-            }
+    /// Rotate single snapshot from `idx - 1` to `idx`.
+    /// KVS file is renamed before hash file, so interrupted rotation can be completed by
+    /// `repair_rotation`.
+    ///
+    /// # Parameters
+    ///   * `instance_id`: Instance ID
+    ///   * `idx`: Index of the rotated snapshot after rotation
+    ///
+    /// # Return Values
+    ///   * Ok: Rotation successful, also if no rotation was needed
+    ///   * `ErrorCode::IntegrityCorrupted`: Either snapshot or hash file is missing
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn snapshot_rotate_single(&self, instance_id: InstanceId, idx: usize) -> Result<(), ErrorCode> {
+        let old_snapshot_id = SnapshotId(idx - 1);
+        let new_snapshot_id = SnapshotId(idx);
+
+        // Old paths.
+        let hash_path_old = self.hash_file_path(instance_id, old_snapshot_id);
+        let snap_name_old = Self::kvs_file_name(instance_id, old_snapshot_id);
+        let snap_path_old = self.kvs_file_path(instance_id, old_snapshot_id);
+
+        // Check snapshot and hash files exist.
+        let snap_old_exists = snap_path_old.exists();
+        let hash_old_exists = hash_path_old.exists();
+
+        // Both files must exist to rotate.
+        // If neither exist - nothing to do.
+        if !snap_old_exists && !hash_old_exists {
+            return Ok(());
+        }
+        // In other case - this is erroneous scenario.
+        // Either snapshot or hash file got removed.
+        else if !snap_old_exists || !hash_old_exists {
+            return Err(ErrorCode::IntegrityCorrupted);
+        }
 
-            // New paths.
-            let hash_path_new = self.hash_file_path(instance_id, new_snapshot_id);
-            let snap_name_new = Self::kvs_file_name(instance_id, new_snapshot_id);
-            let snap_path_new = self.kvs_file_path(instance_id, new_snapshot_id);
+        // New paths.
+        let hash_path_new = self.hash_file_path(instance_id, new_snapshot_id);
+        let snap_name_new = Self::kvs_file_name(instance_id, new_snapshot_id);
+        let snap_path_new = self.kvs_file_path(instance_id, new_snapshot_id);
 
-            println!("rotating: {snap_name_old} -> {snap_name_new}");
+        println!("rotating: {snap_name_old} -> {snap_name_new}");
 
-            fs::rename(hash_path_old, hash_path_new)?;
-            fs::rename(snap_path_old, snap_path_new)?;
-        }
+        fs::rename(snap_path_old, snap_path_new)?;
+        fs::rename(hash_path_old, hash_path_new)?;
 
         Ok(())
     }
 
-    /// Check path extensions are correct.
-    fn check_path_extensions(kvs_path: &Path, hash_path: &Path) -> Result<(), ErrorCode> {
-        fn check_extension(path: &Path, extension: &str) -> bool {
-            let ext = path.extension();
-            ext.is_some_and(|ep| ep.to_str().is_some_and(|es| es == extension))
+    /// Repair snapshot rotation interrupted between renaming KVS file and hash file.
+    ///
+    /// Interrupted rotation leaves hash file of `idx - 1` without its KVS file, while KVS file of
+    /// `idx` is already replaced. Rotation is completed by renaming the hash file.
+    /// Files left by rotation renaming hash file first are also repaired: hash file is renamed
+    /// back to `idx - 1`, and KVS file of `idx` which lost its hash file is removed.
+    /// Hash file is only renamed if it matches the KVS file, other missing files are left to be
+    /// reported as corrupted. Failures are reported as warnings.
+    ///
+    /// # Parameters
+    ///   * `instance_id`: Instance ID
+    fn repair_rotation(&self, instance_id: InstanceId) {
+        // Timestamped files are never renamed.
+        if self.rotation_strategy == RotationStrategy::Timestamped {
+            return;
+        }
+
+        for idx in 1..self.snapshot_max_count() {
+            let old_snapshot_id = SnapshotId(idx - 1);
+            let new_snapshot_id = SnapshotId(idx);
+            let kvs_path_old = self.kvs_file_path(instance_id, old_snapshot_id);
+            let hash_path_old = self.hash_file_path(instance_id, old_snapshot_id);
+            let kvs_path_new = self.kvs_file_path(instance_id, new_snapshot_id);
+            let hash_path_new = self.hash_file_path(instance_id, new_snapshot_id);
+            let matches = |kvs_path: &Path, hash_path: &Path| {
+                Self::load_bytes(self.storage_layer.as_ref(), kvs_path, hash_path, true).is_ok()
+            };
+
+            let kvs_old_exists = kvs_path_old.exists();
+            let hash_old_exists = hash_path_old.exists();
+            if !kvs_old_exists && hash_old_exists && matches(&kvs_path_new, &hash_path_old) {
+                eprintln!("warn: completing interrupted rotation of snapshot {old_snapshot_id}");
+                if let Err(e) = fs::rename(&hash_path_old, &hash_path_new) {
+                    eprintln!("warn: failed to complete rotation: {e}");
+                }
+            } else if kvs_old_exists && !hash_old_exists && matches(&kvs_path_old, &hash_path_new) {
+                eprintln!("warn: rolling back interrupted rotation of snapshot {old_snapshot_id}");
+                if let Err(e) = fs::rename(&hash_path_new, &hash_path_old) {
+                    eprintln!("warn: failed to roll back rotation: {e}");
+                    continue;
+                }
+                // Hash file of replaced snapshot is lost, it cannot be verified anymore.
+                if let Err(e) = fs::remove_file(&kvs_path_new) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        eprintln!("warn: failed to remove unverifiable snapshot {new_snapshot_id}: {e}");
+                    }
+                }
+            }
         }
+    }
 
-        if !check_extension(kvs_path, "json") {
-            return Err(ErrorCode::KvsFileReadError);
+    /// Get number of KVS or hash file of provided instance.
+    /// Number is snapshot ID for `RotationStrategy::Shift` and creation time for
+    /// `RotationStrategy::Timestamped`.
+    ///
+    /// # Return Values
+    ///   * Some: Number in file name
+    ///   * None: File name is not a KVS or hash file of the instance
+    fn parse_snapshot_file_name(instance_id: InstanceId, file_name: &str) -> Option<usize> {
+        let rest = file_name.strip_prefix(&format!("kvs_{instance_id}_"))?;
+        let (number, extension) = rest.split_once('.')?;
+        match extension {
+            "json" | "json.gz" | "hash" => number.parse().ok(),
+            _ => None,
         }
-        if !check_extension(hash_path, "hash") {
-            return Err(ErrorCode::KvsHashFileReadError);
+    }
+
+    /// Find KVS and hash files of instance, together with number in file name.
+    fn snapshot_files(&self, instance_id: InstanceId) -> Result<Vec<(usize, PathBuf)>, ErrorCode> {
+        let dir = Self::dir_path(&self.working_dir);
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let file_name = entry?.file_name();
+            let number = file_name
+                .to_str()
+                .and_then(|name| Self::parse_snapshot_file_name(instance_id, name));
+            if let Some(number) = number {
+                files.push((number, self.working_dir.join(file_name)));
+            }
         }
 
-        Ok(())
+        Ok(files)
     }
 
-    pub(super) fn load(kvs_path: &Path, hash_path: &Path) -> Result<KvsMap, ErrorCode> {
-        Self::check_path_extensions(kvs_path, hash_path)?;
+    /// Find KVS and hash files of instance with snapshot ID beyond `snapshot_max_count`.
+    fn excess_snapshot_files(&self, instance_id: InstanceId) -> Result<Vec<PathBuf>, ErrorCode> {
+        let mut paths: Vec<PathBuf> = self
+            .snapshot_files(instance_id)?
+            .into_iter()
+            .filter(|(number, _)| *number >= self.snapshot_max_count())
+            .map(|(_, path)| path)
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
 
-        // Load KVS file.
-        let json_str = fs::read_to_string(kvs_path)?;
+    /// Get creation times of timestamped snapshots of instance, newest first.
+    /// Missing working directory is treated as no snapshots.
+    fn snapshot_timestamps(&self, instance_id: InstanceId) -> Vec<usize> {
+        let files = match self.snapshot_files(instance_id) {
+            Ok(files) => files,
+            Err(ErrorCode::FileNotFound) => Vec::new(),
+            Err(e) => {
+                eprintln!("warn: failed to list snapshots: {e:?}");
+                Vec::new()
+            },
+        };
 
-        // Load hash file.
-        let hash_bytes = fs::read(hash_path)?;
+        let mut timestamps: Vec<usize> = files.into_iter().map(|(number, _)| number).collect();
+        timestamps.sort_unstable_by(|a, b| b.cmp(a));
+        timestamps.dedup();
+        timestamps
+    }
 
-        // Perform hash check.
-        if hash_bytes.len() != 4 {
-            return Err(ErrorCode::ValidationFailed);
+    /// Get file name stem (without extension) of provided snapshot.
+    fn snapshot_file_stem(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> String {
+        let number = match self.rotation_strategy {
+            RotationStrategy::Shift => Some(snapshot_id.0),
+            RotationStrategy::Timestamped => self.snapshot_timestamps(instance_id).get(snapshot_id.0).copied(),
+        };
+
+        match number {
+            Some(number) => format!("kvs_{instance_id}_{number}"),
+            // Name never matching a snapshot file.
+            None => format!("kvs_{instance_id}_absent_{snapshot_id}"),
         }
+    }
 
-        let file_hash = u32::from_be_bytes([hash_bytes[0], hash_bytes[1], hash_bytes[2], hash_bytes[3]]);
-        let hash_kvs = adler32::RollingAdler32::from_buffer(json_str.as_bytes()).hash();
+    /// Get paths of KVS and hash file written by next flush.
+    fn new_snapshot_paths(&self, instance_id: InstanceId) -> (PathBuf, PathBuf) {
+        let stem = match self.rotation_strategy {
+            RotationStrategy::Shift => self.snapshot_file_stem(instance_id, SnapshotId(0)),
+            RotationStrategy::Timestamped => {
+                let now = self
+                    .clock
+                    .now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| usize::try_from(d.as_millis()).unwrap_or(usize::MAX));
+
+                // New snapshot must be the newest, also if clock went backwards.
+                let newest = self.snapshot_timestamps(instance_id).first().map_or(0, |t| t + 1);
+                format!("kvs_{instance_id}_{}", now.max(newest))
+            },
+        };
+        (
+            self.kvs_path_from_stem(&stem),
+            self.working_dir.join(format!("{stem}.hash")),
+        )
+    }
 
-        if hash_kvs != file_hash {
-            return Err(ErrorCode::ValidationFailed);
+    /// Remove timestamped snapshots beyond `snapshot_max_count`.
+    /// Failures are reported and don't fail the flush.
+    fn remove_old_snapshots(&self, instance_id: InstanceId) {
+        if self.rotation_strategy != RotationStrategy::Timestamped {
+            return;
         }
 
-        // Parse KVS from string to `JsonValue`.
-        let json_value = Self::parse(&json_str)?;
-
-        // Cast from `JsonValue` to `KvsValue`.
-        let kvs_value = KvsValue::from(json_value);
-        if let KvsValue::Object(kvs_map) = kvs_value {
-            Ok(kvs_map)
-        } else {
-            Err(ErrorCode::JsonParserError)
+        let Some(oldest_kept) = self
+            .snapshot_timestamps(instance_id)
+            .get(self.snapshot_max_count().saturating_sub(1))
+            .copied()
+        else {
+            return;
+        };
+
+        let files = self.snapshot_files(instance_id).unwrap_or_default();
+        for (_, path) in files.into_iter().filter(|(number, _)| *number < oldest_kept) {
+            match fs::remove_file(&path) {
+                Ok(()) => println!("removed old snapshot file: {}", path.display()),
+                Err(e) => eprintln!("warn: failed to remove old snapshot file {}: {e}", path.display()),
+            }
         }
     }
 
-    pub(super) fn save(kvs_map: &KvsMap, kvs_path: &Path, hash_path: &Path) -> Result<(), ErrorCode> {
-        Self::check_path_extensions(kvs_path, hash_path)?;
+    /// Report or remove excess snapshot files of instance, only on first call per instance.
+    /// Failures are reported and don't fail the flush.
+    fn handle_excess_snapshots(&self, instance_id: InstanceId) {
+        // Timestamped snapshots beyond limit are removed on each flush.
+        if self.rotation_strategy == RotationStrategy::Timestamped {
+            return;
+        }
 
-        // Cast from `KvsValue` to `JsonValue`.
-        let kvs_value = KvsValue::Object(kvs_map.clone());
-        let json_value = JsonValue::from(kvs_value);
+        // Set guards no invariants, it cannot be left inconsistent.
+        let first_check = self
+            .checked_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(instance_id);
+        if !first_check {
+            return;
+        }
 
-        // Stringify `JsonValue` and save to KVS file.
-        let json_str = Self::stringify(&json_value)?;
-        fs::write(kvs_path, &json_str)?;
+        let paths = match self.excess_snapshot_files(instance_id) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("warn: failed to check excess snapshots: {e:?}");
+                return;
+            },
+        };
+
+        let prune = self.prune_excess_snapshots || self.retention_reduced.load(Ordering::Relaxed);
+        for path in paths {
+            if !prune {
+                eprintln!("warn: excess snapshot file: {}", path.display());
+            } else if let Err(e) = fs::remove_file(&path) {
+                eprintln!("warn: failed to remove excess snapshot file {}: {e}", path.display());
+            } else {
+                println!("removed excess snapshot file: {}", path.display());
+            }
+        }
+    }
 
-        // Generate hash and save to hash file.
-        let hash = adler32::RollingAdler32::from_buffer(json_str.as_bytes()).hash();
-        fs::write(hash_path, hash.to_be_bytes())?;
+    /// Size of value serialized as compact t-tagged JSON.
+    fn value_size(value: &KvsValue) -> Result<usize, ErrorCode> {
+        Ok(Self::stringify(&JsonValue::from(value.clone()), false)?.len())
+    }
 
+    /// Check sizes of all values against `max_value_bytes`.
+    ///
+    /// # Return Values
+    ///   * Ok: All values within limit, also if limit is not set
+    ///   * `ErrorCode::QuotaExceeded`: Value exceeding limit found
+    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize value
+    fn check_value_sizes(&self, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+        let Some(max_value_bytes) = self.max_value_bytes else {
+            return Ok(());
+        };
+
+        for (key, value) in kvs_map {
+            let size = Self::value_size(value)?;
+            if size > max_value_bytes {
+                eprintln!("error: value of key {key} exceeds size limit: {size} > {max_value_bytes} bytes");
+                return Err(ErrorCode::QuotaExceeded);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check size of serialized KVS against `max_store_bytes`.
+    ///
+    /// # Return Values
+    ///   * Ok: Size within limit, also if limit is not set
+    ///   * `ErrorCode::QuotaExceeded`: Size exceeds limit
+    fn check_store_size(&self, size: usize) -> Result<(), ErrorCode> {
+        match self.max_store_bytes {
+            Some(max_store_bytes) if size > max_store_bytes => {
+                eprintln!("error: KVS exceeds size limit: {size} > {max_store_bytes} bytes");
+                Err(ErrorCode::QuotaExceeded)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check KVS against `max_value_bytes` and `max_store_bytes`.
+    fn check_quotas(&self, kvs_map: &KvsMap, expiry_map: &KvsExpiryMap) -> Result<(), ErrorCode> {
+        self.check_value_sizes(kvs_map)?;
+        if self.max_store_bytes.is_some() {
+            let json_str = Self::to_json_string_with_expiry(kvs_map, expiry_map, self.pretty)?;
+            self.check_store_size(json_str.len())?;
+        }
+        Ok(())
+    }
+
+    /// Check path has provided extension.
+    fn check_extension(path: &Path, extension: &str) -> bool {
+        let ext = path.extension();
+        ext.is_some_and(|ep| ep.to_str().is_some_and(|es| es == extension))
+    }
+
+    /// Check path points to gzip-compressed JSON file (`.json.gz`).
+    fn is_gzip_path(path: &Path) -> bool {
+        Self::check_extension(path, "gz")
+            && path
+                .file_stem()
+                .is_some_and(|s| Self::check_extension(Path::new(s), "json"))
+    }
+
+    /// Get storage layer matching file extension.
+    /// Used for files not stored with backend storage layer, e.g. defaults.
+    fn path_layer(path: &Path) -> Box<dyn StorageLayer> {
+        if Self::is_gzip_path(path) {
+            Box::new(GzipLayer::new(Box::new(FileLayer)))
+        } else {
+            Box::new(FileLayer)
+        }
+    }
+
+    /// Check path extensions are correct, wrong extension is reported as
+    /// `ErrorCode::InvalidFileExtension` regardless of file presence.
+    fn check_path_extensions(kvs_path: &Path, hash_path: &Path) -> Result<(), ErrorCode> {
+        if !Self::check_extension(kvs_path, "json") && !Self::is_gzip_path(kvs_path) {
+            eprintln!("error: invalid KVS file extension: {}", kvs_path.display());
+            return Err(ErrorCode::InvalidFileExtension);
+        }
+        if !Self::check_extension(hash_path, "hash") {
+            eprintln!("error: invalid hash file extension: {}", hash_path.display());
+            return Err(ErrorCode::InvalidFileExtension);
+        }
+
+        Ok(())
+    }
+
+    /// Load raw file content using storage layer and validate it against hash file.
+    /// Hash is computed over content returned by the storage layer.
+    /// Missing hash file is accepted if `require_hash` is not set.
+    fn load_bytes(
+        storage_layer: &dyn StorageLayer,
+        kvs_path: &Path,
+        hash_path: &Path,
+        require_hash: bool,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        Self::check_path_extensions(kvs_path, hash_path)?;
+
+        let bytes = storage_layer.read(kvs_path)?;
+        record_bytes!(bytes.len());
+        let hash_kvs = adler32::RollingAdler32::from_buffer(&bytes).hash();
+        Self::verify_hash(hash_kvs, hash_path, require_hash)?;
+
+        Ok(bytes)
+    }
+
+    /// Validate hash of loaded content against hash file.
+    /// Missing hash file is accepted if `require_hash` is not set.
+    ///
+    /// # Return Values
+    ///   * Ok: Hash matches, or hash file is missing and not required
+    ///   * `ErrorCode::HashFileNotFound`: Hash file is missing and required
+    ///   * `ErrorCode::ValidationFailed`: Hash doesn't match or hash file is malformed
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn verify_hash(hash_kvs: u32, hash_path: &Path, require_hash: bool) -> Result<(), ErrorCode> {
+        // Load hash file.
+        // Content was already read, missing hash file is not reported as `FileNotFound`, which
+        // would be treated as missing KVS.
+        let hash_bytes = match fs::read(hash_path) {
+            Ok(hash_bytes) => hash_bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if require_hash {
+                    eprintln!("error: hash file not found: {}", hash_path.display());
+                    return Err(ErrorCode::HashFileNotFound);
+                }
+                eprintln!(
+                    "warn: hash file not found, integrity not verified: {}",
+                    hash_path.display()
+                );
+                return Ok(());
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        // Perform hash check.
+        if hash_bytes.len() != 4 {
+            return Err(ErrorCode::ValidationFailed);
+        }
+
+        let file_hash = u32::from_be_bytes([hash_bytes[0], hash_bytes[1], hash_bytes[2], hash_bytes[3]]);
+        if hash_kvs != file_hash {
+            return Err(ErrorCode::ValidationFailed);
+        }
+
+        Ok(())
+    }
+
+    /// Load file content as `JsonValue` and validate it against hash file.
+    ///
+    /// Content is parsed while being read, so the whole file is never kept in memory if the
+    /// storage layer supports streaming. Hash is computed over content returned by the storage
+    /// layer in the same pass and checked before content is used.
+    /// Duplicate keys within an object are rejected.
+    fn load_json(
+        storage_layer: &dyn StorageLayer,
+        kvs_path: &Path,
+        hash_path: &Path,
+        require_hash: bool,
+    ) -> Result<JsonValue, ErrorCode> {
+        Self::check_path_extensions(kvs_path, hash_path)?;
+
+        // Parse content, remaining content is read to complete the hash.
+        let mut chars = HashingChars::new(storage_layer.reader(kvs_path)?);
+        let mut checked_chars = DuplicateKeyCheck::new(&mut chars);
+        let parse_result = JsonParser::new(&mut checked_chars).parse();
+        let duplicate_check = checked_chars.finish();
+        let hash_kvs = chars.finish()?;
+
+        // Corrupted content is reported as such, even if it also fails to parse.
+        Self::verify_hash(hash_kvs, hash_path, require_hash)?;
+        duplicate_check?;
+        Ok(parse_result?)
+    }
+
+    /// Save raw file content using storage layer and its hash.
+    /// Hash is computed over content passed to the storage layer and returned.
+    fn save_bytes(
+        storage_layer: &dyn StorageLayer,
+        bytes: &[u8],
+        kvs_path: &Path,
+        hash_path: &Path,
+        sync_policy: SyncPolicy,
+    ) -> Result<u32, ErrorCode> {
+        Self::check_path_extensions(kvs_path, hash_path)?;
+
+        storage_layer.write(kvs_path, bytes)?;
+        record_bytes!(bytes.len());
+
+        // Generate hash and save to hash file.
+        let hash = adler32::RollingAdler32::from_buffer(bytes).hash();
+        fs::write(hash_path, hash.to_be_bytes())?;
+
+        Self::sync(kvs_path, hash_path, sync_policy)?;
+        Ok(hash)
+    }
+
+    /// Sync written KVS and hash files, and their directory, according to policy.
+    fn sync(kvs_path: &Path, hash_path: &Path, sync_policy: SyncPolicy) -> Result<(), ErrorCode> {
+        if sync_policy == SyncPolicy::None {
+            return Ok(());
+        }
+
+        fs::File::open(kvs_path)?.sync_all()?;
+        fs::File::open(hash_path)?.sync_all()?;
+        if sync_policy == SyncPolicy::DataAndDir {
+            let dir = Self::dir_path(kvs_path.parent().unwrap_or(Path::new("")));
+            fs::File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Check snapshot with given ID exists and can be restored.
+    fn check_restore_id(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
+        // fail if the snapshot ID is the current KVS
+        if snapshot_id == SnapshotId(0) {
+            eprintln!("error: tried to restore current KVS as snapshot");
+            return Err(ErrorCode::InvalidSnapshotId);
+        }
+
+        if self.snapshot_count(instance_id) < snapshot_id.0 {
+            eprintln!("error: tried to restore a non-existing snapshot");
+            return Err(ErrorCode::InvalidSnapshotId);
+        }
+        Ok(())
+    }
+
+    /// Remember hash of content flushed as snapshot 0 of instance.
+    fn record_flush_hash(&self, instance_id: InstanceId, hash: u32) {
+        // Map guards no invariants, it cannot be left inconsistent.
+        let _ = self
+            .flush_hashes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(instance_id, hash);
+    }
+
+    /// Convert expiry instant to milliseconds since UNIX epoch.
+    /// Instants before UNIX epoch are stored as 0 - already expired.
+    fn expiry_to_json(expiry: SystemTime) -> JsonValue {
+        let millis = expiry.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        JsonValue::Number(millis as f64)
+    }
+
+    /// Convert milliseconds since UNIX epoch to expiry instant.
+    fn expiry_from_json(value: &JsonValue) -> Result<SystemTime, ErrorCode> {
+        match value {
+            JsonValue::Number(millis) if millis.is_finite() && *millis >= 0.0 => {
+                Ok(UNIX_EPOCH + Duration::from_millis(*millis as u64))
+            },
+            _ => {
+                eprintln!("error: invalid expiry: {value:?}");
+                Err(ErrorCode::JsonParserError)
+            },
+        }
+    }
+
+    /// Get top-level entries of the stored map, t-tagged or not.
+    fn top_level_entries(json_value: &mut JsonValue) -> Option<&mut HashMap<String, JsonValue>> {
+        let JsonValue::Object(obj) = json_value else {
+            return None;
+        };
+        if !obj.contains_key("t") {
+            return Some(obj);
+        }
+        match obj.get_mut("v") {
+            Some(JsonValue::Object(entries)) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Parse t-tagged JSON string into `KvsMap` and `KvsExpiryMap`.
+    pub(crate) fn from_json_str_with_expiry(json_str: &str) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        Self::split_expiry(Self::parse(json_str)?, false)
+    }
+
+    /// Parse t-tagged JSON string into `KvsMap` and `KvsExpiryMap`, validating types of all values.
+    /// Unlike `from_json_str_with_expiry`, unknown or mismatched types are rejected instead of being
+    /// replaced with `KvsValue::Null`.
+    pub(crate) fn from_json_str_validated(json_str: &str) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        Self::split_expiry(Self::parse(json_str)?, true)
+    }
+
+    /// Check integer stored as JSON number is in range of the target type.
+    fn validate_integer(value: f64, min: f64, max: f64) -> Result<(), ErrorCode> {
+        if value.fract() == 0.0 && value >= min && value <= max {
+            Ok(())
+        } else {
+            eprintln!("error: integer value out of range: {value}");
+            Err(ErrorCode::ConversionFailed)
+        }
+    }
+
+    /// Check t-tagged value and all its nested values have known types matching their content.
+    fn validate_tagged(value: &JsonValue) -> Result<(), ErrorCode> {
+        let tagged = match value {
+            JsonValue::Object(obj) if obj.len() == 2 => (obj.get("t"), obj.get("v")),
+            _ => (None, None),
+        };
+        match tagged {
+            (Some(JsonValue::String(type_str)), Some(v)) => match (type_str.as_str(), v) {
+                ("i8", JsonValue::Number(n)) => Self::validate_integer(*n, i8::MIN as f64, i8::MAX as f64),
+                ("u8", JsonValue::Number(n)) => Self::validate_integer(*n, 0.0, u8::MAX as f64),
+                ("i16", JsonValue::Number(n)) => Self::validate_integer(*n, i16::MIN as f64, i16::MAX as f64),
+                ("u16", JsonValue::Number(n)) => Self::validate_integer(*n, 0.0, u16::MAX as f64),
+                ("i32", JsonValue::Number(n)) => Self::validate_integer(*n, i32::MIN as f64, i32::MAX as f64),
+                ("u32", JsonValue::Number(n)) => Self::validate_integer(*n, 0.0, u32::MAX as f64),
+                ("i64", JsonValue::Number(n)) => Self::validate_integer(*n, i64::MIN as f64, i64::MAX as f64),
+                ("u64", JsonValue::Number(n)) => Self::validate_integer(*n, 0.0, u64::MAX as f64),
+                ("f32" | "f64", JsonValue::String(s)) if non_finite_from_str(s).is_some() => Ok(()),
+                ("f32", JsonValue::Number(_))
+                | ("f64", JsonValue::Number(_))
+                | ("bool", JsonValue::Boolean(_))
+                | ("str", JsonValue::String(_))
+                | ("null", JsonValue::Null) => Ok(()),
+                ("arr", JsonValue::Array(v)) => v.iter().try_for_each(Self::validate_tagged),
+                ("obj", JsonValue::Object(v)) => v.values().try_for_each(Self::validate_tagged),
+                (type_str, _) => {
+                    eprintln!("error: invalid value of type \"{type_str}\"");
+                    Err(ErrorCode::ConversionFailed)
+                },
+            },
+            _ => {
+                eprintln!("error: value is not t-tagged");
+                Err(ErrorCode::ConversionFailed)
+            },
+        }
+    }
+
+    /// Extract expiry of top-level entries and convert remaining `JsonValue` into `KvsMap`.
+    fn split_expiry(mut json_value: JsonValue, validate: bool) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        // Extract expiry of top-level t-tagged entries.
+        let mut expiry_map = KvsExpiryMap::new();
+        if let Some(entries) = Self::top_level_entries(&mut json_value) {
+            for (key, entry) in entries.iter_mut() {
+                if let JsonValue::Object(entry) = entry {
+                    if !entry.contains_key("t") {
+                        continue;
+                    }
+                    if let Some(expiry) = entry.remove("exp") {
+                        expiry_map.insert(key.clone(), Self::expiry_from_json(&expiry)?);
+                    }
+                }
+            }
+        }
+
+        if validate {
+            Self::validate_tagged(&json_value)?;
+        }
+
+        Ok((Self::to_kvs_map(json_value)?, expiry_map))
+    }
+
+    /// Cast parsed `JsonValue` into `KvsMap`.
+    /// Top-level value other than object is reported as `ErrorCode::InvalidStorageFormat`.
+    fn to_kvs_map(json_value: JsonValue) -> Result<KvsMap, ErrorCode> {
+        match KvsValue::from(json_value) {
+            KvsValue::Object(kvs_map) => Ok(kvs_map),
+            _ => {
+                eprintln!("error: top-level JSON value is not an object");
+                Err(ErrorCode::InvalidStorageFormat)
+            },
+        }
+    }
+
+    /// Stringify `KvsMap` and `KvsExpiryMap` into t-tagged JSON string, indented if `pretty` is set.
+    pub(crate) fn to_json_string_with_expiry(
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+        pretty: bool,
+    ) -> Result<String, ErrorCode> {
+        // Cast from `KvsValue` to `JsonValue`.
+        let kvs_value = KvsValue::Object(kvs_map.clone());
+        let mut json_value = JsonValue::from(kvs_value);
+
+        // Add expiry to top-level entries.
+        if let Some(entries) = Self::top_level_entries(&mut json_value) {
+            for (key, expiry) in expiry_map {
+                if let Some(JsonValue::Object(entry)) = entries.get_mut(key) {
+                    entry.insert("exp".to_string(), Self::expiry_to_json(*expiry));
+                }
+            }
+        }
+
+        // Stringify `JsonValue`.
+        Self::stringify(&json_value, pretty)
+    }
+
+    /// Parse t-tagged JSON string into `KvsMap`.
+    pub(crate) fn from_json_str(json_str: &str) -> Result<KvsMap, ErrorCode> {
+        // Parse KVS from string to `JsonValue`.
+        let json_value = Self::parse(json_str)?;
+
+        // Cast from `JsonValue` to `KvsMap`.
+        Self::to_kvs_map(json_value)
+    }
+
+    /// Stringify `KvsMap` into t-tagged JSON string.
+    pub(crate) fn to_json_string(kvs_map: &KvsMap) -> Result<String, ErrorCode> {
+        // Cast from `KvsValue` to `JsonValue`.
+        let kvs_value = KvsValue::Object(kvs_map.clone());
+        let json_value = JsonValue::from(kvs_value);
+
+        // Stringify `JsonValue`.
+        Self::stringify(&json_value, false)
+    }
+
+    /// Load KVS using storage layer matching file extension.
+    pub(super) fn load(kvs_path: &Path, hash_path: &Path, require_hash: bool) -> Result<KvsMap, ErrorCode> {
+        Self::load_with_layer(&*Self::path_layer(kvs_path), kvs_path, hash_path, require_hash)
+    }
+
+    fn load_with_layer(
+        storage_layer: &dyn StorageLayer,
+        kvs_path: &Path,
+        hash_path: &Path,
+        require_hash: bool,
+    ) -> Result<KvsMap, ErrorCode> {
+        let json_value = Self::load_json(storage_layer, kvs_path, hash_path, require_hash)?;
+        Self::to_kvs_map(json_value)
+    }
+
+    /// Save compact KVS without expiry, used to prepare files in tests.
+    /// Storage layer matching file extension is used.
+    #[cfg(test)]
+    pub(super) fn save(kvs_map: &KvsMap, kvs_path: &Path, hash_path: &Path) -> Result<(), ErrorCode> {
+        Self::check_path_extensions(kvs_path, hash_path)?;
+        let json_str = Self::to_json_string(kvs_map)?;
+        Self::save_bytes(
+            &*Self::path_layer(kvs_path),
+            json_str.as_bytes(),
+            kvs_path,
+            hash_path,
+            SyncPolicy::None,
+        )?;
         Ok(())
     }
 
+    fn load_with_expiry(&self, kvs_path: &Path, hash_path: &Path) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        let json_value = Self::load_json(&*self.storage_layer, kvs_path, hash_path, self.require_hash)?;
+        Self::split_expiry(json_value, false)
+    }
+
+    fn save_with_expiry(
+        &self,
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+        kvs_path: &Path,
+        hash_path: &Path,
+    ) -> Result<u32, ErrorCode> {
+        Self::check_path_extensions(kvs_path, hash_path)?;
+        let json_str = Self::to_json_string_with_expiry(kvs_map, expiry_map, self.pretty)?;
+        Self::save_bytes(
+            &*self.storage_layer,
+            json_str.as_bytes(),
+            kvs_path,
+            hash_path,
+            self.sync_policy,
+        )
+    }
+
     /// Get KVS file name.
     pub fn kvs_file_name(instance_id: InstanceId, snapshot_id: SnapshotId) -> String {
         format!("kvs_{instance_id}_{snapshot_id}.json")
     }
 
     /// Get KVS file path in working directory.
+    /// `.gz` suffix is added if compression is enabled.
+    /// For `RotationStrategy::Timestamped`, file of snapshot with given age is looked up.
     pub fn kvs_file_path(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> PathBuf {
-        self.working_dir.join(Self::kvs_file_name(instance_id, snapshot_id))
+        self.kvs_path_from_stem(&self.snapshot_file_stem(instance_id, snapshot_id))
+    }
+
+    fn kvs_path_from_stem(&self, stem: &str) -> PathBuf {
+        match self.compression {
+            Compression::None => self.working_dir.join(format!("{stem}.json")),
+            Compression::Gzip => self.working_dir.join(format!("{stem}.json.gz")),
+        }
     }
 
     /// Get hash file name.
@@ -333,8 +1587,10 @@ impl JsonBackend {
     }
 
     /// Get hash file path in working directory.
+    /// For `RotationStrategy::Timestamped`, file of snapshot with given age is looked up.
     pub fn hash_file_path(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> PathBuf {
-        self.working_dir.join(Self::hash_file_name(instance_id, snapshot_id))
+        let stem = self.snapshot_file_stem(instance_id, snapshot_id);
+        self.working_dir.join(format!("{stem}.hash"))
     }
 
     /// Get defaults file name.
@@ -356,40 +1612,238 @@ impl JsonBackend {
     pub fn defaults_hash_file_path(&self, instance_id: InstanceId) -> PathBuf {
         self.working_dir.join(Self::defaults_hash_file_name(instance_id))
     }
+
+    /// Save defaults file and its hash, replacing existing ones.
+    /// Defaults are always stored uncompressed, pretty-printing and sync policy are applied.
+    ///
+    /// # Parameters
+    ///   * `instance_id`: Instance ID
+    ///   * `defaults_map`: Default values
+    ///
+    /// # Return Values
+    ///   * Ok: Defaults saved
+    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize defaults
+    ///   * `ErrorCode::FileNotFound`: Working directory not found
+    ///   * `ErrorCode::UnmappedError`: Failed to write files
+    pub fn save_defaults(&self, instance_id: InstanceId, defaults_map: &KvsMap) -> Result<(), ErrorCode> {
+        let defaults_path = self.defaults_file_path(instance_id);
+        let defaults_hash_path = self.defaults_hash_file_path(instance_id);
+        let json_str = Self::to_json_string_with_expiry(defaults_map, &KvsExpiryMap::new(), self.pretty)?;
+        Self::save_bytes(
+            &*Self::path_layer(&defaults_path),
+            json_str.as_bytes(),
+            &defaults_path,
+            &defaults_hash_path,
+            self.sync_policy,
+        )?;
+        Ok(())
+    }
+}
+
+/// Chunked flush state.
+enum JsonChunkedFlushState {
+    /// Rotate snapshot to provided index.
+    Rotate(usize),
+
+    /// Save current KVS as snapshot 0.
+    Save,
+
+    /// Flush completed or failed.
+    Done,
+}
+
+/// Chunked flush of `JsonBackend`.
+/// Each step performs either single snapshot rotation or save of current KVS.
+pub struct JsonChunkedFlush {
+    backend: Arc<JsonBackend>,
+    instance_id: InstanceId,
+    kvs_map: KvsMap,
+    expiry_map: KvsExpiryMap,
+    state: JsonChunkedFlushState,
+
+    /// Quotas are checked on first step, before any snapshot is rotated.
+    quotas_checked: bool,
+}
+
+impl JsonChunkedFlush {
+    fn new(backend: Arc<JsonBackend>, instance_id: InstanceId, kvs_map: KvsMap, expiry_map: KvsExpiryMap) -> Self {
+        let state = match (backend.rotation_strategy, backend.snapshot_max_count()) {
+            (RotationStrategy::Timestamped, _) | (_, 0 | 1) => JsonChunkedFlushState::Save,
+            (RotationStrategy::Shift, n) => JsonChunkedFlushState::Rotate(n - 1),
+        };
+        Self {
+            backend,
+            instance_id,
+            kvs_map,
+            expiry_map,
+            state,
+            quotas_checked: false,
+        }
+    }
+}
+
+impl ChunkedFlush for JsonChunkedFlush {
+    fn step(&mut self) -> Result<FlushProgress, ErrorCode> {
+        if !self.quotas_checked {
+            self.quotas_checked = true;
+            if let Err(e) = self.backend.check_quotas(&self.kvs_map, &self.expiry_map) {
+                self.state = JsonChunkedFlushState::Done;
+                return Err(e);
+            }
+        }
+
+        match self.state {
+            JsonChunkedFlushState::Rotate(idx) => {
+                if let Err(e) = self.backend.snapshot_rotate_single(self.instance_id, idx) {
+                    eprintln!("error: snapshot_rotate failed: {e:?}");
+                    self.state = JsonChunkedFlushState::Done;
+                    return Err(e);
+                }
+                self.state = if idx > 1 {
+                    JsonChunkedFlushState::Rotate(idx - 1)
+                } else {
+                    JsonChunkedFlushState::Save
+                };
+                Ok(FlushProgress::Pending)
+            },
+            JsonChunkedFlushState::Save => {
+                self.state = JsonChunkedFlushState::Done;
+                let (kvs_path, hash_path) = self.backend.new_snapshot_paths(self.instance_id);
+                let hash = self
+                    .backend
+                    .save_with_expiry(&self.kvs_map, &self.expiry_map, &kvs_path, &hash_path)
+                    .map_err(|e| {
+                        eprintln!("error: save failed: {e:?}");
+                        e
+                    })?;
+                self.backend.record_flush_hash(self.instance_id, hash);
+                self.backend.remove_old_snapshots(self.instance_id);
+                Ok(FlushProgress::Done)
+            },
+            JsonChunkedFlushState::Done => Ok(FlushProgress::Done),
+        }
+    }
 }
 
 impl KvsBackend for JsonBackend {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            snapshots: true,
+            persistence: true,
+            defaults: true,
+            integrity: true,
+        }
+    }
+
     fn load_kvs(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        span!("load", instance_id, snapshot_id = snapshot_id);
+        self.repair_rotation(instance_id);
         let kvs_path = self.kvs_file_path(instance_id, snapshot_id);
         let hash_path = self.hash_file_path(instance_id, snapshot_id);
-        Self::load(&kvs_path, &hash_path)
+        Self::load_with_layer(&*self.storage_layer, &kvs_path, &hash_path, self.require_hash)
     }
 
     fn load_defaults(&self, instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
         let defaults_path = self.defaults_file_path(instance_id);
         let defaults_hash_path = self.defaults_hash_file_path(instance_id);
-        Self::load(&defaults_path, &defaults_hash_path)
+        Self::load(&defaults_path, &defaults_hash_path, self.require_hash)
     }
 
     fn flush(&self, instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+        self.flush_with_expiry(instance_id, kvs_map, &KvsExpiryMap::new())
+    }
+
+    fn load_kvs_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        span!("load", instance_id, snapshot_id = snapshot_id);
+        self.repair_rotation(instance_id);
+        let kvs_path = self.kvs_file_path(instance_id, snapshot_id);
+        let hash_path = self.hash_file_path(instance_id, snapshot_id);
+        self.load_with_expiry(&kvs_path, &hash_path)
+    }
+
+    fn flush_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+    ) -> Result<(), ErrorCode> {
+        span!("flush", instance_id);
+        self.check_quotas(kvs_map, expiry_map)?;
+        self.handle_excess_snapshots(instance_id);
+        self.repair_rotation(instance_id);
         self.snapshot_rotate(instance_id).map_err(|e| {
             eprintln!("error: snapshot_rotate failed: {e:?}");
             e
         })?;
-        let snapshot_id = SnapshotId(0);
+        let (kvs_path, hash_path) = self.new_snapshot_paths(instance_id);
+        let hash = self
+            .save_with_expiry(kvs_map, expiry_map, &kvs_path, &hash_path)
+            .map_err(|e| {
+                eprintln!("error: save failed: {e:?}");
+                e
+            })?;
+        self.record_flush_hash(instance_id, hash);
+        self.remove_old_snapshots(instance_id);
+        Ok(())
+    }
+
+    fn flush_chunked(
+        self: Arc<Self>,
+        instance_id: InstanceId,
+        kvs_map: KvsMap,
+        expiry_map: KvsExpiryMap,
+    ) -> Box<dyn ChunkedFlush> {
+        self.handle_excess_snapshots(instance_id);
+        self.repair_rotation(instance_id);
+        Box::new(JsonChunkedFlush::new(self, instance_id, kvs_map, expiry_map))
+    }
+
+    fn load_kvs_bytes(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<Vec<u8>, ErrorCode> {
+        span!("load", instance_id, snapshot_id = snapshot_id);
+        self.repair_rotation(instance_id);
         let kvs_path = self.kvs_file_path(instance_id, snapshot_id);
         let hash_path = self.hash_file_path(instance_id, snapshot_id);
-        Self::save(kvs_map, &kvs_path, &hash_path).map_err(|e| {
-            eprintln!("error: save failed: {e:?}");
+        Self::load_bytes(&*self.storage_layer, &kvs_path, &hash_path, self.require_hash)
+    }
+
+    fn flush_bytes(&self, instance_id: InstanceId, bytes: &[u8]) -> Result<(), ErrorCode> {
+        self.check_store_size(bytes.len())?;
+        self.handle_excess_snapshots(instance_id);
+        self.repair_rotation(instance_id);
+        self.snapshot_rotate(instance_id).map_err(|e| {
+            eprintln!("error: snapshot_rotate failed: {e:?}");
             e
         })?;
+        let (kvs_path, hash_path) = self.new_snapshot_paths(instance_id);
+        let hash =
+            Self::save_bytes(&*self.storage_layer, bytes, &kvs_path, &hash_path, self.sync_policy).map_err(|e| {
+                eprintln!("error: save failed: {e:?}");
+                e
+            })?;
+        self.record_flush_hash(instance_id, hash);
+        self.remove_old_snapshots(instance_id);
         Ok(())
     }
 
     fn snapshot_count(&self, instance_id: InstanceId) -> usize {
+        if self.rotation_strategy == RotationStrategy::Timestamped {
+            return self
+                .snapshot_timestamps(instance_id)
+                .len()
+                .min(self.snapshot_max_count());
+        }
+
         let mut count = 0;
 
-        for idx in 0..self.snapshot_max_count {
+        for idx in 0..self.snapshot_max_count() {
             let snapshot_id = SnapshotId(idx);
             let snapshot_path = self.kvs_file_path(instance_id, snapshot_id);
             if !snapshot_path.exists() {
@@ -402,24 +1856,84 @@ impl KvsBackend for JsonBackend {
         count
     }
 
-    fn snapshot_max_count(&self) -> usize {
-        self.snapshot_max_count
+    fn snapshots(&self, instance_id: InstanceId) -> Vec<SnapshotId> {
+        (0..self.snapshot_max_count())
+            .map(SnapshotId)
+            .filter(|snapshot_id| {
+                self.kvs_file_path(instance_id, *snapshot_id).exists()
+                    && self.hash_file_path(instance_id, *snapshot_id).exists()
+            })
+            .collect()
     }
 
-    fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
-        // fail if the snapshot ID is the current KVS
-        if snapshot_id == SnapshotId(0) {
-            eprintln!("error: tried to restore current KVS as snapshot");
-            return Err(ErrorCode::InvalidSnapshotId);
-        }
+    fn verify(&self, instance_id: InstanceId) -> Result<Vec<(SnapshotId, bool)>, ErrorCode> {
+        Ok(self
+            .snapshots(instance_id)
+            .into_iter()
+            .map(|snapshot_id| {
+                // Hash is always required, regardless of `require_hash`.
+                let kvs_path = self.kvs_file_path(instance_id, snapshot_id);
+                let hash_path = self.hash_file_path(instance_id, snapshot_id);
+                let result = Self::load_with_layer(&*self.storage_layer, &kvs_path, &hash_path, true);
+                if let Err(e) = &result {
+                    eprintln!("warn: snapshot {snapshot_id} invalid: {e:?}");
+                }
+                (snapshot_id, result.is_ok())
+            })
+            .collect())
+    }
 
-        if self.snapshot_count(instance_id) < snapshot_id.0 {
-            eprintln!("error: tried to restore a non-existing snapshot");
-            return Err(ErrorCode::InvalidSnapshotId);
+    fn last_flush_hash(&self, instance_id: InstanceId) -> Option<u32> {
+        self.flush_hashes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&instance_id)
+            .copied()
+    }
+
+    fn snapshot_max_count(&self) -> usize {
+        self.snapshot_max_count.load(Ordering::Relaxed)
+    }
+
+    fn set_snapshot_max_count(&self, snapshot_max_count: usize) -> Result<(), ErrorCode> {
+        let previous = self.snapshot_max_count.swap(snapshot_max_count, Ordering::Relaxed);
+        if snapshot_max_count < previous {
+            // Excess snapshots are checked again and removed on next flush of each instance.
+            self.retention_reduced.store(true, Ordering::Relaxed);
+            self.checked_instances
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clear();
         }
+        Ok(())
+    }
 
+    fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+        span!("snapshot_restore", instance_id, snapshot_id = snapshot_id);
+        self.check_restore_id(instance_id, snapshot_id)?;
         self.load_kvs(instance_id, snapshot_id)
     }
+
+    fn snapshot_restore_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        span!("snapshot_restore", instance_id, snapshot_id = snapshot_id);
+        self.check_restore_id(instance_id, snapshot_id)?;
+        self.load_kvs_with_expiry(instance_id, snapshot_id)
+    }
+
+    fn working_dir(&self) -> Option<&Path> {
+        Some(&self.working_dir)
+    }
+
+    fn snapshot_paths(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Option<(PathBuf, PathBuf)> {
+        Some((
+            self.kvs_file_path(instance_id, snapshot_id),
+            self.hash_file_path(instance_id, snapshot_id),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -518,6 +2032,32 @@ mod json_value_to_kvs_value_conversion_tests {
         assert_eq!(kv, KvsValue::F64(-432.1));
     }
 
+    #[test]
+    fn test_f32_ok() {
+        let jv = JsonValue::from(HashMap::from([
+            ("t".to_string(), JsonValue::String("f32".to_string())),
+            ("v".to_string(), JsonValue::Number(0.1f32 as f64)),
+        ]));
+        let kv = KvsValue::from(jv);
+        assert_eq!(kv, KvsValue::F32(0.1));
+    }
+
+    #[test]
+    fn test_non_finite_ok() {
+        let tagged = |t: &str, v: &str| {
+            JsonValue::from(HashMap::from([
+                ("t".to_string(), JsonValue::String(t.to_string())),
+                ("v".to_string(), JsonValue::String(v.to_string())),
+            ]))
+        };
+        assert!(matches!(KvsValue::from(tagged("f64", "NaN")), KvsValue::F64(v) if v.is_nan()));
+        assert_eq!(KvsValue::from(tagged("f64", "Infinity")), KvsValue::F64(f64::INFINITY));
+        assert_eq!(
+            KvsValue::from(tagged("f32", "-Infinity")),
+            KvsValue::F32(f32::NEG_INFINITY)
+        );
+    }
+
     #[test]
     fn test_f64_invalid_type() {
         let jv = JsonValue::from(HashMap::from([
@@ -727,21 +2267,56 @@ mod kvs_value_to_json_value_conversion_tests {
     }
 
     #[test]
-    fn test_f64_ok() {
-        let kv = KvsValue::F64(-432.1);
+    fn test_f32_ok() {
+        let kv = KvsValue::F32(0.1);
         let jv = JsonValue::from(kv);
 
         assert_eq!(
             jv,
             JsonValue::Object(HashMap::from([
-                ("t".to_string(), JsonValue::String("f64".to_string())),
-                ("v".to_string(), JsonValue::Number(-432.1)),
+                ("t".to_string(), JsonValue::String("f32".to_string())),
+                ("v".to_string(), JsonValue::Number(0.1f32 as f64)),
             ]))
         );
     }
 
     #[test]
-    fn test_bool_ok() {
+    fn test_f64_ok() {
+        let kv = KvsValue::F64(-432.1);
+        let jv = JsonValue::from(kv);
+
+        assert_eq!(
+            jv,
+            JsonValue::Object(HashMap::from([
+                ("t".to_string(), JsonValue::String("f64".to_string())),
+                ("v".to_string(), JsonValue::Number(-432.1)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_non_finite_ok() {
+        let jv = JsonValue::from(KvsValue::F64(f64::NAN));
+        assert_eq!(
+            jv,
+            JsonValue::Object(HashMap::from([
+                ("t".to_string(), JsonValue::String("f64".to_string())),
+                ("v".to_string(), JsonValue::String("NaN".to_string())),
+            ]))
+        );
+
+        let jv = JsonValue::from(KvsValue::F32(f32::INFINITY));
+        assert_eq!(
+            jv,
+            JsonValue::Object(HashMap::from([
+                ("t".to_string(), JsonValue::String("f32".to_string())),
+                ("v".to_string(), JsonValue::String("Infinity".to_string())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_bool_ok() {
         let kv = KvsValue::Boolean(true);
         let jv = JsonValue::from(kv);
 
@@ -854,6 +2429,9 @@ mod error_code_tests {
 
 #[cfg(test)]
 mod json_backend_builder_tests {
+    use crate::error_code::ErrorCode;
+    use crate::json_backend::{Compression, RotationStrategy};
+    use crate::storage_layer::{FileLayer, GzipLayer};
     use crate::{json_backend::JsonBackendBuilder, prelude::KvsBackend};
     use std::path::PathBuf;
     use tempfile::tempdir;
@@ -902,6 +2480,88 @@ mod json_backend_builder_tests {
         assert_eq!(backend.snapshot_max_count(), 3);
     }
 
+    #[test]
+    fn test_base_dir_relative() {
+        let dir = tempdir().unwrap();
+        let base_dir = dir.path().to_path_buf();
+        std::fs::create_dir(base_dir.join("sub")).unwrap();
+
+        let backend = JsonBackendBuilder::new()
+            .base_dir(base_dir.clone())
+            .working_dir(PathBuf::from("sub"))
+            .try_build()
+            .unwrap();
+        assert_eq!(backend.working_dir, base_dir.join("sub").canonicalize().unwrap());
+
+        // Not confined, path is resolved with `..`, but not checked.
+        let backend = JsonBackendBuilder::new()
+            .base_dir(base_dir.join("sub"))
+            .working_dir(PathBuf::from(".."))
+            .try_build()
+            .unwrap();
+        assert_eq!(backend.working_dir, base_dir.canonicalize().unwrap());
+
+        // `build` joins without canonicalization.
+        let backend = JsonBackendBuilder::new()
+            .base_dir(base_dir.clone())
+            .working_dir(PathBuf::from("sub"))
+            .build();
+        assert_eq!(backend.working_dir, base_dir.join("sub"));
+    }
+
+    #[test]
+    fn test_base_dir_absolute() {
+        let dir = tempdir().unwrap();
+        let base_dir = dir.path().join("base");
+        std::fs::create_dir(&base_dir).unwrap();
+
+        let backend = JsonBackendBuilder::new()
+            .base_dir(base_dir.clone())
+            .working_dir(dir.path().to_path_buf())
+            .try_build()
+            .unwrap();
+        assert_eq!(backend.working_dir, dir.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_base_dir_confine() {
+        let dir = tempdir().unwrap();
+        let base_dir = dir.path().join("base");
+        std::fs::create_dir_all(base_dir.join("sub")).unwrap();
+
+        let backend = JsonBackendBuilder::new()
+            .base_dir(base_dir.clone())
+            .working_dir(PathBuf::from("sub/../sub"))
+            .confine(true)
+            .try_build()
+            .unwrap();
+        assert_eq!(backend.working_dir, base_dir.join("sub").canonicalize().unwrap());
+
+        let result = JsonBackendBuilder::new()
+            .base_dir(base_dir.clone())
+            .working_dir(PathBuf::from("sub/../.."))
+            .confine(true)
+            .try_build();
+        assert!(result.is_err_and(|e| e == ErrorCode::PathOutsideBase));
+
+        let result = JsonBackendBuilder::new()
+            .base_dir(base_dir)
+            .working_dir(dir.path().to_path_buf())
+            .confine(true)
+            .try_build();
+        assert!(result.is_err_and(|e| e == ErrorCode::PathOutsideBase));
+    }
+
+    #[test]
+    fn test_base_dir_not_found() {
+        let dir = tempdir().unwrap();
+        let result = JsonBackendBuilder::new()
+            .base_dir(dir.path().to_path_buf())
+            .working_dir(PathBuf::from("missing"))
+            .try_build();
+        assert!(result.is_err_and(|e| e == ErrorCode::FileNotFound));
+    }
+
     #[test]
     fn test_snapshot_max_count_ok() {
         let builder = JsonBackendBuilder::new().snapshot_max_count(10);
@@ -916,6 +2576,137 @@ mod json_backend_builder_tests {
         assert_eq!(backend.snapshot_max_count(), 10);
     }
 
+    #[test]
+    fn test_compression_ok() {
+        let builder = JsonBackendBuilder::new().compression(Compression::Gzip);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert_eq!(builder.compression, Compression::Gzip);
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert_eq!(backend.compression, Compression::Gzip);
+    }
+
+    #[test]
+    fn test_pretty_ok() {
+        let builder = JsonBackendBuilder::new().pretty(true);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert!(builder.pretty);
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert!(backend.pretty);
+    }
+
+    #[test]
+    fn test_require_hash_ok() {
+        let builder = JsonBackendBuilder::new().require_hash(false);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert!(!builder.require_hash);
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert!(!backend.require_hash);
+    }
+
+    #[test]
+    fn test_rotation_strategy_ok() {
+        let builder = JsonBackendBuilder::new().rotation_strategy(RotationStrategy::Timestamped);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert_eq!(builder.rotation_strategy, RotationStrategy::Timestamped);
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert_eq!(backend.rotation_strategy, RotationStrategy::Timestamped);
+        assert!(backend != JsonBackendBuilder::new().build());
+    }
+
+    #[test]
+    fn test_prune_excess_snapshots_ok() {
+        let builder = JsonBackendBuilder::new().prune_excess_snapshots(true);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert!(builder.prune_excess_snapshots);
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert!(backend.prune_excess_snapshots);
+        assert!(backend != JsonBackendBuilder::new().build());
+    }
+
+    #[test]
+    fn test_max_value_bytes_ok() {
+        let builder = JsonBackendBuilder::new().max_value_bytes(1024);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert_eq!(builder.max_value_bytes, Some(1024));
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert_eq!(backend.max_value_bytes, Some(1024));
+        assert!(backend != JsonBackendBuilder::new().build());
+    }
+
+    #[test]
+    fn test_max_store_bytes_ok() {
+        let builder = JsonBackendBuilder::new().max_store_bytes(4096);
+
+        // Assert builder params.
+        assert_eq!(builder.working_dir, PathBuf::new());
+        assert_eq!(builder.snapshot_max_count, 3);
+        assert_eq!(builder.max_store_bytes, Some(4096));
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.working_dir, PathBuf::new());
+        assert_eq!(backend.snapshot_max_count(), 3);
+        assert_eq!(backend.max_store_bytes, Some(4096));
+        assert!(backend != JsonBackendBuilder::new().build());
+    }
+
+    #[test]
+    fn test_storage_layer_ok() {
+        let builder = JsonBackendBuilder::new().storage_layer(Box::new(GzipLayer::new(Box::new(FileLayer))));
+
+        // Assert builder params.
+        assert!(builder.storage_layer.is_some());
+
+        // Build and assert backend params.
+        let backend = builder.build();
+        assert_eq!(backend.compression, Compression::None);
+        assert!(backend.storage_layer.dyn_eq(&GzipLayer::new(Box::new(FileLayer))));
+        assert!(backend != JsonBackendBuilder::new().compression(Compression::Gzip).build());
+        assert!(backend != JsonBackendBuilder::new().build());
+    }
+
     #[test]
     fn test_chained_ok() {
         let dir = tempdir().unwrap();
@@ -933,16 +2724,88 @@ mod json_backend_builder_tests {
         assert_eq!(backend.working_dir, dir_path);
         assert_eq!(backend.snapshot_max_count(), 10);
     }
+
+    #[test]
+    fn test_eq_equivalent_working_dir() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().join("data");
+        std::fs::create_dir(&dir_path).unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+
+        // Same directory reached through different path.
+        let equivalent_path = dir.path().join("data").join("..").join("data");
+        assert_ne!(equivalent_path, dir_path);
+        assert!(backend == JsonBackendBuilder::new().working_dir(equivalent_path.clone()).build());
+
+        // Other parameters are still compared.
+        assert!(
+            backend
+                != JsonBackendBuilder::new()
+                    .working_dir(equivalent_path)
+                    .snapshot_max_count(10)
+                    .build()
+        );
+        assert!(backend != JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build());
+    }
+
+    #[test]
+    fn test_eq_missing_working_dir() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().join("missing");
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+
+        // Missing directories cannot be canonicalized, only equal paths match.
+        assert!(backend == JsonBackendBuilder::new().working_dir(dir_path).build());
+        assert!(
+            backend
+                != JsonBackendBuilder::new()
+                    .working_dir(dir.path().join("missing").join("..").join("missing"))
+                    .build()
+        );
+    }
 }
 
 #[cfg(test)]
 mod json_backend_tests {
     use crate::error_code::ErrorCode;
-    use crate::json_backend::{JsonBackend, JsonBackendBuilder};
+    use crate::json_backend::{Compression, JsonBackend, JsonBackendBuilder};
     use crate::kvs_api::{InstanceId, SnapshotId};
     use crate::kvs_value::{KvsMap, KvsValue};
     use std::path::{Path, PathBuf};
     use tempfile::tempdir;
+    use tinyjson::JsonValue;
+
+    #[test]
+    fn test_stringify_sorted() {
+        let json_value = JsonBackend::parse(r#"{"b":1,"a":{"d":[],"c":true,"e":[null,{}]}}"#).unwrap();
+
+        assert_eq!(
+            JsonBackend::stringify(&json_value, false).unwrap(),
+            r#"{"a":{"c":true,"d":[],"e":[null,{}]},"b":1}"#
+        );
+        assert_eq!(
+            JsonBackend::stringify(&json_value, true).unwrap(),
+            "{\n  \"a\": {\n    \"c\": true,\n    \"d\": [],\n    \"e\": [\n      null,\n      {}\n    ]\n  },\n  \"b\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn test_stringify_tagged() {
+        let kvs_value = KvsValue::from(KvsMap::from([
+            ("q\"k\n".to_string(), KvsValue::from("v\\\u{1}")),
+            ("n".to_string(), KvsValue::from(-1.5)),
+            (
+                "i".to_string(),
+                KvsValue::from(vec![KvsValue::from(7u8), KvsValue::Null]),
+            ),
+        ]));
+
+        // Output matches TinyJSON, which generates the same layout with unsorted keys.
+        assert_eq!(
+            JsonBackend::stringify(&JsonValue::from(kvs_value), false).unwrap(),
+            r#"{"t":"obj","v":{"i":{"t":"arr","v":[{"t":"u8","v":7},{"t":"null","v":null}]},"n":{"t":"f64","v":-1.5},"q\"k\n":{"t":"str","v":"v\\\u0001"}}}"#
+        );
+    }
 
     fn create_kvs_files(working_dir: &Path) -> (PathBuf, PathBuf) {
         let kvs_map = KvsMap::from([
@@ -962,7 +2825,7 @@ mod json_backend_tests {
         let dir_path = dir.path().to_path_buf();
         let (kvs_path, hash_path) = create_kvs_files(&dir_path);
 
-        let kvs_map = JsonBackend::load(&kvs_path, &hash_path).unwrap();
+        let kvs_map = JsonBackend::load(&kvs_path, &hash_path, true).unwrap();
         assert_eq!(kvs_map.len(), 3);
     }
 
@@ -973,7 +2836,19 @@ mod json_backend_tests {
         let (kvs_path, hash_path) = create_kvs_files(&dir_path);
         std::fs::remove_file(&kvs_path).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::FileNotFound));
+    }
+
+    #[test]
+    fn test_load_kvs_and_hash_not_found() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let (kvs_path, hash_path) = create_kvs_files(&dir_path);
+        std::fs::remove_file(&kvs_path).unwrap();
+        std::fs::remove_file(&hash_path).unwrap();
+
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, false).is_err_and(|e| e == ErrorCode::FileNotFound));
     }
 
     #[test]
@@ -983,7 +2858,21 @@ mod json_backend_tests {
         let kvs_path = dir_path.join("kvs.invalid_ext");
         let hash_path = dir_path.join("kvs.hash");
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::KvsFileReadError));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::InvalidFileExtension));
+    }
+
+    #[test]
+    fn test_load_invalid_extension_existing_file() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let (kvs_path, hash_path) = create_kvs_files(&dir_path);
+
+        // Readable file with wrong extension is distinguished from file that cannot be read.
+        let txt_path = dir_path.join("kvs.txt");
+        std::fs::copy(&kvs_path, &txt_path).unwrap();
+        assert!(JsonBackend::load(&txt_path, &hash_path, true).is_err_and(|e| e == ErrorCode::InvalidFileExtension));
+        let missing_path = dir_path.join("missing.json");
+        assert!(JsonBackend::load(&missing_path, &hash_path, true).is_err_and(|e| e == ErrorCode::FileNotFound));
     }
 
     #[test]
@@ -993,7 +2882,38 @@ mod json_backend_tests {
         let (kvs_path, hash_path) = create_kvs_files(&dir_path);
         std::fs::remove_file(&hash_path).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::HashFileNotFound));
+    }
+
+    #[test]
+    fn test_load_hash_not_required_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let (kvs_path, hash_path) = create_kvs_files(&dir_path);
+
+        let kvs_map = JsonBackend::load(&kvs_path, &hash_path, false).unwrap();
+        assert_eq!(kvs_map.len(), 3);
+    }
+
+    #[test]
+    fn test_load_hash_not_required_not_found() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let (kvs_path, hash_path) = create_kvs_files(&dir_path);
+        std::fs::remove_file(&hash_path).unwrap();
+
+        let kvs_map = JsonBackend::load(&kvs_path, &hash_path, false).unwrap();
+        assert_eq!(kvs_map.len(), 3);
+    }
+
+    #[test]
+    fn test_load_hash_not_required_invalid_content() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let (kvs_path, hash_path) = create_kvs_files(&dir_path);
+        std::fs::write(hash_path.clone(), vec![0x12, 0x34, 0x56, 0x78]).unwrap();
+
+        assert!(JsonBackend::load(&kvs_path, &hash_path, false).is_err_and(|e| e == ErrorCode::ValidationFailed));
     }
 
     #[test]
@@ -1003,7 +2923,7 @@ mod json_backend_tests {
         let kvs_path = dir_path.join("kvs.json");
         let hash_path = dir_path.join("kvs.invalid_ext");
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::KvsHashFileReadError));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::InvalidFileExtension));
     }
 
     #[test]
@@ -1018,7 +2938,7 @@ mod json_backend_tests {
         std::fs::write(kvs_path.clone(), contents).unwrap();
         std::fs::write(hash_path.clone(), hash.to_be_bytes()).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::JsonParserError));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::JsonParserError));
     }
 
     #[test]
@@ -1033,7 +2953,59 @@ mod json_backend_tests {
         std::fs::write(kvs_path.clone(), contents).unwrap();
         std::fs::write(hash_path.clone(), hash.to_be_bytes()).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::JsonParserError));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::InvalidStorageFormat));
+    }
+
+    #[test]
+    fn test_load_duplicate_key() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs_path = dir_path.join("kvs.json");
+        let hash_path = dir_path.join("kvs.hash");
+
+        // Hash is valid, so only content is rejected.
+        let contents = r#"{"key": {"t": "i32", "v": 1}, "key": {"t": "i32", "v": 2}}"#;
+        let hash = adler32::RollingAdler32::from_buffer(contents.as_bytes()).hash();
+        std::fs::write(kvs_path.clone(), contents).unwrap();
+        std::fs::write(hash_path.clone(), hash.to_be_bytes()).unwrap();
+
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::JsonParserError));
+    }
+
+    #[test]
+    fn test_parse_duplicate_key() {
+        for json_str in [
+            r#"{"a": 1, "a": 2}"#,
+            r#"{"a": {"t": "obj", "v": {"b": 1, "c": 2, "b": 3}}}"#,
+            r#"[{"a": 1}, {"b": 1, "b": 1}]"#,
+            r#"{"a": 1, "\u0061": 2}"#,
+            r#"{"\"": 1, "\u0022": 2}"#,
+        ] {
+            assert!(
+                JsonBackend::parse(json_str).is_err_and(|e| e == ErrorCode::JsonParserError),
+                "{json_str}"
+            );
+        }
+        assert!(
+            JsonBackend::from_json_str(r#"{"a": {"t": "i32", "v": 1}, "a": {"t": "i32", "v": 1}}"#)
+                .is_err_and(|e| e == ErrorCode::JsonParserError)
+        );
+    }
+
+    #[test]
+    fn test_parse_unique_keys() {
+        // Same keys in different objects, key-like strings in values and arrays.
+        let json_str =
+            r#"{"a": {"a": "a", "b": ["a", "a"]}, "b": {"a": "\"}, \"a\": {"}, "c\\": [{"a": 1}, {"a": 2}]}"#;
+        let json_value = JsonBackend::parse(json_str).unwrap();
+        assert_eq!(json_value, json_str.parse::<JsonValue>().unwrap());
+    }
+
+    #[test]
+    fn test_load_tagged_non_object() {
+        let json_str = r#"{"t": "arr", "v": [{"t": "i32", "v": 1}]}"#;
+        assert!(JsonBackend::from_json_str(json_str).is_err_and(|e| e == ErrorCode::InvalidStorageFormat));
+        assert!(JsonBackend::from_json_str_with_expiry(json_str).is_err_and(|e| e == ErrorCode::InvalidStorageFormat));
     }
 
     #[test]
@@ -1043,7 +3015,7 @@ mod json_backend_tests {
         let (kvs_path, hash_path) = create_kvs_files(&dir_path);
         std::fs::write(hash_path.clone(), vec![0x12, 0x34, 0x56, 0x78]).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::ValidationFailed));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::ValidationFailed));
     }
 
     #[test]
@@ -1053,60 +3025,178 @@ mod json_backend_tests {
         let (kvs_path, hash_path) = create_kvs_files(&dir_path);
         std::fs::write(hash_path.clone(), vec![0x12, 0x34, 0x56]).unwrap();
 
-        assert!(JsonBackend::load(&kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::ValidationFailed));
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::ValidationFailed));
     }
 
     #[test]
-    fn test_save_ok() {
+    fn test_load_large_same_as_string_parse() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-
-        let kvs_map = KvsMap::from([
-            ("k1".to_string(), KvsValue::from("v1")),
-            ("k2".to_string(), KvsValue::from(true)),
-            ("k3".to_string(), KvsValue::from(123.4)),
-        ]);
         let kvs_path = dir_path.join("kvs.json");
         let hash_path = dir_path.join("kvs.hash");
+
+        let kvs_map: KvsMap = (0..10_000)
+            .map(|i| {
+                let value = match i % 4 {
+                    0 => KvsValue::from(i),
+                    1 => KvsValue::from(format!("zażółć gęślą jaźń 😀 {i}")),
+                    2 => KvsValue::from(vec![KvsValue::from(i as f64 / 3.0), KvsValue::from(true)]),
+                    _ => KvsValue::from(KvsMap::from([("nested".to_string(), KvsValue::from(()))])),
+                };
+                (format!("key_{i}"), value)
+            })
+            .collect();
         JsonBackend::save(&kvs_map, &kvs_path, &hash_path).unwrap();
 
-        assert!(kvs_path.exists());
+        let json_str = std::fs::read_to_string(&kvs_path).unwrap();
+        let expected = JsonBackend::from_json_str(&json_str).unwrap();
+        let loaded = JsonBackend::load(&kvs_path, &hash_path, true).unwrap();
+        assert_eq!(loaded, expected);
+        assert_eq!(loaded, kvs_map);
     }
 
     #[test]
-    fn test_save_kvs_invalid_extension() {
+    fn test_load_invalid_utf8() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-
-        let kvs_map = KvsMap::new();
-        let kvs_path = dir_path.join("kvs.invalid_ext");
+        let kvs_path = dir_path.join("kvs.json");
         let hash_path = dir_path.join("kvs.hash");
 
-        assert!(JsonBackend::save(&kvs_map, &kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::KvsFileReadError));
+        let contents = b"{\"k\": {\"t\": \"str\", \"v\": \"\xC3\x28\"}}";
+        let hash = adler32::RollingAdler32::from_buffer(contents).hash();
+        std::fs::write(kvs_path.clone(), contents).unwrap();
+        std::fs::write(hash_path.clone(), hash.to_be_bytes()).unwrap();
+
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::ConversionFailed));
     }
 
     #[test]
-    fn test_save_hash_invalid_extension() {
+    fn test_load_malformed_json_invalid_hash() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-
-        let kvs_map = KvsMap::new();
         let kvs_path = dir_path.join("kvs.json");
-        let hash_path = dir_path.join("kvs.invalid_ext");
+        let hash_path = dir_path.join("kvs.hash");
+
+        // Parsing stops early, hash must still cover the whole content.
+        let contents = "{\"malformed_json\"} trailing content";
+        let hash = adler32::RollingAdler32::from_buffer(&contents.as_bytes()[..10]).hash();
+        std::fs::write(kvs_path.clone(), contents).unwrap();
+        std::fs::write(hash_path.clone(), hash.to_be_bytes()).unwrap();
+
+        assert!(JsonBackend::load(&kvs_path, &hash_path, true).is_err_and(|e| e == ErrorCode::ValidationFailed));
+    }
+
+    #[test]
+    fn test_save_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let kvs_map = KvsMap::from([
+            ("k1".to_string(), KvsValue::from("v1")),
+            ("k2".to_string(), KvsValue::from(true)),
+            ("k3".to_string(), KvsValue::from(123.4)),
+        ]);
+        let kvs_path = dir_path.join("kvs.json");
+        let hash_path = dir_path.join("kvs.hash");
+        JsonBackend::save(&kvs_map, &kvs_path, &hash_path).unwrap();
+
+        assert!(kvs_path.exists());
+    }
+
+    #[test]
+    fn test_save_kvs_invalid_extension() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let kvs_map = KvsMap::new();
+        let kvs_path = dir_path.join("kvs.invalid_ext");
+        let hash_path = dir_path.join("kvs.hash");
 
-        assert!(JsonBackend::save(&kvs_map, &kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::KvsHashFileReadError));
+        assert!(JsonBackend::save(&kvs_map, &kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::InvalidFileExtension));
     }
 
     #[test]
-    fn test_save_impossible_str() {
+    fn test_save_hash_invalid_extension() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let kvs_map = KvsMap::new();
+        let kvs_path = dir_path.join("kvs.json");
+        let hash_path = dir_path.join("kvs.invalid_ext");
+
+        assert!(JsonBackend::save(&kvs_map, &kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::InvalidFileExtension));
+    }
+
+    #[test]
+    fn test_save_non_finite_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
+        // Non-finite floats are not representable by JSON numbers, encoded as strings instead.
         let kvs_map = KvsMap::from([("inf".to_string(), KvsValue::from(f64::INFINITY))]);
         let kvs_path = dir_path.join("kvs.json");
         let hash_path = dir_path.join("kvs.hash");
 
-        assert!(JsonBackend::save(&kvs_map, &kvs_path, &hash_path).is_err_and(|e| e == ErrorCode::JsonGeneratorError));
+        JsonBackend::save(&kvs_map, &kvs_path, &hash_path).unwrap();
+        assert_eq!(JsonBackend::load(&kvs_path, &hash_path, true).unwrap(), kvs_map);
+    }
+
+    #[test]
+    fn test_save_load_gzip_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let kvs_map = KvsMap::from([
+            ("k1".to_string(), KvsValue::from("v1")),
+            ("k2".to_string(), KvsValue::from(true)),
+            ("k3".to_string(), KvsValue::from(123.4)),
+        ]);
+        let kvs_path = dir_path.join("kvs.json.gz");
+        let hash_path = dir_path.join("kvs.hash");
+        JsonBackend::save(&kvs_map, &kvs_path, &hash_path).unwrap();
+
+        // Check gzip magic bytes.
+        let bytes = std::fs::read(&kvs_path).unwrap();
+        assert_eq!(bytes[..2], [0x1f, 0x8b]);
+
+        let loaded_map = JsonBackend::load(&kvs_path, &hash_path, true).unwrap();
+        assert_eq!(loaded_map, kvs_map);
+    }
+
+    #[test]
+    fn test_save_gzip_smaller() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let kvs_map: KvsMap = (0..1000)
+            .map(|i| (format!("key_{i}"), KvsValue::from(format!("value_{i}"))))
+            .collect();
+        let plain_path = dir_path.join("plain.json");
+        let plain_hash_path = dir_path.join("plain.hash");
+        let gzip_path = dir_path.join("gzip.json.gz");
+        let gzip_hash_path = dir_path.join("gzip.hash");
+        JsonBackend::save(&kvs_map, &plain_path, &plain_hash_path).unwrap();
+        JsonBackend::save(&kvs_map, &gzip_path, &gzip_hash_path).unwrap();
+
+        let plain_len = std::fs::metadata(&plain_path).unwrap().len();
+        let gzip_len = std::fs::metadata(&gzip_path).unwrap().len();
+        assert!(gzip_len < plain_len);
+    }
+
+    #[test]
+    fn test_kvs_file_path_gzip() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path.clone())
+            .compression(Compression::Gzip)
+            .build();
+
+        let instance_id = InstanceId(123);
+        let snapshot_id = SnapshotId(2);
+        let exp_name = dir_path.join(format!("kvs_{instance_id}_{snapshot_id}.json.gz"));
+        let act_name = backend.kvs_file_path(instance_id, snapshot_id);
+        assert_eq!(exp_name, act_name);
     }
 
     #[test]
@@ -1195,12 +3285,18 @@ mod json_backend_tests {
 
 #[cfg(test)]
 mod kvs_backend_tests {
+    use crate::clock::MockClock;
     use crate::error_code::ErrorCode;
-    use crate::json_backend::{JsonBackend, JsonBackendBuilder};
+    use crate::json_backend::{Compression, JsonBackend, JsonBackendBuilder, RotationStrategy, SyncPolicy};
     use crate::kvs_api::{InstanceId, SnapshotId};
-    use crate::kvs_backend::KvsBackend;
-    use crate::kvs_value::{KvsMap, KvsValue};
+    use crate::kvs_backend::{BackendCapabilities, FlushProgress, KvsBackend};
+    use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue};
+    use crate::storage_layer::{EncryptLayer, FileLayer, GzipLayer, StorageLayer};
+    use core::time::Duration;
     use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::UNIX_EPOCH;
     use tempfile::tempdir;
 
     fn create_kvs_files(backend: &JsonBackend, instance_id: InstanceId, snapshot_id: SnapshotId) {
@@ -1238,6 +3334,26 @@ mod kvs_backend_tests {
         assert_eq!(kvs_map.len(), 3);
     }
 
+    #[test]
+    fn test_load_kvs_hash_not_required() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let instance_id = InstanceId(1);
+        let snapshot_id = SnapshotId(1);
+        let strict_backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let lenient_backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .require_hash(false)
+            .build();
+        create_kvs_files(&strict_backend, instance_id, snapshot_id);
+        fs::remove_file(strict_backend.hash_file_path(instance_id, snapshot_id)).unwrap();
+
+        assert!(strict_backend
+            .load_kvs(instance_id, snapshot_id)
+            .is_err_and(|e| e == ErrorCode::HashFileNotFound));
+        assert_eq!(lenient_backend.load_kvs(instance_id, snapshot_id).unwrap().len(), 3);
+    }
+
     #[test]
     fn test_load_defaults_ok() {
         // Main `load` tests are performed by `test_load_*` tests.
@@ -1251,6 +3367,44 @@ mod kvs_backend_tests {
         assert_eq!(kvs_map.len(), 2);
     }
 
+    #[test]
+    fn test_save_defaults_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .compression(Compression::Gzip)
+            .require_hash(true)
+            .build();
+        let instance_id = InstanceId(1);
+
+        let defaults_map = KvsMap::from([
+            ("number".to_string(), KvsValue::from(1.5)),
+            ("nested".to_string(), KvsValue::from(vec![KvsValue::from("value")])),
+        ]);
+        backend.save_defaults(instance_id, &defaults_map).unwrap();
+
+        // Defaults are not compressed.
+        let defaults_path = backend.defaults_file_path(instance_id);
+        assert!(std::fs::read_to_string(&defaults_path).unwrap().starts_with('{'));
+        assert!(backend.defaults_hash_file_path(instance_id).exists());
+        assert_eq!(backend.load_defaults(instance_id).unwrap(), defaults_map);
+
+        // Existing defaults are replaced.
+        backend.save_defaults(instance_id, &KvsMap::new()).unwrap();
+        assert!(backend.load_defaults(instance_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_defaults_dir_not_found() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().join("missing");
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+
+        let result = backend.save_defaults(InstanceId(1), &KvsMap::new());
+        assert!(result.is_err_and(|e| e == ErrorCode::FileNotFound));
+    }
+
     #[test]
     fn test_flush_ok() {
         let dir = tempdir().unwrap();
@@ -1271,141 +3425,1137 @@ mod kvs_backend_tests {
     }
 
     #[test]
-    fn test_flush_kvs_removed() {
+    fn test_flush_f32_round_trip() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
         let instance_id = InstanceId(1);
 
-        // Flush.
-        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        // `0.1f32` and `0.1f64` have different representations.
+        let kvs_map = KvsMap::from([
+            ("f32".to_string(), KvsValue::from(0.1f32)),
+            ("f64".to_string(), KvsValue::from(0.1f64)),
+        ]);
         backend.flush(instance_id, &kvs_map).unwrap();
 
-        // Remove KVS file.
-        let snapshot_id = SnapshotId(0);
-        let kvs_path = backend.kvs_file_path(instance_id, snapshot_id);
-        fs::remove_file(kvs_path).unwrap();
-
-        // Flush again.
-        let result = backend.flush(instance_id, &kvs_map);
-        assert!(result.is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        let loaded = backend.load_kvs(instance_id, SnapshotId(0)).unwrap();
+        assert_eq!(loaded.get("f32"), Some(&KvsValue::F32(0.1)));
+        assert_eq!(loaded.get("f64"), Some(&KvsValue::F64(0.1)));
+        assert_eq!(loaded, kvs_map);
     }
 
     #[test]
-    fn test_flush_hash_removed() {
+    fn test_flush_small_int_round_trip() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
         let instance_id = InstanceId(1);
 
-        // Flush.
-        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        let kvs_map = KvsMap::from([
+            ("i8".to_string(), KvsValue::from(i8::MIN)),
+            ("u8".to_string(), KvsValue::from(u8::MAX)),
+            ("i16".to_string(), KvsValue::from(i16::MIN)),
+            ("u16".to_string(), KvsValue::from(u16::MAX)),
+        ]);
         backend.flush(instance_id, &kvs_map).unwrap();
 
-        // Remove KVS file.
-        let snapshot_id = SnapshotId(0);
-        let hash_path = backend.hash_file_path(instance_id, snapshot_id);
-        fs::remove_file(hash_path).unwrap();
+        // Width is kept in type tag.
+        let kvs_path = backend.kvs_file_path(instance_id, SnapshotId(0));
+        let json_str = std::fs::read_to_string(kvs_path).unwrap();
+        assert!(json_str.contains(r#""u8":{"t":"u8","v":255}"#));
 
-        // Flush again.
-        let result = backend.flush(instance_id, &kvs_map);
-        assert!(result.is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        let loaded = backend.load_kvs(instance_id, SnapshotId(0)).unwrap();
+        assert_eq!(loaded.get("u8"), Some(&KvsValue::U8(u8::MAX)));
+        assert_eq!(loaded, kvs_map);
+        assert!(JsonBackend::from_json_str_validated(&json_str).is_ok());
     }
 
     #[test]
-    fn test_snapshot_count_zero() {
+    fn test_validate_small_int_out_of_range() {
+        assert!(JsonBackend::from_json_str_validated(r#"{"t":"obj","v":{"k":{"t":"u8","v":255}}}"#).is_ok());
+        assert!(
+            JsonBackend::from_json_str_validated(r#"{"t":"obj","v":{"k":{"t":"u8","v":256}}}"#)
+                .is_err_and(|e| e == ErrorCode::ConversionFailed)
+        );
+        assert!(
+            JsonBackend::from_json_str_validated(r#"{"t":"obj","v":{"k":{"t":"i16","v":-32769}}}"#)
+                .is_err_and(|e| e == ErrorCode::ConversionFailed)
+        );
+    }
+
+    #[test]
+    fn test_flush_non_finite_round_trip() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let instance_id = InstanceId(1);
 
-        assert_eq!(backend.snapshot_count(instance_id), 0);
+        let kvs_map = KvsMap::from([
+            ("nan".to_string(), KvsValue::from(f64::NAN)),
+            ("inf".to_string(), KvsValue::from(f64::INFINITY)),
+            ("neg_inf".to_string(), KvsValue::from(f64::NEG_INFINITY)),
+            ("f32_nan".to_string(), KvsValue::from(f32::NAN)),
+            ("f32_inf".to_string(), KvsValue::from(f32::INFINITY)),
+            ("f32_neg_inf".to_string(), KvsValue::from(f32::NEG_INFINITY)),
+        ]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Stored file is valid JSON.
+        let kvs_path = backend.kvs_file_path(instance_id, SnapshotId(0));
+        let json_str = std::fs::read_to_string(kvs_path).unwrap();
+        assert!(json_str.parse::<tinyjson::JsonValue>().is_ok());
+
+        let loaded = backend.load_kvs(instance_id, SnapshotId(0)).unwrap();
+        assert!(matches!(loaded.get("nan"), Some(KvsValue::F64(v)) if v.is_nan()));
+        assert_eq!(loaded.get("inf"), Some(&KvsValue::F64(f64::INFINITY)));
+        assert_eq!(loaded.get("neg_inf"), Some(&KvsValue::F64(f64::NEG_INFINITY)));
+        assert!(matches!(loaded.get("f32_nan"), Some(KvsValue::F32(v)) if v.is_nan()));
+        assert_eq!(loaded.get("f32_inf"), Some(&KvsValue::F32(f32::INFINITY)));
+        assert_eq!(loaded.get("f32_neg_inf"), Some(&KvsValue::F32(f32::NEG_INFINITY)));
+
+        // Encoded values pass type validation.
+        assert!(JsonBackend::from_json_str_validated(&json_str).is_ok());
     }
 
     #[test]
-    fn test_snapshot_count_to_one() {
+    fn test_flush_sync_policy() {
+        let instance_id = InstanceId(1);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        for sync_policy in [SyncPolicy::None, SyncPolicy::Data, SyncPolicy::DataAndDir] {
+            let dir = tempdir().unwrap();
+            let backend = JsonBackendBuilder::new()
+                .working_dir(dir.path().to_path_buf())
+                .sync_policy(sync_policy)
+                .build();
+
+            // Rotation, chunked flush and raw bytes flush run through the same save path.
+            backend.flush(instance_id, &kvs_map).unwrap();
+            backend.flush(instance_id, &kvs_map).unwrap();
+            let mut flush = Arc::new(backend.clone()).flush_chunked(instance_id, kvs_map.clone(), KvsExpiryMap::new());
+            while flush.step().unwrap() != FlushProgress::Done {}
+            let bytes = backend.load_kvs_bytes(instance_id, SnapshotId(0)).unwrap();
+            backend.flush_bytes(instance_id, &bytes).unwrap();
+
+            assert_eq!(backend.snapshot_count(instance_id), 3);
+            assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+        }
+    }
+
+    #[test]
+    fn test_flush_sync_policy_cwd() {
+        // Directory of file in CWD is synced as `.`.
+        assert!(JsonBackend::sync(Path::new("Cargo.toml"), Path::new("Cargo.toml"), SyncPolicy::DataAndDir).is_ok());
+    }
+
+    #[test]
+    fn test_flush_pretty_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).pretty(true).build();
+        let instance_id = InstanceId(1);
 
-        backend.flush(instance_id, &KvsMap::new()).unwrap();
-        assert_eq!(backend.snapshot_count(instance_id), 1);
+        // Flush.
+        let kvs_map = KvsMap::from([
+            ("key".to_string(), KvsValue::from("value")),
+            ("number".to_string(), KvsValue::from(123.4)),
+        ]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Check file is indented, hash is validated on load and round-trip is correct.
+        let snapshot_id = SnapshotId(0);
+        let content = fs::read_to_string(backend.kvs_file_path(instance_id, snapshot_id)).unwrap();
+        assert!(content.contains("\n  "));
+        assert_eq!(backend.load_kvs(instance_id, snapshot_id).unwrap(), kvs_map);
+
+        // Pretty-printed file is readable by compact backend.
+        let compact_backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        assert_eq!(compact_backend.load_kvs(instance_id, snapshot_id).unwrap(), kvs_map);
     }
 
     #[test]
-    fn test_snapshot_count_to_max() {
+    fn test_flush_deterministic() {
+        let instance_id = InstanceId(1);
+        let snapshot_id = SnapshotId(0);
+
+        // Same content is flushed from separately built maps into separate directories.
+        let flush = || {
+            let dir = tempdir().unwrap();
+            let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+            let mut kvs_map: KvsMap = (0..32).map(|i| (format!("key{i}"), KvsValue::from(i))).collect();
+            let nested: KvsMap = (0..8)
+                .map(|i| (format!("sub{i}"), KvsValue::from(i % 2 == 0)))
+                .collect();
+            kvs_map.insert("nested".to_string(), KvsValue::from(nested));
+            backend.flush(instance_id, &kvs_map).unwrap();
+
+            let kvs_bytes = fs::read(backend.kvs_file_path(instance_id, snapshot_id)).unwrap();
+            let hash_bytes = fs::read(backend.hash_file_path(instance_id, snapshot_id)).unwrap();
+            (kvs_bytes, hash_bytes)
+        };
+
+        assert_eq!(flush(), flush());
+    }
+
+    #[test]
+    fn test_flush_gzip_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .compression(Compression::Gzip)
+            .build();
+        let instance_id = InstanceId(1);
 
-        for i in 1..=backend.snapshot_max_count() {
-            backend.flush(instance_id, &KvsMap::new()).unwrap();
-            assert_eq!(backend.snapshot_count(instance_id), i);
-        }
+        // Flush.
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
 
-        backend.flush(instance_id, &KvsMap::new()).unwrap();
-        backend.flush(instance_id, &KvsMap::new()).unwrap();
-        assert_eq!(backend.snapshot_count(instance_id), backend.snapshot_max_count());
+        // Check compressed file exists and round-trip is correct.
+        let snapshot_id = SnapshotId(0);
+        let kvs_path = backend.kvs_file_path(instance_id, snapshot_id);
+        assert!(kvs_path.to_string_lossy().ends_with(".json.gz"));
+        assert!(kvs_path.exists());
+        assert_eq!(backend.load_kvs(instance_id, snapshot_id).unwrap(), kvs_map);
     }
 
     #[test]
-    fn test_snapshot_max_count() {
-        let max_count = 1234;
-        let backend = JsonBackendBuilder::new().snapshot_max_count(max_count).build();
-        assert_eq!(backend.snapshot_max_count(), max_count);
+    fn test_flush_gzip_layer_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .storage_layer(Box::new(GzipLayer::new(Box::new(FileLayer))))
+            .build();
+        let instance_id = InstanceId(1);
+
+        // Flush.
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        let expiry_map = KvsExpiryMap::from([("key".to_string(), UNIX_EPOCH + Duration::from_secs(1))]);
+        backend.flush_with_expiry(instance_id, &kvs_map, &expiry_map).unwrap();
+
+        // Check stored file is compressed and round-trip is correct.
+        let snapshot_id = SnapshotId(0);
+        let kvs_path = backend.kvs_file_path(instance_id, snapshot_id);
+        assert_eq!(fs::read(&kvs_path).unwrap()[..2], [0x1f, 0x8b]);
+        assert_eq!(backend.load_kvs(instance_id, snapshot_id).unwrap(), kvs_map);
+        assert_eq!(
+            backend.load_kvs_with_expiry(instance_id, snapshot_id).unwrap(),
+            (kvs_map, expiry_map)
+        );
+
+        // Plain backend cannot read compressed content.
+        let plain_backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        assert!(plain_backend.load_kvs(instance_id, snapshot_id).is_err());
     }
 
     #[test]
-    fn test_snapshot_restore_ok() {
+    fn test_flush_gzip_over_encrypt_layer_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .compression(Compression::Gzip)
+            .storage_layer(Box::new(EncryptLayer::new(Box::new(FileLayer), [0x42; 32])))
+            .build();
+        let instance_id = InstanceId(1);
 
-        // Prepare snapshots.
-        for i in 1..=backend.snapshot_max_count() {
-            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
+        // Flush multiple times to rotate snapshots.
+        for i in 0..2 {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(i))]);
             backend.flush(instance_id, &kvs_map).unwrap();
         }
 
-        // Check restore.
-        let kvs_map = backend.snapshot_restore(instance_id, SnapshotId(1)).unwrap();
-        assert_eq!(*kvs_map.get("counter").unwrap(), KvsValue::I32(2));
+        assert_eq!(backend.snapshot_count(instance_id), 2);
+        assert_eq!(
+            backend.snapshot_restore(instance_id, SnapshotId(1)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(0))])
+        );
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(0)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
     }
 
     #[test]
-    fn test_snapshot_restore_invalid_id() {
+    fn test_flush_with_expiry_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let instance_id = InstanceId(1);
 
-        // Prepare snapshots.
-        for i in 1..=backend.snapshot_max_count() {
-            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
-            backend.flush(instance_id, &kvs_map).unwrap();
-        }
+        // Flush.
+        let kvs_map = KvsMap::from([
+            ("cached".to_string(), KvsValue::from(1)),
+            ("persistent".to_string(), KvsValue::from(2)),
+        ]);
+        let expiry_map = KvsExpiryMap::from([(
+            "cached".to_string(),
+            UNIX_EPOCH + Duration::from_millis(1_767_225_600_123),
+        )]);
+        backend.flush_with_expiry(instance_id, &kvs_map, &expiry_map).unwrap();
 
-        let result = backend.snapshot_restore(instance_id, SnapshotId(123));
-        assert!(result.is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+        // Check expiry is stored in t-tagged entry and round-trip is correct.
+        let snapshot_id = SnapshotId(0);
+        let content = fs::read_to_string(backend.kvs_file_path(instance_id, snapshot_id)).unwrap();
+        assert!(content.contains("\"exp\":1767225600123"));
+        assert_eq!(
+            backend.load_kvs_with_expiry(instance_id, snapshot_id).unwrap(),
+            (kvs_map.clone(), expiry_map)
+        );
+
+        // Expiry is ignored if loaded without it.
+        assert_eq!(backend.load_kvs(instance_id, snapshot_id).unwrap(), kvs_map);
     }
 
     #[test]
-    fn test_snapshot_restore_current_id() {
+    fn test_snapshot_restore_with_expiry_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
-        let instance_id = InstanceId(2);
+        let instance_id = InstanceId(1);
 
-        // Prepare snapshots.
-        for i in 1..=backend.snapshot_max_count() {
-            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
-            backend.flush(instance_id, &kvs_map).unwrap();
-        }
+        // Prepare snapshot with expiry, overwritten as current KVS.
+        let kvs_map = KvsMap::from([("cached".to_string(), KvsValue::from(1))]);
+        let expiry_map = KvsExpiryMap::from([("cached".to_string(), UNIX_EPOCH + Duration::from_secs(60))]);
+        backend.flush_with_expiry(instance_id, &kvs_map, &expiry_map).unwrap();
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
 
-        let result = backend.snapshot_restore(instance_id, SnapshotId(0));
-        assert!(result.is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+        // Values and expiry come from the restored snapshot.
+        assert_eq!(
+            backend
+                .snapshot_restore_with_expiry(instance_id, SnapshotId(1))
+                .unwrap(),
+            (kvs_map, expiry_map)
+        );
+        assert!(backend
+            .snapshot_restore_with_expiry(instance_id, SnapshotId(0))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+        assert!(backend
+            .snapshot_restore_with_expiry(instance_id, SnapshotId(123))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+    }
+
+    #[test]
+    fn test_load_with_expiry_invalid() {
+        let json_str = r#"{"k": {"t": "i32", "v": 1, "exp": "never"}}"#;
+        assert!(JsonBackend::from_json_str_with_expiry(json_str).is_err_and(|e| e == ErrorCode::JsonParserError));
+    }
+
+    #[test]
+    fn test_flush_chunked_ok() {
+        let chunked_dir = tempdir().unwrap();
+        let chunked_backend = Arc::new(
+            JsonBackendBuilder::new()
+                .working_dir(chunked_dir.path().to_path_buf())
+                .build(),
+        );
+        let regular_dir = tempdir().unwrap();
+        let regular_backend = JsonBackendBuilder::new()
+            .working_dir(regular_dir.path().to_path_buf())
+            .build();
+        let instance_id = InstanceId(1);
+
+        for i in 0..chunked_backend.snapshot_max_count() + 1 {
+            let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from(i as i32))]);
+
+            // Drive chunked flush to completion, one step per rotation and one for save.
+            let mut flush = chunked_backend
+                .clone()
+                .flush_chunked(instance_id, kvs_map.clone(), KvsExpiryMap::new());
+            let mut steps = 1;
+            while flush.step().unwrap() == FlushProgress::Pending {
+                steps += 1;
+            }
+            assert_eq!(steps, chunked_backend.snapshot_max_count());
+            assert_eq!(flush.step().unwrap(), FlushProgress::Done);
+
+            regular_backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        // Check result matches regular flush.
+        assert_eq!(
+            chunked_backend.snapshot_count(instance_id),
+            regular_backend.snapshot_count(instance_id)
+        );
+        for idx in 0..chunked_backend.snapshot_count(instance_id) {
+            let snapshot_id = SnapshotId(idx);
+            assert_eq!(
+                chunked_backend.load_kvs(instance_id, snapshot_id).unwrap(),
+                regular_backend.load_kvs(instance_id, snapshot_id).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_flush_chunked_hash_removed() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        // Flush.
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Remove hash file.
+        let hash_path = backend.hash_file_path(instance_id, SnapshotId(0));
+        fs::remove_file(hash_path).unwrap();
+
+        // Drive chunked flush until error occurs, state machine is finished afterwards.
+        let mut flush = Arc::new(backend).flush_chunked(instance_id, kvs_map, KvsExpiryMap::new());
+        let result = loop {
+            match flush.step() {
+                Ok(FlushProgress::Pending) => continue,
+                other => break other,
+            }
+        };
+        assert!(result.is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert_eq!(flush.step().unwrap(), FlushProgress::Done);
+    }
+
+    #[test]
+    fn test_flush_kvs_removed() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        // Flush.
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Remove KVS file.
+        let snapshot_id = SnapshotId(0);
+        let kvs_path = backend.kvs_file_path(instance_id, snapshot_id);
+        fs::remove_file(kvs_path).unwrap();
+
+        // Flush again.
+        let result = backend.flush(instance_id, &kvs_map);
+        assert!(result.is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    #[test]
+    fn test_flush_hash_removed() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        // Flush.
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Remove KVS file.
+        let snapshot_id = SnapshotId(0);
+        let hash_path = backend.hash_file_path(instance_id, snapshot_id);
+        fs::remove_file(hash_path).unwrap();
+
+        // Flush again.
+        let result = backend.flush(instance_id, &kvs_map);
+        assert!(result.is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    /// Simulate rotation of snapshot `idx - 1` interrupted after renaming its hash file,
+    /// as left by rotation renaming hash file first.
+    fn interrupt_rotation(backend: &JsonBackend, instance_id: InstanceId, idx: usize) {
+        let hash_path_old = backend.hash_file_path(instance_id, SnapshotId(idx - 1));
+        let hash_path_new = backend.hash_file_path(instance_id, SnapshotId(idx));
+        let kvs_path_new = backend.kvs_file_path(instance_id, SnapshotId(idx));
+        if kvs_path_new.exists() {
+            fs::rename(&kvs_path_new, backend.kvs_file_path(instance_id, SnapshotId(idx + 1))).unwrap();
+            fs::rename(&hash_path_new, backend.hash_file_path(instance_id, SnapshotId(idx + 1))).unwrap();
+        }
+        fs::rename(hash_path_old, hash_path_new).unwrap();
+    }
+
+    #[test]
+    fn test_load_interrupted_rotation() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        interrupt_rotation(&backend, instance_id, 1);
+
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+        assert!(backend.hash_file_path(instance_id, SnapshotId(0)).exists());
+        assert!(!backend.hash_file_path(instance_id, SnapshotId(1)).exists());
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+    }
+
+    #[test]
+    fn test_flush_interrupted_rotation() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        for i in 0..2 {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(i))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+        interrupt_rotation(&backend, instance_id, 1);
+
+        // Interrupted rotation is rolled back, then performed again.
+        let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(2))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(1)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+        assert_eq!(
+            backend
+                .verify(instance_id)
+                .unwrap()
+                .iter()
+                .filter(|(_, ok)| !ok)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_flush_interrupted_rotation_hash_first_full() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        for i in 0..3 {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(i))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+        // Hash file of snapshot 1 replaced hash file of oldest snapshot.
+        fs::rename(
+            backend.hash_file_path(instance_id, SnapshotId(1)),
+            backend.hash_file_path(instance_id, SnapshotId(2)),
+        )
+        .unwrap();
+
+        // Snapshot 1 is restored, oldest snapshot without hash file is dropped.
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(1)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+        assert!(!backend.kvs_file_path(instance_id, SnapshotId(2)).exists());
+        assert_eq!(backend.snapshot_count(instance_id), 2);
+
+        let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(3))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(2)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+        assert!(backend.verify(instance_id).unwrap().iter().all(|(_, ok)| *ok));
+    }
+
+    #[test]
+    fn test_flush_interrupted_rotation_full() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(1);
+
+        for i in 0..3 {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(i))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+        // KVS file of snapshot 1 replaced KVS file of oldest snapshot, hash file was not renamed.
+        fs::rename(
+            backend.kvs_file_path(instance_id, SnapshotId(1)),
+            backend.kvs_file_path(instance_id, SnapshotId(2)),
+        )
+        .unwrap();
+
+        // Rotation is completed.
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(2)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+        assert!(!backend.hash_file_path(instance_id, SnapshotId(1)).exists());
+
+        let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::from(3))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(1)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(2))])
+        );
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(2)).unwrap(),
+            KvsMap::from([("counter".to_string(), KvsValue::from(1))])
+        );
+        assert!(backend.verify(instance_id).unwrap().iter().all(|(_, ok)| *ok));
+    }
+
+    #[test]
+    fn test_snapshot_count_zero() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        assert_eq!(backend.snapshot_count(instance_id), 0);
+    }
+
+    #[test]
+    fn test_snapshot_count_to_one() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+    }
+
+    #[test]
+    fn test_snapshot_count_to_max() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        for i in 1..=backend.snapshot_max_count() {
+            backend.flush(instance_id, &KvsMap::new()).unwrap();
+            assert_eq!(backend.snapshot_count(instance_id), i);
+        }
+
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
+        assert_eq!(backend.snapshot_count(instance_id), backend.snapshot_max_count());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+
+        assert_eq!(
+            backend.capabilities(),
+            BackendCapabilities {
+                snapshots: true,
+                persistence: true,
+                defaults: true,
+                integrity: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_working_dir() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+
+        assert_eq!(KvsBackend::working_dir(&backend), Some(dir_path.as_path()));
+    }
+
+    #[test]
+    fn test_snapshots_gap() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+        assert!(backend.snapshots(instance_id).is_empty());
+
+        for _ in 0..backend.snapshot_max_count() {
+            backend.flush(instance_id, &KvsMap::new()).unwrap();
+        }
+        assert_eq!(
+            backend.snapshots(instance_id),
+            vec![SnapshotId(0), SnapshotId(1), SnapshotId(2)]
+        );
+
+        // Missing middle snapshot is reported as a gap, `snapshot_count` stops at it.
+        std::fs::remove_file(backend.kvs_file_path(instance_id, SnapshotId(1))).unwrap();
+        assert_eq!(backend.snapshots(instance_id), vec![SnapshotId(0), SnapshotId(2)]);
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+
+        // Snapshot without hash file is not listed.
+        std::fs::remove_file(backend.hash_file_path(instance_id, SnapshotId(2))).unwrap();
+        assert_eq!(backend.snapshots(instance_id), vec![SnapshotId(0)]);
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .snapshot_max_count(4)
+            .build();
+        let instance_id = InstanceId(2);
+        assert!(backend.verify(instance_id).unwrap().is_empty());
+
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        for _ in 0..4 {
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        // Content not matching its hash.
+        std::fs::write(backend.kvs_file_path(instance_id, SnapshotId(1)), "{}").unwrap();
+
+        // Malformed JSON matching its hash.
+        let contents = "{\"malformed_json\"}";
+        let hash = adler32::RollingAdler32::from_buffer(contents.as_bytes()).hash();
+        std::fs::write(backend.kvs_file_path(instance_id, SnapshotId(3)), contents).unwrap();
+        std::fs::write(backend.hash_file_path(instance_id, SnapshotId(3)), hash.to_be_bytes()).unwrap();
+
+        assert_eq!(
+            backend.verify(instance_id).unwrap(),
+            vec![
+                (SnapshotId(0), true),
+                (SnapshotId(1), false),
+                (SnapshotId(2), true),
+                (SnapshotId(3), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_max_count() {
+        let max_count = 1234;
+        let backend = JsonBackendBuilder::new().snapshot_max_count(max_count).build();
+        assert_eq!(backend.snapshot_max_count(), max_count);
+    }
+
+    fn create_excess_snapshots(dir_path: &std::path::Path, instance_id: InstanceId) {
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path.to_path_buf())
+            .snapshot_max_count(5)
+            .build();
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        for _ in 0..5 {
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_flush_prune_excess_snapshots() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let instance_id = InstanceId(1);
+        create_excess_snapshots(&dir_path, instance_id);
+
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .snapshot_max_count(3)
+            .prune_excess_snapshots(true)
+            .build();
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        for idx in 0..3 {
+            assert!(backend.kvs_file_path(instance_id, SnapshotId(idx)).exists());
+            assert!(backend.hash_file_path(instance_id, SnapshotId(idx)).exists());
+        }
+        for idx in 3..5 {
+            assert!(!backend.kvs_file_path(instance_id, SnapshotId(idx)).exists());
+            assert!(!backend.hash_file_path(instance_id, SnapshotId(idx)).exists());
+        }
+    }
+
+    /// String value with serialized size equal to provided size.
+    fn value_of_size(size: usize) -> KvsValue {
+        let overhead = JsonBackend::value_size(&KvsValue::from("")).unwrap();
+        KvsValue::from("x".repeat(size - overhead))
+    }
+
+    #[test]
+    fn test_flush_max_value_bytes_under_limit() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .max_value_bytes(64)
+            .build();
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("key".to_string(), value_of_size(64))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+    }
+
+    #[test]
+    fn test_flush_max_value_bytes_over_limit() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .max_value_bytes(64)
+            .build();
+        let instance_id = InstanceId(1);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Whole flush is rejected, current snapshot is not rotated.
+        let oversized = KvsMap::from([
+            ("key".to_string(), KvsValue::from("value")),
+            ("big".to_string(), value_of_size(65)),
+        ]);
+        assert!(backend
+            .flush(instance_id, &oversized)
+            .is_err_and(|e| e == ErrorCode::QuotaExceeded));
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+    }
+
+    #[test]
+    fn test_flush_chunked_max_value_bytes_over_limit() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Arc::new(
+            JsonBackendBuilder::new()
+                .working_dir(dir_path)
+                .max_value_bytes(64)
+                .build(),
+        );
+        let instance_id = InstanceId(1);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        let oversized = KvsMap::from([("big".to_string(), value_of_size(65))]);
+        let mut flush = backend
+            .clone()
+            .flush_chunked(instance_id, oversized, KvsExpiryMap::new());
+        assert!(flush.step().is_err_and(|e| e == ErrorCode::QuotaExceeded));
+        assert_eq!(flush.step().unwrap(), FlushProgress::Done);
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+    }
+
+    #[test]
+    fn test_flush_max_store_bytes_under_limit() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        let size = JsonBackend::to_json_string(&kvs_map).unwrap().len();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .max_store_bytes(size)
+            .build();
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &kvs_map).unwrap();
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+    }
+
+    #[test]
+    fn test_flush_max_store_bytes_over_limit() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        let size = JsonBackend::to_json_string(&kvs_map).unwrap().len();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .max_store_bytes(size)
+            .build();
+        let instance_id = InstanceId(1);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Existing snapshots are left intact.
+        let oversized = KvsMap::from([("key".to_string(), KvsValue::from("value1"))]);
+        assert!(backend
+            .flush(instance_id, &oversized)
+            .is_err_and(|e| e == ErrorCode::QuotaExceeded));
+        assert!(backend
+            .flush_bytes(instance_id, &[b' '; 1024])
+            .is_err_and(|e| e == ErrorCode::QuotaExceeded));
+        assert_eq!(backend.snapshot_count(instance_id), 1);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), kvs_map);
+    }
+
+    /// Storage layer reporting full storage on write.
+    #[derive(PartialEq)]
+    struct FullLayer;
+
+    impl StorageLayer for FullLayer {
+        fn read(&self, path: &Path) -> Result<Vec<u8>, ErrorCode> {
+            FileLayer.read(path)
+        }
+
+        fn write(&self, _path: &Path, _bytes: &[u8]) -> Result<(), ErrorCode> {
+            Err(std::io::Error::from(std::io::ErrorKind::StorageFull).into())
+        }
+    }
+
+    #[test]
+    fn test_flush_out_of_storage_space() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .storage_layer(Box::new(FullLayer))
+            .build();
+        let instance_id = InstanceId(1);
+
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        assert!(backend
+            .flush(instance_id, &kvs_map)
+            .is_err_and(|e| e == ErrorCode::OutOfStorageSpace));
+    }
+
+    /// Flush maps with "version" set to `0..count`, then check snapshots are restored by age.
+    fn check_restore_by_age(rotation_strategy: RotationStrategy) {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .rotation_strategy(rotation_strategy)
+            .build();
+        let instance_id = InstanceId(1);
+
+        for version in 0..5 {
+            let kvs_map = KvsMap::from([("version".to_string(), KvsValue::from(version))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        assert_eq!(backend.snapshot_count(instance_id), 3);
+        assert_eq!(
+            backend.snapshots(instance_id),
+            vec![SnapshotId(0), SnapshotId(1), SnapshotId(2)]
+        );
+        for (idx, version) in [(0, 4), (1, 3), (2, 2)] {
+            let kvs_map = backend.load_kvs(instance_id, SnapshotId(idx)).unwrap();
+            assert_eq!(kvs_map["version"], KvsValue::I32(version));
+        }
+        assert_eq!(
+            backend.snapshot_restore(instance_id, SnapshotId(2)).unwrap()["version"],
+            KvsValue::I32(2)
+        );
+        assert!(backend
+            .snapshot_restore(instance_id, SnapshotId(0))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+
+        // Only files of kept snapshots remain.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 6);
+    }
+
+    #[test]
+    fn test_restore_by_age_shift() {
+        check_restore_by_age(RotationStrategy::Shift);
+    }
+
+    #[test]
+    fn test_restore_by_age_timestamped() {
+        check_restore_by_age(RotationStrategy::Timestamped);
+    }
+
+    #[test]
+    fn test_flush_timestamped_clock() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_millis(5000));
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path.clone())
+            .rotation_strategy(RotationStrategy::Timestamped)
+            .clock(Box::new(clock.clone()))
+            .build();
+        let instance_id = InstanceId(1);
+
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
+        assert_eq!(
+            backend.kvs_file_path(instance_id, SnapshotId(0)),
+            dir_path.join("kvs_1_5000.json")
+        );
+
+        // Timestamps stay increasing if clock goes backwards.
+        clock.set(UNIX_EPOCH);
+        backend.flush(instance_id, &KvsMap::new()).unwrap();
+        assert_eq!(
+            backend.kvs_file_path(instance_id, SnapshotId(0)),
+            dir_path.join("kvs_1_5001.json")
+        );
+    }
+
+    #[test]
+    fn test_flush_timestamped_files_not_renamed() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Arc::new(
+            JsonBackendBuilder::new()
+                .working_dir(dir_path)
+                .rotation_strategy(RotationStrategy::Timestamped)
+                .build(),
+        );
+        let instance_id = InstanceId(1);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+
+        backend.flush(instance_id, &kvs_map).unwrap();
+        let kvs_path = backend.kvs_file_path(instance_id, SnapshotId(0));
+        let hash_path = backend.hash_file_path(instance_id, SnapshotId(0));
+        let file_name = kvs_path.file_name().unwrap().to_str().unwrap();
+        assert!(file_name.starts_with("kvs_1_") && file_name != "kvs_1_0.json");
+
+        // Chunked flush saves in single step.
+        let mut flush = backend
+            .clone()
+            .flush_chunked(instance_id, kvs_map.clone(), KvsExpiryMap::new());
+        assert_eq!(flush.step().unwrap(), FlushProgress::Done);
+
+        // Older snapshot keeps its files.
+        assert_eq!(backend.kvs_file_path(instance_id, SnapshotId(1)), kvs_path);
+        assert_eq!(backend.hash_file_path(instance_id, SnapshotId(1)), hash_path);
+        assert_ne!(backend.kvs_file_path(instance_id, SnapshotId(0)), kvs_path);
+        assert!(!backend.kvs_file_path(instance_id, SnapshotId(2)).exists());
+    }
+
+    #[test]
+    fn test_flush_excess_snapshots_kept() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let instance_id = InstanceId(1);
+        create_excess_snapshots(&dir_path, instance_id);
+
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path)
+            .snapshot_max_count(3)
+            .build();
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        for idx in 0..5 {
+            assert!(backend.kvs_file_path(instance_id, SnapshotId(idx)).exists());
+            assert!(backend.hash_file_path(instance_id, SnapshotId(idx)).exists());
+        }
+    }
+
+    #[test]
+    fn test_snapshot_restore_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        // Prepare snapshots.
+        for i in 1..=backend.snapshot_max_count() {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        // Check restore.
+        let kvs_map = backend.snapshot_restore(instance_id, SnapshotId(1)).unwrap();
+        assert_eq!(*kvs_map.get("counter").unwrap(), KvsValue::I32(2));
+    }
+
+    #[test]
+    fn test_snapshot_restore_invalid_id() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        // Prepare snapshots.
+        for i in 1..=backend.snapshot_max_count() {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        let result = backend.snapshot_restore(instance_id, SnapshotId(123));
+        assert!(result.is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+    }
+
+    #[test]
+    fn test_snapshot_restore_current_id() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let instance_id = InstanceId(2);
+
+        // Prepare snapshots.
+        for i in 1..=backend.snapshot_max_count() {
+            let kvs_map = KvsMap::from([("counter".to_string(), KvsValue::I32(i as i32))]);
+            backend.flush(instance_id, &kvs_map).unwrap();
+        }
+
+        let result = backend.snapshot_restore(instance_id, SnapshotId(0));
+        assert!(result.is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod json_backend_tracing_tests {
+    use crate::json_backend::JsonBackendBuilder;
+    use crate::kvs_api::{InstanceId, SnapshotId};
+    use crate::kvs_backend::KvsBackend;
+    use crate::kvs_value::{KvsMap, KvsValue};
+    use core::fmt;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    /// Recorded span: name and field values.
+    type RecordedSpan = (&'static str, HashMap<String, String>);
+
+    /// Layer recording all created spans.
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        open: Arc<Mutex<HashMap<Id, usize>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            let _ = self.0.insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            let mut spans = self.spans.lock().unwrap();
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            spans.push((attrs.metadata().name(), fields));
+            // Span IDs are reused after close, only open spans are mapped.
+            let _ = self.open.lock().unwrap().insert(id.clone(), spans.len() - 1);
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            let index = self.open.lock().unwrap()[id];
+            values.record(&mut FieldVisitor(&mut self.spans.lock().unwrap()[index].1));
+        }
+    }
+
+    #[test]
+    fn test_flush_load_restore_spans() {
+        let dir = tempdir().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let instance_id = InstanceId(7);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from(1))]);
+
+        let recorder = SpanRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            backend.flush(instance_id, &kvs_map).unwrap();
+            backend.flush(instance_id, &kvs_map).unwrap();
+            let _ = backend.load_kvs_bytes(instance_id, SnapshotId(0)).unwrap();
+            let _ = backend.snapshot_restore(instance_id, SnapshotId(1)).unwrap();
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let names: Vec<&str> = spans.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["flush", "flush", "load", "snapshot_restore", "load"]);
+
+        // Flush span carries instance ID and written byte count.
+        let flushed: usize = spans[0].1["bytes"].parse().unwrap();
+        assert_eq!(spans[0].1["instance_id"], "7");
+        assert!(flushed > 0);
+
+        // Load span carries snapshot ID and read byte count.
+        assert_eq!(spans[2].1["instance_id"], "7");
+        assert_eq!(spans[2].1["snapshot_id"], "0");
+        assert_eq!(spans[2].1["bytes"], flushed.to_string());
+        assert_eq!(spans[3].1["snapshot_id"], "1");
     }
 }
@@ -10,12 +10,38 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
+use crate::audit::{AuditEvent, AuditOperation, AuditSink};
 use crate::error_code::ErrorCode;
-use crate::kvs_api::{InstanceId, KvsApi, KvsDefaults, KvsLoad, SnapshotId};
-use crate::kvs_backend::KvsBackend;
-use crate::kvs_builder::KvsData;
-use crate::kvs_value::{KvsMap, KvsValue};
-use std::sync::{Arc, Mutex};
+use crate::json_backend::JsonBackend;
+use crate::kvs_api::{DefaultsSource, InstanceId, KvsApi, KvsDefaults, KvsLoad, MergePolicy, SnapshotId};
+use crate::kvs_backend::{ChunkedFlush, CompletedFlush, FlushProgress, KvsBackend};
+use crate::kvs_builder::{KvsData, KvsInner, KVS_POOL};
+#[cfg(feature = "metrics")]
+use crate::kvs_metrics::{KvsCounters, KvsMetrics};
+use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue};
+use core::fmt;
+use core::time::Duration;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+use std::thread;
+use std::time::SystemTime;
+
+/// Increment operation counter of the instance, no-op without `metrics` feature.
+macro_rules! count {
+    ($parameters:expr, $counter:ident) => {
+        count!($parameters, $counter, 1)
+    };
+    ($parameters:expr, $counter:ident, $n:expr) => {
+        #[cfg(feature = "metrics")]
+        $parameters
+            .metrics
+            .$counter
+            .fetch_add($n, core::sync::atomic::Ordering::Relaxed);
+    };
+}
 
 /// KVS instance parameters.
 pub struct KvsParameters {
@@ -28,415 +54,3769 @@ pub struct KvsParameters {
     /// KVS load mode.
     pub kvs_load: KvsLoad,
 
-    /// Backend.
-    pub backend: Box<dyn KvsBackend>,
+    /// Backend, can be replaced at runtime with `Kvs::swap_backend`.
+    backend: RwLock<Arc<dyn KvsBackend>>,
+
+    /// Serializes flushes, so content captured later is never overwritten by earlier flush.
+    flush_lock: Mutex<()>,
+
+    /// Asynchronous flush waiting for flush lock, shared by calls made before it starts.
+    pending_async_flush: Mutex<Option<Arc<FlushState>>>,
+
+    /// Key validator, all keys are accepted if not set.
+    pub(crate) key_validator: Option<fn(&str) -> bool>,
+
+    /// Skip `flush` if storage data is unchanged since last flush.
+    pub(crate) skip_clean_flush: bool,
+
+    /// Keys are normalized to lowercase.
+    pub(crate) case_insensitive_keys: bool,
+
+    /// Keys whose default value takes precedence over stored value and which cannot be set.
+    pub(crate) locked_keys: HashSet<String>,
+
+    /// Receiver of audit events, mutations are not audited if not set.
+    pub(crate) audit_sink: Option<Arc<dyn AuditSink>>,
+
+    /// Operation counters.
+    #[cfg(feature = "metrics")]
+    metrics: KvsCounters,
+}
+
+impl KvsParameters {
+    pub(crate) fn new(
+        instance_id: InstanceId,
+        defaults: KvsDefaults,
+        kvs_load: KvsLoad,
+        backend: Box<dyn KvsBackend>,
+    ) -> Self {
+        Self {
+            instance_id,
+            defaults,
+            kvs_load,
+            backend: RwLock::new(Arc::from(backend)),
+            flush_lock: Mutex::new(()),
+            pending_async_flush: Mutex::new(None),
+            key_validator: None,
+            skip_clean_flush: false,
+            case_insensitive_keys: false,
+            locked_keys: HashSet::new(),
+            audit_sink: None,
+            #[cfg(feature = "metrics")]
+            metrics: KvsCounters::default(),
+        }
+    }
+
+    /// Create parameters of another instance sharing settings and backend of this one.
+    /// Operation counters are not shared.
+    fn with_instance_id(&self, instance_id: InstanceId) -> Self {
+        Self {
+            instance_id,
+            defaults: self.defaults,
+            kvs_load: self.kvs_load,
+            backend: RwLock::new(self.backend()),
+            flush_lock: Mutex::new(()),
+            pending_async_flush: Mutex::new(None),
+            key_validator: self.key_validator,
+            skip_clean_flush: self.skip_clean_flush,
+            case_insensitive_keys: self.case_insensitive_keys,
+            locked_keys: self.locked_keys.clone(),
+            audit_sink: self.audit_sink.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: KvsCounters::default(),
+        }
+    }
+
+    /// Currently active backend.
+    pub fn backend(&self) -> Arc<dyn KvsBackend> {
+        // Lock only guards pointer replacement, data cannot be left inconsistent.
+        self.backend.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Acquire flush lock of the instance.
+    fn lock_flush(&self) -> MutexGuard<'_, ()> {
+        // Lock guards no data, it cannot be left inconsistent.
+        self.flush_lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Normalize key, lowercase if keys are case-insensitive.
+    pub(crate) fn normalize_key<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        if self.case_insensitive_keys {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+
+    /// Normalize keys of a map, see `normalize_key`.
+    ///
+    /// # Return Values
+    ///   * Ok: Map with normalized keys
+    ///   * `ErrorCode::InvalidKey`: Multiple keys normalized to the same key
+    pub(crate) fn normalize_keys<V>(&self, map: HashMap<String, V>) -> Result<HashMap<String, V>, ErrorCode> {
+        if !self.case_insensitive_keys {
+            return Ok(map);
+        }
+
+        let mut normalized = HashMap::with_capacity(map.len());
+        for (key, value) in map {
+            let normalized_key = key.to_lowercase();
+            if normalized.insert(normalized_key, value).is_some() {
+                eprintln!("error: key conflicts with another key differing only in case: {key:?}");
+                return Err(ErrorCode::InvalidKey);
+            }
+        }
+        Ok(normalized)
+    }
+
+    /// Check key is accepted by key validator.
+    ///
+    /// # Return Values
+    ///   * Ok: Key is valid
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    fn check_key(&self, key: &str) -> Result<(), ErrorCode> {
+        match self.key_validator {
+            Some(validator) if !validator(key) => {
+                count!(self, validation_failures);
+                eprintln!("error: invalid key: {key:?}");
+                Err(ErrorCode::InvalidKey)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check key is not locked.
+    ///
+    /// # Return Values
+    ///   * Ok: Key is not locked
+    ///   * `ErrorCode::KeyLocked`: Key is locked
+    fn check_unlocked(&self, key: &str) -> Result<(), ErrorCode> {
+        if self.locked_keys.contains(key) {
+            eprintln!("error: key is locked: {key:?}");
+            return Err(ErrorCode::KeyLocked);
+        }
+        Ok(())
+    }
+
+    /// Check audit events are recorded.
+    /// Events are created only if so, to avoid cloning values.
+    fn audited(&self) -> bool {
+        self.audit_sink.is_some()
+    }
+
+    /// Record audit event, if any.
+    /// Must be called with data lock released.
+    fn audit(&self, event: Option<AuditEvent>) {
+        if let (Some(sink), Some(event)) = (&self.audit_sink, event) {
+            sink.record(&event);
+        }
+    }
+}
+
+/// Completion state of an asynchronous flush.
+#[derive(Default)]
+struct FlushState {
+    /// Flush result, set on completion.
+    result: Mutex<Option<Result<(), ErrorCode>>>,
+
+    /// Notified on completion.
+    done: Condvar,
+}
+
+impl FlushState {
+    /// Store flush result and wake up waiting handles.
+    fn complete(&self, result: Result<(), ErrorCode>) {
+        *self.result.lock().unwrap_or_else(PoisonError::into_inner) = Some(result);
+        self.done.notify_all();
+    }
+}
+
+/// Handle of an asynchronous flush started with [`Kvs::flush_async`].
+///
+/// Dropping the handle doesn't cancel the flush.
+pub struct FlushHandle {
+    /// Completion state shared with the flushing thread.
+    state: Arc<FlushState>,
+}
+
+impl FlushHandle {
+    /// Check whether flush completed.
+    pub fn is_finished(&self) -> bool {
+        self.state
+            .result
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Wait for flush completion
+    ///
+    /// # Return Values
+    ///   * Result of the flush, same as for `flush`
+    pub fn join(self) -> Result<(), ErrorCode> {
+        // Result is set once and never modified afterwards.
+        let result = self.state.result.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = self
+            .state
+            .done
+            .wait_while(result, |result| result.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+        result.clone().unwrap_or(Err(ErrorCode::UnmappedError))
+    }
+}
+
+/// Periodic flush of changed data, see `KvsBuilder::autoflush_interval`.
+///
+/// Flushing thread holds only weak references to the instance. Guard is shared by all handles of
+/// the instance, thread is stopped and joined when the last handle is dropped.
+pub(crate) struct AutoFlush {
+    /// Stop request, notified on drop.
+    stop: Arc<(Mutex<bool>, Condvar)>,
+
+    /// Flushing thread.
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl AutoFlush {
+    /// Start flushing thread.
+    ///
+    /// # Parameters
+    ///   * `data`: Instance data
+    ///   * `parameters`: Instance parameters
+    ///   * `interval`: Time between dirty checks
+    ///
+    /// # Return Values
+    ///   * Ok: Guard of the flushing thread
+    ///   * `ErrorCode::UnmappedError`: Thread could not be spawned
+    pub(crate) fn start(
+        data: &Arc<RwLock<KvsData>>,
+        parameters: &Arc<KvsParameters>,
+        interval: Duration,
+    ) -> Result<Self, ErrorCode> {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let data = Arc::downgrade(data);
+        let parameters_weak = Arc::downgrade(parameters);
+        let thread = thread::Builder::new()
+            .name(format!("kvs_autoflush_{}", parameters.instance_id))
+            .spawn(move || Self::run(&thread_stop, &data, &parameters_weak, interval))
+            .map_err(|e| {
+                eprintln!("error: failed to spawn auto-flush thread: {e}");
+                ErrorCode::UnmappedError
+            })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Flush changed data every interval until stop is requested or instance is gone.
+    fn run(
+        stop: &(Mutex<bool>, Condvar),
+        data: &Weak<RwLock<KvsData>>,
+        parameters: &Weak<KvsParameters>,
+        interval: Duration,
+    ) {
+        let (stopped, wakeup) = stop;
+        let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            stopped = wakeup
+                .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+            if *stopped {
+                return;
+            }
+            let (Some(data), Some(parameters)) = (data.upgrade(), parameters.upgrade()) else {
+                return;
+            };
+
+            // Flush without holding stop lock, so drop is not blocked until flush completes.
+            drop(stopped);
+            let kvs = Kvs::new(data, parameters);
+            if kvs.is_dirty().unwrap_or(false) {
+                if let Err(e) = kvs.flush() {
+                    eprintln!("error: auto-flush failed: {e:?}");
+                }
+            }
+            drop(kvs);
+            stopped = stop.0.lock().unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl Drop for AutoFlush {
+    fn drop(&mut self) {
+        *self.stop.0.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.stop.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            // Thread never holds a reference to the guard, so it cannot join itself.
+            if thread.join().is_err() {
+                eprintln!("error: auto-flush thread panicked");
+            }
+        }
+    }
+}
+
+impl fmt::Debug for KvsParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KvsParameters")
+            .field("instance_id", &self.instance_id)
+            .field("defaults", &self.defaults)
+            .field("kvs_load", &self.kvs_load)
+            .field("backend", &self.backend().name())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Key-change callback.
+pub(crate) type WatchCallback = Arc<dyn Fn(&str, &KvsValue) + Send + Sync>;
+
+/// Key-change callback registration.
+pub(crate) struct KvsWatcher {
+    /// Registration ID, used for deregistration.
+    pub(crate) id: u64,
+
+    /// Watched key.
+    pub(crate) key: String,
+
+    /// Callback invoked on key change.
+    pub(crate) callback: WatchCallback,
+}
+
+/// Handle of a key-change callback registration.
+///
+/// Callback is deregistered when handle is dropped.
+pub struct WatchHandle {
+    /// KVS instance data containing the registration.
+    data: Weak<RwLock<KvsData>>,
+
+    /// Registration ID.
+    id: u64,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        // Instance data might already be gone, nothing to deregister then.
+        if let Some(data) = self.data.upgrade() {
+            let mut data = data.write().unwrap_or_else(PoisonError::into_inner);
+            data.watchers.retain(|w| w.id != self.id);
+        }
+    }
+}
+
+/// Merge `other` map into `target` map using provided conflict policy.
+///
+/// # Parameters
+///   * `target`: Map to merge into
+///   * `other`: Map to merge from
+///   * `policy`: Conflict policy
+pub(crate) fn merge_maps(target: &mut KvsMap, other: KvsMap, policy: MergePolicy) {
+    for (key, incoming) in other {
+        match policy {
+            MergePolicy::PreferExisting => {
+                target.entry(key).or_insert(incoming);
+            },
+            MergePolicy::PreferIncoming => {
+                target.insert(key, incoming);
+            },
+            MergePolicy::DeepMergeObjects => match (target.get_mut(&key), incoming) {
+                (Some(KvsValue::Object(existing)), KvsValue::Object(incoming)) => {
+                    merge_maps(existing, incoming, policy);
+                },
+                (_, incoming) => {
+                    target.insert(key, incoming);
+                },
+            },
+        }
+    }
+}
+
+/// Chunked flush of an instance, see `Kvs::flush_chunked`.
+/// Flush lock of the instance is held until the flush completes or fails.
+/// Instance data is updated on completion same as by `flush`.
+struct KvsChunkedFlush<'a> {
+    /// Flushed instance.
+    kvs: &'a Kvs,
+
+    /// Flush lock guard, released on completion.
+    flush_guard: Option<MutexGuard<'a, ()>>,
+
+    /// Backend performing the flush, `None` if flush is ignored.
+    backend: Option<Arc<dyn KvsBackend>>,
+
+    /// Backend flush state machine.
+    flush: Box<dyn ChunkedFlush>,
+}
+
+impl ChunkedFlush for KvsChunkedFlush<'_> {
+    fn step(&mut self) -> Result<FlushProgress, ErrorCode> {
+        if self.flush_guard.is_none() {
+            return Ok(FlushProgress::Done);
+        }
+
+        let result = match self.flush.step() {
+            Ok(FlushProgress::Pending) => return Ok(FlushProgress::Pending),
+            Ok(FlushProgress::Done) => {
+                let hash = self
+                    .backend
+                    .as_ref()
+                    .and_then(|backend| backend.last_flush_hash(self.kvs.parameters.instance_id));
+                self.kvs.flush_completed(hash).map(|()| FlushProgress::Done)
+            },
+            Err(e) => {
+                self.kvs.data.write()?.dirty = true;
+                Err(e)
+            },
+        };
+        self.flush_guard = None;
+        result
+    }
+}
+
+impl Drop for KvsChunkedFlush<'_> {
+    fn drop(&mut self) {
+        // Flush abandoned before completion, captured content must be flushed again.
+        if self.flush_guard.is_some() {
+            self.kvs.data.write().unwrap_or_else(PoisonError::into_inner).dirty = true;
+        }
+    }
 }
 
 /// Key-value-storage data
 pub struct Kvs {
     /// KVS instance data.
-    data: Arc<Mutex<KvsData>>,
+    data: Arc<RwLock<KvsData>>,
 
     /// KVS instance parameters.
     parameters: Arc<KvsParameters>,
+
+    /// Periodic flush, shared by handles of the instance.
+    autoflush: Option<Arc<AutoFlush>>,
 }
 
 impl Kvs {
-    pub(crate) fn new(data: Arc<Mutex<KvsData>>, parameters: Arc<KvsParameters>) -> Self {
-        Self { data, parameters }
+    pub(crate) fn new(data: Arc<RwLock<KvsData>>, parameters: Arc<KvsParameters>) -> Self {
+        Self {
+            data,
+            parameters,
+            autoflush: None,
+        }
+    }
+
+    /// Set periodic flush kept running by the handle.
+    pub(crate) fn with_autoflush(mut self, autoflush: Option<Arc<AutoFlush>>) -> Self {
+        self.autoflush = autoflush;
+        self
+    }
+
+    /// Create another handle of the instance.
+    fn handle(&self) -> Kvs {
+        Kvs::new(self.data.clone(), self.parameters.clone()).with_autoflush(self.autoflush.clone())
     }
 
     /// KVS instance parameters.
     pub fn parameters(&self) -> &KvsParameters {
         &self.parameters
     }
-}
 
-impl KvsApi for Kvs {
-    /// Resets a key-value-storage to its initial state
+    /// Directory data is persisted to by the current backend.
     ///
     /// # Return Values
-    ///   * Ok: Reset of the KVS was successful
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    fn reset(&self) -> Result<(), ErrorCode> {
-        let mut data = self.data.lock()?;
-        data.kvs_map = KvsMap::new();
-        Ok(())
+    ///   * Some: Working directory of the backend
+    ///   * None: Backend is not file-based
+    pub fn working_dir(&self) -> Option<PathBuf> {
+        self.parameters.backend().working_dir().map(Path::to_path_buf)
     }
 
-    /// Reset a key-value pair in the storage to its initial state
+    /// Paths of files storing snapshot with given ID, e.g. to locate them for backup
+    ///
+    /// Paths are reported also if files do not exist, check `snapshots` for available snapshots.
     ///
     /// # Parameters
-    ///    * 'key': Key being reset to default
+    ///   * `snapshot_id`: Snapshot ID, `SnapshotId(0)` for current KVS
     ///
     /// # Return Values
-    ///    * Ok: Reset of the key-value pair was successful
-    ///    * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///    * `ErrorCode::KeyDefaultNotFound`: Key has no default value
-    fn reset_key(&self, key: &str) -> Result<(), ErrorCode> {
-        let mut data = self.data.lock()?;
-        if !data.defaults_map.contains_key(key) {
-            eprintln!("error: resetting key without a default value");
-            return Err(ErrorCode::KeyDefaultNotFound);
-        }
-
-        let _ = data.kvs_map.remove(key);
-        Ok(())
+    ///   * Some: Paths of KVS file and hash file
+    ///   * None: Backend does not store snapshot in files
+    pub fn snapshot_paths(&self, snapshot_id: SnapshotId) -> Option<(PathBuf, PathBuf)> {
+        self.parameters
+            .backend()
+            .snapshot_paths(self.parameters.instance_id, snapshot_id)
     }
 
-    /// Get list of all keys
+    /// Close the instance and release its slot in the instance pool
+    ///
+    /// Instance ID can be built again afterwards, also with different parameters. Data is not
+    /// flushed. Other handles of the closed instance remain usable until dropped, but are no
+    /// longer shared with newly built instances.
     ///
     /// # Return Values
-    ///   * Ok: List of all keys
+    ///   * Ok: Instance closed, also if already closed by another handle
     ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    fn get_all_keys(&self) -> Result<Vec<String>, ErrorCode> {
-        let data = self.data.lock()?;
-        Ok(data.kvs_map.keys().map(|x| x.to_string()).collect())
+    pub fn close(self) -> Result<(), ErrorCode> {
+        // Entry is dropped after unlocking the pool.
+        let kvs_inner = KVS_POOL.lock()?.remove(self.parameters.instance_id, &self.data);
+        drop(kvs_inner);
+        Ok(())
     }
 
-    /// Check if a key exists
+    /// Create read-only handle of the instance
     ///
-    /// # Parameters
-    ///   * `key`: Key to check for existence
+    /// Handle shares data with this instance, writes made through other handles are visible.
     ///
     /// # Return Values
-    ///   * Ok(`true`): Key exists
-    ///   * Ok(`false`): Key doesn't exist
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    fn key_exists(&self, key: &str) -> Result<bool, ErrorCode> {
-        let data = self.data.lock()?;
-        Ok(data.kvs_map.contains_key(key))
+    ///   * Handle exposing only reading operations
+    pub fn read_only(&self) -> ReadOnlyKvs {
+        ReadOnlyKvs { kvs: self.handle() }
     }
 
-    /// Get the assigned value for a given key
+    /// Create new instance with a copy of the data of this instance
     ///
-    /// # Features
-    ///   * `FEAT_REQ__KVS__default_values`
+    /// Target uses same settings and backend, files are distinguished by instance ID. Stored values,
+    /// their expiry and defaults are copied, watchers are not. Target is independent afterwards and
+    /// its persisted data is replaced on its first flush.
     ///
     /// # Parameters
-    ///   * `key`: Key to retrieve the value from
+    ///   * `target`: Instance ID of the new instance
     ///
-    /// # Return Value
-    ///   * Ok: Type specific value if key was found
+    /// # Return Values
+    ///   * Ok: New instance, with unflushed changes
+    ///   * `ErrorCode::InstanceParametersMismatch`: Target is this instance or is already open
+    ///   * `ErrorCode::InvalidInstanceId`: Target exceeds `KvsBuilder::max_instances`
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
     ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///   * `ErrorCode::KeyNotFound`: Key wasn't found in KVS nor in defaults
-    fn get_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
-        let data = self.data.lock()?;
-        if let Some(value) = data.kvs_map.get(key) {
-            Ok(value.clone())
-        } else if let Some(value) = data.defaults_map.get(key) {
-            Ok(value.clone())
-        } else {
-            eprintln!("error: get_value could not find key: {key}");
-            Err(ErrorCode::KeyNotFound)
+    pub fn clone_into(&self, target: InstanceId) -> Result<Kvs, ErrorCode> {
+        let mut kvs_pool = KVS_POOL.lock()?;
+        if kvs_pool.instances.contains_key(&target) || target == self.parameters.instance_id {
+            eprintln!("error: target instance already open: {target}");
+            return Err(ErrorCode::InstanceParametersMismatch);
         }
+        kvs_pool.check_instance_id(target)?;
+
+        let data = {
+            let source = self.data.read()?;
+            source.check_writable()?;
+            let mut data = KvsData::new(source.kvs_map.clone(), source.defaults_map.clone());
+            data.expiry_map = source.expiry_map.clone();
+            data.defaults_sources = source.defaults_sources.clone();
+            data.clock = source.clock.clone();
+            data.lazy_defaults = source.lazy_defaults.clone();
+            data.dirty = true;
+            data
+        };
+        let data = Arc::new(RwLock::new(data));
+        let parameters = Arc::new(self.parameters.with_instance_id(target));
+        let _ = kvs_pool.instances.insert(
+            target,
+            KvsInner {
+                parameters: parameters.clone(),
+                data: data.clone(),
+                autoflush_interval: None,
+                autoflush: Weak::new(),
+            },
+        );
+
+        Ok(Kvs::new(data, parameters))
     }
 
-    /// Get the assigned value for a given key
+    /// Operation counters of the instance, shared by all its handles.
     ///
-    /// See [Variants](https://docs.rs/tinyjson/latest/tinyjson/enum.JsonValue.html#variants) for
-    /// supported value types.
+    /// # Return Values
+    ///   * Current counter values
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> KvsMetrics {
+        self.parameters.metrics.snapshot()
+    }
+
+    /// Flush the in-memory key-value-storage on a background thread
     ///
-    /// # Features
-    ///   * `FEAT_REQ__KVS__default_values`
+    /// Flush starts once no other flush of the instance is in progress, content is captured on
+    /// start. Calls made before a pending flush started share it and receive handles to the same
+    /// result. Therefore content persisted by the returned handle includes all changes made before
+    /// the call, and flushes of the instance are never reordered: content captured later is never
+    /// overwritten by content captured earlier.
+    ///
+    /// # Return Values
+    ///   * Handle providing flush result with `FlushHandle::join`
+    pub fn flush_async(&self) -> FlushHandle {
+        let mut pending = self
+            .parameters
+            .pending_async_flush
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = pending.as_ref() {
+            return FlushHandle { state: state.clone() };
+        }
+
+        let state = Arc::new(FlushState::default());
+        *pending = Some(state.clone());
+        drop(pending);
+
+        let kvs = self.handle();
+        let thread_state = state.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("kvs_flush_{}", self.parameters.instance_id))
+            .spawn(move || thread_state.complete(kvs.flush_pending()));
+        if let Err(e) = spawned {
+            eprintln!("error: failed to spawn flush thread: {e}");
+            let _ = self
+                .parameters
+                .pending_async_flush
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            state.complete(Err(ErrorCode::UnmappedError));
+        }
+
+        FlushHandle { state }
+    }
+
+    /// Replace backend of the instance
+    ///
+    /// Current data is flushed with the new backend first. New backend becomes active for all
+    /// handles of the instance only if flush succeeded. Waits for flush in progress, so content
+    /// captured before the swap is never written to the new backend.
     ///
     /// # Parameters
-    ///   * `key`: Key to retrieve the value from
+    ///   * `new_backend`: Backend replacing the current one
     ///
-    /// # Return Value
-    ///   * Ok: Type specific value if key was found
+    /// # Return Values
+    ///   * Ok: Backend replaced
     ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///   * `ErrorCode::ConversionFailed`: Type conversion failed
-    ///   * `ErrorCode::KeyNotFound`: Key wasn't found in KVS nor in defaults
-    fn get_value_as<T>(&self, key: &str) -> Result<T, ErrorCode>
-    where
-        for<'a> T: TryFrom<&'a KvsValue> + core::clone::Clone,
-        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
-    {
-        let data = self.data.lock()?;
-        if let Some(value) = data.kvs_map.get(key) {
-            match T::try_from(value) {
-                Ok(value) => Ok(value),
-                Err(err) => {
-                    eprintln!("error: get_value could not convert KvsValue from KVS store: {err:#?}");
-                    Err(ErrorCode::ConversionFailed)
-                },
-            }
-        } else if let Some(value) = data.defaults_map.get(key) {
-            // check if key has a default value
-            match T::try_from(value) {
-                Ok(value) => Ok(value),
-                Err(err) => {
-                    eprintln!("error: get_value could not convert KvsValue from default store: {err:#?}");
-                    Err(ErrorCode::ConversionFailed)
-                },
-            }
-        } else {
-            eprintln!("error: get_value could not find key: {key}");
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * Errors returned by `new_backend` flush
+    pub fn swap_backend(&self, new_backend: Box<dyn KvsBackend>) -> Result<(), ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+        let (kvs_map, expiry_map, dirty) = {
+            let mut data = self.data.write()?;
+            data.check_writable()?;
 
-            Err(ErrorCode::KeyNotFound)
-        }
+            // Changes made during backend IO mark data dirty again.
+            let dirty = core::mem::replace(&mut data.dirty, false);
+            (data.kvs_map.clone(), data.expiry_map.clone(), dirty)
+        };
+
+        let new_backend: Arc<dyn KvsBackend> = Arc::from(new_backend);
+        let hash = match self.flush_with(new_backend.as_ref(), &kvs_map, &expiry_map) {
+            Ok(hash) => hash,
+            Err(e) => {
+                // Data is not flushed, but old backend stays active.
+                self.data.write()?.dirty |= dirty;
+                return Err(e);
+            },
+        };
+        *self.parameters.backend.write().unwrap_or_else(PoisonError::into_inner) = new_backend;
+        self.flush_completed(hash)
     }
 
-    /// Get default value for a given key
+    /// Change maximum number of snapshots at runtime
+    ///
+    /// New count is applied by subsequent flushes, e.g. to reduce retention during low disk space
+    /// conditions. If reduced, snapshots beyond the new count are removed by the next flush.
+    /// Same as with count set on backend build, `0` disables flushing.
     ///
     /// # Features
-    ///   * `FEAT_REQ__KVS__default_values`
-    ///   * `FEAT_REQ__KVS__default_value_retrieval`
+    ///   * `FEAT_REQ__KVS__snapshots`
     ///
     /// # Parameters
-    ///   * `key`: Key to get the default for
+    ///   * `snapshot_max_count`: Maximum count of snapshots
     ///
     /// # Return Values
-    ///   * Ok: `KvsValue` for the key
-    ///   * `ErrorCode::KeyNotFound`: Key not found in defaults
-    fn get_default_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
-        let data = self.data.lock()?;
-        if let Some(value) = data.defaults_map.get(key) {
-            Ok(value.clone())
-        } else {
-            Err(ErrorCode::KeyNotFound)
-        }
+    ///   * Ok: Count changed
+    ///   * `ErrorCode::UnmappedError`: Runtime change not supported by backend
+    pub fn set_snapshot_max_count(&self, snapshot_max_count: usize) -> Result<(), ErrorCode> {
+        self.parameters.backend().set_snapshot_max_count(snapshot_max_count)
     }
 
-    /// Return if the value wasn't set yet and uses its default value
+    /// Register callback invoked when value of a key changes
     ///
-    /// # Features
-    ///   * `FEAT_REQ__KVS__default_values`
+    /// Callback is invoked by `set_value`, `remove_key` and `reset_key` with the key and its new
+    /// effective value. Callbacks are invoked after the data lock is released, therefore they
+    /// are allowed to call back into the KVS.
     ///
     /// # Parameters
-    ///   * `key`: Key to check if a default exists
+    ///   * `key`: Key to watch
+    ///   * `callback`: Callback invoked on change
     ///
     /// # Return Values
-    ///   * Ok(true): Key currently returns the default value
-    ///   * Ok(false): Key returns the set value
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///   * `ErrorCode::KeyNotFound`: Key wasn't found
-    fn is_value_default(&self, key: &str) -> Result<bool, ErrorCode> {
-        let data = self.data.lock()?;
-        if data.kvs_map.contains_key(key) {
-            Ok(false)
-        } else if data.defaults_map.contains_key(key) {
-            Ok(true)
-        } else {
-            Err(ErrorCode::KeyNotFound)
+    ///   * Handle deregistering the callback when dropped
+    pub fn watch<F: Fn(&str, &KvsValue) + Send + Sync + 'static>(&self, key: &str, callback: F) -> WatchHandle {
+        let key = &*self.parameters.normalize_key(key);
+        let mut data = self.data.write().unwrap_or_else(PoisonError::into_inner);
+        let id = data.next_watcher_id;
+        data.next_watcher_id += 1;
+        data.watchers.push(KvsWatcher {
+            id,
+            key: key.to_string(),
+            callback: Arc::new(callback),
+        });
+
+        WatchHandle {
+            data: Arc::downgrade(&self.data),
+            id,
         }
     }
 
-    /// Assign a value to a given key
+    /// Find newest snapshot matching a predicate
+    ///
+    /// Snapshots are loaded one by one, starting from current KVS (`SnapshotId(0)`). Snapshots
+    /// failing to load (e.g. corrupted) are skipped with a warning. In-memory data is not altered.
     ///
     /// # Parameters
-    ///   * `key`: Key to set value
-    ///   * `value`: Value to be set
+    ///   * `predicate`: Called with content of each snapshot
     ///
     /// # Return Values
-    ///   * Ok: Value was assigned to key
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    fn set_value<S: Into<String>, V: Into<KvsValue>>(&self, key: S, value: V) -> Result<(), ErrorCode> {
-        let mut data = self.data.lock()?;
-        data.kvs_map.insert(key.into(), value.into());
-        Ok(())
+    ///   * Ok(Some): Newest snapshot matching the predicate
+    ///   * Ok(None): No snapshot matches the predicate
+    pub fn find_snapshot<F: Fn(&KvsMap) -> bool>(&self, predicate: F) -> Result<Option<SnapshotId>, ErrorCode> {
+        let backend = self.parameters.backend();
+        let instance_id = self.parameters.instance_id;
+        for snapshot_id in backend.snapshots(instance_id) {
+            match backend.load_kvs(instance_id, snapshot_id) {
+                Ok(kvs_map) if predicate(&kvs_map) => return Ok(Some(snapshot_id)),
+                Ok(_) => {},
+                Err(e) => eprintln!("warn: skipping snapshot {snapshot_id}: {e:?}"),
+            }
+        }
+        Ok(None)
     }
 
-    /// Remove a key
+    /// Read value of a key from a snapshot
+    ///
+    /// Snapshot is loaded without being restored, in-memory data is not altered. Current KVS
+    /// (`SnapshotId(0)`) can be read as well. Defaults are not considered.
     ///
     /// # Parameters
-    ///   * `key`: Key to remove
+    ///   * `snapshot_id`: Snapshot ID
+    ///   * `key`: Key to read
     ///
     /// # Return Values
-    ///   * Ok: Key removed successfully
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///   * `ErrorCode::KeyNotFound`: Key not found
-    fn remove_key(&self, key: &str) -> Result<(), ErrorCode> {
-        let mut data = self.data.lock()?;
-        if data.kvs_map.remove(key).is_some() {
-            Ok(())
-        } else {
-            Err(ErrorCode::KeyNotFound)
+    ///   * Ok(Some): Value of the key stored in the snapshot
+    ///   * Ok(None): Key not stored in the snapshot
+    ///   * `ErrorCode::InvalidSnapshotId`: Snapshot not available
+    ///   * Errors returned by backend
+    pub fn snapshot_read(&self, snapshot_id: SnapshotId, key: &str) -> Result<Option<KvsValue>, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let backend = self.parameters.backend();
+        let instance_id = self.parameters.instance_id;
+        if !backend.snapshots(instance_id).contains(&snapshot_id) {
+            eprintln!("error: tried to read a non-existing snapshot");
+            return Err(ErrorCode::InvalidSnapshotId);
         }
+
+        let mut kvs_map = backend.load_kvs(instance_id, snapshot_id)?;
+        Ok(kvs_map.remove(key))
     }
 
-    /// Flush the in-memory key-value-storage to the persistent storage
+    /// Verify integrity of all available snapshots
     ///
-    /// # Features
-    ///   * `FEAT_REQ__KVS__snapshots`
-    ///   * `FEAT_REQ__KVS__persistency`
-    ///   * `FEAT_REQ__KVS__integrity_check`
+    /// Snapshots are checked against their hashes without being loaded into the instance, in-memory
+    /// data is not altered. Invalid snapshots are reported with a warning, remaining ones are still
+    /// verified.
     ///
     /// # Return Values
-    ///   * Ok: Flush successful
-    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
-    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize to JSON
-    ///   * `ErrorCode::ConversionFailed`: JSON could not serialize into String
-    ///   * `ErrorCode::UnmappedError`: Unmapped error
-    fn flush(&self) -> Result<(), ErrorCode> {
-        if self.snapshot_max_count() == 0 {
-            eprintln!("warn: snapshot_max_count == 0, flush ignored");
-            return Ok(());
-        }
-
-        let data = self.data.lock()?;
-        self.parameters
-            .backend
-            .flush(self.parameters.instance_id, &data.kvs_map)
+    ///   * Ok: Validity of each snapshot, starting from current KVS (`SnapshotId(0)`)
+    ///   * Errors returned by backend
+    pub fn verify(&self) -> Result<Vec<(SnapshotId, bool)>, ErrorCode> {
+        self.parameters.backend().verify(self.parameters.instance_id)
     }
 
-    /// Get the count of snapshots
+    /// Get hash of content written by the last flush
+    ///
+    /// Hash is reported by the backend on `flush`, `flush_async` and `snapshot_restore_and_flush`
+    /// and is stored with the current KVS (`SnapshotId(0)`). Can be compared across replicas
+    /// without reading the persisted data.
     ///
     /// # Return Values
-    ///   * usize: Count of found snapshots
-    fn snapshot_count(&self) -> usize {
-        self.parameters.backend.snapshot_count(self.parameters.instance_id)
+    ///   * Some: Hash of last flushed content, Adler32 for `JsonBackend`
+    ///   * None: No flush performed yet, or hash not reported by backend
+    pub fn last_flush_hash(&self) -> Option<u32> {
+        // Value is replaced as a whole, it cannot be left inconsistent.
+        self.data.read().unwrap_or_else(PoisonError::into_inner).last_flush_hash
     }
 
-    /// Return maximum number of snapshots to store.
+    /// Export stored key-value pairs as a single JSON document
+    ///
+    /// Document uses the t-tagged representation of `JsonBackend`, including expiry of values.
+    /// Pairs are captured under a single lock, defaults and expired values are not included.
+    ///
+    /// # Parameters
+    ///   * `w`: Writer receiving the document
     ///
     /// # Return Values
-    ///   * usize: Maximum count of snapshots
-    fn snapshot_max_count(&self) -> usize {
-        self.parameters.backend.snapshot_max_count()
+    ///   * Ok: Export successful
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize to JSON
+    ///   * `ErrorCode::UnmappedError`: Write failed
+    pub fn export<W: Write>(&self, mut w: W) -> Result<(), ErrorCode> {
+        let json_str = {
+            let data = self.data.read()?;
+            data.check_writable()?;
+            let kvs_map: KvsMap = data
+                .kvs_map
+                .iter()
+                .filter(|(k, _)| !data.is_expired(k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            JsonBackend::to_json_string_with_expiry(&kvs_map, &data.expiry_map, false)?
+        };
+
+        w.write_all(json_str.as_bytes())?;
+        Ok(())
     }
 
-    /// Recover key-value-storage from snapshot
+    /// Import key-value pairs from a JSON document created by `export`
     ///
-    /// Restore a previously created KVS snapshot.
-    ///
-    /// # Features
-    ///   * `FEAT_REQ__KVS__snapshots`
+    /// Types of all values are validated before anything is applied. Expiry of imported values is
+    /// taken over, unless existing value is preserved by the merge policy.
     ///
     /// # Parameters
-    ///   * `id`: Snapshot ID
+    ///   * `r`: Reader providing the document
+    ///   * `policy`: Conflict policy for keys existing in both the KVS and the document
     ///
     /// # Return Values
-    ///   * `Ok`: Snapshot restored
-    ///   * `ErrorCode::InvalidSnapshotId`: Invalid snapshot ID
-    ///   * `ErrorCode::ValidationFailed`: KVS hash validation failed
+    ///   * Ok: Import successful
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
     ///   * `ErrorCode::JsonParserError`: JSON parser error
-    ///   * `ErrorCode::KvsFileReadError`: KVS file not found
-    ///   * `ErrorCode::KvsHashFileReadError`: KVS hash file read error
-    ///   * `ErrorCode::UnmappedError`: Generic error
-    fn snapshot_restore(&self, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
-        let mut data = self.data.lock()?;
-        data.kvs_map = self
+    ///   * `ErrorCode::ConversionFailed`: Invalid value type
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key),
+    ///     nothing was imported
+    ///   * `ErrorCode::UnmappedError`: Read failed
+    pub fn import<R: Read>(&self, mut r: R, policy: MergePolicy) -> Result<(), ErrorCode> {
+        let mut json_str = String::new();
+        r.read_to_string(&mut json_str)?;
+        let (kvs_map, expiry_map) = JsonBackend::from_json_str_validated(&json_str)?;
+        let kvs_map = self.parameters.normalize_keys(kvs_map)?;
+        let expiry_map = self.parameters.normalize_keys(expiry_map)?;
+        for key in kvs_map.keys() {
+            self.parameters.check_key(key)?;
+            self.parameters.check_unlocked(key)?;
+        }
+
+        let mut data = self.data.write()?;
+        data.check_writable()?;
+        for key in kvs_map.keys() {
+            data.remove_expired(key);
+            if policy == MergePolicy::PreferExisting && data.kvs_map.contains_key(key) {
+                continue;
+            }
+            match expiry_map.get(key) {
+                Some(expiry) => data.expiry_map.insert(key.clone(), *expiry),
+                None => data.expiry_map.remove(key),
+            };
+        }
+        let events = self.merge_locked(&mut data, kvs_map, policy);
+        drop(data);
+        self.audit_all(events);
+        Ok(())
+    }
+
+    /// Lock instance data for reading.
+    /// Expired stored value of `key` is removed first, taking write lock only if required.
+    fn read_data(&self, key: &str) -> Result<RwLockReadGuard<'_, KvsData>, ErrorCode> {
+        let data = self.data.read()?;
+        if !data.is_expired(key) {
+            return Ok(data);
+        }
+
+        drop(data);
+        self.data.write()?.remove_expired(key);
+        Ok(self.data.read()?)
+    }
+
+    /// Lock instance data for reading a value, defaults are considered.
+    /// Defaults deferred by `KvsBuilder::lazy_defaults` are loaded and expired stored value of
+    /// `key` is removed first, taking write lock only if required.
+    fn read_value(&self, key: &str) -> Result<RwLockReadGuard<'_, KvsData>, ErrorCode> {
+        let data = self.data.read()?;
+        if data.lazy_defaults.is_none() && !data.is_expired(key) {
+            return Ok(data);
+        }
+
+        drop(data);
+        let mut data = self.write_with_defaults()?;
+        data.remove_expired(key);
+        drop(data);
+        Ok(self.data.read()?)
+    }
+
+    /// Lock instance data for reading.
+    /// Defaults deferred by `KvsBuilder::lazy_defaults` are loaded first, taking write lock only
+    /// if required.
+    fn read_with_defaults(&self) -> Result<RwLockReadGuard<'_, KvsData>, ErrorCode> {
+        let data = self.data.read()?;
+        if data.lazy_defaults.is_none() {
+            return Ok(data);
+        }
+
+        drop(data);
+        drop(self.write_with_defaults()?);
+        Ok(self.data.read()?)
+    }
+
+    /// Lock instance data for writing.
+    /// Defaults deferred by `KvsBuilder::lazy_defaults` are loaded first.
+    fn write_with_defaults(&self) -> Result<RwLockWriteGuard<'_, KvsData>, ErrorCode> {
+        let mut data = self.data.write()?;
+        data.load_lazy_defaults()?;
+        Ok(data)
+    }
+
+    /// Invoke key-change callbacks.
+    /// Must be called without data lock held.
+    fn notify(callbacks: &[WatchCallback], key: &str, value: &KvsValue) {
+        for callback in callbacks {
+            callback(key, value);
+        }
+    }
+
+    /// Perform pending asynchronous flush.
+    fn flush_pending(&self) -> Result<(), ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+
+        // Content is captured below, later calls must start another flush.
+        let _ = self
             .parameters
-            .backend
-            .snapshot_restore(self.parameters.instance_id, snapshot_id)?;
+            .pending_async_flush
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.flush_locked()
+    }
+
+    /// Capture and flush current content.
+    /// Must be called with flush lock held.
+    fn flush_locked(&self) -> Result<(), ErrorCode> {
+        let (kvs_map, expiry_map) = {
+            let mut data = self.data.write()?;
+            data.check_writable()?;
+            if !data.dirty && self.parameters.skip_clean_flush {
+                return Ok(());
+            }
+
+            // Changes made during backend IO mark data dirty again.
+            data.dirty = false;
+            (data.kvs_map.clone(), data.expiry_map.clone())
+        };
+        self.flush_captured(&kvs_map, &expiry_map)
+    }
+
+    /// Flush captured content without holding data lock.
+    /// Must be called with flush lock held, data is expected to be marked clean on capture.
+    fn flush_captured(&self, kvs_map: &KvsMap, expiry_map: &KvsExpiryMap) -> Result<(), ErrorCode> {
+        let hash = match self.flush_data(kvs_map, expiry_map) {
+            Ok(hash) => hash,
+            Err(e) => {
+                self.data.write()?.dirty = true;
+                return Err(e);
+            },
+        };
+        self.flush_completed(hash)
+    }
+
+    /// Record completed flush of captured content.
+    /// Must be called with flush lock held.
+    fn flush_completed(&self, hash: Option<u32>) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        data.restore_stash = None;
+        data.last_flush_hash = hash;
+        count!(self.parameters, flushes);
+        let event = self.storage_event(&data, AuditOperation::Flush);
+        drop(data);
+        self.parameters.audit(event);
+        Ok(())
+    }
+
+    /// Flush provided content with current backend.
+    /// Hash of written content is returned if reported by backend.
+    fn flush_data(&self, kvs_map: &KvsMap, expiry_map: &KvsExpiryMap) -> Result<Option<u32>, ErrorCode> {
+        self.flush_with(self.parameters.backend().as_ref(), kvs_map, expiry_map)
+    }
+
+    /// Flush provided content with provided backend.
+    /// Hash of written content is returned if reported by backend.
+    fn flush_with(
+        &self,
+        backend: &dyn KvsBackend,
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+    ) -> Result<Option<u32>, ErrorCode> {
+        if backend.snapshot_max_count() == 0 {
+            eprintln!("warn: snapshot_max_count == 0, flush ignored");
+            return Ok(None);
+        }
+
+        backend.flush_with_expiry(self.parameters.instance_id, kvs_map, expiry_map)?;
+        Ok(backend.last_flush_hash(self.parameters.instance_id))
+    }
+
+    /// Restore snapshot into provided instance data.
+    /// Replaced data is stashed, so the restore can be undone.
+    fn restore_data(&self, data: &mut KvsData, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
+        let backend = self.parameters.backend();
+        if !backend.capabilities().snapshots {
+            eprintln!("error: snapshots are not supported by backend \"{}\"", backend.name());
+            return Err(ErrorCode::UnmappedError);
+        }
+        let (kvs_map, expiry_map) = backend.snapshot_restore_with_expiry(self.parameters.instance_id, snapshot_id)?;
+        let kvs_map = core::mem::replace(&mut data.kvs_map, kvs_map);
+        let expiry_map = core::mem::replace(&mut data.expiry_map, expiry_map);
+        data.restore_stash = Some((kvs_map, expiry_map));
+        data.dirty = true;
+        Ok(())
+    }
+
+    /// Store value with optional time to live and notify callbacks.
+    fn store_value(&self, key: String, value: KvsValue, ttl: Option<Duration>) -> Result<(), ErrorCode> {
+        let key = self.parameters.normalize_key(&key).into_owned();
+        self.parameters.check_key(&key)?;
+        self.parameters.check_unlocked(&key)?;
+        let data = self.data.write()?;
+        data.check_writable()?;
+
+        // Value never expires if expiry instant is not representable.
+        let expiry = ttl.and_then(|ttl| {
+            let expiry = data.clock.now().checked_add(ttl);
+            if expiry.is_none() {
+                eprintln!("warn: TTL out of range, value never expires");
+            }
+            expiry
+        });
+        let event = self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation: AuditOperation::Set,
+            key: Some(key.clone()),
+            old: data.stored_value(&key).cloned(),
+            new: Some(value.clone()),
+        });
+        Self::store_locked(data, key, value, expiry);
+        count!(self.parameters, sets);
+        self.parameters.audit(event);
         Ok(())
     }
+
+    /// Get stored value visible to reads.
+    /// Stored value of a locked key is hidden if the key has a default value.
+    fn visible_value<'a>(&self, data: &'a KvsData, key: &str) -> Option<&'a KvsValue> {
+        if self.parameters.locked_keys.contains(key) && data.defaults_map.contains_key(key) {
+            None
+        } else {
+            data.stored_value(key)
+        }
+    }
+
+    /// Create audit event of an operation on a key, if events are recorded.
+    /// Old value is the current stored value of the key.
+    fn key_event(
+        &self,
+        data: &KvsData,
+        operation: AuditOperation,
+        key: &str,
+        new: Option<&KvsValue>,
+    ) -> Option<AuditEvent> {
+        self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation,
+            key: Some(key.to_string()),
+            old: data.stored_value(key).cloned(),
+            new: new.cloned(),
+        })
+    }
+
+    /// Create audit event of an operation on the whole storage, if events are recorded.
+    fn storage_event(&self, data: &KvsData, operation: AuditOperation) -> Option<AuditEvent> {
+        self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation,
+            key: None,
+            old: None,
+            new: None,
+        })
+    }
+
+    /// Merge map into locked instance data.
+    /// Audit events of merged keys are returned, if events are recorded.
+    fn merge_locked(&self, data: &mut KvsData, other: KvsMap, policy: MergePolicy) -> Vec<AuditEvent> {
+        let merged: Vec<(String, Option<KvsValue>)> = if self.parameters.audited() {
+            other
+                .keys()
+                .filter(|key| policy != MergePolicy::PreferExisting || !data.kvs_map.contains_key(*key))
+                .map(|key| (key.clone(), data.kvs_map.get(key).cloned()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        merge_maps(&mut data.kvs_map, other, policy);
+        data.dirty = true;
+
+        let timestamp = data.clock.now();
+        merged
+            .into_iter()
+            .map(|(key, old)| AuditEvent {
+                timestamp,
+                operation: AuditOperation::Set,
+                new: data.kvs_map.get(&key).cloned(),
+                key: Some(key),
+                old,
+            })
+            .collect()
+    }
+
+    /// Record audit events.
+    /// Must be called with data lock released.
+    fn audit_all(&self, events: Vec<AuditEvent>) {
+        for event in events {
+            self.parameters.audit(Some(event));
+        }
+    }
+
+    /// Store value with optional expiry into locked instance data and notify callbacks.
+    /// Lock is released before callbacks are invoked.
+    fn store_locked(mut data: RwLockWriteGuard<'_, KvsData>, key: String, value: KvsValue, expiry: Option<SystemTime>) {
+        data.restore_stash = None;
+        data.dirty = true;
+
+        match expiry {
+            Some(expiry) => data.expiry_map.insert(key.clone(), expiry),
+            None => data.expiry_map.remove(&key),
+        };
+
+        let callbacks = data.key_watchers(&key);
+        if callbacks.is_empty() {
+            data.kvs_map.insert(key, value);
+        } else {
+            data.kvs_map.insert(key.clone(), value.clone());
+            drop(data);
+            Self::notify(&callbacks, &key, &value);
+        }
+    }
 }
 
-#[cfg(test)]
-mod kvs_tests {
-    use crate::error_code::ErrorCode;
-    use crate::json_backend::JsonBackendBuilder;
-    use crate::kvs::{Kvs, KvsParameters};
-    use crate::kvs_api::{InstanceId, KvsApi, KvsDefaults, KvsLoad, SnapshotId};
-    use crate::kvs_backend::KvsBackend;
-    use crate::kvs_builder::KvsData;
-    use crate::kvs_value::{KvsMap, KvsValue};
-    use std::sync::{Arc, Mutex};
-    use tempfile::tempdir;
+impl KvsApi for Kvs {
+    /// Resets a key-value-storage to its initial state
+    ///
+    /// # Return Values
+    ///   * Ok: Reset of the KVS was successful
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn reset(&self) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        data.check_writable()?;
+        data.kvs_map = KvsMap::new();
+        data.expiry_map = KvsExpiryMap::new();
+        data.dirty = true;
+        let event = self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation: AuditOperation::Reset,
+            key: None,
+            old: None,
+            new: None,
+        });
+        drop(data);
+        self.parameters.audit(event);
+        Ok(())
+    }
 
-    /// Most tests can be performed with mocked backend.
-    /// Only those with file handling must use concrete implementation.
-    #[derive(PartialEq)]
-    struct MockBackend;
+    /// Reset a key-value pair in the storage to its initial state
+    ///
+    /// # Parameters
+    ///    * 'key': Key being reset to default
+    ///
+    /// # Return Values
+    ///    * Ok: Reset of the key-value pair was successful
+    ///    * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///    * `ErrorCode::KeyDefaultNotFound`: Key has no default value
+    ///    * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn reset_key(&self, key: &str) -> Result<(), ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+        if !data.defaults_map.contains_key(key) {
+            eprintln!("error: resetting key without a default value");
+            return Err(ErrorCode::KeyDefaultNotFound);
+        }
 
-    impl KvsBackend for MockBackend {
-        fn load_kvs(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
-            unimplemented!()
+        let event = self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation: AuditOperation::ResetKey,
+            key: Some(key.to_string()),
+            old: data.stored_value(key).cloned(),
+            new: None,
+        });
+        let _ = data.kvs_map.remove(key);
+        let _ = data.expiry_map.remove(key);
+        data.dirty = true;
+
+        let callbacks = data.key_watchers(key);
+        let notification = (!callbacks.is_empty()).then(|| data.effective_value(key));
+        drop(data);
+        if let Some(value) = notification {
+            Self::notify(&callbacks, key, &value);
         }
+        self.parameters.audit(event);
+        Ok(())
+    }
 
-        fn load_defaults(&self, _instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
-            unimplemented!()
+    /// Reset all keys having a default value to their default values
+    ///
+    /// Stored values of keys with a default value are removed under a single lock, so the keys
+    /// fall back to their defaults. Keys without a default value are kept.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Return Values
+    ///    * Ok: Number of keys reset, expired values are not counted
+    ///    * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///    * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn reset_to_defaults(&self) -> Result<usize, ErrorCode> {
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+
+        let keys: Vec<String> = data
+            .kvs_map
+            .keys()
+            .filter(|k| data.defaults_map.contains_key(*k))
+            .cloned()
+            .collect();
+        let mut reset = Vec::new();
+        let mut events = Vec::new();
+        for key in keys {
+            // Expired values already fall back to defaults.
+            let expired = data.is_expired(&key);
+            if !expired {
+                events.extend(self.key_event(&data, AuditOperation::ResetKey, &key, None));
+            }
+            let _ = data.kvs_map.remove(&key);
+            let _ = data.expiry_map.remove(&key);
+            data.dirty = true;
+            if !expired {
+                reset.push(key);
+            }
         }
 
-        fn flush(&self, _instance_id: InstanceId, _kvs_map: &KvsMap) -> Result<(), ErrorCode> {
-            unimplemented!()
+        let notifications: Vec<_> = reset
+            .iter()
+            .map(|key| (data.key_watchers(key), key, data.effective_value(key)))
+            .filter(|(callbacks, _, _)| !callbacks.is_empty())
+            .collect();
+        drop(data);
+        for (callbacks, key, value) in notifications {
+            Self::notify(&callbacks, key, &value);
         }
+        self.audit_all(events);
+        Ok(reset.len())
+    }
 
-        fn snapshot_count(&self, _instance_id: InstanceId) -> usize {
-            unimplemented!()
+    /// Store the default value of a key as its explicit value
+    ///
+    /// Value no longer follows changes of defaults afterwards, until `reset_key` is called.
+    /// Explicitly stored value is kept. Callbacks are not invoked, the effective value is unchanged.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///    * `key`: Key to store the default value for
+    ///
+    /// # Return Values
+    ///    * Ok: Default value stored, or explicit value already stored
+    ///    * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///    * `ErrorCode::KeyDefaultNotFound`: Key has no default value
+    ///    * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///    * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn materialize_default(&self, key: &str) -> Result<(), ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        self.parameters.check_unlocked(key)?;
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+        let Some(value) = data.defaults_map.get(key).cloned() else {
+            eprintln!("error: materializing key without a default value");
+            return Err(ErrorCode::KeyDefaultNotFound);
+        };
+
+        data.remove_expired(key);
+        if data.kvs_map.contains_key(key) {
+            return Ok(());
         }
 
-        fn snapshot_max_count(&self) -> usize {
-            unimplemented!()
+        let event = self.key_event(&data, AuditOperation::Set, key, Some(&value));
+        data.kvs_map.insert(key.to_string(), value);
+        data.restore_stash = None;
+        data.dirty = true;
+        drop(data);
+        self.parameters.audit(event);
+        Ok(())
+    }
+
+    /// Get list of all keys
+    ///
+    /// No keys are returned if KVS is marked corrupt.
+    ///
+    /// # Return Values
+    ///   * Ok: List of all keys
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn get_all_keys(&self) -> Result<Vec<String>, ErrorCode> {
+        let data = self.data.read()?;
+        if data.corrupt_reason.is_some() {
+            return Ok(Vec::new());
         }
+        Ok(data
+            .kvs_map
+            .keys()
+            .filter(|k| !data.is_expired(k))
+            .map(|x| x.to_string())
+            .collect())
+    }
 
-        fn snapshot_restore(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
-            unimplemented!()
+    /// Get list of keys starting with a prefix
+    ///
+    /// Prefix is matched on the exact string, e.g. `net` matches both `net.eth0` and `network`.
+    /// Include the separator for matching on key segments only, e.g. `net.`. Empty prefix matches
+    /// all keys. Defaults are not included, no keys are returned if KVS is marked corrupt.
+    ///
+    /// # Parameters
+    ///   * `prefix`: Prefix of keys
+    ///
+    /// # Return Values
+    ///   * Ok: List of matching keys
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, ErrorCode> {
+        let prefix = &*self.parameters.normalize_key(prefix);
+        let data = self.data.read()?;
+        if data.corrupt_reason.is_some() {
+            return Ok(Vec::new());
         }
+        Ok(data
+            .kvs_map
+            .keys()
+            .filter(|k| k.starts_with(prefix) && !data.is_expired(k))
+            .cloned()
+            .collect())
     }
 
-    fn get_kvs(backend: Box<dyn KvsBackend>, kvs_map: KvsMap, defaults_map: KvsMap) -> Kvs {
-        let instance_id = InstanceId(1);
-        let data = Arc::new(Mutex::new(KvsData { kvs_map, defaults_map }));
-        let parameters = Arc::new(KvsParameters {
-            instance_id,
-            defaults: KvsDefaults::Optional,
-            kvs_load: KvsLoad::Optional,
-            backend,
-        });
-        Kvs::new(data, parameters)
+    /// Get all stored key-value pairs
+    ///
+    /// Pairs are captured under a single lock, defaults are not included.
+    /// No pairs are returned if KVS is marked corrupt.
+    ///
+    /// # Return Values
+    ///   * Ok: List of all stored key-value pairs
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn entries(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode> {
+        let data = self.data.read()?;
+        if data.corrupt_reason.is_some() {
+            return Ok(Vec::new());
+        }
+        Ok(data
+            .kvs_map
+            .iter()
+            .filter(|(k, _)| !data.is_expired(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    /// Get all key-value pairs including defaults
+    ///
+    /// Pairs are captured under a single lock. Stored values take precedence over defaults, same as
+    /// in `get_value`.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Return Values
+    ///   * Ok: List of all effective key-value pairs
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn entries_with_defaults(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode> {
+        let data = self.read_with_defaults()?;
+        let mut entries = data.defaults_map.clone();
+        if data.corrupt_reason.is_none() {
+            entries.extend(
+                data.kvs_map
+                    .iter()
+                    .filter(|(k, _)| !data.is_expired(k))
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+        Ok(entries.into_iter().collect())
+    }
+
+    /// Check if a key exists
+    ///
+    /// # Parameters
+    ///   * `key`: Key to check for existence
+    ///
+    /// # Return Values
+    ///   * Ok(`true`): Key exists
+    ///   * Ok(`false`): Key doesn't exist
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn key_exists(&self, key: &str) -> Result<bool, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let data = self.read_data(key)?;
+        Ok(data.stored_value(key).is_some())
+    }
+
+    /// Get number of stored keys
+    ///
+    /// Defaults and expired values are not counted, zero is returned if KVS is marked corrupt.
+    ///
+    /// # Return Values
+    ///   * Ok: Number of stored keys
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn len(&self) -> Result<usize, ErrorCode> {
+        let data = self.data.read()?;
+        if data.corrupt_reason.is_some() {
+            return Ok(0);
+        }
+        Ok(data.kvs_map.keys().filter(|k| !data.is_expired(k)).count())
+    }
+
+    /// Check if no keys are stored
+    ///
+    /// # Return Values
+    ///   * Ok(`true`): No keys are stored
+    ///   * Ok(`false`): At least one key is stored
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn is_empty(&self) -> Result<bool, ErrorCode> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Get the assigned value for a given key
+    ///
+    /// Only default values are returned if KVS is marked corrupt. Default value of a locked key
+    /// takes precedence over its stored value.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to retrieve the value from
+    ///
+    /// # Return Value
+    ///   * Ok: Type specific value if key was found
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::KeyNotFound`: Key wasn't found in KVS nor in defaults
+    fn get_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        count!(self.parameters, gets);
+        let data = self.read_value(key)?;
+        if let Some(value) = self.visible_value(&data, key) {
+            Ok(value.clone())
+        } else if let Some(value) = data.defaults_map.get(key) {
+            Ok(value.clone())
+        } else {
+            count!(self.parameters, key_not_found);
+            eprintln!("error: get_value could not find key: {key}");
+            Err(ErrorCode::KeyNotFound)
+        }
+    }
+
+    /// Get the assigned value for a given key, if any
+    ///
+    /// Unlike `get_value`, absent key is not an error. Stored `KvsValue::Null` is returned as
+    /// `Some(KvsValue::Null)`, `None` is returned only if key is found neither in KVS nor in defaults.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to retrieve the value from
+    ///
+    /// # Return Value
+    ///   * Ok(Some): Value if key was found
+    ///   * Ok(None): Key wasn't found in KVS nor in defaults
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn try_get_value(&self, key: &str) -> Result<Option<KvsValue>, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        count!(self.parameters, gets);
+        let data = self.read_value(key)?;
+        Ok(self
+            .visible_value(&data, key)
+            .or_else(|| data.defaults_map.get(key))
+            .cloned())
+    }
+
+    /// Borrow the assigned value for a given key, if any
+    ///
+    /// Value is looked up same as by `try_get_value`, but passed to the closure by reference
+    /// instead of being cloned. Useful for large `Array`, `Object` or `String` values.
+    ///
+    /// Data lock is held while the closure runs. Closure must not call back into this KVS
+    /// instance (or any handle sharing it), modifying calls would deadlock.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to retrieve the value from
+    ///   * `f`: Closure receiving the value, `None` if key wasn't found in KVS nor in defaults
+    ///
+    /// # Return Value
+    ///   * Ok: Result of the closure
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn with_value<R, F: FnOnce(Option<&KvsValue>) -> R>(&self, key: &str, f: F) -> Result<R, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        count!(self.parameters, gets);
+        let data = self.read_value(key)?;
+        Ok(f(self.visible_value(&data, key).or_else(|| data.defaults_map.get(key))))
+    }
+
+    /// Get the assigned value for a given key
+    ///
+    /// See [Variants](https://docs.rs/tinyjson/latest/tinyjson/enum.JsonValue.html#variants) for
+    /// supported value types. Numeric values of other variants are converted if they fit into the
+    /// requested type losslessly, e.g. `KvsValue::I32` read as `i64`.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to retrieve the value from
+    ///
+    /// # Return Value
+    ///   * Ok: Type specific value if key was found
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::ConversionFailed`: Type conversion failed
+    ///   * `ErrorCode::KeyNotFound`: Key wasn't found in KVS nor in defaults
+    fn get_value_as<T>(&self, key: &str) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + core::clone::Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
+    {
+        let key = &*self.parameters.normalize_key(key);
+        count!(self.parameters, gets);
+        let data = self.read_value(key)?;
+        if let Some(value) = self.visible_value(&data, key) {
+            match T::try_from(value) {
+                Ok(value) => Ok(value),
+                Err(err) => {
+                    eprintln!("error: get_value could not convert KvsValue from KVS store: {err:#?}");
+                    Err(ErrorCode::ConversionFailed)
+                },
+            }
+        } else if let Some(value) = data.defaults_map.get(key) {
+            // check if key has a default value
+            match T::try_from(value) {
+                Ok(value) => Ok(value),
+                Err(err) => {
+                    eprintln!("error: get_value could not convert KvsValue from default store: {err:#?}");
+                    Err(ErrorCode::ConversionFailed)
+                },
+            }
+        } else {
+            count!(self.parameters, key_not_found);
+            eprintln!("error: get_value could not find key: {key}");
+
+            Err(ErrorCode::KeyNotFound)
+        }
+    }
+
+    /// Get the assigned value for a given key, or provided fallback if key is absent
+    ///
+    /// Fallback is returned only if key is found neither in KVS nor in defaults. Present value of
+    /// a wrong type is still an error.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to retrieve the value from
+    ///   * `default`: Value returned if key is absent
+    ///
+    /// # Return Value
+    ///   * Ok: Type specific value if key was found, `default` otherwise
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::ConversionFailed`: Type conversion failed
+    fn get_value_or<T>(&self, key: &str, default: T) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + core::clone::Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
+    {
+        let key = &*self.parameters.normalize_key(key);
+        count!(self.parameters, gets);
+        let data = self.read_value(key)?;
+        match self.visible_value(&data, key).or_else(|| data.defaults_map.get(key)) {
+            Some(value) => T::try_from(value).map_err(|err| {
+                eprintln!("error: get_value_or could not convert KvsValue: {err:#?}");
+                ErrorCode::ConversionFailed
+            }),
+            None => Ok(default),
+        }
+    }
+
+    /// Get default value for a given key
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///   * `FEAT_REQ__KVS__default_value_retrieval`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to get the default for
+    ///
+    /// # Return Values
+    ///   * Ok: `KvsValue` for the key
+    ///   * `ErrorCode::KeyNotFound`: Key not found in defaults
+    fn get_default_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let data = self.read_with_defaults()?;
+        if let Some(value) = data.defaults_map.get(key) {
+            Ok(value.clone())
+        } else {
+            Err(ErrorCode::KeyNotFound)
+        }
+    }
+
+    /// Get list of all keys having a default value
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Return Values
+    ///   * Ok: List of keys with defaults, in unspecified order
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn default_keys(&self) -> Result<Vec<String>, ErrorCode> {
+        Ok(self.read_with_defaults()?.defaults_map.keys().cloned().collect())
+    }
+
+    /// Check if a key has a default value
+    ///
+    /// Stored value of the key is not considered.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to check for a default
+    ///
+    /// # Return Values
+    ///   * Ok(true): Key has a default value
+    ///   * Ok(false): Key has no default value
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn contains_default(&self, key: &str) -> Result<bool, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        Ok(self.read_with_defaults()?.defaults_map.contains_key(key))
+    }
+
+    /// Return if the value wasn't set yet and uses its default value
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `key`: Key to check if a default exists
+    ///
+    /// # Return Values
+    ///   * Ok(true): Key currently returns the default value
+    ///   * Ok(false): Key returns the set value
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::KeyNotFound`: Key wasn't found
+    fn is_value_default(&self, key: &str) -> Result<bool, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let data = self.read_with_defaults()?;
+        if self.visible_value(&data, key).is_some() {
+            Ok(false)
+        } else if data.defaults_map.contains_key(key) {
+            Ok(true)
+        } else {
+            Err(ErrorCode::KeyNotFound)
+        }
+    }
+
+    /// Get report of effective default values with their sources
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Return Values
+    ///   * Ok: Effective default values with sources, sorted by key
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn effective_defaults_report(&self) -> Result<Vec<(String, KvsValue, DefaultsSource)>, ErrorCode> {
+        let data = self.read_with_defaults()?;
+        let mut report: Vec<(String, KvsValue, DefaultsSource)> = data
+            .defaults_map
+            .iter()
+            .map(|(key, value)| {
+                let source = data.defaults_sources.get(key).copied().unwrap_or(DefaultsSource::File);
+                (key.clone(), value.clone(), source)
+            })
+            .collect();
+        report.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(report)
+    }
+
+    /// Replace all default values
+    ///
+    /// Only in-memory defaults are replaced, defaults file is not modified. Replaced defaults are
+    /// attributed to programmatic source and are lost once the instance is rebuilt.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Parameters
+    ///   * `defaults`: New default values
+    ///
+    /// # Return Values
+    ///   * Ok: Default values replaced
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::InvalidKey`: Keys differ only in case with case-insensitive keys
+    fn set_defaults(&self, defaults: KvsMap) -> Result<(), ErrorCode> {
+        let defaults = self.parameters.normalize_keys(defaults)?;
+        let mut data = self.data.write()?;
+        data.lazy_defaults = None;
+        data.defaults_map.clear();
+        data.defaults_sources.clear();
+        data.apply_defaults(defaults, DefaultsSource::Programmatic, true);
+        Ok(())
+    }
+
+    /// Remove all default values
+    ///
+    /// Only in-memory defaults are removed, defaults file is not modified.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__default_values`
+    ///
+    /// # Return Values
+    ///   * Ok: Default values removed
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn clear_defaults(&self) -> Result<(), ErrorCode> {
+        self.set_defaults(KvsMap::new())
+    }
+
+    /// Assign a value to a given key
+    ///
+    /// # Parameters
+    ///   * `key`: Key to set value
+    ///   * `value`: Value to be set
+    ///
+    /// # Return Values
+    ///   * Ok: Value was assigned to key
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn set_value<S: Into<String>, V: Into<KvsValue>>(&self, key: S, value: V) -> Result<(), ErrorCode> {
+        self.store_value(key.into(), value.into(), None)
+    }
+
+    /// Assign a value expiring after given time to a given key
+    ///
+    /// Expired values are treated as absent and removed on access. Expiry is based on wall-clock
+    /// time of the clock set with `KvsBuilder::clock` and persisted on flush, so it remains
+    /// meaningful across restarts.
+    ///
+    /// # Parameters
+    ///   * `key`: Key to set value
+    ///   * `value`: Value to be set
+    ///   * `ttl`: Time after which value expires
+    ///
+    /// # Return Values
+    ///   * Ok: Value was assigned to key
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn set_value_with_ttl<S: Into<String>, V: Into<KvsValue>>(
+        &self,
+        key: S,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), ErrorCode> {
+        self.store_value(key.into(), value.into(), Some(ttl))
+    }
+
+    /// Assign a value to a given key if current value matches expected value
+    ///
+    /// Check and assignment are performed under a single lock. Current value is the value returned
+    /// by `get_value`, default values are considered.
+    ///
+    /// # Parameters
+    ///   * `key`: Key to set value
+    ///   * `expected`: Expected current value, `None` if key is expected to be absent
+    ///   * `new`: Value to be set
+    ///
+    /// # Return Values
+    ///   * Ok(`true`): Current value matched, value was assigned to key
+    ///   * Ok(`false`): Current value didn't match, nothing was modified
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn compare_and_set<V: Into<KvsValue>>(
+        &self,
+        key: &str,
+        expected: Option<&KvsValue>,
+        new: V,
+    ) -> Result<bool, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        self.parameters.check_key(key)?;
+        self.parameters.check_unlocked(key)?;
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+        data.remove_expired(key);
+
+        let current = data.stored_value(key).or_else(|| data.defaults_map.get(key));
+        if current != expected {
+            return Ok(false);
+        }
+
+        let new = new.into();
+        let event = self.key_event(&data, AuditOperation::Set, key, Some(&new));
+        Self::store_locked(data, key.to_string(), new, None);
+        count!(self.parameters, sets);
+        self.parameters.audit(event);
+        Ok(true)
+    }
+
+    /// Assign a value to a given key and return its previous value
+    ///
+    /// Read and assignment are performed under a single lock. Previous value is the value
+    /// returned by `get_value`, default values are considered.
+    ///
+    /// # Parameters
+    ///   * `key`: Key to set value
+    ///   * `value`: Value to be set
+    ///
+    /// # Return Values
+    ///   * Ok(Some): Value was assigned, previous value is returned
+    ///   * Ok(None): Value was assigned, key was found neither in KVS nor in defaults
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn swap<V: Into<KvsValue>>(&self, key: &str, value: V) -> Result<Option<KvsValue>, ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        self.parameters.check_key(key)?;
+        self.parameters.check_unlocked(key)?;
+        let data = self.write_with_defaults()?;
+        data.check_writable()?;
+
+        let previous = data.stored_value(key).or_else(|| data.defaults_map.get(key)).cloned();
+        let value = value.into();
+        let event = self.key_event(&data, AuditOperation::Set, key, Some(&value));
+        Self::store_locked(data, key.to_string(), value, None);
+        count!(self.parameters, sets);
+        self.parameters.audit(event);
+        Ok(previous)
+    }
+
+    /// Move stored value of a key to another key
+    ///
+    /// Value and its expiry are moved under a single lock. Only stored values are moved, defaults
+    /// of both keys are kept. Watchers of both keys are notified with their new effective values.
+    ///
+    /// # Parameters
+    ///   * `from`: Key to move value from
+    ///   * `to`: Key to move value to
+    ///   * `overwrite`: Replace stored value of `to`, otherwise it must not exist
+    ///
+    /// # Return Values
+    ///   * Ok: Value moved, or `from` and `to` are same key with stored value
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::KeyNotFound`: `from` has no stored value
+    ///   * `ErrorCode::KeyAlreadyExists`: `to` has stored value and `overwrite` is not set
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: `to` rejected by key validator
+    ///   * `ErrorCode::KeyLocked`: Either key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key)
+    fn rename_key(&self, from: &str, to: &str, overwrite: bool) -> Result<(), ErrorCode> {
+        let from = &*self.parameters.normalize_key(from);
+        let to = &*self.parameters.normalize_key(to);
+        self.parameters.check_key(to)?;
+        self.parameters.check_unlocked(from)?;
+        self.parameters.check_unlocked(to)?;
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+        data.remove_expired(from);
+        data.remove_expired(to);
+        if !data.kvs_map.contains_key(from) {
+            count!(self.parameters, key_not_found);
+            return Err(ErrorCode::KeyNotFound);
+        }
+        if from == to {
+            return Ok(());
+        }
+        if !overwrite && data.kvs_map.contains_key(to) {
+            return Err(ErrorCode::KeyAlreadyExists);
+        }
+
+        // Presence checked above.
+        let Some(value) = data.kvs_map.remove(from) else {
+            return Err(ErrorCode::KeyNotFound);
+        };
+        let old = data.kvs_map.insert(to.to_string(), value.clone());
+        match data.expiry_map.remove(from) {
+            Some(expiry) => data.expiry_map.insert(to.to_string(), expiry),
+            None => data.expiry_map.remove(to),
+        };
+        data.restore_stash = None;
+        data.dirty = true;
+        count!(self.parameters, removes);
+        count!(self.parameters, sets);
+        let events = self.parameters.audited().then(|| {
+            let timestamp = data.clock.now();
+            [
+                AuditEvent {
+                    timestamp,
+                    operation: AuditOperation::Remove,
+                    key: Some(from.to_string()),
+                    old: Some(value.clone()),
+                    new: None,
+                },
+                AuditEvent {
+                    timestamp,
+                    operation: AuditOperation::Set,
+                    key: Some(to.to_string()),
+                    old,
+                    new: Some(value.clone()),
+                },
+            ]
+        });
+
+        let from_callbacks = data.key_watchers(from);
+        let to_callbacks = data.key_watchers(to);
+        let from_value = (!from_callbacks.is_empty()).then(|| data.effective_value(from));
+        drop(data);
+        if let Some(from_value) = from_value {
+            Self::notify(&from_callbacks, from, &from_value);
+        }
+        Self::notify(&to_callbacks, to, &value);
+        for event in events.into_iter().flatten() {
+            self.parameters.audit(Some(event));
+        }
+        Ok(())
+    }
+
+    /// Remove a key
+    ///
+    /// # Parameters
+    ///   * `key`: Key to remove
+    ///
+    /// # Return Values
+    ///   * Ok: Key removed successfully
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::KeyNotFound`: Key not found
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn remove_key(&self, key: &str) -> Result<(), ErrorCode> {
+        let key = &*self.parameters.normalize_key(key);
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+        data.remove_expired(key);
+        let Some(old) = data.kvs_map.remove(key) else {
+            count!(self.parameters, key_not_found);
+            return Err(ErrorCode::KeyNotFound);
+        };
+        let _ = data.expiry_map.remove(key);
+        data.dirty = true;
+        count!(self.parameters, removes);
+        let event = self.parameters.audited().then(|| AuditEvent {
+            timestamp: data.clock.now(),
+            operation: AuditOperation::Remove,
+            key: Some(key.to_string()),
+            old: Some(old),
+            new: None,
+        });
+
+        let callbacks = data.key_watchers(key);
+        let notification = (!callbacks.is_empty()).then(|| data.effective_value(key));
+        drop(data);
+        if let Some(value) = notification {
+            Self::notify(&callbacks, key, &value);
+        }
+        self.parameters.audit(event);
+        Ok(())
+    }
+
+    /// Remove all keys starting with a prefix
+    ///
+    /// Keys are removed under a single lock, prefix is matched same as in `keys_with_prefix`.
+    /// Only stored values are removed, defaults are kept.
+    ///
+    /// # Parameters
+    ///   * `prefix`: Prefix of keys
+    ///
+    /// # Return Values
+    ///   * Ok: Count of removed keys
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn remove_prefix(&self, prefix: &str) -> Result<usize, ErrorCode> {
+        let prefix = &*self.parameters.normalize_key(prefix);
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+
+        let keys: Vec<String> = data.kvs_map.keys().filter(|k| k.starts_with(prefix)).cloned().collect();
+        let mut removed = Vec::new();
+        let mut events = Vec::new();
+        for key in keys {
+            // Expired values are already treated as absent.
+            let expired = data.is_expired(&key);
+            if !expired {
+                events.extend(self.key_event(&data, AuditOperation::Remove, &key, None));
+            }
+            let _ = data.kvs_map.remove(&key);
+            let _ = data.expiry_map.remove(&key);
+            data.dirty = true;
+            if !expired {
+                removed.push(key);
+            }
+        }
+
+        let notifications: Vec<_> = removed
+            .iter()
+            .map(|key| (data.key_watchers(key), key, data.effective_value(key)))
+            .filter(|(callbacks, _, _)| !callbacks.is_empty())
+            .collect();
+        drop(data);
+        for (callbacks, key, value) in notifications {
+            Self::notify(&callbacks, key, &value);
+        }
+        self.audit_all(events);
+        count!(self.parameters, removes, removed.len() as u64);
+        Ok(removed.len())
+    }
+
+    /// Drop stored values not matching a predicate and flush the result
+    ///
+    /// Filtering and flush are done under a single lock, so no other change can get in between.
+    /// Expired values are dropped regardless of the predicate. Defaults are kept.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///   * `FEAT_REQ__KVS__persistency`
+    ///
+    /// # Parameters
+    ///   * `keep`: Predicate called with key and value, returns `true` to keep the entry
+    ///
+    /// # Return Values
+    ///   * Ok: Entries dropped and result flushed
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize to JSON
+    ///   * `ErrorCode::ConversionFailed`: JSON could not serialize into String
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn compact<F: Fn(&str, &KvsValue) -> bool>(&self, keep: F) -> Result<(), ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+        let mut data = self.write_with_defaults()?;
+        data.check_writable()?;
+
+        let keys: Vec<String> = data
+            .kvs_map
+            .iter()
+            .filter(|(k, v)| data.is_expired(k) || !keep(k, v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        let mut removed = Vec::new();
+        let mut events = Vec::new();
+        for key in keys {
+            // Expired values are already treated as absent.
+            let expired = data.is_expired(&key);
+            if !expired {
+                events.extend(self.key_event(&data, AuditOperation::Remove, &key, None));
+            }
+            let _ = data.kvs_map.remove(&key);
+            let _ = data.expiry_map.remove(&key);
+            data.dirty = true;
+            if !expired {
+                removed.push(key);
+            }
+        }
+
+        let result = if !data.dirty && self.parameters.skip_clean_flush {
+            Ok(())
+        } else {
+            self.flush_data(&data.kvs_map, &data.expiry_map).map(|hash| {
+                data.last_flush_hash = hash;
+                data.restore_stash = None;
+                data.dirty = false;
+                count!(self.parameters, flushes);
+                events.extend(self.storage_event(&data, AuditOperation::Flush));
+            })
+        };
+
+        // Watchers are notified even if flush failed, dropped values stay dropped in memory.
+        let notifications: Vec<_> = removed
+            .iter()
+            .map(|key| (data.key_watchers(key), key, data.effective_value(key)))
+            .filter(|(callbacks, _, _)| !callbacks.is_empty())
+            .collect();
+        drop(data);
+        for (callbacks, key, value) in notifications {
+            Self::notify(&callbacks, key, &value);
+        }
+        self.audit_all(events);
+        count!(self.parameters, removes, removed.len() as u64);
+        result
+    }
+
+    /// Merge another map into the key-value-storage
+    ///
+    /// # Parameters
+    ///   * `other`: Map to merge
+    ///   * `policy`: Conflict policy for keys existing in both maps
+    ///
+    /// # Return Values
+    ///   * Ok: Map merged
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::InvalidKey`: Key rejected by key validator or keys differ only in case with
+    ///     case-insensitive keys, nothing was merged
+    ///   * `ErrorCode::KeyLocked`: Key is locked by [`KvsBuilder::locked_key`](crate::kvs_builder::KvsBuilder::locked_key),
+    ///     nothing was merged
+    fn merge(&self, other: KvsMap, policy: MergePolicy) -> Result<(), ErrorCode> {
+        let other = self.parameters.normalize_keys(other)?;
+        for key in other.keys() {
+            self.parameters.check_key(key)?;
+            self.parameters.check_unlocked(key)?;
+        }
+
+        let mut data = self.data.write()?;
+        data.check_writable()?;
+
+        // Merged values don't expire, unless existing value is preserved.
+        for key in other.keys() {
+            data.remove_expired(key);
+            if policy != MergePolicy::PreferExisting {
+                let _ = data.expiry_map.remove(key);
+            }
+        }
+
+        let events = self.merge_locked(&mut data, other, policy);
+        drop(data);
+        self.audit_all(events);
+        Ok(())
+    }
+
+    /// Check whether storage data changed since last flush
+    ///
+    /// Data is changed by any successful modification, also if the resulting content is equal,
+    /// and by removal of expired values.
+    ///
+    /// # Return Values
+    ///   * Ok: `true` if data changed since last flush
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn is_dirty(&self) -> Result<bool, ErrorCode> {
+        Ok(self.data.read()?.dirty)
+    }
+
+    /// Flush the in-memory key-value-storage to the persistent storage
+    ///
+    /// Current KVS state is captured on call and written without holding the data lock, so
+    /// other threads are not blocked by the backend IO. Changes made while the flush is in
+    /// progress are not included and are persisted by the next flush.
+    ///
+    /// If enabled with `KvsBuilder::skip_clean_flush`, flush of unchanged data is skipped without
+    /// touching the backend.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///   * `FEAT_REQ__KVS__persistency`
+    ///   * `FEAT_REQ__KVS__integrity_check`
+    ///
+    /// # Return Values
+    ///   * Ok: Flush successful
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::JsonGeneratorError`: Failed to serialize to JSON
+    ///   * `ErrorCode::ConversionFailed`: JSON could not serialize into String
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::QuotaExceeded`: Size limit of backend exceeded
+    ///   * `ErrorCode::OutOfStorageSpace`: Storage is full
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn flush(&self) -> Result<(), ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+        self.flush_locked()
+    }
+
+    /// Flush the in-memory key-value-storage to the persistent storage and report written snapshot
+    ///
+    /// Same as `flush`, ID is reported by the backend while flush lock is still held. Returned ID
+    /// refers to the written snapshot until the next flush rotates it, e.g. to `SnapshotId(1)`.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///   * `FEAT_REQ__KVS__persistency`
+    ///
+    /// # Return Values
+    ///   * Ok: ID of written snapshot, `SnapshotId(0)` for `JsonBackend`
+    ///   * Errors same as for `flush`
+    fn flush_with_id(&self) -> Result<SnapshotId, ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+        self.flush_locked()?;
+        Ok(self
+            .parameters
+            .backend()
+            .flushed_snapshot_id(self.parameters.instance_id))
+    }
+
+    /// Flush the in-memory key-value-storage to the persistent storage in steps
+    ///
+    /// Current KVS state is captured on call, changes made while driving the flush are not
+    /// included. Result after completion is same as for `flush`, including dirty state and flush
+    /// hash. Flush dropped before completion leaves data changed.
+    ///
+    /// If enabled with `KvsBuilder::skip_clean_flush`, returned flush of unchanged data completes
+    /// without touching the backend.
+    ///
+    /// Flush lock of the instance is held until the returned flush completes, fails or is dropped.
+    /// Other flushes of the instance wait until then, so snapshots are not rotated in between steps.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///   * `FEAT_REQ__KVS__persistency`
+    ///   * `FEAT_REQ__KVS__integrity_check`
+    ///
+    /// # Return Values
+    ///   * Ok: Chunked flush state machine, drive with `ChunkedFlush::step`
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn flush_chunked(&self) -> Result<Box<dyn ChunkedFlush + '_>, ErrorCode> {
+        let flush_guard = self.parameters.lock_flush();
+        let (kvs_map, expiry_map) = {
+            let mut data = self.data.write()?;
+            data.check_writable()?;
+            if !data.dirty && self.parameters.skip_clean_flush {
+                return Ok(Box::new(CompletedFlush));
+            }
+
+            // Changes made while driving the flush mark data dirty again.
+            data.dirty = false;
+            data.restore_stash = None;
+            (data.kvs_map.clone(), data.expiry_map.clone())
+        };
+
+        let backend = self.parameters.backend();
+        let (backend, flush): (_, Box<dyn ChunkedFlush>) = if backend.snapshot_max_count() == 0 {
+            eprintln!("warn: snapshot_max_count == 0, flush ignored");
+            (None, Box::new(CompletedFlush))
+        } else {
+            let flush = backend
+                .clone()
+                .flush_chunked(self.parameters.instance_id, kvs_map, expiry_map);
+            (Some(backend), flush)
+        };
+
+        Ok(Box::new(KvsChunkedFlush {
+            kvs: self,
+            flush_guard: Some(flush_guard),
+            backend,
+            flush,
+        }))
+    }
+
+    /// Get the count of snapshots
+    ///
+    /// # Return Values
+    ///   * usize: Count of found snapshots
+    fn snapshot_count(&self) -> usize {
+        self.parameters.backend().snapshot_count(self.parameters.instance_id)
+    }
+
+    /// Get IDs of available snapshots
+    ///
+    /// Unlike `snapshot_count`, gaps between snapshots are reported instead of truncating.
+    ///
+    /// # Return Values
+    ///   * Vec<SnapshotId>: IDs of found snapshots in ascending order
+    fn snapshots(&self) -> Vec<SnapshotId> {
+        self.parameters.backend().snapshots(self.parameters.instance_id)
+    }
+
+    /// Return maximum number of snapshots to store.
+    ///
+    /// # Return Values
+    ///   * usize: Maximum count of snapshots
+    fn snapshot_max_count(&self) -> usize {
+        self.parameters.backend().snapshot_max_count()
+    }
+
+    /// Recover key-value-storage from snapshot
+    ///
+    /// Restore a previously created KVS snapshot. Replaced data is stashed and can be brought
+    /// back with `snapshot_unrestore`.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///
+    /// # Parameters
+    ///   * `id`: Snapshot ID
+    ///
+    /// # Return Values
+    ///   * `Ok`: Snapshot restored
+    ///   * `ErrorCode::InvalidSnapshotId`: Invalid snapshot ID
+    ///   * `ErrorCode::ValidationFailed`: KVS hash validation failed
+    ///   * `ErrorCode::JsonParserError`: JSON parser error
+    ///   * `ErrorCode::InvalidStorageFormat`: KVS file is valid JSON, but not an object
+    ///   * `ErrorCode::KvsFileReadError`: KVS file not found
+    ///   * `ErrorCode::KvsHashFileReadError`: KVS hash file read error
+    ///   * `ErrorCode::HashFileNotFound`: KVS hash file not found
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    ///   * `ErrorCode::UnmappedError`: Snapshots not supported by backend or generic error
+    fn snapshot_restore(&self, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        data.check_writable()?;
+        self.restore_data(&mut data, snapshot_id)?;
+        let event = self.storage_event(&data, AuditOperation::Restore);
+        drop(data);
+        self.parameters.audit(event);
+        Ok(())
+    }
+
+    /// Recover key-value-storage from snapshot and flush it
+    ///
+    /// Restore a previously created KVS snapshot and flush restored state, so it becomes the
+    /// current snapshot (0). Flush lock is held throughout, so no other flush gets in between.
+    /// Restored state is captured under the data lock and written without holding it, as in
+    /// `flush`. Snapshots are rotated as in a regular `flush`: the
+    /// previous current snapshot becomes snapshot 1 and the restored snapshot `n` itself becomes
+    /// `n + 1`, or is dropped if it was the oldest one kept.
+    ///
+    /// In-memory state is restored even if flush fails.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///   * `FEAT_REQ__KVS__persistency`
+    ///
+    /// # Parameters
+    ///   * `id`: Snapshot ID
+    ///
+    /// # Return Values
+    ///   * `Ok`: Snapshot restored and flushed
+    ///   * Errors returned by `snapshot_restore`
+    ///   * Errors returned by `flush`
+    fn snapshot_restore_and_flush(&self, snapshot_id: SnapshotId) -> Result<(), ErrorCode> {
+        let _flush_guard = self.parameters.lock_flush();
+        let (kvs_map, expiry_map, event) = {
+            let mut data = self.data.write()?;
+            data.check_writable()?;
+            self.restore_data(&mut data, snapshot_id)?;
+
+            // Changes made during backend IO mark data dirty again.
+            data.dirty = false;
+            let event = self.storage_event(&data, AuditOperation::Restore);
+            (data.kvs_map.clone(), data.expiry_map.clone(), event)
+        };
+        self.parameters.audit(event);
+        self.flush_captured(&kvs_map, &expiry_map)
+    }
+
+    /// Undo the last snapshot restore
+    ///
+    /// Data replaced by `snapshot_restore` is brought back within the same session. Stashed data
+    /// is kept in memory only and is cleared by `set_value`, `flush` and a successful
+    /// `snapshot_restore_and_flush`. Only the last restore can be undone.
+    ///
+    /// # Features
+    ///   * `FEAT_REQ__KVS__snapshots`
+    ///
+    /// # Return Values
+    ///   * Ok: Data from before the restore is brought back
+    ///   * `ErrorCode::InvalidSnapshotId`: No restore to undo
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    ///   * `ErrorCode::IntegrityCorrupted`: KVS marked corrupt
+    fn snapshot_unrestore(&self) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        data.check_writable()?;
+        let Some((kvs_map, expiry_map)) = data.restore_stash.take() else {
+            eprintln!("error: no snapshot restore to undo");
+            return Err(ErrorCode::InvalidSnapshotId);
+        };
+        data.kvs_map = kvs_map;
+        data.expiry_map = expiry_map;
+        data.dirty = true;
+        let event = self.storage_event(&data, AuditOperation::Restore);
+        drop(data);
+        self.parameters.audit(event);
+        Ok(())
+    }
+
+    /// Mark key-value-storage as corrupt and enter degraded mode
+    ///
+    /// In degraded mode mutations and flush are rejected and only default values are served.
+    /// State is shared by all handles of the instance and persists until `clear_corrupt` is called.
+    ///
+    /// # Parameters
+    ///   * `reason`: Reason of the corruption, reported on rejected operations
+    ///
+    /// # Return Values
+    ///   * Ok: KVS marked corrupt
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn mark_corrupt(&self, reason: &str) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        eprintln!("error: KVS marked corrupt: {reason}");
+        data.corrupt_reason = Some(reason.to_string());
+        Ok(())
+    }
+
+    /// Leave degraded mode and restore normal operation
+    ///
+    /// # Return Values
+    ///   * Ok: Normal operation restored, also if KVS was not marked corrupt
+    ///   * `ErrorCode::MutexLockFailed`: Mutex locking failed
+    fn clear_corrupt(&self) -> Result<(), ErrorCode> {
+        let mut data = self.data.write()?;
+        data.corrupt_reason = None;
+        Ok(())
+    }
+}
+
+/// Read-only handle of a KVS instance
+///
+/// Created with [`Kvs::read_only`]. Only reading operations are exposed, modifications are
+/// rejected at compile time. Instance parameters and backend are not accessible through the handle,
+/// so it cannot be used to write persisted data either.
+///
+/// ```compile_fail
+/// use rust_kvs::prelude::*;
+///
+/// let kvs = KvsBuilder::new(InstanceId(0)).build().unwrap();
+/// let read_only = kvs.read_only();
+/// let _ = read_only.parameters().backend();
+/// ```
+pub struct ReadOnlyKvs {
+    /// Instance the handle reads from.
+    kvs: Kvs,
+}
+
+impl ReadOnlyKvs {
+    /// Instance ID of the instance the handle reads from.
+    pub fn instance_id(&self) -> InstanceId {
+        self.kvs.parameters.instance_id
+    }
+
+    /// Get value assigned to key, see [`KvsApi::get_value`].
+    pub fn get_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
+        self.kvs.get_value(key)
+    }
+
+    /// Borrow value assigned to key, see [`KvsApi::with_value`].
+    pub fn with_value<R, F: FnOnce(Option<&KvsValue>) -> R>(&self, key: &str, f: F) -> Result<R, ErrorCode> {
+        self.kvs.with_value(key, f)
+    }
+
+    /// Get value assigned to key converted to `T`, see [`KvsApi::get_value_as`].
+    pub fn get_value_as<T>(&self, key: &str) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
+    {
+        self.kvs.get_value_as(key)
+    }
+
+    /// Get value assigned to key converted to `T`, or fallback, see [`KvsApi::get_value_or`].
+    pub fn get_value_or<T>(&self, key: &str, default: T) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
+    {
+        self.kvs.get_value_or(key, default)
+    }
+
+    /// Get list of all keys, see [`KvsApi::get_all_keys`].
+    pub fn get_all_keys(&self) -> Result<Vec<String>, ErrorCode> {
+        self.kvs.get_all_keys()
+    }
+
+    /// Check if key exists, see [`KvsApi::key_exists`].
+    pub fn key_exists(&self, key: &str) -> Result<bool, ErrorCode> {
+        self.kvs.key_exists(key)
+    }
+
+    /// Check if value is the default value, see [`KvsApi::is_value_default`].
+    pub fn is_value_default(&self, key: &str) -> Result<bool, ErrorCode> {
+        self.kvs.is_value_default(key)
+    }
+
+    /// Get default value of key, see [`KvsApi::get_default_value`].
+    pub fn get_default_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
+        self.kvs.get_default_value(key)
+    }
+
+    /// Get list of keys with defaults, see [`KvsApi::default_keys`].
+    pub fn default_keys(&self) -> Result<Vec<String>, ErrorCode> {
+        self.kvs.default_keys()
+    }
+
+    /// Check if key has a default, see [`KvsApi::contains_default`].
+    pub fn contains_default(&self, key: &str) -> Result<bool, ErrorCode> {
+        self.kvs.contains_default(key)
+    }
+
+    /// Get count of snapshots, see [`KvsApi::snapshot_count`].
+    pub fn snapshot_count(&self) -> usize {
+        self.kvs.snapshot_count()
+    }
+}
+
+#[cfg(test)]
+mod kvs_tests {
+    use crate::error_code::ErrorCode;
+    use crate::json_backend::JsonBackendBuilder;
+    use crate::kvs::{Kvs, KvsParameters, ReadOnlyKvs};
+    use crate::kvs_api::{DefaultsSource, InstanceId, KvsApi, KvsDefaults, KvsLoad, MergePolicy, SnapshotId};
+    use crate::kvs_backend::{BackendCapabilities, FlushProgress, KvsBackend};
+    use crate::kvs_builder::KvsData;
+    use crate::kvs_value::{KvsMap, KvsValue};
+    use core::time::Duration;
+    use std::sync::{Arc, Barrier, Mutex, RwLock};
+    use std::time::{Instant, SystemTime};
+    use tempfile::tempdir;
+
+    /// Most tests can be performed with mocked backend.
+    /// Only those with file handling must use concrete implementation.
+    #[derive(PartialEq)]
+    struct MockBackend;
+
+    impl KvsBackend for MockBackend {
+        fn load_kvs(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            unimplemented!()
+        }
+
+        fn load_defaults(&self, _instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
+            unimplemented!()
+        }
+
+        fn flush(&self, _instance_id: InstanceId, _kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+            unimplemented!()
+        }
+
+        fn snapshot_count(&self, _instance_id: InstanceId) -> usize {
+            unimplemented!()
+        }
+
+        fn snapshot_max_count(&self) -> usize {
+            unimplemented!()
+        }
+
+        fn snapshot_restore(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            unimplemented!()
+        }
+    }
+
+    /// In-memory backend, flushed data is shared between clones.
+    #[derive(Clone, Default)]
+    struct MemoryBackend {
+        kvs_map: Arc<Mutex<Option<KvsMap>>>,
+    }
+
+    impl PartialEq for MemoryBackend {
+        fn eq(&self, other: &Self) -> bool {
+            Arc::ptr_eq(&self.kvs_map, &other.kvs_map)
+        }
+    }
+
+    impl KvsBackend for MemoryBackend {
+        fn load_kvs(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            self.kvs_map.lock().unwrap().clone().ok_or(ErrorCode::FileNotFound)
+        }
+
+        fn load_defaults(&self, _instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
+            Err(ErrorCode::FileNotFound)
+        }
+
+        fn flush(&self, _instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+            *self.kvs_map.lock().unwrap() = Some(kvs_map.clone());
+            Ok(())
+        }
+
+        fn snapshot_count(&self, _instance_id: InstanceId) -> usize {
+            self.kvs_map.lock().unwrap().iter().count()
+        }
+
+        fn snapshot_max_count(&self) -> usize {
+            1
+        }
+
+        fn snapshot_restore(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            Err(ErrorCode::InvalidSnapshotId)
+        }
+    }
+
+    /// Backend with slow flush, signals flush start and stores flushed data.
+    #[derive(Clone)]
+    struct SlowBackend {
+        flush_started: Arc<Barrier>,
+        kvs_map: Arc<Mutex<Option<KvsMap>>>,
+    }
+
+    impl PartialEq for SlowBackend {
+        fn eq(&self, other: &Self) -> bool {
+            Arc::ptr_eq(&self.kvs_map, &other.kvs_map)
+        }
+    }
+
+    impl SlowBackend {
+        const FLUSH_DURATION: Duration = Duration::from_millis(500);
+    }
+
+    impl KvsBackend for SlowBackend {
+        fn load_kvs(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            self.kvs_map.lock().unwrap().clone().ok_or(ErrorCode::FileNotFound)
+        }
+
+        fn load_defaults(&self, _instance_id: InstanceId) -> Result<KvsMap, ErrorCode> {
+            Err(ErrorCode::FileNotFound)
+        }
+
+        fn flush(&self, _instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode> {
+            self.flush_started.wait();
+            std::thread::sleep(Self::FLUSH_DURATION);
+            *self.kvs_map.lock().unwrap() = Some(kvs_map.clone());
+            Ok(())
+        }
+
+        fn snapshot_count(&self, _instance_id: InstanceId) -> usize {
+            self.kvs_map.lock().unwrap().iter().count()
+        }
+
+        fn snapshot_max_count(&self) -> usize {
+            1
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities {
+                snapshots: true,
+                ..BackendCapabilities::default()
+            }
+        }
+
+        fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode> {
+            self.load_kvs(instance_id, snapshot_id)
+        }
+    }
+
+    fn get_kvs(backend: Box<dyn KvsBackend>, kvs_map: KvsMap, defaults_map: KvsMap) -> Kvs {
+        let instance_id = InstanceId(1);
+        let data = Arc::new(RwLock::new(KvsData::new(kvs_map, defaults_map)));
+        let parameters = Arc::new(KvsParameters::new(
+            instance_id,
+            KvsDefaults::Optional,
+            KvsLoad::Optional,
+            backend,
+        ));
+        Kvs::new(data, parameters)
+    }
+
+    #[test]
+    fn test_new_ok() {
+        // Check only if panic happens.
+        get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+    }
+
+    #[test]
+    fn test_parameters_ok() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        assert_eq!(kvs.parameters().instance_id, InstanceId(1));
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
+        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
+        assert!(kvs.parameters().backend().dyn_eq(&MockBackend));
+    }
+
+    #[test]
+    fn test_parameters_debug() {
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+        let output = format!("{:?}", kvs.parameters());
+        assert!(output.contains("instance_id: InstanceId(1)"));
+        assert!(output.contains("backend: \"json\""));
+
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        assert!(format!("{:?}", kvs.parameters()).contains("backend: \"unknown\""));
+    }
+
+    #[test]
+    fn test_read_only_sees_writes() {
+        let kvs = get_kvs(
+            Box::new(MemoryBackend::default()),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("default".to_string(), KvsValue::from(true))]),
+        );
+        let read_only: ReadOnlyKvs = kvs.read_only();
+        assert_eq!(read_only.instance_id(), InstanceId(1));
+        assert_eq!(read_only.get_value("key").unwrap(), KvsValue::I32(1));
+        assert_eq!(read_only.snapshot_count(), 0);
+
+        kvs.set_value("key", 2).unwrap();
+        kvs.set_value("new_key", "value").unwrap();
+        kvs.set_value("default", false).unwrap();
+        kvs.flush().unwrap();
+
+        assert_eq!(read_only.get_value("key").unwrap(), KvsValue::I32(2));
+        assert_eq!(read_only.get_value_as::<String>("new_key").unwrap(), "value");
+        assert_eq!(
+            read_only.with_value("new_key", |v| v.cloned()).unwrap(),
+            Some(KvsValue::from("value"))
+        );
+        assert!(read_only.key_exists("new_key").unwrap());
+        let mut keys = read_only.get_all_keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["default", "key", "new_key"]);
+        assert!(!read_only.is_value_default("default").unwrap());
+        assert_eq!(read_only.get_default_value("default").unwrap(), KvsValue::Boolean(true));
+        assert_eq!(read_only.snapshot_count(), 1);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_ok() {
+        let kvs = get_kvs(Box::new(MemoryBackend::default()), KvsMap::new(), KvsMap::new());
+        assert_eq!(kvs.metrics(), crate::kvs_metrics::KvsMetrics::default());
+
+        kvs.set_value("key", 1).unwrap();
+        kvs.set_value("prefix/a", 2).unwrap();
+        kvs.set_value("prefix/b", 3).unwrap();
+        assert!(kvs.compare_and_set("key", Some(&KvsValue::from(1)), 4).unwrap());
+        assert!(!kvs.compare_and_set("key", None, 5).unwrap());
+        kvs.get_value("key").unwrap();
+        kvs.get_value_as::<i32>("key").unwrap();
+        assert!(kvs.try_get_value("missing").unwrap().is_none());
+        assert!(kvs.get_value("missing").is_err_and(|e| e == ErrorCode::KeyNotFound));
+        kvs.remove_key("key").unwrap();
+        assert!(kvs.remove_key("key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert_eq!(kvs.remove_prefix("prefix/").unwrap(), 2);
+        kvs.flush().unwrap();
+
+        let metrics = kvs.metrics();
+        assert_eq!(metrics.gets, 4);
+        assert_eq!(metrics.sets, 4);
+        assert_eq!(metrics.removes, 3);
+        assert_eq!(metrics.flushes, 1);
+        assert_eq!(metrics.key_not_found, 2);
+        assert_eq!(metrics.validation_failures, 0);
+
+        // Counters are shared between handles.
+        assert_eq!(kvs.read_only().kvs.metrics(), metrics);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_validation_failures() {
+        let kvs = get_kvs(Box::new(MemoryBackend::default()), KvsMap::new(), KvsMap::new());
+        let mut parameters = KvsParameters::new(
+            InstanceId(1),
+            KvsDefaults::Optional,
+            KvsLoad::Optional,
+            Box::new(MockBackend),
+        );
+        parameters.key_validator = Some(|key| !key.contains('/'));
+        let kvs = Kvs::new(kvs.data.clone(), Arc::new(parameters));
+
+        assert!(kvs
+            .set_value("invalid/key", 1)
+            .is_err_and(|e| e == ErrorCode::InvalidKey));
+        kvs.set_value("valid_key", 1).unwrap();
+
+        let metrics = kvs.metrics();
+        assert_eq!(metrics.validation_failures, 1);
+        assert_eq!(metrics.sets, 1);
+    }
+
+    #[test]
+    fn test_working_dir_not_file_based() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        assert_eq!(kvs.working_dir(), None);
+        assert_eq!(kvs.snapshot_paths(SnapshotId(0)), None);
+    }
+
+    #[test]
+    fn test_snapshot_paths() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path.clone()).build());
+        let kvs = get_kvs(backend, KvsMap::new(), KvsMap::new());
+
+        kvs.set_value("key", "value").unwrap();
+        kvs.flush().unwrap();
+
+        let (kvs_path, hash_path) = kvs.snapshot_paths(SnapshotId(0)).unwrap();
+        assert_eq!(kvs_path, dir_path.join("kvs_1_0.json"));
+        assert_eq!(hash_path, dir_path.join("kvs_1_0.hash"));
+        assert!(kvs_path.exists() && hash_path.exists());
+        assert!(std::fs::read_to_string(kvs_path).unwrap().contains("\"key\""));
+
+        // Paths of snapshots not yet created are reported too.
+        let (kvs_path, hash_path) = kvs.snapshot_paths(SnapshotId(1)).unwrap();
+        assert!(!kvs_path.exists() && !hash_path.exists());
+    }
+
+    #[test]
+    fn test_reset() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("explicit_value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        kvs.reset().unwrap();
+        assert_eq!(kvs.get_all_keys().unwrap().len(), 0);
+        assert!(kvs.is_empty().unwrap());
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
+        assert!(kvs
+            .get_value_as::<bool>("example2")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[cfg_attr(miri, ignore)]
+    #[test]
+    fn test_reset_key() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("explicit_value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        kvs.reset_key("example1").unwrap();
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
+
+        // TODO: determine why resetting entry without default value is an error.
+        assert!(kvs
+            .reset_key("example2")
+            .is_err_and(|e| e == ErrorCode::KeyDefaultNotFound));
+    }
+
+    #[test]
+    fn test_reset_to_defaults() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("explicit_value")),
+                ("example2".to_string(), KvsValue::from(true)),
+                ("example3".to_string(), KvsValue::from(3.0)),
+            ]),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("default_value")),
+                ("example3".to_string(), KvsValue::from(0.0)),
+                ("example4".to_string(), KvsValue::from(4.0)),
+            ]),
+        );
+
+        assert_eq!(kvs.reset_to_defaults().unwrap(), 2);
+        assert!(kvs.is_dirty().unwrap());
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
+        assert_eq!(kvs.get_value_as::<f64>("example3").unwrap(), 0.0);
+        assert!(kvs.is_value_default("example4").unwrap());
+
+        // Keys without default value are kept.
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["example2".to_string()]);
+        assert!(kvs.get_value_as::<bool>("example2").unwrap());
+
+        assert_eq!(kvs.reset_to_defaults().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reset_to_defaults_notifies_watchers() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("explicit_value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        let _handle = kvs.watch("example1", move |_, value| {
+            seen_clone.lock().unwrap().push(value.clone())
+        });
+
+        kvs.reset_to_defaults().unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![KvsValue::from("default_value")]);
+    }
+
+    #[test]
+    fn test_reset_to_defaults_corrupt() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.mark_corrupt("test").unwrap();
+        assert!(kvs
+            .reset_to_defaults()
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    #[test]
+    fn test_materialize_default() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::new(),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+        assert!(kvs.is_value_default("example1").unwrap());
+
+        kvs.materialize_default("example1").unwrap();
+        assert!(!kvs.is_value_default("example1").unwrap());
+        assert!(kvs.is_dirty().unwrap());
+        assert_eq!(
+            kvs.entries().unwrap(),
+            vec![("example1".to_string(), KvsValue::from("default_value"))]
+        );
+
+        // Explicit value no longer follows defaults.
+        kvs.set_defaults(KvsMap::from([("example1".to_string(), KvsValue::from("new_default"))]))
+            .unwrap();
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
+
+        kvs.reset_key("example1").unwrap();
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "new_default");
+    }
+
+    #[test]
+    fn test_materialize_default_explicit_kept() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("explicit_value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        kvs.materialize_default("example1").unwrap();
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "explicit_value");
+        assert!(!kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_materialize_default_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("explicit_value"))]),
+            KvsMap::new(),
+        );
+
+        assert!(kvs
+            .materialize_default("example1")
+            .is_err_and(|e| e == ErrorCode::KeyDefaultNotFound));
+    }
+
+    #[test]
+    fn test_get_all_keys_some() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        let mut keys = kvs.get_all_keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["example1", "example2"]);
+    }
+
+    #[test]
+    fn test_get_all_keys_empty() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+
+        let keys = kvs.get_all_keys().unwrap();
+        assert_eq!(keys.len(), 0);
+    }
+
+    #[test]
+    fn test_len() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example2".to_string(), KvsValue::from("default"))]),
+        );
+        assert_eq!(kvs.len().unwrap(), 1);
+        assert!(!kvs.is_empty().unwrap());
+
+        kvs.set_value("example2", true).unwrap();
+        kvs.set_value("example3", 123.4).unwrap();
+        assert_eq!(kvs.len().unwrap(), 3);
+
+        kvs.remove_key("example1").unwrap();
+        assert_eq!(kvs.len().unwrap(), 2);
+
+        kvs.remove_key("example2").unwrap();
+        kvs.remove_key("example3").unwrap();
+        assert_eq!(kvs.len().unwrap(), 0);
+        assert!(kvs.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_len_expired() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.set_value("example1", "value").unwrap();
+        kvs.set_value_with_ttl("example2", "value", Duration::ZERO).unwrap();
+
+        assert_eq!(kvs.len().unwrap(), 1);
+    }
+
+    fn get_prefix_kvs() -> Kvs {
+        get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("net.eth0.mtu".to_string(), KvsValue::from(1500)),
+                ("net.eth1.mtu".to_string(), KvsValue::from(9000)),
+                ("network".to_string(), KvsValue::from("lan")),
+                ("other".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("net.eth0.mtu".to_string(), KvsValue::from(1000))]),
+        )
+    }
+
+    #[test]
+    fn test_keys_with_prefix() {
+        let kvs = get_prefix_kvs();
+
+        let mut keys = kvs.keys_with_prefix("net").unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["net.eth0.mtu", "net.eth1.mtu", "network"]);
+
+        let mut keys = kvs.keys_with_prefix("net.").unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["net.eth0.mtu", "net.eth1.mtu"]);
+
+        assert_eq!(kvs.keys_with_prefix("").unwrap().len(), 4);
+        assert!(kvs.keys_with_prefix("invalid").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keys_with_prefix_corrupt() {
+        let kvs = get_prefix_kvs();
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs.keys_with_prefix("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_entries() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example3".to_string(), KvsValue::from(1i32))]),
+        );
+
+        let entries = kvs.entries().unwrap();
+        let mut entry_keys: Vec<String> = entries.iter().map(|(k, _)| k.clone()).collect();
+        let mut keys = kvs.get_all_keys().unwrap();
+        entry_keys.sort();
+        keys.sort();
+        assert_eq!(entry_keys, keys);
+        for (key, value) in entries {
+            assert_eq!(kvs.get_value(&key).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_entries_empty() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        assert!(kvs.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_entries_with_defaults() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("default")),
+                ("example3".to_string(), KvsValue::from(1i32)),
+            ]),
+        );
+
+        let mut entries = kvs.entries_with_defaults().unwrap();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            entries,
+            vec![
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+                ("example3".to_string(), KvsValue::from(1i32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entries_corrupt() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs.entries().unwrap().is_empty());
+        assert_eq!(
+            kvs.entries_with_defaults().unwrap(),
+            vec![("example1".to_string(), KvsValue::from("default"))]
+        );
+    }
+
+    #[test]
+    fn test_key_exists_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        assert!(kvs.key_exists("example1").unwrap());
+        assert!(kvs.key_exists("example2").unwrap());
+    }
+
+    #[test]
+    fn test_key_exists_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        assert!(!kvs.key_exists("invalid_key").unwrap());
+    }
+
+    #[test]
+    fn test_get_value_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        let value = kvs.get_value("example1").unwrap();
+        assert_eq!(value, KvsValue::String("value".to_string()));
+    }
+
+    #[test]
+    fn test_get_value_available_default() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert_eq!(
+            kvs.get_value("example1").unwrap(),
+            KvsValue::String("default_value".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_value_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert!(kvs.get_value("invalid_key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[test]
+    fn test_try_get_value_stored_null() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::Null)]),
+            KvsMap::from([("example2".to_string(), KvsValue::Null)]),
+        );
+
+        assert_eq!(kvs.try_get_value("example1").unwrap(), Some(KvsValue::Null));
+        assert_eq!(kvs.try_get_value("example2").unwrap(), Some(KvsValue::Null));
+    }
+
+    #[test]
+    fn test_with_value() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("x".repeat(1 << 20)))]),
+            KvsMap::from([("example2".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        // Stored value is borrowed, not cloned.
+        let (len, ptr) = kvs
+            .with_value("example1", |value| match value {
+                Some(KvsValue::String(s)) => (s.len(), s.as_ptr()),
+                _ => panic!("unexpected value"),
+            })
+            .unwrap();
+        assert_eq!(len, 1 << 20);
+        match kvs.data.read().unwrap().kvs_map.get("example1") {
+            Some(KvsValue::String(s)) => assert_eq!(s.as_ptr(), ptr),
+            _ => panic!("unexpected value"),
+        }
+
+        assert_eq!(
+            kvs.with_value("example2", |value| value.cloned()).unwrap(),
+            Some(KvsValue::from("default_value"))
+        );
+        assert!(kvs.with_value("invalid_key", |value| value.is_none()).unwrap());
+
+        kvs.set_value_with_ttl("expired", (), Duration::ZERO).unwrap();
+        assert!(kvs.with_value("expired", |value| value.is_none()).unwrap());
+    }
+
+    #[test]
+    fn test_try_get_value_absent() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example2".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert_eq!(kvs.try_get_value("example1").unwrap(), Some(KvsValue::from(true)));
+        assert_eq!(
+            kvs.try_get_value("example2").unwrap(),
+            Some(KvsValue::from("default_value"))
+        );
+        assert_eq!(kvs.try_get_value("invalid_key").unwrap(), None);
+
+        kvs.set_value_with_ttl("expired", (), Duration::ZERO).unwrap();
+        assert_eq!(kvs.try_get_value("expired").unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_value_as_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        let value = kvs.get_value_as::<String>("example1").unwrap();
+        assert_eq!(value, "value");
+    }
+
+    #[test]
+    fn test_get_value_as_available_default() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        let value = kvs.get_value_as::<String>("example1").unwrap();
+        assert_eq!(value, "default_value");
+    }
+
+    #[test]
+    fn test_get_value_as_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert!(kvs
+            .get_value_as::<String>("invalid_key")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[test]
+    fn test_get_value_as_invalid_type() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::new(),
+        );
+
+        assert!(kvs
+            .get_value_as::<f64>("example1")
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+    }
+
+    #[test]
+    fn test_get_value_or_absent() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+
+        assert_eq!(kvs.get_value_or("example1", 42i32).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_get_value_or_present() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from(7i32))]),
+            KvsMap::from([("example2".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert_eq!(kvs.get_value_or("example1", 42i32).unwrap(), 7);
+        assert_eq!(
+            kvs.get_value_or("example2", "fallback".to_string()).unwrap(),
+            "default_value"
+        );
+    }
+
+    #[test]
+    fn test_get_value_or_invalid_type() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::new(),
+        );
+
+        assert!(kvs
+            .get_value_or("example1", 1.0f64)
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+    }
+
+    #[test]
+    fn test_small_int_round_trip() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
+        kvs.set_value("byte", 200u8).unwrap();
+        kvs.flush().unwrap();
+
+        // Stored `u8` is loaded back as `u8`, not widened to `U32`.
+        let loaded = get_kvs(
+            Box::new(backend.clone()),
+            backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::new(),
+        );
+        assert_eq!(loaded.get_value("byte").unwrap(), KvsValue::U8(200));
+        assert_eq!(loaded.get_value_as::<u8>("byte").unwrap(), 200);
+    }
+
+    #[test]
+    fn test_get_value_as_numeric_widening() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from(-7i32)),
+                ("example2".to_string(), KvsValue::from(u32::MAX)),
+                ("example3".to_string(), KvsValue::from(-1i64)),
+            ]),
+            KvsMap::new(),
+        );
+
+        assert_eq!(kvs.get_value_as::<i64>("example1").unwrap(), -7);
+        assert_eq!(kvs.get_value_as::<i64>("example2").unwrap(), i64::from(u32::MAX));
+        assert_eq!(kvs.get_value_as::<f64>("example1").unwrap(), -7.0);
+
+        // Values not fitting into target type are rejected.
+        assert!(kvs
+            .get_value_as::<i32>("example2")
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+        assert!(kvs
+            .get_value_as::<u64>("example3")
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+    }
+
+    #[test]
+    fn test_get_value_as_default_invalid_type() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+        );
+
+        assert!(kvs
+            .get_value_as::<f64>("example1")
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+    }
+
+    #[test]
+    fn test_get_default_value_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
+        );
+
+        let value = kvs.get_default_value("example3").unwrap();
+        assert_eq!(value, KvsValue::String("default".to_string()));
+    }
+
+    #[test]
+    fn test_get_default_value_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
+        );
+
+        assert!(kvs
+            .get_default_value("invalid_key")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[test]
+    fn test_is_value_default_false() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        assert!(!kvs.is_value_default("example1").unwrap());
+    }
+
+    #[test]
+    fn test_is_value_default_true() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
+        );
+
+        assert!(kvs.is_value_default("example3").unwrap());
+    }
+
+    #[test]
+    fn test_is_value_default_not_found() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        assert!(kvs
+            .is_value_default("invalid_key")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[test]
+    fn test_effective_defaults_report() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([
+                ("example2".to_string(), KvsValue::from(true)),
+                ("example1".to_string(), KvsValue::from("default")),
+            ]),
+        );
+
+        assert_eq!(
+            kvs.effective_defaults_report().unwrap(),
+            vec![
+                ("example1".to_string(), KvsValue::from("default"), DefaultsSource::File),
+                ("example2".to_string(), KvsValue::from(true), DefaultsSource::File),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_defaults() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example2".to_string(), KvsValue::from("old_default"))]),
+        );
+
+        kvs.set_defaults(KvsMap::from([
+            ("example1".to_string(), KvsValue::from("default")),
+            ("example3".to_string(), KvsValue::from(true)),
+        ]))
+        .unwrap();
+
+        assert!(kvs
+            .get_default_value("example2")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert_eq!(kvs.get_default_value("example3").unwrap(), KvsValue::from(true));
+        assert!(kvs.is_value_default("example3").unwrap());
+        assert!(!kvs.is_value_default("example1").unwrap());
+        kvs.reset_key("example1").unwrap();
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("default"));
+        assert_eq!(
+            kvs.effective_defaults_report().unwrap(),
+            vec![
+                (
+                    "example1".to_string(),
+                    KvsValue::from("default"),
+                    DefaultsSource::Programmatic
+                ),
+                (
+                    "example3".to_string(),
+                    KvsValue::from(true),
+                    DefaultsSource::Programmatic
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_defaults() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        kvs.clear_defaults().unwrap();
+
+        assert!(kvs
+            .get_default_value("example1")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert!(kvs
+            .reset_key("example1")
+            .is_err_and(|e| e == ErrorCode::KeyDefaultNotFound));
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("value"));
+        assert!(kvs.effective_defaults_report().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_value_new() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+
+        kvs.set_value("key", "value").unwrap();
+        assert_eq!(kvs.get_value_as::<String>("key").unwrap(), "value");
+    }
+
+    #[test]
+    fn test_set_value_exists() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("key".to_string(), KvsValue::from("old_value"))]),
+            KvsMap::new(),
+        );
+
+        kvs.set_value("key", "new_value").unwrap();
+        assert_eq!(kvs.get_value_as::<String>("key").unwrap(), "new_value");
+    }
+
+    #[test]
+    fn test_set_value_with_ttl_not_expired() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.set_value_with_ttl("key", 1, Duration::from_secs(3600)).unwrap();
+
+        assert!(kvs.key_exists("key").unwrap());
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(1));
+        assert_eq!(kvs.get_value_as::<i32>("key").unwrap(), 1);
+
+        // Regular set removes expiry.
+        kvs.set_value("key", 2).unwrap();
+        assert!(kvs.data.read().unwrap().expiry_map.is_empty());
+    }
+
+    #[test]
+    fn test_set_value_with_ttl_expired() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.set_value_with_ttl("key", 1, Duration::ZERO).unwrap();
+
+        assert!(!kvs.key_exists("key").unwrap());
+        assert!(kvs.get_value("key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert!(kvs
+            .get_value_as::<i32>("key")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert!(kvs.get_all_keys().unwrap().is_empty());
+
+        // Expired value is removed lazily.
+        let data = kvs.data.read().unwrap();
+        assert!(data.kvs_map.is_empty());
+        assert!(data.expiry_map.is_empty());
+    }
+
+    #[test]
+    fn test_set_value_with_ttl_expired_default() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::new(),
+            KvsMap::from([("key".to_string(), KvsValue::from(2))]),
+        );
+        kvs.set_value_with_ttl("key", 1, Duration::ZERO).unwrap();
+
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(2));
+        assert!(kvs.is_value_default("key").unwrap());
+    }
+
+    #[test]
+    fn test_compare_and_set_match() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("default".to_string(), KvsValue::from(2))]),
+        );
+
+        assert!(kvs.compare_and_set("key", Some(&KvsValue::from(1)), 3).unwrap());
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(3));
+        assert!(kvs.compare_and_set("default", Some(&KvsValue::from(2)), 4).unwrap());
+        assert_eq!(kvs.get_value("default").unwrap(), KvsValue::I32(4));
+    }
+
+    #[test]
+    fn test_compare_and_set_mismatch() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::new(),
+        );
+
+        assert!(!kvs.compare_and_set("key", Some(&KvsValue::from(2)), 3).unwrap());
+        assert!(!kvs.compare_and_set("key", None, 3).unwrap());
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(1));
+    }
+
+    #[test]
+    fn test_compare_and_set_absent() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+
+        assert!(!kvs.compare_and_set("key", Some(&KvsValue::from(1)), 3).unwrap());
+        assert!(!kvs.key_exists("key").unwrap());
+        assert!(kvs.compare_and_set("key", None, 3).unwrap());
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(3));
+    }
+
+    #[test]
+    fn test_swap_present() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("default".to_string(), KvsValue::from(2))]),
+        );
+
+        assert_eq!(kvs.swap("key", 3).unwrap(), Some(KvsValue::I32(1)));
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(3));
+        assert_eq!(kvs.swap("default", 4).unwrap(), Some(KvsValue::I32(2)));
+        assert_eq!(kvs.get_value("default").unwrap(), KvsValue::I32(4));
+        assert!(kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_swap_absent() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+
+        assert_eq!(kvs.swap("key", 3).unwrap(), None);
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(3));
+        assert_eq!(kvs.swap("key", "value").unwrap(), Some(KvsValue::I32(3)));
+    }
+
+    #[test]
+    fn test_swap_corrupt() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs.swap("key", 3).is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
     }
 
     #[test]
-    fn test_new_ok() {
-        // Check only if panic happens.
-        get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+    fn test_rename_key() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("old".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("old".to_string(), KvsValue::from(2))]),
+        );
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let _handles: Vec<_> = ["old", "new"]
+            .into_iter()
+            .map(|key| {
+                let notifications = notifications.clone();
+                kvs.watch(key, move |k, v| {
+                    notifications.lock().unwrap().push((k.to_string(), v.clone()))
+                })
+            })
+            .collect();
+
+        kvs.rename_key("old", "new", false).unwrap();
+        assert_eq!(kvs.get_value("new").unwrap(), KvsValue::I32(1));
+        // Default of source key is kept.
+        assert!(!kvs.key_exists("old").unwrap());
+        assert_eq!(kvs.get_value("old").unwrap(), KvsValue::I32(2));
+        assert!(kvs.is_dirty().unwrap());
+        assert_eq!(
+            *notifications.lock().unwrap(),
+            vec![
+                ("old".to_string(), KvsValue::I32(2)),
+                ("new".to_string(), KvsValue::I32(1))
+            ]
+        );
+
+        // Renaming key to itself keeps value.
+        kvs.rename_key("new", "new", false).unwrap();
+        assert_eq!(kvs.get_value("new").unwrap(), KvsValue::I32(1));
     }
 
     #[test]
-    fn test_parameters_ok() {
+    fn test_rename_key_expiry() {
         let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
-        assert_eq!(kvs.parameters().instance_id, InstanceId(1));
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
-        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
-        assert!(kvs.parameters().backend.dyn_eq(&MockBackend));
+        kvs.set_value_with_ttl("old", 1, Duration::from_secs(3600)).unwrap();
+        kvs.set_value_with_ttl("new", 2, Duration::from_secs(60)).unwrap();
+
+        // Expiry is moved with the value.
+        kvs.rename_key("old", "new", true).unwrap();
+        let expiry = kvs.data.read().unwrap().expiry_map.clone();
+        assert_eq!(expiry.keys().collect::<Vec<_>>(), vec!["new"]);
+        assert!(expiry["new"] > SystemTime::now() + Duration::from_secs(60));
+
+        // Expired value is absent.
+        kvs.set_value_with_ttl("expired", 3, Duration::ZERO).unwrap();
+        assert!(kvs
+            .rename_key("expired", "other", false)
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
     }
 
     #[test]
-    fn test_reset() {
+    fn test_rename_key_not_found() {
         let kvs = get_kvs(
             Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("explicit_value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+            KvsMap::new(),
+            KvsMap::from([("old".to_string(), KvsValue::from(2))]),
         );
 
-        kvs.reset().unwrap();
-        assert_eq!(kvs.get_all_keys().unwrap().len(), 0);
-        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
+        // Defaults are not moved.
         assert!(kvs
-            .get_value_as::<bool>("example2")
+            .rename_key("old", "new", false)
             .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert!(!kvs.key_exists("new").unwrap());
     }
 
-    #[cfg_attr(miri, ignore)]
     #[test]
-    fn test_reset_key() {
+    fn test_rename_key_exists() {
         let kvs = get_kvs(
             Box::new(MockBackend),
             KvsMap::from([
-                ("example1".to_string(), KvsValue::from("explicit_value")),
-                ("example2".to_string(), KvsValue::from(true)),
+                ("old".to_string(), KvsValue::from(1)),
+                ("new".to_string(), KvsValue::from(2)),
             ]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+            KvsMap::new(),
         );
 
-        kvs.reset_key("example1").unwrap();
-        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default_value");
-
-        // TODO: determine why resetting entry without default value is an error.
         assert!(kvs
-            .reset_key("example2")
-            .is_err_and(|e| e == ErrorCode::KeyDefaultNotFound));
+            .rename_key("old", "new", false)
+            .is_err_and(|e| e == ErrorCode::KeyAlreadyExists));
+        assert_eq!(kvs.get_value("old").unwrap(), KvsValue::I32(1));
+        assert_eq!(kvs.get_value("new").unwrap(), KvsValue::I32(2));
+
+        kvs.rename_key("old", "new", true).unwrap();
+        assert!(!kvs.key_exists("old").unwrap());
+        assert_eq!(kvs.get_value("new").unwrap(), KvsValue::I32(1));
     }
 
     #[test]
-    fn test_get_all_keys_some() {
+    fn test_remove_key_found() {
         let kvs = get_kvs(
             Box::new(MockBackend),
             KvsMap::from([
@@ -446,36 +3826,85 @@ mod kvs_tests {
             KvsMap::new(),
         );
 
-        let mut keys = kvs.get_all_keys().unwrap();
-        keys.sort();
-        assert_eq!(keys, vec!["example1", "example2"]);
+        kvs.remove_key("example1").unwrap();
+        assert!(!kvs.key_exists("example1").unwrap());
     }
 
     #[test]
-    fn test_get_all_keys_empty() {
-        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+    fn test_remove_prefix_segment() {
+        let kvs = get_prefix_kvs();
 
-        let keys = kvs.get_all_keys().unwrap();
-        assert_eq!(keys.len(), 0);
+        assert_eq!(kvs.remove_prefix("net.").unwrap(), 2);
+        assert_eq!(kvs.keys_with_prefix("net").unwrap(), vec!["network"]);
+
+        // Defaults are kept.
+        assert_eq!(kvs.get_value("net.eth0.mtu").unwrap(), KvsValue::I32(1000));
     }
 
     #[test]
-    fn test_key_exists_found() {
+    fn test_remove_prefix_string() {
+        let kvs = get_prefix_kvs();
+
+        assert_eq!(kvs.remove_prefix("net").unwrap(), 3);
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["other"]);
+        assert_eq!(kvs.remove_prefix("net").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_remove_prefix_empty() {
+        let kvs = get_prefix_kvs();
+
+        assert_eq!(kvs.remove_prefix("").unwrap(), 4);
+        assert!(kvs.get_all_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_prefix_corrupt() {
+        let kvs = get_prefix_kvs();
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs.remove_prefix("").is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
         let kvs = get_kvs(
-            Box::new(MockBackend),
+            backend.clone(),
             KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
+                ("a".to_string(), KvsValue::Null),
+                ("b".to_string(), KvsValue::from(1)),
+                ("c".to_string(), KvsValue::Null),
             ]),
-            KvsMap::new(),
+            KvsMap::from([("a".to_string(), KvsValue::from(2))]),
         );
 
-        assert!(kvs.key_exists("example1").unwrap());
-        assert!(kvs.key_exists("example2").unwrap());
+        kvs.compact(|_, v| *v != KvsValue::Null).unwrap();
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["b"]);
+        assert!(!kvs.is_dirty().unwrap());
+
+        // Defaults are kept.
+        assert_eq!(kvs.get_value("a").unwrap(), KvsValue::from(2));
+
+        // Compacted content is persisted.
+        let kvs_map = backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap();
+        assert_eq!(kvs_map, KvsMap::from([("b".to_string(), KvsValue::from(1))]));
     }
 
     #[test]
-    fn test_key_exists_not_found() {
+    fn test_compact_corrupt() {
+        let kvs = get_prefix_kvs();
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs
+            .compact(|_, _| true)
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    #[test]
+    fn test_remove_key_not_found() {
         let kvs = get_kvs(
             Box::new(MockBackend),
             KvsMap::from([
@@ -485,262 +3914,586 @@ mod kvs_tests {
             KvsMap::new(),
         );
 
-        assert!(!kvs.key_exists("invalid_key").unwrap());
+        assert!(kvs
+            .remove_key("invalid_key")
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
     }
 
-    #[test]
-    fn test_get_value_found() {
-        let kvs = get_kvs(
+    fn get_merge_kvs() -> Kvs {
+        get_kvs(
             Box::new(MockBackend),
             KvsMap::from([
                 ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
+                (
+                    "object".to_string(),
+                    KvsValue::from(KvsMap::from([
+                        ("nested1".to_string(), KvsValue::from(1i32)),
+                        (
+                            "inner".to_string(),
+                            KvsValue::from(KvsMap::from([("deep1".to_string(), KvsValue::from(true))])),
+                        ),
+                    ])),
+                ),
             ]),
             KvsMap::new(),
-        );
+        )
+    }
 
-        let value = kvs.get_value("example1").unwrap();
-        assert_eq!(value, KvsValue::String("value".to_string()));
+    fn get_merge_incoming() -> KvsMap {
+        KvsMap::from([
+            ("example1".to_string(), KvsValue::from("incoming")),
+            ("example2".to_string(), KvsValue::from(2i32)),
+            (
+                "object".to_string(),
+                KvsValue::from(KvsMap::from([
+                    ("nested2".to_string(), KvsValue::from(2i32)),
+                    (
+                        "inner".to_string(),
+                        KvsValue::from(KvsMap::from([("deep2".to_string(), KvsValue::from(false))])),
+                    ),
+                ])),
+            ),
+        ])
     }
 
     #[test]
-    fn test_get_value_available_default() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
-        );
+    fn test_merge_prefer_existing() {
+        let kvs = get_merge_kvs();
+        let existing_object = kvs.get_value("object").unwrap();
+
+        kvs.merge(get_merge_incoming(), MergePolicy::PreferExisting).unwrap();
+
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("value"));
+        assert_eq!(kvs.get_value("example2").unwrap(), KvsValue::from(2i32));
+        assert_eq!(kvs.get_value("object").unwrap(), existing_object);
+    }
+
+    #[test]
+    fn test_merge_prefer_incoming() {
+        let kvs = get_merge_kvs();
+        let incoming = get_merge_incoming();
+
+        kvs.merge(incoming.clone(), MergePolicy::PreferIncoming).unwrap();
 
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("incoming"));
+        assert_eq!(kvs.get_value("example2").unwrap(), KvsValue::from(2i32));
+        assert_eq!(kvs.get_value("object").unwrap(), incoming["object"]);
+    }
+
+    #[test]
+    fn test_merge_deep_merge_objects() {
+        let kvs = get_merge_kvs();
+
+        kvs.merge(get_merge_incoming(), MergePolicy::DeepMergeObjects).unwrap();
+
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("incoming"));
+        assert_eq!(kvs.get_value("example2").unwrap(), KvsValue::from(2i32));
         assert_eq!(
-            kvs.get_value("example1").unwrap(),
-            KvsValue::String("default_value".to_string())
+            kvs.get_value("object").unwrap(),
+            KvsValue::from(KvsMap::from([
+                ("nested1".to_string(), KvsValue::from(1i32)),
+                ("nested2".to_string(), KvsValue::from(2i32)),
+                (
+                    "inner".to_string(),
+                    KvsValue::from(KvsMap::from([
+                        ("deep1".to_string(), KvsValue::from(true)),
+                        ("deep2".to_string(), KvsValue::from(false)),
+                    ])),
+                ),
+            ]))
         );
     }
 
     #[test]
-    fn test_get_value_not_found() {
+    fn test_merge_deep_merge_objects_type_conflict() {
+        let kvs = get_merge_kvs();
+
+        // Incoming scalar replaces existing object, incoming object replaces existing scalar.
+        let incoming = KvsMap::from([
+            ("object".to_string(), KvsValue::from(5i32)),
+            (
+                "example1".to_string(),
+                KvsValue::from(KvsMap::from([("nested".to_string(), KvsValue::from(1i32))])),
+            ),
+        ]);
+        kvs.merge(incoming.clone(), MergePolicy::DeepMergeObjects).unwrap();
+
+        assert_eq!(kvs.get_value("object").unwrap(), KvsValue::from(5i32));
+        assert_eq!(kvs.get_value("example1").unwrap(), incoming["example1"]);
+    }
+
+    #[test]
+    fn test_merge_corrupt() {
+        let kvs = get_merge_kvs();
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs
+            .merge(get_merge_incoming(), MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
+
+    #[test]
+    fn test_flush() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
         let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+            backend.clone(),
+            KvsMap::from([("key".to_string(), KvsValue::from("value"))]),
+            KvsMap::new(),
         );
 
-        assert!(kvs.get_value("invalid_key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+        kvs.flush().unwrap();
+
+        // Functions below check if file exist.
+        let instance_id = kvs.parameters().instance_id;
+        let snapshot_id = SnapshotId(0);
+        assert!(backend.kvs_file_path(instance_id, snapshot_id).exists());
+        assert!(backend.hash_file_path(instance_id, snapshot_id).exists());
     }
 
     #[test]
-    fn test_get_value_as_found() {
+    fn test_flush_not_blocking() {
+        let backend = SlowBackend {
+            flush_started: Arc::new(Barrier::new(2)),
+            kvs_map: Arc::new(Mutex::new(None)),
+        };
         let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
+            Box::new(backend.clone()),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
             KvsMap::new(),
         );
 
-        let value = kvs.get_value_as::<String>("example1").unwrap();
-        assert_eq!(value, "value");
+        std::thread::scope(|s| {
+            let flush_thread = s.spawn(|| kvs.flush());
+
+            // Access data while backend IO is in progress.
+            backend.flush_started.wait();
+            let start = Instant::now();
+            assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(1));
+            kvs.set_value("key", 2).unwrap();
+            assert!(start.elapsed() < SlowBackend::FLUSH_DURATION);
+
+            flush_thread.join().unwrap().unwrap();
+        });
+
+        // Change made during the flush is not included.
+        assert_eq!(
+            *backend.kvs_map.lock().unwrap(),
+            Some(KvsMap::from([("key".to_string(), KvsValue::from(1))]))
+        );
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(2));
     }
 
     #[test]
-    fn test_get_value_as_available_default() {
+    fn test_concurrent_readers() {
+        const READERS: usize = 8;
         let kvs = get_kvs(
             Box::new(MockBackend),
-            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("default".to_string(), KvsValue::from(true))]),
         );
+        let readers_ready = Barrier::new(READERS);
 
-        let value = kvs.get_value_as::<String>("example1").unwrap();
-        assert_eq!(value, "default_value");
+        // Readers proceed while data is read-locked, all of them must hold the lock at once.
+        let _read_guard = kvs.data.read().unwrap();
+        std::thread::scope(|s| {
+            let threads: Vec<_> = (0..READERS)
+                .map(|_| {
+                    s.spawn(|| {
+                        readers_ready.wait();
+                        for _ in 0..100 {
+                            assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(1));
+                            assert_eq!(kvs.get_value_as::<i32>("key").unwrap(), 1);
+                            assert!(kvs.key_exists("key").unwrap());
+                            assert_eq!(kvs.get_all_keys().unwrap(), vec!["key".to_string()]);
+                            assert!(kvs.is_value_default("default").unwrap());
+                            assert_eq!(kvs.get_default_value("default").unwrap(), KvsValue::Boolean(true));
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+        });
     }
 
     #[test]
-    fn test_get_value_as_not_found() {
+    fn test_is_dirty() {
         let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
+            Box::new(MemoryBackend::default()),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("key".to_string(), KvsValue::from(0))]),
         );
+        assert!(!kvs.is_dirty().unwrap());
 
-        assert!(kvs
-            .get_value_as::<String>("invalid_key")
-            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        let modifications: [&dyn Fn(&Kvs); 5] = [
+            &|kvs| kvs.set_value("key", 2).unwrap(),
+            &|kvs| kvs.remove_key("key").unwrap(),
+            &|kvs| kvs.reset_key("key").unwrap(),
+            &|kvs| kvs.reset().unwrap(),
+            &|kvs| kvs.merge(KvsMap::new(), MergePolicy::PreferIncoming).unwrap(),
+        ];
+        for modify in modifications {
+            kvs.set_value("key", 1).unwrap();
+            kvs.flush().unwrap();
+            assert!(!kvs.is_dirty().unwrap());
+
+            modify(&kvs);
+            assert!(kvs.is_dirty().unwrap());
+        }
     }
 
     #[test]
-    fn test_get_value_as_invalid_type() {
+    fn test_flush_async_ok() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
+        kvs.set_value("key", "value").unwrap();
+
+        let handle = kvs.flush_async();
+        handle.join().unwrap();
+
+        let kvs_map = backend.load_kvs(kvs.parameters().instance_id, SnapshotId(0)).unwrap();
+        assert_eq!(kvs_map, KvsMap::from([("key".to_string(), KvsValue::from("value"))]));
+    }
+
+    #[test]
+    fn test_flush_async_coalesced() {
+        let backend = SlowBackend {
+            flush_started: Arc::new(Barrier::new(2)),
+            kvs_map: Arc::new(Mutex::new(None)),
+        };
         let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
+            Box::new(backend.clone()),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
             KvsMap::new(),
         );
 
-        assert!(kvs
-            .get_value_as::<f64>("example1")
-            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+        std::thread::scope(|s| {
+            let flush_thread = s.spawn(|| kvs.flush());
+            backend.flush_started.wait();
+
+            // Both calls are made while flush is in progress, second call joins pending flush.
+            let first = kvs.flush_async();
+            kvs.set_value("key", 2).unwrap();
+            let second = kvs.flush_async();
+            assert!(Arc::ptr_eq(&first.state, &second.state));
+            assert!(!first.is_finished());
+
+            flush_thread.join().unwrap().unwrap();
+            backend.flush_started.wait();
+            first.join().unwrap();
+            second.join().unwrap();
+        });
+
+        // Change made before second call is included.
+        assert_eq!(
+            *backend.kvs_map.lock().unwrap(),
+            Some(KvsMap::from([("key".to_string(), KvsValue::from(2))]))
+        );
     }
 
     #[test]
-    fn test_get_value_as_default_invalid_type() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([("example2".to_string(), KvsValue::from(true))]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default_value"))]),
-        );
+    fn test_flush_async_concurrent() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
 
-        assert!(kvs
-            .get_value_as::<f64>("example1")
-            .is_err_and(|e| e == ErrorCode::ConversionFailed));
+        let handles: Vec<_> = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..8)
+                .map(|i| {
+                    let kvs = &kvs;
+                    s.spawn(move || {
+                        kvs.set_value(format!("key{i}"), i).unwrap();
+                        kvs.flush_async()
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Newest snapshot contains all values, all snapshots are valid.
+        let instance_id = kvs.parameters().instance_id;
+        let kvs_map = backend.load_kvs(instance_id, SnapshotId(0)).unwrap();
+        assert_eq!(kvs_map.len(), 8);
+        for idx in 0..backend.snapshot_count(instance_id) {
+            backend.load_kvs(instance_id, SnapshotId(idx)).unwrap();
+        }
     }
 
     #[test]
-    fn test_get_default_value_found() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
-        );
+    fn test_find_snapshot() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
 
-        let value = kvs.get_default_value("example3").unwrap();
-        assert_eq!(value, KvsValue::String("default".to_string()));
+        // Snapshot `n` has version `3 - n`.
+        for version in 1..=3 {
+            kvs.set_value("config.version", version).unwrap();
+            kvs.flush().unwrap();
+        }
+        kvs.set_value("config.version", 4).unwrap();
+
+        let version_is =
+            |version: i32| move |kvs_map: &KvsMap| kvs_map.get("config.version") == Some(&KvsValue::I32(version));
+        assert_eq!(kvs.find_snapshot(version_is(3)).unwrap(), Some(SnapshotId(0)));
+        assert_eq!(kvs.find_snapshot(version_is(2)).unwrap(), Some(SnapshotId(1)));
+        assert_eq!(kvs.find_snapshot(version_is(1)).unwrap(), Some(SnapshotId(2)));
+        assert_eq!(kvs.find_snapshot(version_is(4)).unwrap(), None);
+
+        // In-memory data is not altered.
+        assert_eq!(kvs.get_value("config.version").unwrap(), KvsValue::I32(4));
     }
 
     #[test]
-    fn test_get_default_value_not_found() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
-        );
+    fn test_find_snapshot_corrupted_skipped() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
+        for version in 1..=3 {
+            kvs.set_value("config.version", version).unwrap();
+            kvs.flush().unwrap();
+        }
 
-        assert!(kvs
-            .get_default_value("invalid_key")
-            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        let instance_id = kvs.parameters().instance_id;
+        std::fs::write(backend.kvs_file_path(instance_id, SnapshotId(1)), "corrupted").unwrap();
+
+        let version_below_3 = |kvs_map: &KvsMap| {
+            kvs_map
+                .get("config.version")
+                .is_some_and(|v| *v == KvsValue::I32(1) || *v == KvsValue::I32(2))
+        };
+        assert_eq!(kvs.find_snapshot(version_below_3).unwrap(), Some(SnapshotId(2)));
     }
 
     #[test]
-    fn test_is_value_default_false() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+    fn test_snapshot_read() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend, KvsMap::new(), KvsMap::new());
+
+        // Snapshot `n` has version `2 - n`.
+        for version in 1..=2 {
+            kvs.set_value("config.version", version).unwrap();
+            kvs.flush().unwrap();
+        }
+        kvs.set_value("config.version", 3).unwrap();
+
+        assert_eq!(
+            kvs.snapshot_read(SnapshotId(1), "config.version").unwrap(),
+            Some(KvsValue::I32(1))
+        );
+        assert_eq!(
+            kvs.snapshot_read(SnapshotId(0), "config.version").unwrap(),
+            Some(KvsValue::I32(2))
         );
+        assert_eq!(kvs.snapshot_read(SnapshotId(1), "missing").unwrap(), None);
 
-        assert!(!kvs.is_value_default("example1").unwrap());
+        // In-memory data is not altered.
+        assert_eq!(kvs.get_value("config.version").unwrap(), KvsValue::I32(3));
+        assert!(kvs.is_dirty().unwrap());
     }
 
     #[test]
-    fn test_is_value_default_true() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example3".to_string(), KvsValue::from("default"))]),
+    fn test_flush_with_id() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend, KvsMap::new(), KvsMap::new());
+
+        kvs.set_value("counter", 1).unwrap();
+        let first_id = kvs.flush_with_id().unwrap();
+        assert_eq!(first_id, SnapshotId(0));
+        assert_eq!(kvs.snapshot_read(first_id, "counter").unwrap(), Some(KvsValue::I32(1)));
+
+        // Previous flush is rotated by the next one.
+        kvs.set_value("counter", 2).unwrap();
+        assert_eq!(kvs.flush_with_id().unwrap(), SnapshotId(0));
+        assert_eq!(
+            kvs.snapshot_read(SnapshotId(1), "counter").unwrap(),
+            Some(KvsValue::I32(1))
         );
+        assert!(!kvs.is_dirty().unwrap());
+    }
 
-        assert!(kvs.is_value_default("example3").unwrap());
+    #[test]
+    fn test_flush_with_id_corrupt() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend, KvsMap::new(), KvsMap::new());
+
+        kvs.mark_corrupt("test").unwrap();
+        assert!(kvs.flush_with_id().is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
     }
 
     #[test]
-    fn test_is_value_default_not_found() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
-        );
+    fn test_snapshot_read_invalid_id() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend, KvsMap::new(), KvsMap::new());
+        kvs.set_value("key", 1).unwrap();
+        kvs.flush().unwrap();
 
         assert!(kvs
-            .is_value_default("invalid_key")
-            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+            .snapshot_read(SnapshotId(1), "key")
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
     }
 
     #[test]
-    fn test_set_value_new() {
-        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+    fn test_verify() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
+        for version in 1..=3 {
+            kvs.set_value("config.version", version).unwrap();
+            kvs.flush().unwrap();
+        }
 
-        kvs.set_value("key", "value").unwrap();
-        assert_eq!(kvs.get_value_as::<String>("key").unwrap(), "value");
+        let instance_id = kvs.parameters().instance_id;
+        std::fs::write(backend.kvs_file_path(instance_id, SnapshotId(1)), "corrupted").unwrap();
+
+        assert_eq!(
+            kvs.verify().unwrap(),
+            vec![(SnapshotId(0), true), (SnapshotId(1), false), (SnapshotId(2), true)]
+        );
+        assert_eq!(kvs.get_value("config.version").unwrap(), KvsValue::I32(3));
+        assert!(!kvs.is_dirty().unwrap());
     }
 
     #[test]
-    fn test_set_value_exists() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([("key".to_string(), KvsValue::from("old_value"))]),
-            KvsMap::new(),
-        );
+    fn test_last_flush_hash() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
+        assert_eq!(kvs.last_flush_hash(), None);
 
-        kvs.set_value("key", "new_value").unwrap();
-        assert_eq!(kvs.get_value_as::<String>("key").unwrap(), "new_value");
+        let instance_id = kvs.parameters().instance_id;
+        let hash_path = backend.hash_file_path(instance_id, SnapshotId(0));
+        for version in 1..=2 {
+            kvs.set_value("config.version", version).unwrap();
+            kvs.flush().unwrap();
+
+            let hash_bytes = std::fs::read(&hash_path).unwrap();
+            let expected = u32::from_be_bytes(hash_bytes.try_into().unwrap());
+            assert_eq!(kvs.last_flush_hash(), Some(expected));
+        }
     }
 
     #[test]
-    fn test_remove_key_found() {
+    fn test_flush_chunked() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
         let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
+            backend.clone(),
+            KvsMap::from([("key".to_string(), KvsValue::from("value"))]),
             KvsMap::new(),
         );
 
-        kvs.remove_key("example1").unwrap();
-        assert!(!kvs.key_exists("example1").unwrap());
+        // Flush in steps several times, snapshots are rotated.
+        for _ in 0..2 {
+            let mut flush = kvs.flush_chunked().unwrap();
+            while flush.step().unwrap() == FlushProgress::Pending {}
+        }
+
+        let instance_id = kvs.parameters().instance_id;
+        assert_eq!(kvs.snapshot_count(), 2);
+        assert_eq!(
+            backend.load_kvs(instance_id, SnapshotId(0)).unwrap(),
+            KvsMap::from([("key".to_string(), KvsValue::from("value"))])
+        );
     }
 
     #[test]
-    fn test_remove_key_not_found() {
-        let kvs = get_kvs(
-            Box::new(MockBackend),
-            KvsMap::from([
-                ("example1".to_string(), KvsValue::from("value")),
-                ("example2".to_string(), KvsValue::from(true)),
-            ]),
-            KvsMap::new(),
-        );
+    fn test_flush_chunked_clears_dirty() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
+        let kvs = get_kvs(backend.clone(), KvsMap::new(), KvsMap::new());
+        kvs.set_value("key", 1).unwrap();
 
-        assert!(kvs
-            .remove_key("invalid_key")
-            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        let mut flush = kvs.flush_chunked().unwrap();
+        while flush.step().unwrap() == FlushProgress::Pending {}
+        drop(flush);
+
+        let instance_id = kvs.parameters().instance_id;
+        assert!(!kvs.is_dirty().unwrap());
+        assert_eq!(kvs.last_flush_hash(), backend.last_flush_hash(instance_id));
+        assert!(kvs.last_flush_hash().is_some());
+
+        // Flush dropped before completion leaves data dirty.
+        kvs.set_value("key", 2).unwrap();
+        let mut flush = kvs.flush_chunked().unwrap();
+        assert_eq!(flush.step().unwrap(), FlushProgress::Pending);
+        drop(flush);
+        assert!(kvs.is_dirty().unwrap());
     }
 
     #[test]
-    fn test_flush() {
+    fn test_flush_chunked_holds_flush_lock() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let backend = Box::new(JsonBackendBuilder::new().working_dir(dir_path).build());
         let kvs = get_kvs(
             backend.clone(),
-            KvsMap::from([("key".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
             KvsMap::new(),
         );
-
         kvs.flush().unwrap();
 
-        // Functions below check if file exist.
+        std::thread::scope(|s| {
+            let mut flush = kvs.flush_chunked().unwrap();
+            assert_eq!(flush.step().unwrap(), FlushProgress::Pending);
+
+            // Regular flush waits until chunked flush completes.
+            kvs.set_value("key", 2).unwrap();
+            let flush_thread = s.spawn(|| kvs.flush());
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!flush_thread.is_finished());
+
+            while flush.step().unwrap() == FlushProgress::Pending {}
+            flush_thread.join().unwrap().unwrap();
+        });
+
         let instance_id = kvs.parameters().instance_id;
-        let snapshot_id = SnapshotId(0);
-        assert!(backend.kvs_file_path(instance_id, snapshot_id).exists());
-        assert!(backend.hash_file_path(instance_id, snapshot_id).exists());
+        let load = |snapshot_id| backend.load_kvs(instance_id, snapshot_id).unwrap()["key"].clone();
+        assert_eq!(load(SnapshotId(0)), KvsValue::I32(2));
+        assert_eq!(load(SnapshotId(1)), KvsValue::I32(1));
+        assert_eq!(load(SnapshotId(2)), KvsValue::I32(1));
+    }
+
+    #[test]
+    fn test_flush_chunked_snapshot_max_count_zero() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(
+                JsonBackendBuilder::new()
+                    .working_dir(dir_path)
+                    .snapshot_max_count(0)
+                    .build(),
+            ),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+
+        let mut flush = kvs.flush_chunked().unwrap();
+        assert_eq!(flush.step().unwrap(), FlushProgress::Done);
+        assert_eq!(kvs.snapshot_count(), 0);
     }
 
     #[test]
@@ -811,7 +4564,150 @@ mod kvs_tests {
     }
 
     #[test]
-    fn test_snapshot_count_zero() {
+    fn test_snapshot_count_zero() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+        assert_eq!(kvs.snapshot_count(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_count_to_one() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_count_to_max() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+        for i in 1..=kvs.snapshot_max_count() {
+            kvs.flush().unwrap();
+            assert_eq!(kvs.snapshot_count(), i);
+        }
+        kvs.flush().unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), kvs.snapshot_max_count());
+    }
+
+    #[test]
+    fn test_snapshot_max_count() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+        assert_eq!(kvs.snapshot_max_count(), 3);
+    }
+
+    #[test]
+    fn test_set_snapshot_max_count_increased() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::new(),
+            KvsMap::new(),
+        );
+
+        kvs.set_snapshot_max_count(5).unwrap();
+        assert_eq!(kvs.snapshot_max_count(), 5);
+        for _ in 0..6 {
+            kvs.flush().unwrap();
+        }
+        assert_eq!(kvs.snapshots(), SnapshotId::range(5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_set_snapshot_max_count_reduced() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
+        let instance_id = kvs.parameters().instance_id;
+        for i in 1..=3 {
+            kvs.set_value("counter", i).unwrap();
+            kvs.flush().unwrap();
+        }
+
+        // Excess snapshot is kept until next flush.
+        kvs.set_snapshot_max_count(2).unwrap();
+        assert!(backend.kvs_file_path(instance_id, SnapshotId(2)).exists());
+
+        kvs.set_value("counter", 4).unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshots(), vec![SnapshotId(0), SnapshotId(1)]);
+        assert!(!backend.kvs_file_path(instance_id, SnapshotId(2)).exists());
+        assert!(!backend.hash_file_path(instance_id, SnapshotId(2)).exists());
+        assert_eq!(
+            kvs.snapshot_read(SnapshotId(1), "counter").unwrap(),
+            Some(KvsValue::I32(3))
+        );
+    }
+
+    #[test]
+    fn test_set_snapshot_max_count_zero() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
+        let instance_id = kvs.parameters().instance_id;
+        kvs.flush().unwrap();
+
+        // Flush is disabled, existing snapshot is not touched.
+        kvs.set_snapshot_max_count(0).unwrap();
+        kvs.set_value("key", 1).unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 0);
+        assert_eq!(backend.load_kvs(instance_id, SnapshotId(0)).unwrap(), KvsMap::new());
+    }
+
+    #[test]
+    fn test_set_snapshot_max_count_unsupported() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        assert!(kvs
+            .set_snapshot_max_count(1)
+            .is_err_and(|e| e == ErrorCode::UnmappedError));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
+        assert!(kvs.snapshots().is_empty());
+
+        for i in 1..=kvs.snapshot_max_count() {
+            kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
+            kvs.flush().unwrap();
+        }
+        assert_eq!(kvs.snapshots(), vec![SnapshotId(0), SnapshotId(1), SnapshotId(2)]);
+
+        std::fs::remove_file(backend.kvs_file_path(InstanceId(1), SnapshotId(1))).unwrap();
+        assert_eq!(kvs.snapshots(), vec![SnapshotId(0), SnapshotId(2)]);
+    }
+
+    #[test]
+    fn test_snapshot_restore_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let kvs = get_kvs(
@@ -819,11 +4715,17 @@ mod kvs_tests {
             KvsMap::new(),
             KvsMap::new(),
         );
-        assert_eq!(kvs.snapshot_count(), 0);
+        for i in 1..=kvs.snapshot_max_count() {
+            kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
+            kvs.flush().unwrap();
+        }
+
+        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+        assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 2);
     }
 
     #[test]
-    fn test_snapshot_count_to_one() {
+    fn test_snapshot_unrestore_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let kvs = get_kvs(
@@ -831,12 +4733,26 @@ mod kvs_tests {
             KvsMap::new(),
             KvsMap::new(),
         );
-        kvs.flush().unwrap();
-        assert_eq!(kvs.snapshot_count(), 1);
+        for i in 1..=kvs.snapshot_max_count() {
+            kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
+            kvs.flush().unwrap();
+        }
+        kvs.set_value("counter", KvsValue::I32(100)).unwrap();
+
+        kvs.snapshot_restore(SnapshotId(2)).unwrap();
+        assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 1);
+
+        kvs.snapshot_unrestore().unwrap();
+        assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 100);
+
+        // Stash is consumed by undo.
+        assert!(kvs
+            .snapshot_unrestore()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
     }
 
     #[test]
-    fn test_snapshot_count_to_max() {
+    fn test_snapshot_unrestore_last_restore() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let kvs = get_kvs(
@@ -845,16 +4761,18 @@ mod kvs_tests {
             KvsMap::new(),
         );
         for i in 1..=kvs.snapshot_max_count() {
+            kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
             kvs.flush().unwrap();
-            assert_eq!(kvs.snapshot_count(), i);
         }
-        kvs.flush().unwrap();
-        kvs.flush().unwrap();
-        assert_eq!(kvs.snapshot_count(), kvs.snapshot_max_count());
+
+        kvs.snapshot_restore(SnapshotId(2)).unwrap();
+        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+        kvs.snapshot_unrestore().unwrap();
+        assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 1);
     }
 
     #[test]
-    fn test_snapshot_max_count() {
+    fn test_snapshot_unrestore_cleared() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
         let kvs = get_kvs(
@@ -862,25 +4780,101 @@ mod kvs_tests {
             KvsMap::new(),
             KvsMap::new(),
         );
-        assert_eq!(kvs.snapshot_max_count(), 3);
+        for i in 1..=kvs.snapshot_max_count() {
+            kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
+            kvs.flush().unwrap();
+        }
+
+        // No restore performed.
+        assert!(kvs
+            .snapshot_unrestore()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+
+        // Cleared by `set_value`.
+        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+        kvs.set_value("other", 1).unwrap();
+        assert!(kvs
+            .snapshot_unrestore()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+
+        // Cleared by `flush`.
+        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+        kvs.flush().unwrap();
+        assert!(kvs
+            .snapshot_unrestore()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
     }
 
     #[test]
-    fn test_snapshot_restore_ok() {
+    fn test_snapshot_restore_and_flush_ok() {
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
-        let kvs = get_kvs(
-            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
-            KvsMap::new(),
-            KvsMap::new(),
-        );
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
         for i in 1..=kvs.snapshot_max_count() {
             kvs.set_value("counter", KvsValue::I32(i as i32)).unwrap();
             kvs.flush().unwrap();
         }
 
-        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+        kvs.snapshot_restore_and_flush(SnapshotId(1)).unwrap();
         assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 2);
+
+        // Restored state is current snapshot, previous current snapshot is rotated.
+        let instance_id = InstanceId(1);
+        let counter = |snapshot_id| backend.load_kvs(instance_id, snapshot_id).unwrap()["counter"].clone();
+        assert_eq!(counter(SnapshotId(0)), KvsValue::I32(2));
+        assert_eq!(counter(SnapshotId(1)), KvsValue::I32(3));
+        assert_eq!(counter(SnapshotId(2)), KvsValue::I32(2));
+    }
+
+    #[test]
+    fn test_snapshot_restore_and_flush_not_blocking() {
+        let backend = SlowBackend {
+            flush_started: Arc::new(Barrier::new(2)),
+            kvs_map: Arc::new(Mutex::new(Some(KvsMap::from([("key".to_string(), KvsValue::from(1))])))),
+        };
+        let kvs = get_kvs(
+            Box::new(backend.clone()),
+            KvsMap::from([("key".to_string(), KvsValue::from(5))]),
+            KvsMap::new(),
+        );
+
+        std::thread::scope(|s| {
+            let flush_thread = s.spawn(|| kvs.snapshot_restore_and_flush(SnapshotId(0)));
+
+            // Restored data is accessible while backend IO is in progress.
+            backend.flush_started.wait();
+            let start = Instant::now();
+            assert_eq!(kvs.get_value("key").unwrap(), KvsValue::I32(1));
+            kvs.set_value("key", 2).unwrap();
+            assert!(start.elapsed() < SlowBackend::FLUSH_DURATION);
+
+            flush_thread.join().unwrap().unwrap();
+        });
+
+        // Change made during the flush is not included.
+        assert_eq!(
+            *backend.kvs_map.lock().unwrap(),
+            Some(KvsMap::from([("key".to_string(), KvsValue::from(1))]))
+        );
+        assert!(kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_restore_and_flush_invalid_id() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(Box::new(backend.clone()), KvsMap::new(), KvsMap::new());
+        kvs.set_value("counter", KvsValue::I32(1)).unwrap();
+        kvs.flush().unwrap();
+
+        assert!(kvs
+            .snapshot_restore_and_flush(SnapshotId(2))
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+
+        // Nothing was flushed.
+        assert_eq!(backend.snapshot_count(InstanceId(1)), 1);
     }
 
     #[test]
@@ -939,4 +4933,378 @@ mod kvs_tests {
             .snapshot_restore(SnapshotId(3))
             .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
     }
+
+    #[test]
+    fn test_snapshot_restore_unsupported() {
+        let backend = MemoryBackend::default();
+        assert!(!backend.capabilities().persistence);
+        assert!(!backend.capabilities().snapshots);
+
+        let kvs = get_kvs(Box::new(backend), KvsMap::new(), KvsMap::new());
+        kvs.set_value("counter", KvsValue::I32(1)).unwrap();
+        kvs.flush().unwrap();
+
+        assert!(kvs
+            .snapshot_restore(SnapshotId(1))
+            .is_err_and(|e| e == ErrorCode::UnmappedError));
+        assert!(kvs
+            .snapshot_restore_and_flush(SnapshotId(1))
+            .is_err_and(|e| e == ErrorCode::UnmappedError));
+        assert_eq!(kvs.get_value_as::<i32>("counter").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mark_corrupt_writes_blocked() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs
+            .set_value("example2", true)
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs
+            .remove_key("example1")
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs
+            .reset_key("example1")
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs.reset().is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs.flush().is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs.flush_chunked().is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs
+            .snapshot_restore(SnapshotId(1))
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert_eq!(kvs.snapshot_count(), 0);
+    }
+
+    #[test]
+    fn test_mark_corrupt_reads_defaults() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs.get_all_keys().unwrap().is_empty());
+        assert!(!kvs.key_exists("example1").unwrap());
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("default"));
+        assert_eq!(kvs.get_value_as::<String>("example1").unwrap(), "default");
+        assert!(kvs.is_value_default("example1").unwrap());
+        assert!(kvs.get_value("example2").is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
+
+    #[test]
+    fn test_clear_corrupt() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = get_kvs(
+            Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::from([("example1".to_string(), KvsValue::from("default"))]),
+        );
+
+        kvs.mark_corrupt("invariant violated").unwrap();
+        kvs.clear_corrupt().unwrap();
+
+        // Stored values are served again and writes are accepted.
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("value"));
+        kvs.set_value("example2", true).unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 1);
+    }
+
+    #[test]
+    fn test_swap_backend() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let json_backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = get_kvs(
+            Box::new(json_backend.clone()),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))]),
+            KvsMap::new(),
+        );
+        kvs.flush().unwrap();
+
+        // Swap to memory backend, current data is flushed through it.
+        let memory_backend = MemoryBackend::default();
+        kvs.swap_backend(Box::new(memory_backend.clone())).unwrap();
+        assert!(kvs.parameters().backend().dyn_eq(&memory_backend));
+        assert_eq!(
+            memory_backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))])
+        );
+
+        // Reads and writes continue against new backend.
+        kvs.set_value("example2", true).unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.get_value("example1").unwrap(), KvsValue::from("value"));
+        assert_eq!(
+            memory_backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([
+                ("example1".to_string(), KvsValue::from("value")),
+                ("example2".to_string(), KvsValue::from(true)),
+            ])
+        );
+
+        // Old backend is no longer used.
+        assert_eq!(json_backend.snapshot_count(InstanceId(1)), 1);
+        assert_eq!(
+            json_backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([("example1".to_string(), KvsValue::from("value"))])
+        );
+    }
+
+    #[test]
+    fn test_swap_backend_waits_for_flush() {
+        let backend = SlowBackend {
+            flush_started: Arc::new(Barrier::new(2)),
+            kvs_map: Arc::new(Mutex::new(None)),
+        };
+        let kvs = get_kvs(
+            Box::new(backend.clone()),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::new(),
+        );
+
+        let memory_backend = MemoryBackend::default();
+        std::thread::scope(|s| {
+            let flush_thread = s.spawn(|| kvs.flush());
+
+            // Swap while flush of earlier content is in progress.
+            backend.flush_started.wait();
+            kvs.set_value("key", 2).unwrap();
+            kvs.swap_backend(Box::new(memory_backend.clone())).unwrap();
+            assert!(backend.kvs_map.lock().unwrap().is_some());
+
+            flush_thread.join().unwrap().unwrap();
+        });
+
+        // Earlier content is written to old backend only, new backend holds current content.
+        assert_eq!(
+            *backend.kvs_map.lock().unwrap(),
+            Some(KvsMap::from([("key".to_string(), KvsValue::from(1))]))
+        );
+        assert_eq!(
+            memory_backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([("key".to_string(), KvsValue::from(2))])
+        );
+        assert!(!kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_swap_backend_corrupt() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        let memory_backend = MemoryBackend::default();
+        assert!(kvs
+            .swap_backend(Box::new(memory_backend.clone()))
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs.parameters().backend().dyn_eq(&MockBackend));
+        assert_eq!(memory_backend.snapshot_count(InstanceId(1)), 0);
+    }
+
+    /// Changes recorded by callback.
+    type RecordedChanges = Arc<Mutex<Vec<(String, KvsValue)>>>;
+
+    /// Create callback recording received changes.
+    fn recording_callback() -> (RecordedChanges, impl Fn(&str, &KvsValue) + Clone + Send + Sync) {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_clone = changes.clone();
+        let callback = move |key: &str, value: &KvsValue| {
+            changes_clone.lock().unwrap().push((key.to_string(), value.clone()));
+        };
+        (changes, callback)
+    }
+
+    #[test]
+    fn test_watch_set_value() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        let (changes, callback) = recording_callback();
+        let _handle = kvs.watch("key", callback);
+
+        kvs.set_value("key", 1).unwrap();
+        kvs.set_value("other", 2).unwrap();
+
+        assert_eq!(*changes.lock().unwrap(), vec![("key".to_string(), KvsValue::I32(1))]);
+    }
+
+    #[test]
+    fn test_watch_remove_key() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("key".to_string(), KvsValue::from(1)),
+                ("defaulted".to_string(), KvsValue::from(2)),
+            ]),
+            KvsMap::from([("defaulted".to_string(), KvsValue::from(3))]),
+        );
+        let (changes, callback) = recording_callback();
+        let _handle = kvs.watch("key", callback.clone());
+        let _handle_defaulted = kvs.watch("defaulted", callback);
+
+        kvs.remove_key("key").unwrap();
+        kvs.remove_key("defaulted").unwrap();
+        assert!(kvs.remove_key("key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                ("key".to_string(), KvsValue::Null),
+                ("defaulted".to_string(), KvsValue::I32(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_reset_key() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("key".to_string(), KvsValue::from(1))]),
+            KvsMap::from([("key".to_string(), KvsValue::from(2))]),
+        );
+        let (changes, callback) = recording_callback();
+        let _handle = kvs.watch("key", callback);
+
+        kvs.reset_key("key").unwrap();
+
+        assert_eq!(*changes.lock().unwrap(), vec![("key".to_string(), KvsValue::I32(2))]);
+    }
+
+    #[test]
+    fn test_watch_handle_dropped() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        let (changes, callback) = recording_callback();
+        let handle = kvs.watch("key", callback);
+
+        kvs.set_value("key", 1).unwrap();
+        drop(handle);
+        kvs.set_value("key", 2).unwrap();
+
+        assert_eq!(*changes.lock().unwrap(), vec![("key".to_string(), KvsValue::I32(1))]);
+        assert!(kvs.data.read().unwrap().watchers.is_empty());
+    }
+
+    #[test]
+    fn test_watch_reentrant() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        let kvs_clone = Kvs::new(kvs.data.clone(), kvs.parameters.clone());
+        let _handle = kvs.watch("key", move |_key, value| {
+            // Callback is invoked without lock held - accessing KVS must not deadlock.
+            assert_eq!(kvs_clone.get_value("key").unwrap(), *value);
+            kvs_clone.set_value("mirror", value.clone()).unwrap();
+        });
+
+        kvs.set_value("key", 1).unwrap();
+
+        assert_eq!(kvs.get_value("mirror").unwrap(), KvsValue::I32(1));
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([
+                ("u64".to_string(), KvsValue::from(u64::MAX >> 12)),
+                ("str".to_string(), KvsValue::from("value")),
+                (
+                    "obj".to_string(),
+                    KvsValue::from(KvsMap::from([(
+                        "arr".to_string(),
+                        KvsValue::from(vec![KvsValue::from(1.5), KvsValue::Null]),
+                    )])),
+                ),
+            ]),
+            KvsMap::from([("default".to_string(), KvsValue::from(1))]),
+        );
+        kvs.set_value_with_ttl("cached", 1, Duration::from_secs(3600)).unwrap();
+        kvs.set_value_with_ttl("expired", 2, Duration::ZERO).unwrap();
+
+        let mut document = Vec::new();
+        kvs.export(&mut document).unwrap();
+
+        let fresh_kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        fresh_kvs
+            .import(document.as_slice(), MergePolicy::PreferIncoming)
+            .unwrap();
+
+        // Defaults and expired values are not exported, expiry is kept.
+        let mut expected = kvs.data.read().unwrap().kvs_map.clone();
+        expected.remove("expired");
+        let fresh_data = fresh_kvs.data.read().unwrap();
+        assert_eq!(fresh_data.kvs_map, expected);
+        assert_eq!(
+            fresh_data.expiry_map.keys().collect::<Vec<_>>(),
+            vec![&"cached".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_import_merge_policy() {
+        let document = r#"{"t":"obj","v":{"k1":{"t":"i32","v":10},"k2":{"t":"str","v":"new"}}}"#;
+
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("k1".to_string(), KvsValue::from(1))]),
+            KvsMap::new(),
+        );
+        kvs.import(document.as_bytes(), MergePolicy::PreferExisting).unwrap();
+        assert_eq!(kvs.get_value("k1").unwrap(), KvsValue::I32(1));
+        assert_eq!(kvs.get_value("k2").unwrap(), KvsValue::from("new"));
+
+        kvs.import(document.as_bytes(), MergePolicy::PreferIncoming).unwrap();
+        assert_eq!(kvs.get_value("k1").unwrap(), KvsValue::I32(10));
+    }
+
+    #[test]
+    fn test_import_invalid_type() {
+        let kvs = get_kvs(
+            Box::new(MockBackend),
+            KvsMap::from([("k1".to_string(), KvsValue::from(1))]),
+            KvsMap::new(),
+        );
+
+        for document in [
+            r#"{"t":"obj","v":{"k1":{"t":"i32","v":"text"}}}"#,
+            r#"{"t":"obj","v":{"k1":{"t":"u32","v":-1}}}"#,
+            r#"{"t":"obj","v":{"k1":{"t":"unknown","v":1}}}"#,
+            r#"{"t":"obj","v":{"k2":{"t":"arr","v":[{"t":"i32","v":1.5}]}}}"#,
+            r#"{"k1":{"t":"i32","v":2}}"#,
+        ] {
+            assert!(kvs
+                .import(document.as_bytes(), MergePolicy::PreferIncoming)
+                .is_err_and(|e| e == ErrorCode::ConversionFailed));
+        }
+        assert!(kvs
+            .import("{".as_bytes(), MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::JsonParserError));
+
+        // Nothing was applied.
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["k1"]);
+        assert_eq!(kvs.get_value("k1").unwrap(), KvsValue::I32(1));
+    }
+
+    #[test]
+    fn test_export_import_corrupt() {
+        let kvs = get_kvs(Box::new(MockBackend), KvsMap::new(), KvsMap::new());
+        kvs.mark_corrupt("invariant violated").unwrap();
+
+        assert!(kvs
+            .export(Vec::new())
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+        assert!(kvs
+            .import(r#"{"t":"obj","v":{}}"#.as_bytes(), MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::IntegrityCorrupted));
+    }
 }
@@ -11,11 +11,14 @@
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
 use crate::error_code::ErrorCode;
-use crate::kvs_value::KvsValue;
+use crate::kvs_backend::ChunkedFlush;
+use crate::kvs_value::{KvsMap, KvsValue};
 use core::fmt;
+use core::str::FromStr;
+use core::time::Duration;
 
 /// Instance ID
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct InstanceId(pub usize);
 
 impl fmt::Display for InstanceId {
@@ -30,10 +33,40 @@ impl From<InstanceId> for usize {
     }
 }
 
+impl From<usize> for InstanceId {
+    fn from(value: usize) -> Self {
+        InstanceId(value)
+    }
+}
+
+impl FromStr for InstanceId {
+    type Err = ErrorCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(InstanceId).map_err(|_| {
+            eprintln!("error: invalid instance ID: {s:?}");
+            ErrorCode::InvalidInstanceId
+        })
+    }
+}
+
 /// Snapshot ID
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SnapshotId(pub usize);
 
+impl SnapshotId {
+    /// Iterate over snapshot IDs in ascending order, starting from current KVS (`SnapshotId(0)`).
+    ///
+    /// # Parameters
+    ///   * `max`: Number of IDs, e.g. snapshot max count
+    ///
+    /// # Return Values
+    ///   * Iterator over `SnapshotId(0)` to `SnapshotId(max - 1)`
+    pub fn range(max: usize) -> impl Iterator<Item = SnapshotId> {
+        (0..max).map(SnapshotId)
+    }
+}
+
 impl fmt::Display for SnapshotId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -46,6 +79,23 @@ impl From<SnapshotId> for usize {
     }
 }
 
+impl From<usize> for SnapshotId {
+    fn from(value: usize) -> Self {
+        SnapshotId(value)
+    }
+}
+
+impl FromStr for SnapshotId {
+    type Err = ErrorCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(SnapshotId).map_err(|_| {
+            eprintln!("error: invalid snapshot ID: {s:?}");
+            ErrorCode::InvalidSnapshotId
+        })
+    }
+}
+
 /// Defaults handling mode.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum KvsDefaults {
@@ -63,37 +113,122 @@ pub enum KvsDefaults {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum KvsLoad {
     /// KVS is not loaded.
+    /// Combined with backend `snapshot_max_count` of 0 instance serves defaults only: snapshots
+    /// are neither read nor written, `flush` succeeds without creating files and set values are
+    /// kept in memory.
     Ignored,
 
     /// KVS is loaded if available.
+    /// KVS file without its required hash file is reported as `ErrorCode::HashFileNotFound`.
     Optional,
 
     /// KVS must be loaded.
     Required,
+
+    /// Newest valid snapshot is loaded if available.
+    /// Snapshots failing integrity check are skipped.
+    LatestValid,
+}
+
+/// Source of a default value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultsSource {
+    /// Defaults loaded by backend (e.g. defaults file).
+    File,
+
+    /// Defaults provided with `KvsBuilder::default_value` or `KvsBuilder::default_values`.
+    Programmatic,
+}
+
+/// Precedence between defaults sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultsPrecedence {
+    /// Defaults loaded by backend take precedence.
+    File,
+
+    /// Programmatic defaults take precedence.
+    Programmatic,
+}
+
+/// Conflict policy used when merging maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Existing values are kept, only missing keys are added.
+    PreferExisting,
+
+    /// Incoming values overwrite existing values.
+    PreferIncoming,
+
+    /// Nested objects are merged recursively, incoming values win for all other values.
+    DeepMergeObjects,
 }
 
 pub trait KvsApi {
     fn reset(&self) -> Result<(), ErrorCode>;
     fn reset_key(&self, key: &str) -> Result<(), ErrorCode>;
+    fn reset_to_defaults(&self) -> Result<usize, ErrorCode>;
+    fn materialize_default(&self, key: &str) -> Result<(), ErrorCode>;
     fn get_all_keys(&self) -> Result<Vec<String>, ErrorCode>;
+    fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, ErrorCode>;
     fn key_exists(&self, key: &str) -> Result<bool, ErrorCode>;
+    fn len(&self) -> Result<usize, ErrorCode>;
+    fn is_empty(&self) -> Result<bool, ErrorCode>;
+    fn entries(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode>;
+    fn entries_with_defaults(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode>;
     fn get_value(&self, key: &str) -> Result<KvsValue, ErrorCode>;
+    fn try_get_value(&self, key: &str) -> Result<Option<KvsValue>, ErrorCode>;
+    fn with_value<R, F: FnOnce(Option<&KvsValue>) -> R>(&self, key: &str, f: F) -> Result<R, ErrorCode>;
     fn get_value_as<T>(&self, key: &str) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug;
+    fn get_value_or<T>(&self, key: &str, default: T) -> Result<T, ErrorCode>
     where
         for<'a> T: TryFrom<&'a KvsValue> + Clone,
         for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug;
     fn get_default_value(&self, key: &str) -> Result<KvsValue, ErrorCode>;
+    fn default_keys(&self) -> Result<Vec<String>, ErrorCode>;
+    fn contains_default(&self, key: &str) -> Result<bool, ErrorCode>;
     fn is_value_default(&self, key: &str) -> Result<bool, ErrorCode>;
+    fn effective_defaults_report(&self) -> Result<Vec<(String, KvsValue, DefaultsSource)>, ErrorCode>;
+    fn set_defaults(&self, defaults: KvsMap) -> Result<(), ErrorCode>;
+    fn clear_defaults(&self) -> Result<(), ErrorCode>;
     fn set_value<S: Into<String>, J: Into<KvsValue>>(&self, key: S, value: J) -> Result<(), ErrorCode>;
+    fn set_value_with_ttl<S: Into<String>, J: Into<KvsValue>>(
+        &self,
+        key: S,
+        value: J,
+        ttl: Duration,
+    ) -> Result<(), ErrorCode>;
+    fn compare_and_set<V: Into<KvsValue>>(
+        &self,
+        key: &str,
+        expected: Option<&KvsValue>,
+        new: V,
+    ) -> Result<bool, ErrorCode>;
+    fn swap<V: Into<KvsValue>>(&self, key: &str, value: V) -> Result<Option<KvsValue>, ErrorCode>;
+    fn rename_key(&self, from: &str, to: &str, overwrite: bool) -> Result<(), ErrorCode>;
     fn remove_key(&self, key: &str) -> Result<(), ErrorCode>;
+    fn remove_prefix(&self, prefix: &str) -> Result<usize, ErrorCode>;
+    fn compact<F: Fn(&str, &KvsValue) -> bool>(&self, keep: F) -> Result<(), ErrorCode>;
+    fn merge(&self, other: KvsMap, policy: MergePolicy) -> Result<(), ErrorCode>;
+    fn is_dirty(&self) -> Result<bool, ErrorCode>;
     fn flush(&self) -> Result<(), ErrorCode>;
+    fn flush_with_id(&self) -> Result<SnapshotId, ErrorCode>;
+    fn flush_chunked(&self) -> Result<Box<dyn ChunkedFlush + '_>, ErrorCode>;
     fn snapshot_count(&self) -> usize;
+    fn snapshots(&self) -> Vec<SnapshotId>;
     fn snapshot_max_count(&self) -> usize;
     fn snapshot_restore(&self, snapshot_id: SnapshotId) -> Result<(), ErrorCode>;
+    fn snapshot_restore_and_flush(&self, snapshot_id: SnapshotId) -> Result<(), ErrorCode>;
+    fn snapshot_unrestore(&self) -> Result<(), ErrorCode>;
+    fn mark_corrupt(&self, reason: &str) -> Result<(), ErrorCode>;
+    fn clear_corrupt(&self) -> Result<(), ErrorCode>;
 }
 
 #[cfg(test)]
 mod kvs_api_tests {
+    use crate::error_code::ErrorCode;
     use crate::kvs_api::{InstanceId, SnapshotId};
 
     #[test]
@@ -119,4 +254,56 @@ mod kvs_api_tests {
         let id = SnapshotId(0);
         assert_eq!(usize::from(id), 0);
     }
+
+    #[test]
+    fn test_instance_id_from_usize() {
+        assert_eq!(InstanceId::from(7), InstanceId(7));
+    }
+
+    #[test]
+    fn test_snapshot_id_from_usize() {
+        assert_eq!(SnapshotId::from(2), SnapshotId(2));
+    }
+
+    #[test]
+    fn test_instance_id_from_str() {
+        assert_eq!("42".parse::<InstanceId>().unwrap(), InstanceId(42));
+        assert_eq!(" 3 ".parse::<InstanceId>().unwrap(), InstanceId(3));
+        assert!("-1"
+            .parse::<InstanceId>()
+            .is_err_and(|e| e == ErrorCode::InvalidInstanceId));
+        assert!("abc"
+            .parse::<InstanceId>()
+            .is_err_and(|e| e == ErrorCode::InvalidInstanceId));
+    }
+
+    #[test]
+    fn test_snapshot_id_from_str() {
+        assert_eq!("1".parse::<SnapshotId>().unwrap(), SnapshotId(1));
+        assert!(""
+            .parse::<SnapshotId>()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+        assert!("1.5"
+            .parse::<SnapshotId>()
+            .is_err_and(|e| e == ErrorCode::InvalidSnapshotId));
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(InstanceId(1) < InstanceId(2));
+        assert!(SnapshotId(3) > SnapshotId(0));
+
+        let mut ids = vec![SnapshotId(2), SnapshotId(0), SnapshotId(1)];
+        ids.sort();
+        assert_eq!(ids, vec![SnapshotId(0), SnapshotId(1), SnapshotId(2)]);
+    }
+
+    #[test]
+    fn test_snapshot_id_range() {
+        assert_eq!(
+            SnapshotId::range(3).collect::<Vec<_>>(),
+            vec![SnapshotId(0), SnapshotId(1), SnapshotId(2)]
+        );
+        assert_eq!(SnapshotId::range(0).count(), 0);
+    }
 }
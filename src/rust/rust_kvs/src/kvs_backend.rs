@@ -12,8 +12,10 @@
 // *******************************************************************************
 use crate::error_code::ErrorCode;
 use crate::kvs_api::{InstanceId, SnapshotId};
-use crate::kvs_value::KvsMap;
+use crate::kvs_value::{KvsExpiryMap, KvsMap};
 use core::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Trait for comparisons between types.
 pub trait DynEq: Any {
@@ -23,10 +25,7 @@ pub trait DynEq: Any {
     fn as_any(&self) -> &dyn Any;
 }
 
-impl<T: PartialEq + Any> DynEq for T
-where
-    T: KvsBackend,
-{
+impl<T: PartialEq + Any> DynEq for T {
     fn dyn_eq(&self, other: &dyn Any) -> bool {
         if let Some(other) = other.downcast_ref::<T>() {
             self == other
@@ -40,8 +39,87 @@ where
     }
 }
 
+/// Features supported by a backend.
+///
+/// Reported by [`KvsBackend::capabilities`], allows generic code to check support before use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BackendCapabilities {
+    /// Snapshots are kept and can be restored.
+    pub snapshots: bool,
+
+    /// Flushed data is persisted and outlives the process.
+    pub persistence: bool,
+
+    /// Defaults are provided.
+    pub defaults: bool,
+
+    /// Stored data is validated on load.
+    pub integrity: bool,
+}
+
+/// Progress reported by a single step of a chunked flush.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushProgress {
+    /// More steps are required to complete the flush.
+    Pending,
+
+    /// Flush completed.
+    Done,
+}
+
+/// Chunked flush state machine.
+///
+/// Each call to [`ChunkedFlush::step`] performs a single file operation, allowing a cooperative
+/// scheduler to run other tasks in between.
+pub trait ChunkedFlush {
+    /// Perform next flush step.
+    ///
+    /// # Return Values
+    ///   * `FlushProgress::Pending`: Step performed, more steps required
+    ///   * `FlushProgress::Done`: Flush completed, further calls have no effect
+    fn step(&mut self) -> Result<FlushProgress, ErrorCode>;
+}
+
+/// Chunked flush performing regular flush in a single step.
+struct SingleStepFlush<B: KvsBackend + ?Sized> {
+    backend: Arc<B>,
+    instance_id: InstanceId,
+    content: Option<(KvsMap, KvsExpiryMap)>,
+}
+
+impl<B: KvsBackend + ?Sized> ChunkedFlush for SingleStepFlush<B> {
+    fn step(&mut self) -> Result<FlushProgress, ErrorCode> {
+        if let Some((kvs_map, expiry_map)) = self.content.take() {
+            self.backend
+                .flush_with_expiry(self.instance_id, &kvs_map, &expiry_map)?;
+        }
+        Ok(FlushProgress::Done)
+    }
+}
+
+/// Chunked flush that is already completed.
+pub(crate) struct CompletedFlush;
+
+impl ChunkedFlush for CompletedFlush {
+    fn step(&mut self) -> Result<FlushProgress, ErrorCode> {
+        Ok(FlushProgress::Done)
+    }
+}
+
 /// KVS backend interface.
 pub trait KvsBackend: DynEq + Sync + Send {
+    /// Backend name, used for diagnostics.
+    /// Default implementation reports `"unknown"`.
+    fn name(&self) -> &str {
+        "unknown"
+    }
+
+    /// Features supported by the backend.
+    /// Default implementation reports no capabilities.
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::default()
+    }
+
     /// Load KVS content.
     fn load_kvs(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode>;
 
@@ -52,12 +130,134 @@ pub trait KvsBackend: DynEq + Sync + Send {
     /// Snapshots are rotated and current state is stored as first (0).
     fn flush(&self, instance_id: InstanceId, kvs_map: &KvsMap) -> Result<(), ErrorCode>;
 
+    /// Load KVS content together with key expiry.
+    /// Default implementation loads KVS content without expiry.
+    fn load_kvs_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        Ok((self.load_kvs(instance_id, snapshot_id)?, KvsExpiryMap::new()))
+    }
+
+    /// Flush KvsMap together with key expiry to persistent storage.
+    /// Default implementation performs `flush`, expiry is not persisted.
+    fn flush_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        kvs_map: &KvsMap,
+        expiry_map: &KvsExpiryMap,
+    ) -> Result<(), ErrorCode> {
+        if !expiry_map.is_empty() {
+            eprintln!("warn: key expiry is not supported by backend, not persisted");
+        }
+        self.flush(instance_id, kvs_map)
+    }
+
+    /// Flush KvsMap together with key expiry to persistent storage in steps.
+    /// Result after completion must be same as for `flush_with_expiry`.
+    /// Default implementation performs `flush_with_expiry` in a single step.
+    fn flush_chunked(
+        self: Arc<Self>,
+        instance_id: InstanceId,
+        kvs_map: KvsMap,
+        expiry_map: KvsExpiryMap,
+    ) -> Box<dyn ChunkedFlush> {
+        Box::new(SingleStepFlush {
+            backend: self,
+            instance_id,
+            content: Some((kvs_map, expiry_map)),
+        })
+    }
+
+    /// Load raw KVS content.
+    /// Content is validated, but not interpreted.
+    /// Default implementation reports raw content as unsupported.
+    fn load_kvs_bytes(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Result<Vec<u8>, ErrorCode> {
+        eprintln!("error: raw content is not supported by backend");
+        Err(ErrorCode::UnmappedError)
+    }
+
+    /// Flush raw content to persistent storage.
+    /// Snapshots are rotated same as in `flush`.
+    /// Default implementation reports raw content as unsupported.
+    fn flush_bytes(&self, _instance_id: InstanceId, _bytes: &[u8]) -> Result<(), ErrorCode> {
+        eprintln!("error: raw content is not supported by backend");
+        Err(ErrorCode::UnmappedError)
+    }
+
     /// Count available snapshots.
     fn snapshot_count(&self, instance_id: InstanceId) -> usize;
 
+    /// List IDs of available snapshots in ascending order.
+    /// Unlike `snapshot_count`, snapshots following a missing one are also listed.
+    /// Default implementation assumes snapshots up to `snapshot_count` are available.
+    fn snapshots(&self, instance_id: InstanceId) -> Vec<SnapshotId> {
+        SnapshotId::range(self.snapshot_count(instance_id)).collect()
+    }
+
+    /// Check integrity of available snapshots, listed same as in `snapshots`.
+    /// Invalid snapshots are reported as such, verification continues with remaining ones.
+    /// Default implementation considers snapshot valid if it can be loaded.
+    fn verify(&self, instance_id: InstanceId) -> Result<Vec<(SnapshotId, bool)>, ErrorCode> {
+        Ok(self
+            .snapshots(instance_id)
+            .into_iter()
+            .map(|snapshot_id| {
+                let result = self.load_kvs(instance_id, snapshot_id);
+                if let Err(e) = &result {
+                    eprintln!("warn: snapshot {snapshot_id} invalid: {e:?}");
+                }
+                (snapshot_id, result.is_ok())
+            })
+            .collect())
+    }
+
+    /// Hash of content last flushed for the instance by this backend, stored with snapshot 0.
+    /// Default implementation reports no hash, e.g. for backends not computing one.
+    fn last_flush_hash(&self, _instance_id: InstanceId) -> Option<u32> {
+        None
+    }
+
+    /// ID of snapshot written by the last successful `flush` of the instance.
+    /// ID is valid until subsequent flush rotates the snapshot.
+    /// Default implementation reports current KVS (`SnapshotId(0)`), where flushes are written to.
+    fn flushed_snapshot_id(&self, _instance_id: InstanceId) -> SnapshotId {
+        SnapshotId(0)
+    }
+
     /// Max number of snapshots.
     fn snapshot_max_count(&self) -> usize;
 
+    /// Change max number of snapshots, applied by subsequent flushes.
+    /// Default implementation reports runtime change as unsupported.
+    fn set_snapshot_max_count(&self, _snapshot_max_count: usize) -> Result<(), ErrorCode> {
+        eprintln!("error: changing snapshot max count is not supported by backend");
+        Err(ErrorCode::UnmappedError)
+    }
+
     /// Restore snapshot with given ID.
     fn snapshot_restore(&self, instance_id: InstanceId, snapshot_id: SnapshotId) -> Result<KvsMap, ErrorCode>;
+
+    /// Restore snapshot with given ID together with key expiry.
+    /// Default implementation restores snapshot without expiry.
+    fn snapshot_restore_with_expiry(
+        &self,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        Ok((self.snapshot_restore(instance_id, snapshot_id)?, KvsExpiryMap::new()))
+    }
+
+    /// Directory data is persisted to.
+    /// Default implementation reports no directory, e.g. for non file-based backends.
+    fn working_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Paths of KVS file and hash file of snapshot with given ID, files may not exist.
+    /// Default implementation reports no paths, e.g. for non file-based backends.
+    fn snapshot_paths(&self, _instance_id: InstanceId, _snapshot_id: SnapshotId) -> Option<(PathBuf, PathBuf)> {
+        None
+    }
 }
@@ -10,16 +10,18 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
+use crate::audit::AuditSink;
+use crate::clock::{Clock, SystemClock};
 use crate::error_code::ErrorCode;
 use crate::json_backend::JsonBackendBuilder;
-use crate::kvs::{Kvs, KvsParameters};
-use crate::kvs_api::{InstanceId, KvsDefaults, KvsLoad, SnapshotId};
+use crate::kvs::{AutoFlush, Kvs, KvsParameters, KvsWatcher, WatchCallback};
+use crate::kvs_api::{DefaultsPrecedence, DefaultsSource, InstanceId, KvsApi, KvsDefaults, KvsLoad, SnapshotId};
 use crate::kvs_backend::KvsBackend;
-use crate::kvs_value::KvsMap;
-use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError};
-
-/// Maximum number of instances.
-const KVS_MAX_INSTANCES: usize = 10;
+use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue, KvsValueType};
+use core::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 /// KVS instance data.
 /// Expected to be shared between instance pool and instances.
@@ -27,12 +29,166 @@ pub(crate) struct KvsData {
     /// Storage data.
     pub(crate) kvs_map: KvsMap,
 
+    /// Expiry of stored values.
+    pub(crate) expiry_map: KvsExpiryMap,
+
     /// Optional default values.
     pub(crate) defaults_map: KvsMap,
+
+    /// Source of each default value.
+    pub(crate) defaults_sources: HashMap<String, DefaultsSource>,
+
+    /// Reason provided when instance was marked corrupt.
+    /// Instance is in degraded mode if set.
+    pub(crate) corrupt_reason: Option<String>,
+
+    /// Registered key-change callbacks.
+    pub(crate) watchers: Vec<KvsWatcher>,
+
+    /// ID assigned to the next registered callback.
+    pub(crate) next_watcher_id: u64,
+
+    /// Storage data and expiry replaced by the last snapshot restore.
+    /// Used to undo the restore, cleared on `set_value` and flush.
+    pub(crate) restore_stash: Option<(KvsMap, KvsExpiryMap)>,
+
+    /// Storage data changed since last flush.
+    pub(crate) dirty: bool,
+
+    /// Hash of content written by the last flush, if reported by backend.
+    pub(crate) last_flush_hash: Option<u32>,
+
+    /// Clock used to set and check expiry of values.
+    pub(crate) clock: Arc<dyn Clock>,
+
+    /// Defaults not loaded yet, see `KvsBuilder::lazy_defaults`.
+    pub(crate) lazy_defaults: Option<Arc<LazyDefaults>>,
+}
+
+/// Defaults loading deferred until first access.
+pub(crate) struct LazyDefaults {
+    /// Load defaults, loaded values are migrated, normalized and type-checked.
+    pub(crate) load: Box<dyn Fn() -> Result<KvsMap, ErrorCode> + Send + Sync>,
+
+    /// Loaded values overwrite already present programmatic defaults.
+    pub(crate) overwrite: bool,
+}
+
+impl KvsData {
+    /// Create KVS instance data in normal (not degraded) mode.
+    /// Provided default values are attributed to defaults file.
+    pub(crate) fn new(kvs_map: KvsMap, defaults_map: KvsMap) -> Self {
+        let defaults_sources = defaults_map.keys().map(|k| (k.clone(), DefaultsSource::File)).collect();
+        Self {
+            kvs_map,
+            expiry_map: KvsExpiryMap::new(),
+            defaults_map,
+            defaults_sources,
+            corrupt_reason: None,
+            watchers: Vec::new(),
+            next_watcher_id: 0,
+            restore_stash: None,
+            dirty: false,
+            last_flush_hash: None,
+            clock: Arc::new(SystemClock),
+            lazy_defaults: None,
+        }
+    }
+
+    /// Load defaults deferred by `KvsBuilder::lazy_defaults`, if not loaded yet.
+    /// Failed load is retried on next call.
+    pub(crate) fn load_lazy_defaults(&mut self) -> Result<(), ErrorCode> {
+        if let Some(lazy_defaults) = self.lazy_defaults.clone() {
+            let defaults_map = (lazy_defaults.load)()?;
+            self.apply_defaults(defaults_map, DefaultsSource::File, lazy_defaults.overwrite);
+            self.lazy_defaults = None;
+        }
+        Ok(())
+    }
+
+    /// Apply default values on top of existing ones.
+    ///
+    /// # Parameters
+    ///   * `defaults_map`: Default values
+    ///   * `source`: Source of provided default values
+    ///   * `overwrite`: Overwrite values for existing keys, otherwise existing values are kept
+    pub(crate) fn apply_defaults(&mut self, defaults_map: KvsMap, source: DefaultsSource, overwrite: bool) {
+        for (key, value) in defaults_map {
+            if !overwrite && self.defaults_map.contains_key(&key) {
+                continue;
+            }
+            self.defaults_sources.insert(key.clone(), source);
+            self.defaults_map.insert(key, value);
+        }
+    }
+
+    /// Check stored value of a key is expired.
+    pub(crate) fn is_expired(&self, key: &str) -> bool {
+        self.expiry_map
+            .get(key)
+            .is_some_and(|expiry| *expiry <= self.clock.now())
+    }
+
+    /// Remove stored value of a key if expired.
+    pub(crate) fn remove_expired(&mut self, key: &str) {
+        if self.is_expired(key) {
+            let _ = self.kvs_map.remove(key);
+            let _ = self.expiry_map.remove(key);
+            self.dirty = true;
+        }
+    }
+
+    /// Get stored value, defaults are not considered.
+    /// Stored values are hidden in degraded mode and when expired.
+    pub(crate) fn stored_value(&self, key: &str) -> Option<&KvsValue> {
+        if self.corrupt_reason.is_some() || self.is_expired(key) {
+            None
+        } else {
+            self.kvs_map.get(key)
+        }
+    }
+
+    /// Get callbacks registered for a key.
+    /// Callbacks are cloned so they can be invoked after the data lock is released.
+    pub(crate) fn key_watchers(&self, key: &str) -> Vec<WatchCallback> {
+        self.watchers
+            .iter()
+            .filter(|w| w.key == key)
+            .map(|w| w.callback.clone())
+            .collect()
+    }
+
+    /// Get effective value of a key, defaults are considered.
+    /// `KvsValue::Null` is returned if key has neither a stored nor a default value.
+    pub(crate) fn effective_value(&self, key: &str) -> KvsValue {
+        self.stored_value(key)
+            .or_else(|| self.defaults_map.get(key))
+            .cloned()
+            .unwrap_or(KvsValue::Null)
+    }
+
+    /// Check instance accepts mutations.
+    ///
+    /// # Return Values
+    ///   * Ok: Instance is in normal mode
+    ///   * `ErrorCode::IntegrityCorrupted`: Instance is in degraded mode
+    pub(crate) fn check_writable(&self) -> Result<(), ErrorCode> {
+        if let Some(reason) = &self.corrupt_reason {
+            eprintln!("error: KVS marked corrupt, operation rejected: {reason}");
+            return Err(ErrorCode::IntegrityCorrupted);
+        }
+        Ok(())
+    }
+}
+
+impl From<PoisonError<RwLockReadGuard<'_, KvsData>>> for ErrorCode {
+    fn from(_cause: PoisonError<RwLockReadGuard<'_, KvsData>>) -> Self {
+        ErrorCode::MutexLockFailed
+    }
 }
 
-impl From<PoisonError<MutexGuard<'_, KvsData>>> for ErrorCode {
-    fn from(_cause: PoisonError<MutexGuard<'_, KvsData>>) -> Self {
+impl From<PoisonError<RwLockWriteGuard<'_, KvsData>>> for ErrorCode {
+    fn from(_cause: PoisonError<RwLockWriteGuard<'_, KvsData>>) -> Self {
         ErrorCode::MutexLockFailed
     }
 }
@@ -43,18 +199,97 @@ pub(crate) struct KvsInner {
     pub(crate) parameters: Arc<KvsParameters>,
 
     /// KVS instance data.
-    pub(crate) data: Arc<Mutex<KvsData>>,
+    pub(crate) data: Arc<RwLock<KvsData>>,
+
+    /// Interval of periodic flush, see [`KvsBuilder::autoflush_interval`].
+    pub(crate) autoflush_interval: Option<Duration>,
+
+    /// Periodic flush, owned by handles of the instance.
+    pub(crate) autoflush: Weak<AutoFlush>,
+}
+
+impl KvsInner {
+    /// Create handle of the instance.
+    /// Periodic flush is restarted if all previous handles were dropped.
+    ///
+    /// # Return Values
+    ///   * Ok: KVS instance
+    ///   * `ErrorCode::UnmappedError`: Flushing thread could not be spawned
+    pub(crate) fn handle(&mut self) -> Result<Kvs, ErrorCode> {
+        let autoflush = match (self.autoflush.upgrade(), self.autoflush_interval) {
+            (Some(autoflush), _) => Some(autoflush),
+            (None, Some(interval)) => {
+                let autoflush = Arc::new(AutoFlush::start(&self.data, &self.parameters, interval)?);
+                self.autoflush = Arc::downgrade(&autoflush);
+                Some(autoflush)
+            },
+            (None, None) => None,
+        };
+        Ok(Kvs::new(self.data.clone(), self.parameters.clone()).with_autoflush(autoflush))
+    }
+}
+
+/// KVS instance pool.
+pub(crate) struct KvsPool {
+    /// Initialized instances.
+    pub(crate) instances: HashMap<InstanceId, KvsInner>,
+
+    /// Optional limit of instance IDs.
+    /// Instance IDs greater or equal to the limit are rejected.
+    pub(crate) max_instances: Option<usize>,
 }
 
-static KVS_POOL: LazyLock<Mutex<[Option<KvsInner>; KVS_MAX_INSTANCES]>> =
-    LazyLock::new(|| Mutex::new([const { None }; KVS_MAX_INSTANCES]));
+impl KvsPool {
+    /// Create empty pool without instance ID limit.
+    pub(crate) fn new() -> Self {
+        Self {
+            instances: HashMap::new(),
+            max_instances: None,
+        }
+    }
+
+    /// Check instance ID is within configured limit.
+    ///
+    /// # Return Values
+    ///   * Ok: Instance ID is valid
+    ///   * `ErrorCode::InvalidInstanceId`: Instance ID out of range
+    pub(crate) fn check_instance_id(&self, instance_id: InstanceId) -> Result<(), ErrorCode> {
+        if self.max_instances.is_some_and(|max| instance_id.0 >= max) {
+            eprintln!("error: instance ID out of range: {instance_id}");
+            return Err(ErrorCode::InvalidInstanceId);
+        }
+        Ok(())
+    }
+
+    /// Remove instance from pool.
+    /// Instance is removed only if it's backed by provided data, rebuilt instances are kept.
+    ///
+    /// # Parameters
+    ///   * `instance_id`: Instance ID
+    ///   * `data`: Data of the removed instance
+    ///
+    /// # Return Values
+    ///   * `Some`: Removed instance
+    ///   * `None`: Instance not found in pool
+    pub(crate) fn remove(&mut self, instance_id: InstanceId, data: &Arc<RwLock<KvsData>>) -> Option<KvsInner> {
+        match self.instances.get(&instance_id) {
+            Some(kvs_inner) if Arc::ptr_eq(&kvs_inner.data, data) => self.instances.remove(&instance_id),
+            _ => None,
+        }
+    }
+}
 
-impl From<PoisonError<MutexGuard<'_, [Option<KvsInner>; KVS_MAX_INSTANCES]>>> for ErrorCode {
-    fn from(_cause: PoisonError<MutexGuard<'_, [Option<KvsInner>; KVS_MAX_INSTANCES]>>) -> Self {
+pub(crate) static KVS_POOL: LazyLock<Mutex<KvsPool>> = LazyLock::new(|| Mutex::new(KvsPool::new()));
+
+impl From<PoisonError<MutexGuard<'_, KvsPool>>> for ErrorCode {
+    fn from(_cause: PoisonError<MutexGuard<'_, KvsPool>>) -> Self {
         ErrorCode::MutexLockFailed
     }
 }
 
+/// Migration transforming loaded data in place, see [`KvsBuilder::migration`].
+pub type KvsMigration = fn(&mut KvsMap) -> Result<(), ErrorCode>;
+
 /// Key-value-storage builder.
 pub struct KvsBuilder {
     /// Instance ID.
@@ -68,6 +303,42 @@ pub struct KvsBuilder {
 
     /// Backend.
     backend: Option<Box<dyn KvsBackend>>,
+
+    /// Programmatic default values.
+    default_values: KvsMap,
+
+    /// Precedence between defaults loaded by backend and programmatic defaults.
+    defaults_precedence: DefaultsPrecedence,
+
+    /// Expected value types of keys.
+    expected_types: HashMap<String, KvsValueType>,
+
+    /// Key validator.
+    key_validator: Option<fn(&str) -> bool>,
+
+    /// Skip flush if storage data is unchanged.
+    skip_clean_flush: bool,
+
+    /// Clock used for value expiry.
+    clock: Option<Box<dyn Clock>>,
+
+    /// Keys are normalized to lowercase.
+    case_insensitive_keys: bool,
+
+    /// Keys whose default value always takes precedence.
+    locked_keys: HashSet<String>,
+
+    /// Receiver of audit events.
+    audit_sink: Option<Box<dyn AuditSink>>,
+
+    /// Migrations applied to loaded data, in registration order.
+    migrations: Vec<KvsMigration>,
+
+    /// Defaults are loaded on first access instead of on build.
+    lazy_defaults: bool,
+
+    /// Interval of periodic flush of changed data.
+    autoflush_interval: Option<Duration>,
 }
 
 impl KvsBuilder {
@@ -87,15 +358,66 @@ impl KvsBuilder {
             defaults: None,
             kvs_load: None,
             backend: None,
+            default_values: KvsMap::new(),
+            defaults_precedence: DefaultsPrecedence::File,
+            expected_types: HashMap::new(),
+            key_validator: None,
+            skip_clean_flush: false,
+            clock: None,
+            case_insensitive_keys: false,
+            locked_keys: HashSet::new(),
+            audit_sink: None,
+            migrations: Vec::new(),
+            lazy_defaults: false,
+            autoflush_interval: None,
         }
     }
 
     /// Return maximum number of allowed KVS instances.
     ///
     /// # Return Values
-    ///   * Max number of KVS instances
-    pub fn max_instances() -> usize {
-        KVS_MAX_INSTANCES
+    ///   * Some: Max number of KVS instances, instance IDs must be lower
+    ///   * None: Number of KVS instances is not limited
+    pub fn max_instances() -> Option<usize> {
+        KVS_POOL.lock().unwrap_or_else(PoisonError::into_inner).max_instances
+    }
+
+    /// Set maximum number of allowed KVS instances.
+    ///
+    /// Limit is shared by all builders and checked when a new instance is initialized.
+    /// Already initialized instances are not affected.
+    ///
+    /// # Parameters
+    ///   * `max_instances`: Max number of KVS instances, `None` to remove the limit
+    pub fn set_max_instances(max_instances: Option<usize>) {
+        KVS_POOL.lock().unwrap_or_else(PoisonError::into_inner).max_instances = max_instances;
+    }
+
+    /// Flush all initialized KVS instances, e.g. on shutdown.
+    ///
+    /// Instances are flushed one after another in order of instance IDs, without holding the pool
+    /// lock. Failure of an instance doesn't prevent flushing the remaining ones.
+    ///
+    /// # Return Values
+    ///   * Flush result of each instance, errors same as for `Kvs::flush`
+    pub fn flush_all() -> Vec<(InstanceId, Result<(), ErrorCode>)> {
+        let mut instances: Vec<(InstanceId, Kvs)> = {
+            let kvs_pool = KVS_POOL.lock().unwrap_or_else(PoisonError::into_inner);
+            kvs_pool
+                .instances
+                .iter()
+                .map(|(instance_id, kvs_inner)| {
+                    let kvs = Kvs::new(kvs_inner.data.clone(), kvs_inner.parameters.clone());
+                    (*instance_id, kvs)
+                })
+                .collect()
+        };
+        instances.sort_by_key(|(instance_id, _)| *instance_id);
+
+        instances
+            .into_iter()
+            .map(|(instance_id, kvs)| (instance_id, kvs.flush()))
+            .collect()
     }
 
     /// Configure defaults handling mode.
@@ -135,6 +457,317 @@ impl KvsBuilder {
         self
     }
 
+    /// Add programmatic default values.
+    /// Values are merged with defaults loaded by backend according to defaults precedence.
+    /// Applied only when instance is initialized, ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `default_values`: Default values
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn default_values(mut self, default_values: KvsMap) -> Self {
+        self.default_values.extend(default_values);
+        self
+    }
+
+    /// Add single programmatic default value.
+    /// See [`KvsBuilder::default_values`] for details.
+    ///
+    /// # Parameters
+    ///   * `key`: Key
+    ///   * `value`: Default value
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn default_value<S: Into<String>, V: Into<KvsValue>>(mut self, key: S, value: V) -> Self {
+        self.default_values.insert(key.into(), value.into());
+        self
+    }
+
+    /// Configure precedence between defaults loaded by backend and programmatic defaults.
+    ///
+    /// # Parameters
+    ///   * `precedence`: defaults precedence (default: [`DefaultsPrecedence::File`](DefaultsPrecedence::File))
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn defaults_precedence(mut self, precedence: DefaultsPrecedence) -> Self {
+        self.defaults_precedence = precedence;
+        self
+    }
+
+    /// Expect value of a key to be of given type.
+    /// Both default and stored values are checked when instance is initialized, missing values
+    /// are accepted. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `key`: Key
+    ///   * `value_type`: Expected value type
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn expect_type<S: Into<String>>(mut self, key: S, value_type: KvsValueType) -> Self {
+        self.expected_types.insert(key.into(), value_type);
+        self
+    }
+
+    /// Set key validator.
+    /// Keys rejected by validator are reported as `ErrorCode::InvalidKey` on `set_value`,
+    /// `set_value_with_ttl`, `compare_and_set`, `merge` and `import`. All keys are accepted if not
+    /// set. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `validator`: Returns `true` for valid keys
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn key_validator(mut self, validator: fn(&str) -> bool) -> Self {
+        self.key_validator = Some(validator);
+        self
+    }
+
+    /// Set whether `flush` is skipped if storage data is unchanged since last flush.
+    /// If not set, `flush` always writes and rotates snapshots. Ignored for already existing
+    /// instance.
+    ///
+    /// # Parameters
+    ///   * `skip_clean_flush`: Skip flush of unchanged data
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn skip_clean_flush(mut self, skip_clean_flush: bool) -> Self {
+        self.skip_clean_flush = skip_clean_flush;
+        self
+    }
+
+    /// Set whether defaults are loaded on first access instead of on `build`.
+    /// Speeds up `build` with large defaults file. Defaults are loaded by the first operation
+    /// reading defaults, including operations notifying watchers, and kept afterwards. Errors of
+    /// loading, migrating and type-checking defaults, e.g. malformed or missing required defaults
+    /// file, are returned by that operation instead of `build`, loading is retried by the next
+    /// one. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `lazy_defaults`: Defer loading of defaults
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn lazy_defaults(mut self, lazy_defaults: bool) -> Self {
+        self.lazy_defaults = lazy_defaults;
+        self
+    }
+
+    /// Set interval of periodic flush.
+    /// Background thread checks every interval whether data changed since last flush and flushes
+    /// it, so changes are persisted without explicit `flush`. Flush errors are reported and
+    /// retried on next interval. Thread runs while any handle of the instance exists, also after
+    /// `Kvs::close`, and is restarted when an instance left in the pool is built again. Ignored
+    /// for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `interval`: Time between checks
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn autoflush_interval(mut self, interval: Duration) -> Self {
+        self.autoflush_interval = Some(interval);
+        self
+    }
+
+    /// Set whether keys are case-insensitive.
+    /// If set, keys passed to all key operations are normalized to lowercase, so e.g. `Timeout`
+    /// and `timeout` refer to the same value. Loaded and default keys are normalized on `build`,
+    /// which fails with `ErrorCode::InvalidKey` if keys differ only in case. Keys are persisted
+    /// normalized. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `case_insensitive_keys`: Normalize keys to lowercase
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn case_insensitive_keys(mut self, case_insensitive_keys: bool) -> Self {
+        self.case_insensitive_keys = case_insensitive_keys;
+        self
+    }
+
+    /// Lock a key, can be called repeatedly to lock multiple keys.
+    /// Default value of a locked key takes precedence over a stored value on read, and setting a
+    /// locked key fails with `ErrorCode::KeyLocked`. Locked keys are instance configuration, they
+    /// are not persisted. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `key`: Key to lock
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn locked_key<S: Into<String>>(mut self, key: S) -> Self {
+        let _ = self.locked_keys.insert(key.into());
+        self
+    }
+
+    /// Set receiver of audit events, e.g. [`FileAuditSink`](crate::audit::FileAuditSink).
+    /// Event is recorded after each successful change of stored values and each flush, see
+    /// [`AuditOperation`](crate::audit::AuditOperation). Changes of defaults and expiry of values
+    /// are not recorded. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `audit_sink`: Audit sink
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn audit_sink(mut self, audit_sink: Box<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(audit_sink);
+        self
+    }
+
+    /// Set clock used to set and check expiry of values, e.g. [`MockClock`](crate::clock::MockClock)
+    /// in tests. [`SystemClock`] is used if not set. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `clock`: Clock
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Add migration applied to loaded data, e.g. to rename keys or convert values stored by
+    /// older application versions.
+    /// Migration is applied during `build` to loaded KVS and, separately, to defaults loaded by
+    /// backend, before values are checked against expected types. Programmatic defaults are not
+    /// migrated. Migrations run in order they were added. Ignored for already existing instance.
+    ///
+    /// # Parameters
+    ///   * `migration`: Transforms loaded data in place, error fails the `build`
+    ///
+    /// # Return Values
+    ///   * KvsBuilder instance
+    pub fn migration(mut self, migration: KvsMigration) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    /// Apply migrations to loaded data.
+    ///
+    /// # Parameters
+    ///   * `migrations`: Applied migrations
+    ///   * `kvs_map`: Migrated values
+    ///   * `origin`: Origin of migrated values, used for reporting
+    ///
+    /// # Return Values
+    ///   * Ok: All migrations succeeded
+    ///   * Errors returned by migrations
+    fn migrate(migrations: &[KvsMigration], kvs_map: &mut KvsMap, origin: &str) -> Result<(), ErrorCode> {
+        for migration in migrations {
+            migration(kvs_map).map_err(|e| {
+                eprintln!("error: migration of {origin} values failed: {e:?}");
+                e
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Load defaults according to handling mode.
+    ///
+    /// # Parameters
+    ///   * `backend`: Backend to load defaults from
+    ///   * `instance_id`: Instance ID
+    ///   * `defaults`: Defaults handling mode
+    ///
+    /// # Return Values
+    ///   * Ok: Loaded defaults, empty if ignored or optional and not found
+    ///   * Errors returned by backend
+    fn load_defaults(
+        backend: &dyn KvsBackend,
+        instance_id: InstanceId,
+        defaults: KvsDefaults,
+    ) -> Result<KvsMap, ErrorCode> {
+        match defaults {
+            KvsDefaults::Ignored => Ok(KvsMap::new()),
+            KvsDefaults::Optional => match backend.load_defaults(instance_id) {
+                Ok(map) => Ok(map),
+                Err(ErrorCode::FileNotFound) => Ok(KvsMap::new()),
+                Err(e) => Err(e),
+            },
+            KvsDefaults::Required => backend.load_defaults(instance_id),
+        }
+    }
+
+    /// Check values match expected types.
+    ///
+    /// # Parameters
+    ///   * `expected_types`: Expected value types of keys
+    ///   * `kvs_map`: Checked values
+    ///   * `origin`: Origin of checked values, used for reporting
+    ///
+    /// # Return Values
+    ///   * Ok: All values match expected types
+    ///   * `ErrorCode::SchemaMismatch`: Value of unexpected type found
+    fn check_types(
+        expected_types: &HashMap<String, KvsValueType>,
+        kvs_map: &KvsMap,
+        origin: &str,
+    ) -> Result<(), ErrorCode> {
+        for (key, expected) in expected_types {
+            if let Some(value) = kvs_map.get(key) {
+                let actual = value.value_type();
+                if actual != *expected {
+                    eprintln!(
+                        "error: {origin} value type mismatch for key {key}: expected {expected:?}, found {actual:?}"
+                    );
+                    return Err(ErrorCode::SchemaMismatch);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load newest snapshot passing integrity check.
+    ///
+    /// Snapshots are tried starting from current KVS (`SnapshotId(0)`), falling back to older ones
+    /// on validation and parser errors and on missing hash file. Empty map is returned if no KVS
+    /// is stored.
+    ///
+    /// # Return Values
+    ///   * Ok: Map and expiry loaded from the newest valid snapshot
+    ///   * Error of the current KVS if no snapshot is valid
+    ///   * Other errors returned by backend
+    fn load_latest_valid(
+        backend: &dyn KvsBackend,
+        instance_id: InstanceId,
+    ) -> Result<(KvsMap, KvsExpiryMap), ErrorCode> {
+        let mut first_error = None;
+        for idx in 0..backend.snapshot_max_count().max(1) {
+            let snapshot_id = SnapshotId(idx);
+            match backend.load_kvs_with_expiry(instance_id, snapshot_id) {
+                Ok(content) => {
+                    if idx > 0 {
+                        eprintln!("warn: current KVS invalid, loaded snapshot {snapshot_id}");
+                    }
+                    return Ok(content);
+                },
+                Err(ErrorCode::FileNotFound) if idx == 0 => return Ok((KvsMap::new(), KvsExpiryMap::new())),
+                Err(ErrorCode::FileNotFound) => break,
+                Err(
+                    e @ (ErrorCode::ValidationFailed
+                    | ErrorCode::JsonParserError
+                    | ErrorCode::InvalidStorageFormat
+                    | ErrorCode::HashFileNotFound),
+                ) => {
+                    eprintln!("warn: snapshot {snapshot_id} invalid: {e:?}");
+                    first_error.get_or_insert(e);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+
+        eprintln!("error: no valid snapshot found");
+        Err(first_error.unwrap_or(ErrorCode::FileNotFound))
+    }
+
     /// Compare existing parameters with expected configuration.
     fn compare_parameters(&self, other: &KvsParameters) -> bool {
         // Compare instance ID.
@@ -153,7 +786,11 @@ impl KvsBuilder {
             false
         }
         // Compare backend.
-        else if self.backend.as_ref().is_some_and(|v| !v.dyn_eq(other.backend.as_any())) {
+        else if self
+            .backend
+            .as_ref()
+            .is_some_and(|v| !v.dyn_eq(other.backend().as_any()))
+        {
             eprintln!("error: backend parameters mismatched");
             false
         }
@@ -176,109 +813,183 @@ impl KvsBuilder {
     ///   * Ok: KVS instance
     ///   * `ErrorCode::ValidationFailed`: KVS hash validation failed
     ///   * `ErrorCode::JsonParserError`: JSON parser error
+    ///   * `ErrorCode::InvalidStorageFormat`: KVS file is valid JSON, but not an object
     ///   * `ErrorCode::KvsFileReadError`: KVS file read error
     ///   * `ErrorCode::KvsHashFileReadError`: KVS hash file read error
+    ///   * `ErrorCode::HashFileNotFound`: KVS file exists, but its required hash file is missing
+    ///   * `ErrorCode::InvalidInstanceId`: Instance ID exceeds [`KvsBuilder::max_instances`]
+    ///   * `ErrorCode::SchemaMismatch`: Value type doesn't match [`KvsBuilder::expect_type`]
+    ///   * `ErrorCode::InvalidKey`: Keys differ only in case with [`KvsBuilder::case_insensitive_keys`]
     ///   * `ErrorCode::UnmappedError`: Generic error
+    ///   * Errors returned by migrations added with [`KvsBuilder::migration`]
+    ///
+    /// Errors related to defaults are deferred with [`KvsBuilder::lazy_defaults`].
     pub fn build(self) -> Result<Kvs, ErrorCode> {
         let instance_id = self.instance_id;
-        let instance_id_index: usize = instance_id.into();
 
         // Check if instance already exists.
         {
-            let kvs_pool = KVS_POOL.lock()?;
-            let kvs_inner_option = match kvs_pool.get(instance_id_index) {
-                Some(kvs_pool_entry) => match kvs_pool_entry {
-                    // If instance exists then parameters must match.
-                    Some(kvs_inner) => {
-                        if self.compare_parameters(&kvs_inner.parameters) {
-                            Ok(Some(kvs_inner))
-                        } else {
-                            Err(ErrorCode::InstanceParametersMismatch)
-                        }
-                    },
-                    // Instance not found - not an error, will initialize later.
-                    None => Ok(None),
+            let mut kvs_pool = KVS_POOL.lock()?;
+            match kvs_pool.instances.get_mut(&instance_id) {
+                // If instance exists then parameters must match.
+                Some(kvs_inner) => {
+                    if self.compare_parameters(&kvs_inner.parameters) {
+                        return kvs_inner.handle();
+                    }
+                    return Err(ErrorCode::InstanceParametersMismatch);
                 },
-                // Instance ID out of range.
-                None => Err(ErrorCode::InvalidInstanceId),
-            }?;
-
-            // Return existing instance if initialized.
-            if let Some(kvs_inner) = kvs_inner_option {
-                return Ok(Kvs::new(kvs_inner.data.clone(), kvs_inner.parameters.clone()));
+                // Instance not found - not an error, will initialize later.
+                None => kvs_pool.check_instance_id(instance_id)?,
             }
         }
 
         // Initialize KVS instance with provided parameters.
-        let parameters = KvsParameters {
+        let mut parameters = KvsParameters::new(
             instance_id,
-            defaults: self.defaults.unwrap_or(KvsDefaults::Optional),
-            kvs_load: self.kvs_load.unwrap_or(KvsLoad::Optional),
-            backend: self.backend.unwrap_or(Box::new(JsonBackendBuilder::new().build())),
-        };
-
-        // Load defaults.
-        let defaults_map = match parameters.defaults {
-            KvsDefaults::Ignored => KvsMap::new(),
-            KvsDefaults::Optional => match parameters.backend.load_defaults(instance_id) {
-                Ok(map) => map,
-                Err(e) => match e {
-                    ErrorCode::FileNotFound => KvsMap::new(),
-                    _ => return Err(e),
-                },
-            },
-            KvsDefaults::Required => parameters.backend.load_defaults(instance_id)?,
+            self.defaults.unwrap_or(KvsDefaults::Optional),
+            self.kvs_load.unwrap_or(KvsLoad::Optional),
+            self.backend.unwrap_or(Box::new(JsonBackendBuilder::new().build())),
+        );
+        parameters.key_validator = self.key_validator;
+        parameters.skip_clean_flush = self.skip_clean_flush;
+        parameters.case_insensitive_keys = self.case_insensitive_keys;
+        parameters.locked_keys = self
+            .locked_keys
+            .iter()
+            .map(|key| parameters.normalize_key(key).into_owned())
+            .collect();
+        parameters.audit_sink = self.audit_sink.map(Arc::from);
+        let parameters = Arc::new(parameters);
+        let backend = parameters.backend();
+
+        // Load defaults, unless deferred.
+        let lazy = self.lazy_defaults && parameters.defaults != KvsDefaults::Ignored;
+        let mut defaults_map = if lazy {
+            KvsMap::new()
+        } else {
+            Self::load_defaults(backend.as_ref(), instance_id, parameters.defaults)?
         };
 
         // Load KVS and hash files.
         let snapshot_id = SnapshotId(0);
-        let kvs_map = match parameters.kvs_load {
-            KvsLoad::Ignored => KvsMap::new(),
-            KvsLoad::Optional => match parameters.backend.load_kvs(instance_id, snapshot_id) {
-                Ok(map) => map,
+        let (mut kvs_map, mut expiry_map) = match parameters.kvs_load {
+            KvsLoad::Ignored => (KvsMap::new(), KvsExpiryMap::new()),
+            KvsLoad::Optional => match backend.load_kvs_with_expiry(instance_id, snapshot_id) {
+                Ok(content) => content,
                 Err(e) => match e {
-                    ErrorCode::FileNotFound => KvsMap::new(),
+                    ErrorCode::FileNotFound => (KvsMap::new(), KvsExpiryMap::new()),
                     _ => return Err(e),
                 },
             },
-            KvsLoad::Required => parameters.backend.load_kvs(instance_id, snapshot_id)?,
+            KvsLoad::Required => backend.load_kvs_with_expiry(instance_id, snapshot_id)?,
+            KvsLoad::LatestValid => Self::load_latest_valid(backend.as_ref(), instance_id)?,
         };
 
-        // Shared object containing data.
-        let data = Arc::new(Mutex::new(KvsData { kvs_map, defaults_map }));
-
-        // Shared object containing parameters.
-        let parameters = Arc::new(parameters);
+        // Migrate loaded data, migrated KVS must be flushed to be persisted.
+        let mut dirty = false;
+        if !self.migrations.is_empty() {
+            if !lazy {
+                Self::migrate(&self.migrations, &mut defaults_map, "default")?;
+            }
+            let loaded = kvs_map.clone();
+            Self::migrate(&self.migrations, &mut kvs_map, "stored")?;
+            expiry_map.retain(|key, _| kvs_map.contains_key(key));
+            dirty = kvs_map != loaded;
+        }
 
-        // Initialize entry in pool and return new KVS instance.
-        {
-            let mut kvs_pool = KVS_POOL.lock()?;
-            let kvs_pool_entry = match kvs_pool.get_mut(instance_id_index) {
-                Some(entry) => entry,
-                None => return Err(ErrorCode::InvalidInstanceId),
+        // Normalize keys, normalized KVS must be flushed to be persisted.
+        dirty |= kvs_map.keys().any(|key| parameters.normalize_key(key) != key.as_str());
+        let kvs_map = parameters.normalize_keys(kvs_map)?;
+        let expiry_map = parameters.normalize_keys(expiry_map)?;
+        let defaults_map = parameters.normalize_keys(defaults_map)?;
+        let default_values = parameters.normalize_keys(self.default_values)?;
+        let expected_types = parameters.normalize_keys(self.expected_types)?;
+
+        // Merge programmatic defaults with loaded defaults.
+        let mut data = KvsData::new(kvs_map, defaults_map);
+        data.expiry_map = expiry_map;
+        data.dirty = dirty;
+        if let Some(clock) = self.clock {
+            data.clock = Arc::from(clock);
+        }
+        let overwrite = self.defaults_precedence == DefaultsPrecedence::Programmatic;
+        data.apply_defaults(default_values, DefaultsSource::Programmatic, overwrite);
+
+        // Check values match expected types.
+        Self::check_types(&expected_types, &data.defaults_map, "default")?;
+        Self::check_types(&expected_types, &data.kvs_map, "stored")?;
+
+        // Deferred defaults are applied on top of programmatic ones, so precedence is inverted.
+        if lazy {
+            let parameters = parameters.clone();
+            let migrations = self.migrations;
+            let load = move || {
+                let backend = parameters.backend();
+                let mut defaults_map = Self::load_defaults(backend.as_ref(), instance_id, parameters.defaults)?;
+                Self::migrate(&migrations, &mut defaults_map, "default")?;
+                let defaults_map = parameters.normalize_keys(defaults_map)?;
+                Self::check_types(&expected_types, &defaults_map, "default")?;
+                Ok(defaults_map)
             };
-
-            let _ = kvs_pool_entry.insert(KvsInner {
-                parameters: parameters.clone(),
-                data: data.clone(),
-            });
+            data.lazy_defaults = Some(Arc::new(LazyDefaults {
+                load: Box::new(load),
+                overwrite: !overwrite,
+            }));
         }
 
-        Ok(Kvs::new(data, parameters))
+        // Shared object containing data.
+        let data = Arc::new(RwLock::new(data));
+
+        // Initialize entry in pool and return new KVS instance.
+        let mut kvs_pool = KVS_POOL.lock()?;
+        kvs_pool.check_instance_id(instance_id)?;
+        let mut kvs_inner = KvsInner {
+            parameters,
+            data,
+            autoflush_interval: self.autoflush_interval,
+            autoflush: Weak::new(),
+        };
+        let kvs = kvs_inner.handle()?;
+        let _ = kvs_pool.instances.insert(instance_id, kvs_inner);
+        Ok(kvs)
     }
 }
 
+/// Open KVS instance persisted with JSON backend in a directory
+///
+/// Shorthand for [`KvsBuilder`] with default settings and [`JsonBackendBuilder`] using provided
+/// working directory. Use the builders directly for other settings.
+///
+/// # Parameters
+///   * `instance_id`: Instance ID
+///   * `working_dir`: Directory data is persisted to
+///
+/// # Return Values
+///   * Ok: KVS instance
+///   * Errors returned by [`KvsBuilder::build`]
+pub fn open(instance_id: InstanceId, working_dir: impl Into<PathBuf>) -> Result<Kvs, ErrorCode> {
+    let backend = JsonBackendBuilder::new().working_dir(working_dir.into()).build();
+    KvsBuilder::new(instance_id).backend(Box::new(backend)).build()
+}
+
 #[cfg(test)]
 mod kvs_builder_tests {
     // Tests reuse JSON backend to ensure valid load/save behavior.
+    use crate::audit::{AuditEvent, AuditOperation, AuditSink};
+    use crate::clock::MockClock;
     use crate::error_code::ErrorCode;
     use crate::json_backend::{JsonBackend, JsonBackendBuilder};
-    use crate::kvs_api::{InstanceId, KvsDefaults, KvsLoad, SnapshotId};
-    use crate::kvs_builder::{KvsBuilder, KVS_MAX_INSTANCES, KVS_POOL};
-    use crate::kvs_value::{KvsMap, KvsValue};
+    use crate::kvs_api::{
+        DefaultsPrecedence, DefaultsSource, InstanceId, KvsApi, KvsDefaults, KvsLoad, MergePolicy, SnapshotId,
+    };
+    use crate::kvs_backend::{FlushProgress, KvsBackend};
+    use crate::kvs_builder::{open, KvsBuilder, KvsPool, KVS_POOL};
+    use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsValue, KvsValueType};
     use core::ops::DerefMut;
+    use core::time::Duration;
     use std::path::{Path, PathBuf};
-    use std::sync::{LazyLock, Mutex, MutexGuard};
+    use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+    use std::time::{Instant, UNIX_EPOCH};
     use tempfile::tempdir;
 
     /// Serial test execution mutex.
@@ -292,7 +1003,7 @@ mod kvs_builder_tests {
         // Reset `KVS_POOL` state to uninitialized.
         // This is to mitigate `InstanceParametersMismatch` errors between tests.
         let mut pool = KVS_POOL.lock().unwrap();
-        *pool.deref_mut() = [const { None }; KVS_MAX_INSTANCES];
+        *pool.deref_mut() = KvsPool::new();
 
         serial_lock
     }
@@ -308,7 +1019,73 @@ mod kvs_builder_tests {
 
     #[test]
     fn test_max_instances() {
-        assert_eq!(KvsBuilder::max_instances(), KVS_MAX_INSTANCES);
+        let _lock = lock_and_reset();
+
+        assert_eq!(KvsBuilder::max_instances(), None);
+        KvsBuilder::set_max_instances(Some(10));
+        assert_eq!(KvsBuilder::max_instances(), Some(10));
+        KvsBuilder::set_max_instances(None);
+        assert_eq!(KvsBuilder::max_instances(), None);
+    }
+
+    #[test]
+    fn test_flush_all() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        for id in [1, 0] {
+            let kvs = KvsBuilder::new(InstanceId(id))
+                .backend(Box::new(backend.clone()))
+                .build()
+                .unwrap();
+            kvs.set_value("id", id as u32).unwrap();
+        }
+
+        let results = KvsBuilder::flush_all();
+        assert_eq!(results, vec![(InstanceId(0), Ok(())), (InstanceId(1), Ok(()))]);
+        for id in [0, 1] {
+            assert_eq!(
+                backend.load_kvs(InstanceId(id), SnapshotId(0)).unwrap(),
+                KvsMap::from([("id".to_string(), KvsValue::from(id as u32))])
+            );
+        }
+    }
+
+    #[test]
+    fn test_flush_all_failed_instance() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let kvs0 = KvsBuilder::new(InstanceId(0))
+            .backend(Box::new(backend.clone()))
+            .build()
+            .unwrap();
+        kvs0.mark_corrupt("test").unwrap();
+        let kvs1 = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend.clone()))
+            .build()
+            .unwrap();
+        kvs1.set_value("key", "value").unwrap();
+
+        // Failure of first instance doesn't prevent flushing the second one.
+        let results = KvsBuilder::flush_all();
+        assert_eq!(
+            results,
+            vec![
+                (InstanceId(0), Err(ErrorCode::IntegrityCorrupted)),
+                (InstanceId(1), Ok(()))
+            ]
+        );
+        assert!(!kvs1.is_dirty().unwrap());
+        assert!(backend.load_kvs(InstanceId(0), SnapshotId(0)).is_err());
+    }
+
+    #[test]
+    fn test_flush_all_empty_pool() {
+        let _lock = lock_and_reset();
+        assert!(KvsBuilder::flush_all().is_empty());
     }
 
     #[test]
@@ -323,7 +1100,7 @@ mod kvs_builder_tests {
         // Check default values.
         assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
-        assert!(kvs.parameters().backend.dyn_eq(&JsonBackendBuilder::new().build()));
+        assert!(kvs.parameters().backend().dyn_eq(&JsonBackendBuilder::new().build()));
     }
 
     #[test]
@@ -337,7 +1114,7 @@ mod kvs_builder_tests {
         assert_eq!(kvs.parameters().instance_id, instance_id);
         assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
-        assert!(kvs.parameters().backend.dyn_eq(&JsonBackendBuilder::new().build()));
+        assert!(kvs.parameters().backend().dyn_eq(&JsonBackendBuilder::new().build()));
     }
 
     #[test]
@@ -351,7 +1128,7 @@ mod kvs_builder_tests {
         assert_eq!(kvs.parameters().instance_id, instance_id);
         assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
-        assert!(kvs.parameters().backend.dyn_eq(&JsonBackendBuilder::new().build()));
+        assert!(kvs.parameters().backend().dyn_eq(&JsonBackendBuilder::new().build()));
     }
 
     #[test]
@@ -372,7 +1149,7 @@ mod kvs_builder_tests {
         assert_eq!(kvs.parameters().instance_id, instance_id);
         assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
-        assert!(kvs.parameters().backend.dyn_eq(
+        assert!(kvs.parameters().backend().dyn_eq(
             &JsonBackendBuilder::new()
                 .working_dir(dir_path)
                 .snapshot_max_count(1234)
@@ -401,7 +1178,7 @@ mod kvs_builder_tests {
         assert_eq!(kvs.parameters().instance_id, instance_id);
         assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
-        assert!(kvs.parameters().backend.dyn_eq(
+        assert!(kvs.parameters().backend().dyn_eq(
             &JsonBackendBuilder::new()
                 .working_dir(dir_path)
                 .snapshot_max_count(1234)
@@ -440,243 +1217,1513 @@ mod kvs_builder_tests {
             .backend(Box::new(backend));
         let kvs = builder2.build().unwrap();
 
-        assert_eq!(kvs.parameters().instance_id, instance_id);
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
-        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
-        assert!(kvs
-            .parameters()
-            .backend
-            .dyn_eq(&JsonBackendBuilder::new().working_dir(dir_path).build()));
+        assert_eq!(kvs.parameters().instance_id, instance_id);
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
+        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
+        assert!(kvs
+            .parameters()
+            .backend()
+            .dyn_eq(&JsonBackendBuilder::new().working_dir(dir_path).build()));
+    }
+
+    #[test]
+    fn test_build_interrupted_rotation() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let instance_id = InstanceId(1);
+        let kvs_map = KvsMap::from([("key".to_string(), KvsValue::from("value"))]);
+        backend.flush(instance_id, &kvs_map).unwrap();
+
+        // Hash file renamed, KVS file not.
+        std::fs::rename(
+            backend.hash_file_path(instance_id, SnapshotId(0)),
+            backend.hash_file_path(instance_id, SnapshotId(1)),
+        )
+        .unwrap();
+
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::Required)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        assert_eq!(kvs.get_value("key").unwrap(), KvsValue::from("value"));
+    }
+
+    #[test]
+    fn test_build_instance_exists_equivalent_working_dir() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().join("data");
+        std::fs::create_dir(&dir_path).unwrap();
+
+        // Create instance, then reuse it with same directory reached through different path.
+        let instance_id = InstanceId(1);
+        let builder1 = KvsBuilder::new(instance_id).backend(Box::new(
+            JsonBackendBuilder::new().working_dir(dir_path.clone()).build(),
+        ));
+        let kvs1 = builder1.build().unwrap();
+        kvs1.set_value("key", "value").unwrap();
+
+        let equivalent_path = dir_path.join("..").join("data");
+        let builder2 = KvsBuilder::new(instance_id)
+            .backend(Box::new(JsonBackendBuilder::new().working_dir(equivalent_path).build()));
+        let kvs2 = builder2.build().unwrap();
+
+        assert_eq!(kvs2.get_value("key").unwrap(), KvsValue::from("value"));
+    }
+
+    #[test]
+    fn test_build_instance_exists_different_params() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        // Create two instances with different parameters.
+        let instance_id = InstanceId(1);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let builder1 = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .kvs_load(KvsLoad::Optional)
+            .backend(Box::new(backend.clone()));
+        let _ = builder1.build().unwrap();
+
+        let builder2 = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Optional)
+            .kvs_load(KvsLoad::Ignored)
+            .backend(Box::new(backend.clone()));
+        let result = builder2.build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+    }
+
+    #[test]
+    fn test_build_instance_exists_params_not_set() {
+        let _lock = lock_and_reset();
+
+        // Create two instances, first with parameters, second without.
+        let instance_id = InstanceId(1);
+        let builder1 = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .kvs_load(KvsLoad::Ignored);
+        let _ = builder1.build().unwrap();
+
+        let builder2 = KvsBuilder::new(instance_id);
+        let kvs = builder2.build().unwrap();
+
+        // Assert params as expected.
+        assert_eq!(kvs.parameters().instance_id, instance_id);
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
+        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
+    }
+
+    #[test]
+    fn test_build_instance_exists_single_matching_param_set() {
+        let _lock = lock_and_reset();
+
+        // Create two instances, first with parameters, second only with `defaults`.
+        let instance_id = InstanceId(1);
+        let builder1 = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .kvs_load(KvsLoad::Ignored);
+        let _ = builder1.build().unwrap();
+
+        let builder2 = KvsBuilder::new(instance_id).defaults(KvsDefaults::Ignored);
+        let kvs = builder2.build().unwrap();
+
+        // Assert params as expected.
+        assert_eq!(kvs.parameters().instance_id, instance_id);
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
+        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
+    }
+
+    #[test]
+    fn test_build_instance_exists_single_mismatched_param_set() {
+        let _lock = lock_and_reset();
+
+        // Create two instances, first with parameters, second only with `defaults`.
+        let instance_id = InstanceId(1);
+        let builder1 = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .kvs_load(KvsLoad::Ignored);
+        let _ = builder1.build().unwrap();
+
+        let builder2 = KvsBuilder::new(instance_id).defaults(KvsDefaults::Optional);
+        let result = builder2.build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+    }
+
+    #[test]
+    fn test_build_instance_id_out_of_range() {
+        let _lock = lock_and_reset();
+        KvsBuilder::set_max_instances(Some(10));
+
+        let instance_id = InstanceId(123);
+        let result = KvsBuilder::new(instance_id).build();
+        assert!(result.is_err_and(|e| e == ErrorCode::InvalidInstanceId));
+    }
+
+    #[test]
+    fn test_build_instance_id_in_range() {
+        let _lock = lock_and_reset();
+        KvsBuilder::set_max_instances(Some(10));
+
+        let instance_id = InstanceId(9);
+        let result = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .kvs_load(KvsLoad::Ignored)
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_build_instance_id_large() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        for id in [10, 42, 1000, usize::MAX] {
+            let instance_id = InstanceId(id);
+            let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+            let kvs = KvsBuilder::new(instance_id).backend(Box::new(backend)).build().unwrap();
+            kvs.set_value("id", id as u64).unwrap();
+        }
+
+        // Existing instances are reused.
+        let kvs = KvsBuilder::new(InstanceId(42)).build().unwrap();
+        assert_eq!(kvs.get_value_as::<u64>("id").unwrap(), 42);
+        assert_eq!(KVS_POOL.lock().unwrap().instances.len(), 4);
+
+        // Parameters must still match.
+        let result = KvsBuilder::new(InstanceId(1000))
+            .defaults(KvsDefaults::Required)
+            .build();
+        assert!(result.is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+    }
+
+    #[test]
+    fn test_build_working_dir() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let kvs = KvsBuilder::new(instance_id).backend(Box::new(backend)).build().unwrap();
+
+        assert_eq!(kvs.working_dir(), Some(dir_path));
+    }
+
+    #[test]
+    fn test_close_rebuild_different_backend() {
+        let _lock = lock_and_reset();
+
+        let dir1 = tempdir().unwrap();
+        let dir2 = tempdir().unwrap();
+        let instance_id = InstanceId(0);
+
+        let backend1 = JsonBackendBuilder::new().working_dir(dir1.path().to_path_buf()).build();
+        let kvs1 = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend1.clone()))
+            .build()
+            .unwrap();
+        kvs1.set_value("key", "first").unwrap();
+        let kvs1_shared = KvsBuilder::new(instance_id).build().unwrap();
+        kvs1.close().unwrap();
+
+        let backend2 = JsonBackendBuilder::new().working_dir(dir2.path().to_path_buf()).build();
+        let kvs2 = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend2.clone()))
+            .build()
+            .unwrap();
+        assert!(kvs2.parameters().backend().dyn_eq(&backend2));
+        assert!(!kvs2.key_exists("key").unwrap());
+
+        // Remaining handle of closed instance is still usable, but no longer shared.
+        assert!(kvs1_shared.parameters().backend().dyn_eq(&backend1));
+        assert_eq!(kvs1_shared.get_value_as::<String>("key").unwrap(), "first");
+        kvs1_shared.set_value("key", "updated").unwrap();
+        kvs1_shared.flush().unwrap();
+        assert!(!kvs2.key_exists("key").unwrap());
+    }
+
+    #[test]
+    fn test_close_stale_handle() {
+        let _lock = lock_and_reset();
+
+        let instance_id = InstanceId(0);
+        let builder = || {
+            KvsBuilder::new(instance_id)
+                .defaults(KvsDefaults::Ignored)
+                .kvs_load(KvsLoad::Ignored)
+        };
+        let kvs1 = builder().build().unwrap();
+        let kvs1_shared = builder().build().unwrap();
+        kvs1.close().unwrap();
+        let kvs2 = builder().build().unwrap();
+        kvs2.set_value("key", "second").unwrap();
+
+        // Closing handle of already closed instance must not remove the rebuilt one.
+        kvs1_shared.close().unwrap();
+        assert!(KVS_POOL.lock().unwrap().instances.contains_key(&instance_id));
+        let kvs2_shared = builder().build().unwrap();
+        assert_eq!(kvs2_shared.get_value_as::<String>("key").unwrap(), "second");
+    }
+
+    #[test]
+    fn test_clone_into_ok() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+        let kvs0 = KvsBuilder::new(InstanceId(0))
+            .backend(Box::new(backend.clone()))
+            .default_value("mode", "auto")
+            .build()
+            .unwrap();
+        kvs0.set_value("tenant", "first").unwrap();
+
+        let kvs1 = kvs0.clone_into(InstanceId(1)).unwrap();
+        assert_eq!(kvs1.parameters().instance_id, InstanceId(1));
+        assert!(kvs1.parameters().backend().dyn_eq(&backend));
+        assert_eq!(kvs1.get_value_as::<String>("tenant").unwrap(), "first");
+        assert_eq!(kvs1.get_default_value("mode").unwrap(), KvsValue::from("auto"));
+        assert!(kvs1.is_dirty().unwrap());
+
+        // Instances are independent.
+        kvs1.set_value("tenant", "second").unwrap();
+        kvs0.remove_key("tenant").unwrap();
+        assert_eq!(kvs1.get_value_as::<String>("tenant").unwrap(), "second");
+        assert!(!kvs0.key_exists("tenant").unwrap());
+
+        // Target is persisted to its own files and shared with newly built handles.
+        kvs1.flush().unwrap();
+        kvs0.flush().unwrap();
+        let kvs1_shared = KvsBuilder::new(InstanceId(1)).build().unwrap();
+        assert_eq!(kvs1_shared.get_value_as::<String>("tenant").unwrap(), "second");
+        assert_eq!(
+            backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([("tenant".to_string(), KvsValue::from("second"))])
+        );
+        assert!(backend.load_kvs(InstanceId(0), SnapshotId(0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_clone_into_target_open() {
+        let _lock = lock_and_reset();
+
+        let kvs0 = KvsBuilder::new(InstanceId(0))
+            .kvs_load(KvsLoad::Ignored)
+            .build()
+            .unwrap();
+        let _kvs1 = KvsBuilder::new(InstanceId(1))
+            .kvs_load(KvsLoad::Ignored)
+            .build()
+            .unwrap();
+
+        assert!(kvs0
+            .clone_into(InstanceId(0))
+            .is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+        assert!(kvs0
+            .clone_into(InstanceId(1))
+            .is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+    }
+
+    #[test]
+    fn test_clone_into_invalid_instance_id() {
+        let _lock = lock_and_reset();
+
+        let kvs0 = KvsBuilder::new(InstanceId(0))
+            .kvs_load(KvsLoad::Ignored)
+            .build()
+            .unwrap();
+        KvsBuilder::set_max_instances(Some(2));
+
+        assert!(kvs0
+            .clone_into(InstanceId(2))
+            .is_err_and(|e| e == ErrorCode::InvalidInstanceId));
+        assert!(!KVS_POOL.lock().unwrap().instances.contains_key(&InstanceId(2)));
+    }
+
+    /// Generate and store file containing example default values.
+    fn create_defaults_file(working_dir: &Path, instance_id: InstanceId) -> Result<(), ErrorCode> {
+        let backend = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
+        let defaults_file_path = backend.defaults_file_path(instance_id);
+        let defaults_hash_file_path = backend.defaults_hash_file_path(instance_id);
+
+        let kvs_map = KvsMap::from([
+            ("number1".to_string(), KvsValue::F64(123.0)),
+            ("bool1".to_string(), KvsValue::Boolean(true)),
+            ("string1".to_string(), KvsValue::String("Hello".to_string())),
+        ]);
+        JsonBackend::save(&kvs_map, &defaults_file_path, &defaults_hash_file_path)?;
+
+        Ok(())
+    }
+
+    /// Generate and store files containing example KVS and hash data.
+    fn create_kvs_files(
+        working_dir: &Path,
+        instance_id: InstanceId,
+        snapshot_id: SnapshotId,
+    ) -> Result<(PathBuf, PathBuf), ErrorCode> {
+        let backend = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
+        let kvs_file_path = backend.kvs_file_path(instance_id, snapshot_id);
+        let hash_file_path = backend.hash_file_path(instance_id, snapshot_id);
+        let kvs_map = KvsMap::from([
+            ("number1".to_string(), KvsValue::F64(321.0)),
+            ("bool1".to_string(), KvsValue::Boolean(false)),
+            ("string1".to_string(), KvsValue::String("Hi".to_string())),
+        ]);
+        JsonBackend::save(&kvs_map, &kvs_file_path, &hash_file_path)?;
+
+        Ok((kvs_file_path, hash_file_path))
+    }
+
+    #[test]
+    fn test_build_defaults_ignored() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Ignored)
+            .backend(Box::new(backend));
+        let kvs = builder.build().unwrap();
+
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
+        let kvs_pool = KVS_POOL.lock().unwrap();
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().defaults_map, KvsMap::new());
+    }
+
+    #[test]
+    fn test_build_defaults_only() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let instance_id = InstanceId(2);
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        create_kvs_files(&dir_path, instance_id, SnapshotId(0)).unwrap();
+        let files = || {
+            let mut files: Vec<_> = std::fs::read_dir(&dir_path)
+                .unwrap()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            files.sort();
+            files
+        };
+        let files_before = files();
+
+        let backend = JsonBackendBuilder::new()
+            .working_dir(dir_path.clone())
+            .snapshot_max_count(0)
+            .build();
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::Ignored)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        // Defaults are served, stored snapshot is not loaded.
+        assert_eq!(kvs.get_value("number1").unwrap(), KvsValue::F64(123.0));
+        assert!(kvs.get_all_keys().unwrap().is_empty());
+
+        // Values are set in memory only.
+        kvs.set_value("number1", 1.0).unwrap();
+        assert_eq!(kvs.get_value("number1").unwrap(), KvsValue::F64(1.0));
+        kvs.flush().unwrap();
+        kvs.flush_with_id().unwrap();
+        kvs.flush_async().join().unwrap();
+        assert_eq!(kvs.snapshot_count(), 0);
+        assert_eq!(files(), files_before);
+    }
+
+    #[test]
+    fn test_build_defaults_optional_not_provided() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Optional)
+            .backend(Box::new(backend));
+        let kvs = builder.build().unwrap();
+
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
+        let kvs_pool = KVS_POOL.lock().unwrap();
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().defaults_map, KvsMap::new());
+    }
+
+    #[test]
+    fn test_build_defaults_optional_provided() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Optional)
+            .backend(Box::new(backend));
+        let kvs = builder.build().unwrap();
+
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
+        let kvs_pool = KVS_POOL.lock().unwrap();
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().defaults_map.len(), 3);
+    }
+
+    #[test]
+    fn test_build_defaults_required_not_provided() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend));
+        let result = builder.build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::FileNotFound));
+    }
+
+    #[test]
+    fn test_build_defaults_required_provided() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend));
+        let kvs = builder.build().unwrap();
+
+        assert_eq!(kvs.parameters().defaults, KvsDefaults::Required);
+        let kvs_pool = KVS_POOL.lock().unwrap();
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().defaults_map.len(), 3);
+    }
+
+    #[test]
+    fn test_build_defaults_required_saved() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let defaults_map = KvsMap::from([
+            ("number".to_string(), KvsValue::from(123.0)),
+            ("string".to_string(), KvsValue::from("Hello")),
+        ]);
+        backend.save_defaults(instance_id, &defaults_map).unwrap();
+
+        let kvs = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        assert_eq!(kvs.get_default_value("number").unwrap(), KvsValue::from(123.0));
+        assert_eq!(kvs.get_value_as::<String>("string").unwrap(), "Hello");
+    }
+
+    #[test]
+    fn test_build_defaults_keys() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend));
+        let kvs = builder.build().unwrap();
+        kvs.set_value("number1", 321.0).unwrap();
+        kvs.set_value("other", 1.0).unwrap();
+
+        let mut keys = kvs.default_keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["bool1", "number1", "string1"]);
+        assert!(kvs.contains_default("number1").unwrap());
+        assert!(kvs.contains_default("string1").unwrap());
+        assert!(!kvs.contains_default("other").unwrap());
+    }
+
+    #[test]
+    fn test_build_autoflush_interval() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(1);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend.clone()))
+            .autoflush_interval(Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        // Changed data is flushed without explicit `flush`.
+        kvs.set_value("key", 1).unwrap();
+        let expected = KvsMap::from([("key".to_string(), KvsValue::I32(1))]);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while backend.load_kvs(instance_id, SnapshotId(0)).ok().as_ref() != Some(&expected) {
+            assert!(Instant::now() < deadline, "data not flushed");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(!kvs.is_dirty().unwrap());
+
+        // Unchanged data is not flushed again.
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(kvs.snapshot_count(), 1);
+
+        // Thread is stopped when the last handle is dropped, instance remains in pool.
+        let autoflush = KVS_POOL.lock().unwrap().instances[&instance_id].autoflush.clone();
+        let read_only = kvs.read_only();
+        drop(kvs);
+        assert!(autoflush.upgrade().is_some());
+        drop(read_only);
+        assert!(autoflush.upgrade().is_none());
+
+        // Thread is restarted when instance is built again.
+        let kvs = KvsBuilder::new(instance_id).build().unwrap();
+        let wait_flushed = |value: i32| {
+            let expected = KvsMap::from([("key".to_string(), KvsValue::I32(value))]);
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while backend.load_kvs(instance_id, SnapshotId(0)).ok().as_ref() != Some(&expected) {
+                assert!(Instant::now() < deadline, "data not flushed");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+        kvs.set_value("key", 2).unwrap();
+        wait_flushed(2);
+
+        // Thread keeps running for remaining handles after close.
+        let autoflush = KVS_POOL.lock().unwrap().instances[&instance_id].autoflush.clone();
+        let stale = KvsBuilder::new(instance_id).build().unwrap();
+        kvs.close().unwrap();
+        assert!(!KVS_POOL.lock().unwrap().instances.contains_key(&instance_id));
+        stale.set_value("key", 3).unwrap();
+        wait_flushed(3);
+        drop(stale);
+        assert!(autoflush.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_build_lazy_defaults() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let kvs = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .lazy_defaults(true)
+            .build()
+            .unwrap();
+
+        let data = KVS_POOL
+            .lock()
+            .unwrap()
+            .instances
+            .get(&instance_id)
+            .unwrap()
+            .data
+            .clone();
+
+        // Defaults are not loaded on build and on access not reading defaults.
+        kvs.set_value("stored", 1).unwrap();
+        assert!(kvs.key_exists("stored").unwrap());
+        assert!(data.read().unwrap().lazy_defaults.is_some());
+        assert!(data.read().unwrap().defaults_map.is_empty());
+
+        // Defaults are loaded on first access and kept.
+        assert_eq!(kvs.get_value("number1").unwrap(), KvsValue::F64(123.0));
+        assert!(data.read().unwrap().lazy_defaults.is_none());
+        assert_eq!(kvs.default_keys().unwrap().len(), 3);
+        assert!(kvs.is_value_default("bool1").unwrap());
+    }
+
+    #[test]
+    fn test_build_lazy_defaults_precedence() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        create_defaults_file(&dir_path, InstanceId(2)).unwrap();
+        create_defaults_file(&dir_path, InstanceId(3)).unwrap();
+
+        for (instance_id, precedence, expected) in [
+            (InstanceId(2), DefaultsPrecedence::File, KvsValue::Boolean(true)),
+            (
+                InstanceId(3),
+                DefaultsPrecedence::Programmatic,
+                KvsValue::Boolean(false),
+            ),
+        ] {
+            let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+            let kvs = KvsBuilder::new(instance_id)
+                .backend(Box::new(backend))
+                .lazy_defaults(true)
+                .defaults_precedence(precedence)
+                .default_value("bool1", false)
+                .default_value("number2", 456)
+                .build()
+                .unwrap();
+
+            assert_eq!(kvs.get_default_value("bool1").unwrap(), expected);
+            assert_eq!(kvs.get_default_value("number1").unwrap(), KvsValue::F64(123.0));
+            assert_eq!(kvs.get_default_value("number2").unwrap(), KvsValue::I32(456));
+        }
+    }
+
+    #[test]
+    fn test_build_lazy_defaults_malformed() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let contents = "{malformed";
+        let hash = adler32::RollingAdler32::from_buffer(contents.as_bytes()).hash();
+        std::fs::write(backend.defaults_file_path(instance_id), contents).unwrap();
+        std::fs::write(backend.defaults_hash_file_path(instance_id), hash.to_be_bytes()).unwrap();
+        let kvs = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .lazy_defaults(true)
+            .build()
+            .unwrap();
+
+        // Error is returned on first access, stored values remain accessible.
+        assert!(kvs.get_value("number1").is_err_and(|e| e == ErrorCode::JsonParserError));
+        kvs.set_value("stored", 1).unwrap();
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["stored".to_string()]);
+
+        // Loading is retried on next access.
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        assert_eq!(kvs.get_value("number1").unwrap(), KvsValue::F64(123.0));
+    }
+
+    #[test]
+    fn test_build_lazy_defaults_required_not_provided() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = KvsBuilder::new(InstanceId(2))
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .lazy_defaults(true)
+            .build()
+            .unwrap();
+
+        assert!(kvs.default_keys().is_err_and(|e| e == ErrorCode::FileNotFound));
+        kvs.clear_defaults().unwrap();
+        assert!(kvs.default_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_build_default_values_layered() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .defaults_precedence(DefaultsPrecedence::Programmatic)
+            .default_values(KvsMap::from([
+                ("bool1".to_string(), KvsValue::Boolean(false)),
+                ("number2".to_string(), KvsValue::I32(456)),
+            ]));
+        let kvs = builder.build().unwrap();
+
+        // Programmatic defaults take precedence over file defaults.
+        assert_eq!(
+            kvs.effective_defaults_report().unwrap(),
+            vec![
+                (
+                    "bool1".to_string(),
+                    KvsValue::Boolean(false),
+                    DefaultsSource::Programmatic
+                ),
+                ("number1".to_string(), KvsValue::F64(123.0), DefaultsSource::File),
+                ("number2".to_string(), KvsValue::I32(456), DefaultsSource::Programmatic),
+                (
+                    "string1".to_string(),
+                    KvsValue::String("Hello".to_string()),
+                    DefaultsSource::File
+                ),
+            ]
+        );
+        assert_eq!(kvs.get_value("bool1").unwrap(), KvsValue::Boolean(false));
+    }
+
+    #[test]
+    fn test_build_default_value_file_precedence() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let builder = KvsBuilder::new(instance_id)
+            .defaults(KvsDefaults::Required)
+            .backend(Box::new(backend))
+            .default_value("bool1", false)
+            .default_value("number2", 456);
+        let kvs = builder.build().unwrap();
+
+        // File defaults take precedence over programmatic defaults.
+        assert_eq!(kvs.get_default_value("bool1").unwrap(), KvsValue::Boolean(true));
+        assert_eq!(kvs.get_default_value("number2").unwrap(), KvsValue::I32(456));
+        assert!(kvs.is_value_default("number2").unwrap());
+        assert_eq!(kvs.get_value_as::<i32>("number2").unwrap(), 456);
+    }
+
+    #[test]
+    fn test_build_default_value_no_file() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let builder = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .default_value("string1", "Hi");
+        let kvs = builder.build().unwrap();
+
+        assert_eq!(
+            kvs.get_default_value("string1").unwrap(),
+            KvsValue::String("Hi".to_string())
+        );
+        kvs.set_value("string1", "Hello").unwrap();
+        assert!(!kvs.is_value_default("string1").unwrap());
+        kvs.reset_key("string1").unwrap();
+        assert!(kvs.is_value_default("string1").unwrap());
+    }
+
+    #[test]
+    fn test_build_expect_type_ok() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        create_kvs_files(&dir_path, instance_id, SnapshotId(0)).unwrap();
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .expect_type("number1", KvsValueType::F64)
+            .expect_type("string1", KvsValueType::String)
+            .expect_type("missing", KvsValueType::I32)
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<f64>("number1").unwrap(), 321.0);
+    }
+
+    #[test]
+    fn test_build_expect_type_defaults_mismatch() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_defaults_file(&dir_path, instance_id).unwrap();
+        let result = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .expect_type("string1", KvsValueType::I32)
+            .build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::SchemaMismatch));
+        assert!(KVS_POOL.lock().unwrap().instances.is_empty());
+    }
+
+    #[test]
+    fn test_build_expect_type_kvs_mismatch() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_kvs_files(&dir_path, instance_id, SnapshotId(0)).unwrap();
+        let result = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .expect_type("bool1", KvsValueType::String)
+            .build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::SchemaMismatch));
+    }
+
+    fn no_slash(key: &str) -> bool {
+        !key.contains('/')
+    }
+
+    #[test]
+    fn test_build_key_validator_set_value() {
+        let _lock = lock_and_reset();
+
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .kvs_load(KvsLoad::Ignored)
+            .key_validator(no_slash)
+            .build()
+            .unwrap();
+
+        kvs.set_value("valid_key", 1.0).unwrap();
+        assert!(kvs
+            .set_value("invalid/key", 1.0)
+            .is_err_and(|e| e == ErrorCode::InvalidKey));
+        assert!(kvs
+            .set_value_with_ttl("invalid/key", 1.0, Duration::from_secs(60))
+            .is_err_and(|e| e == ErrorCode::InvalidKey));
+        assert!(kvs
+            .compare_and_set("invalid/key", None, 1.0)
+            .is_err_and(|e| e == ErrorCode::InvalidKey));
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["valid_key".to_string()]);
+    }
+
+    #[test]
+    fn test_build_key_validator_merge() {
+        let _lock = lock_and_reset();
+
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .kvs_load(KvsLoad::Ignored)
+            .key_validator(no_slash)
+            .build()
+            .unwrap();
+
+        let other = KvsMap::from([
+            ("valid_key".to_string(), KvsValue::from(1.0)),
+            ("invalid/key".to_string(), KvsValue::from(2.0)),
+        ]);
+        assert!(kvs
+            .merge(other, MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::InvalidKey));
+        assert!(kvs.get_all_keys().unwrap().is_empty());
+
+        let other = KvsMap::from([("valid_key".to_string(), KvsValue::from(1.0))]);
+        kvs.merge(other, MergePolicy::PreferIncoming).unwrap();
+        assert_eq!(kvs.get_value_as::<f64>("valid_key").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_build_key_validator_not_set() {
+        let _lock = lock_and_reset();
+
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .kvs_load(KvsLoad::Ignored)
+            .build()
+            .unwrap();
+
+        kvs.set_value("any/key\n", 1.0).unwrap();
+        assert!(kvs.key_exists("any/key\n").unwrap());
+    }
+
+    #[test]
+    fn test_build_skip_clean_flush() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()))
+            .skip_clean_flush(true)
+            .build()
+            .unwrap();
+
+        // Flush when clean is a no-op.
+        assert!(!kvs.is_dirty().unwrap());
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 0);
+
+        // Flush after a set writes.
+        kvs.set_value("key", 1.0).unwrap();
+        assert!(kvs.is_dirty().unwrap());
+        kvs.flush().unwrap();
+        assert!(!kvs.is_dirty().unwrap());
+        assert_eq!(kvs.snapshot_count(), 1);
+
+        // Repeated flushes without writes don't rotate snapshots.
+        kvs.flush().unwrap();
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 1);
+    }
+
+    #[test]
+    fn test_build_skip_clean_flush_chunked() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()))
+            .skip_clean_flush(true)
+            .build()
+            .unwrap();
+        kvs.set_value("key", 1.0).unwrap();
+
+        // Chunked flush marks data clean, repeated chunked flush doesn't rotate snapshots.
+        for _ in 0..2 {
+            let mut flush = kvs.flush_chunked().unwrap();
+            while flush.step().unwrap() == FlushProgress::Pending {}
+        }
+        assert!(!kvs.is_dirty().unwrap());
+        assert_eq!(kvs.snapshot_count(), 1);
+
+        // Regular flush after chunked flush is skipped too.
+        kvs.flush().unwrap();
+        assert_eq!(kvs.snapshot_count(), 1);
+    }
+
+    #[test]
+    fn test_open() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let kvs = open(InstanceId(1), dir.path()).unwrap();
+        kvs.set_value("key", "value").unwrap();
+        kvs.flush().unwrap();
+        assert!(dir.path().join("kvs_1_0.json").exists());
+        assert_eq!(kvs.working_dir().unwrap(), dir.path());
+
+        // Reopen persisted instance.
+        kvs.close().unwrap();
+        let kvs = open(InstanceId(1), dir.path().to_path_buf()).unwrap();
+        assert_eq!(kvs.get_value_as::<String>("key").unwrap(), "value");
     }
 
     #[test]
-    fn test_build_instance_exists_different_params() {
+    fn test_build_skip_clean_flush_not_set() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(JsonBackendBuilder::new().working_dir(dir_path).build()))
+            .build()
+            .unwrap();
+
+        kvs.flush().unwrap();
+        kvs.flush().unwrap();
+        assert!(!kvs.is_dirty().unwrap());
+        assert_eq!(kvs.snapshot_count(), 2);
+    }
 
-        // Create two instances with different parameters.
-        let instance_id = InstanceId(1);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        let builder1 = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Ignored)
-            .kvs_load(KvsLoad::Optional)
-            .backend(Box::new(backend.clone()));
-        let _ = builder1.build().unwrap();
+    #[test]
+    fn test_build_clock_expires_ttl() {
+        let _lock = lock_and_reset();
 
-        let builder2 = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Optional)
-            .kvs_load(KvsLoad::Ignored)
-            .backend(Box::new(backend.clone()));
-        let result = builder2.build();
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(1000));
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend.clone()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+
+        kvs.set_value_with_ttl("key", 1, Duration::from_secs(60)).unwrap();
+        clock.advance(Duration::from_secs(59));
+        assert!(kvs.key_exists("key").unwrap());
+
+        // Expiry is computed from clock and persisted.
+        kvs.flush().unwrap();
+        let (_, expiry_map) = backend.load_kvs_with_expiry(InstanceId(1), SnapshotId(0)).unwrap();
+        assert_eq!(expiry_map.get("key"), Some(&(UNIX_EPOCH + Duration::from_secs(1060))));
+
+        clock.advance(Duration::from_secs(1));
+        assert!(!kvs.key_exists("key").unwrap());
+        assert!(kvs.get_value("key").is_err_and(|e| e == ErrorCode::KeyNotFound));
+    }
 
-        assert!(result.is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+    /// Audit sink collecting events in memory.
+    #[derive(Clone, Default)]
+    struct MemoryAuditSink {
+        events: Arc<Mutex<Vec<AuditEvent>>>,
+    }
+
+    impl AuditSink for MemoryAuditSink {
+        fn record(&self, event: &AuditEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
     }
 
     #[test]
-    fn test_build_instance_exists_params_not_set() {
+    fn test_build_audit_sink() {
         let _lock = lock_and_reset();
 
-        // Create two instances, first with parameters, second without.
-        let instance_id = InstanceId(1);
-        let builder1 = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Ignored)
-            .kvs_load(KvsLoad::Ignored);
-        let _ = builder1.build().unwrap();
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let sink = MemoryAuditSink::default();
+        let clock = MockClock::new(UNIX_EPOCH);
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend))
+            .audit_sink(Box::new(sink.clone()))
+            .clock(Box::new(clock.clone()))
+            .default_value("b", 0)
+            .build()
+            .unwrap();
+
+        kvs.set_value("a", 1).unwrap();
+        clock.advance(Duration::from_secs(1));
+        kvs.set_value("a", 2).unwrap();
+        kvs.set_value("b", 3).unwrap();
+        kvs.remove_key("a").unwrap();
+        kvs.reset_key("b").unwrap();
+        kvs.reset().unwrap();
+        kvs.flush().unwrap();
+
+        // Failed operations are not recorded.
+        assert!(kvs.remove_key("a").is_err());
+
+        let event = |operation, key: Option<&str>, old: Option<i32>, new: Option<i32>| AuditEvent {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1),
+            operation,
+            key: key.map(str::to_string),
+            old: old.map(KvsValue::from),
+            new: new.map(KvsValue::from),
+        };
+        let mut first = event(AuditOperation::Set, Some("a"), None, Some(1));
+        first.timestamp = UNIX_EPOCH;
+        assert_eq!(
+            *sink.events.lock().unwrap(),
+            vec![
+                first,
+                event(AuditOperation::Set, Some("a"), Some(1), Some(2)),
+                event(AuditOperation::Set, Some("b"), None, Some(3)),
+                event(AuditOperation::Remove, Some("a"), Some(2), None),
+                event(AuditOperation::ResetKey, Some("b"), Some(3), None),
+                event(AuditOperation::Reset, None, None, None),
+                event(AuditOperation::Flush, None, None, None),
+            ]
+        );
+    }
 
-        let builder2 = KvsBuilder::new(instance_id);
-        let kvs = builder2.build().unwrap();
+    #[test]
+    fn test_build_audit_sink_bulk_operations() {
+        let _lock = lock_and_reset();
 
-        // Assert params as expected.
-        assert_eq!(kvs.parameters().instance_id, instance_id);
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
-        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let sink = MemoryAuditSink::default();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend))
+            .audit_sink(Box::new(sink.clone()))
+            .clock(Box::new(MockClock::new(UNIX_EPOCH)))
+            .default_value("d", 0)
+            .build()
+            .unwrap();
+
+        assert!(kvs.compare_and_set("a", None, 1).unwrap());
+        assert!(!kvs.compare_and_set("a", None, 2).unwrap());
+        assert_eq!(kvs.swap("a", 2).unwrap(), Some(KvsValue::from(1)));
+        kvs.merge(
+            KvsMap::from([("a".to_string(), KvsValue::from(3))]),
+            MergePolicy::PreferExisting,
+        )
+        .unwrap();
+        kvs.merge(
+            KvsMap::from([("p.b".to_string(), KvsValue::from(4))]),
+            MergePolicy::PreferIncoming,
+        )
+        .unwrap();
+        kvs.materialize_default("d").unwrap();
+        assert_eq!(kvs.remove_prefix("p.").unwrap(), 1);
+        assert_eq!(kvs.reset_to_defaults().unwrap(), 1);
+        kvs.flush().unwrap();
+        kvs.set_value("a", 5).unwrap();
+        kvs.flush().unwrap();
+        kvs.snapshot_restore(SnapshotId(1)).unwrap();
+
+        let event = |operation, key: Option<&str>, old: Option<i32>, new: Option<i32>| AuditEvent {
+            timestamp: UNIX_EPOCH,
+            operation,
+            key: key.map(str::to_string),
+            old: old.map(KvsValue::from),
+            new: new.map(KvsValue::from),
+        };
+        assert_eq!(
+            *sink.events.lock().unwrap(),
+            vec![
+                event(AuditOperation::Set, Some("a"), None, Some(1)),
+                event(AuditOperation::Set, Some("a"), Some(1), Some(2)),
+                event(AuditOperation::Set, Some("p.b"), None, Some(4)),
+                event(AuditOperation::Set, Some("d"), None, Some(0)),
+                event(AuditOperation::Remove, Some("p.b"), Some(4), None),
+                event(AuditOperation::ResetKey, Some("d"), Some(0), None),
+                event(AuditOperation::Flush, None, None, None),
+                event(AuditOperation::Set, Some("a"), Some(2), Some(5)),
+                event(AuditOperation::Flush, None, None, None),
+                event(AuditOperation::Restore, None, None, None),
+            ]
+        );
     }
 
     #[test]
-    fn test_build_instance_exists_single_matching_param_set() {
+    fn test_build_case_insensitive_keys() {
         let _lock = lock_and_reset();
 
-        // Create two instances, first with parameters, second only with `defaults`.
-        let instance_id = InstanceId(1);
-        let builder1 = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Ignored)
-            .kvs_load(KvsLoad::Ignored);
-        let _ = builder1.build().unwrap();
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend.clone()))
+            .case_insensitive_keys(true)
+            .default_value("Timeout", 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value("timeout").unwrap(), KvsValue::I32(10));
+        kvs.set_value("Foo", "bar").unwrap();
+        assert_eq!(kvs.get_value("foo").unwrap(), KvsValue::from("bar"));
+        assert_eq!(kvs.get_value("FOO").unwrap(), KvsValue::from("bar"));
+        assert!(kvs.key_exists("fOo").unwrap());
+        assert_eq!(kvs.get_all_keys().unwrap(), vec!["foo".to_string()]);
+        assert_eq!(kvs.keys_with_prefix("F").unwrap(), vec!["foo".to_string()]);
+
+        // Keys are persisted normalized.
+        kvs.flush().unwrap();
+        assert_eq!(
+            backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(),
+            KvsMap::from([("foo".to_string(), KvsValue::from("bar"))])
+        );
+
+        kvs.remove_key("FOO").unwrap();
+        assert!(!kvs.key_exists("foo").unwrap());
+    }
 
-        let builder2 = KvsBuilder::new(instance_id).defaults(KvsDefaults::Ignored);
-        let kvs = builder2.build().unwrap();
+    #[test]
+    fn test_build_locked_key() {
+        let _lock = lock_and_reset();
 
-        // Assert params as expected.
-        assert_eq!(kvs.parameters().instance_id, instance_id);
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
-        assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        backend
+            .flush(
+                InstanceId(1),
+                &KvsMap::from([
+                    ("locked".to_string(), KvsValue::from(5)),
+                    ("unlocked".to_string(), KvsValue::from(6)),
+                ]),
+            )
+            .unwrap();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend))
+            .locked_key("locked")
+            .locked_key("no_default")
+            .default_value("locked", 1)
+            .default_value("unlocked", 2)
+            .build()
+            .unwrap();
+
+        // Default wins over loaded value of a locked key.
+        assert_eq!(kvs.get_value("locked").unwrap(), KvsValue::I32(1));
+        assert_eq!(kvs.get_value_as::<i32>("locked").unwrap(), 1);
+        assert_eq!(kvs.get_value("unlocked").unwrap(), KvsValue::I32(6));
+
+        assert!(kvs.set_value("locked", 3).is_err_and(|e| e == ErrorCode::KeyLocked));
+        assert!(kvs
+            .compare_and_set("locked", Some(&KvsValue::I32(1)), 3)
+            .is_err_and(|e| e == ErrorCode::KeyLocked));
+        assert_eq!(kvs.get_value("locked").unwrap(), KvsValue::I32(1));
+        assert_eq!(kvs.get_value_as::<i32>("locked").unwrap(), 1);
+
+        // Locked key without default cannot be set either.
+        assert!(kvs.set_value("no_default", 3).is_err_and(|e| e == ErrorCode::KeyLocked));
+        assert!(!kvs.key_exists("no_default").unwrap());
+        kvs.set_value("unlocked", 3).unwrap();
     }
 
     #[test]
-    fn test_build_instance_exists_single_mismatched_param_set() {
+    fn test_build_locked_key_bulk_rejected() {
         let _lock = lock_and_reset();
 
-        // Create two instances, first with parameters, second only with `defaults`.
-        let instance_id = InstanceId(1);
-        let builder1 = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Ignored)
-            .kvs_load(KvsLoad::Ignored);
-        let _ = builder1.build().unwrap();
-
-        let builder2 = KvsBuilder::new(instance_id).defaults(KvsDefaults::Optional);
-        let result = builder2.build();
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        backend
+            .flush(
+                InstanceId(1),
+                &KvsMap::from([("locked".to_string(), KvsValue::from(5))]),
+            )
+            .unwrap();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend))
+            .locked_key("locked")
+            .default_value("locked", 1)
+            .build()
+            .unwrap();
+
+        // Shadowed stored value is not reported.
+        assert!(kvs.is_value_default("locked").unwrap());
+
+        let other = KvsMap::from([
+            ("locked".to_string(), KvsValue::from(7)),
+            ("unlocked".to_string(), KvsValue::from(8)),
+        ]);
+        assert!(kvs
+            .merge(other.clone(), MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::KeyLocked));
+        let json_str = JsonBackend::to_json_string_with_expiry(&other, &KvsExpiryMap::new(), false).unwrap();
+        assert!(kvs
+            .import(json_str.as_bytes(), MergePolicy::PreferIncoming)
+            .is_err_and(|e| e == ErrorCode::KeyLocked));
+        assert!(kvs
+            .materialize_default("locked")
+            .is_err_and(|e| e == ErrorCode::KeyLocked));
 
-        assert!(result.is_err_and(|e| e == ErrorCode::InstanceParametersMismatch));
+        // Nothing was merged, stored value stays as loaded.
+        assert!(!kvs.key_exists("unlocked").unwrap());
+        kvs.clear_defaults().unwrap();
+        assert_eq!(kvs.get_value("locked").unwrap(), KvsValue::I32(5));
     }
 
     #[test]
-    fn test_build_instance_id_out_of_range() {
+    fn test_build_case_insensitive_keys_loaded() {
         let _lock = lock_and_reset();
 
-        let instance_id = InstanceId(123);
-        let result = KvsBuilder::new(instance_id).build();
-        assert!(result.is_err_and(|e| e == ErrorCode::InvalidInstanceId));
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        backend
+            .flush(
+                InstanceId(1),
+                &KvsMap::from([("Timeout".to_string(), KvsValue::from(5))]),
+            )
+            .unwrap();
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend.clone()))
+            .case_insensitive_keys(true)
+            .build()
+            .unwrap();
+
+        // Loaded keys are normalized, normalized data must be flushed.
+        assert_eq!(kvs.get_value("timeout").unwrap(), KvsValue::I32(5));
+        assert!(kvs.is_dirty().unwrap());
     }
 
-    /// Generate and store file containing example default values.
-    fn create_defaults_file(working_dir: &Path, instance_id: InstanceId) -> Result<(), ErrorCode> {
-        let backend = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
-        let defaults_file_path = backend.defaults_file_path(instance_id);
-        let defaults_hash_file_path = backend.defaults_hash_file_path(instance_id);
+    #[test]
+    fn test_build_case_insensitive_keys_conflict() {
+        let _lock = lock_and_reset();
 
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
         let kvs_map = KvsMap::from([
-            ("number1".to_string(), KvsValue::F64(123.0)),
-            ("bool1".to_string(), KvsValue::Boolean(true)),
-            ("string1".to_string(), KvsValue::String("Hello".to_string())),
+            ("Timeout".to_string(), KvsValue::from(5)),
+            ("timeout".to_string(), KvsValue::from(6)),
         ]);
-        JsonBackend::save(&kvs_map, &defaults_file_path, &defaults_hash_file_path)?;
+        backend.flush(InstanceId(1), &kvs_map).unwrap();
 
-        Ok(())
+        let result = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend.clone()))
+            .case_insensitive_keys(true)
+            .build();
+        assert!(result.is_err_and(|e| e == ErrorCode::InvalidKey));
+
+        // Keys differing only in case are distinct by default.
+        let kvs = KvsBuilder::new(InstanceId(1))
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+        assert_eq!(kvs.get_value("Timeout").unwrap(), KvsValue::I32(5));
+        assert_eq!(kvs.get_value("timeout").unwrap(), KvsValue::I32(6));
     }
 
-    /// Generate and store files containing example KVS and hash data.
-    fn create_kvs_files(
-        working_dir: &Path,
-        instance_id: InstanceId,
-        snapshot_id: SnapshotId,
-    ) -> Result<(PathBuf, PathBuf), ErrorCode> {
-        let backend = JsonBackendBuilder::new().working_dir(working_dir.to_path_buf()).build();
-        let kvs_file_path = backend.kvs_file_path(instance_id, snapshot_id);
-        let hash_file_path = backend.hash_file_path(instance_id, snapshot_id);
-        let kvs_map = KvsMap::from([
-            ("number1".to_string(), KvsValue::F64(321.0)),
-            ("bool1".to_string(), KvsValue::Boolean(false)),
-            ("string1".to_string(), KvsValue::String("Hi".to_string())),
-        ]);
-        JsonBackend::save(&kvs_map, &kvs_file_path, &hash_file_path)?;
+    /// Store valid KVS files for snapshots `0..count`, value of "number1" is equal to snapshot ID.
+    fn create_snapshots(backend: &JsonBackend, instance_id: InstanceId, count: usize) {
+        for idx in 0..count {
+            let snapshot_id = SnapshotId(idx);
+            let kvs_map = KvsMap::from([("number1".to_string(), KvsValue::F64(idx as f64))]);
+            JsonBackend::save(
+                &kvs_map,
+                &backend.kvs_file_path(instance_id, snapshot_id),
+                &backend.hash_file_path(instance_id, snapshot_id),
+            )
+            .unwrap();
+        }
+    }
 
-        Ok((kvs_file_path, hash_file_path))
+    /// Corrupt KVS file of provided snapshot, hash file is kept.
+    fn corrupt_snapshot(backend: &JsonBackend, instance_id: InstanceId, snapshot_id: SnapshotId) {
+        std::fs::write(backend.kvs_file_path(instance_id, snapshot_id), "{\"corrupted\"").unwrap();
     }
 
     #[test]
-    fn test_build_defaults_ignored() {
+    fn test_build_kvs_load_latest_valid_current() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
         let instance_id = InstanceId(2);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        create_defaults_file(&dir_path, instance_id).unwrap();
-        let builder = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Ignored)
-            .backend(Box::new(backend));
-        let kvs = builder.build().unwrap();
-
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Ignored);
-        let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().defaults_map, KvsMap::new());
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_snapshots(&backend, instance_id, 3);
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.parameters().kvs_load, KvsLoad::LatestValid);
+        assert_eq!(kvs.get_value_as::<f64>("number1").unwrap(), 0.0);
     }
 
     #[test]
-    fn test_build_defaults_optional_not_provided() {
+    fn test_build_kvs_load_latest_valid_newest_corrupted() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
         let instance_id = InstanceId(2);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        let builder = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Optional)
-            .backend(Box::new(backend));
-        let kvs = builder.build().unwrap();
-
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
-        let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().defaults_map, KvsMap::new());
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_snapshots(&backend, instance_id, 3);
+        corrupt_snapshot(&backend, instance_id, SnapshotId(0));
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<f64>("number1").unwrap(), 1.0);
     }
 
     #[test]
-    fn test_build_defaults_optional_provided() {
+    fn test_build_kvs_load_latest_valid_newest_hash_not_provided() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
         let instance_id = InstanceId(2);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        create_defaults_file(&dir_path, instance_id).unwrap();
-        let builder = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Optional)
-            .backend(Box::new(backend));
-        let kvs = builder.build().unwrap();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_snapshots(&backend, instance_id, 3);
+        std::fs::remove_file(backend.hash_file_path(instance_id, SnapshotId(0))).unwrap();
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<f64>("number1").unwrap(), 1.0);
+    }
 
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Optional);
-        let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().defaults_map.len(), 3);
+    #[test]
+    fn test_build_kvs_load_latest_valid_two_newest_corrupted() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_snapshots(&backend, instance_id, 3);
+        corrupt_snapshot(&backend, instance_id, SnapshotId(0));
+        corrupt_snapshot(&backend, instance_id, SnapshotId(1));
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<f64>("number1").unwrap(), 2.0);
     }
 
     #[test]
-    fn test_build_defaults_required_not_provided() {
+    fn test_build_kvs_load_latest_valid_none_valid() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
         let instance_id = InstanceId(2);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        let builder = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Required)
-            .backend(Box::new(backend));
-        let result = builder.build();
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_snapshots(&backend, instance_id, 2);
+        corrupt_snapshot(&backend, instance_id, SnapshotId(0));
+        corrupt_snapshot(&backend, instance_id, SnapshotId(1));
+        let result = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build();
 
-        assert!(result.is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert!(result.is_err_and(|e| e == ErrorCode::ValidationFailed));
     }
 
     #[test]
-    fn test_build_defaults_required_provided() {
+    fn test_build_kvs_load_latest_valid_not_provided() {
         let _lock = lock_and_reset();
 
         let dir = tempdir().unwrap();
         let dir_path = dir.path().to_path_buf();
 
         let instance_id = InstanceId(2);
-        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
-        create_defaults_file(&dir_path, instance_id).unwrap();
-        let builder = KvsBuilder::new(instance_id)
-            .defaults(KvsDefaults::Required)
-            .backend(Box::new(backend));
-        let kvs = builder.build().unwrap();
-
-        assert_eq!(kvs.parameters().defaults, KvsDefaults::Required);
-        let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().defaults_map.len(), 3);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::LatestValid)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert!(kvs.get_all_keys().unwrap().is_empty());
     }
 
     #[test]
@@ -696,9 +2743,8 @@ mod kvs_builder_tests {
 
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Ignored);
         let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().kvs_map, KvsMap::new());
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().kvs_map, KvsMap::new());
     }
 
     #[test]
@@ -717,13 +2763,11 @@ mod kvs_builder_tests {
 
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
         let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().kvs_map, KvsMap::new());
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().kvs_map, KvsMap::new());
     }
 
     #[test]
-    #[ignore = "Not handled properly yet"]
     fn test_build_kvs_load_optional_kvs_provided_hash_not_provided() {
         let _lock = lock_and_reset();
 
@@ -739,7 +2783,7 @@ mod kvs_builder_tests {
             .backend(Box::new(backend));
         let result = builder.build();
 
-        assert!(result.is_err_and(|e| e == ErrorCode::KvsHashFileReadError));
+        assert!(result.is_err_and(|e| e == ErrorCode::HashFileNotFound));
     }
 
     #[test]
@@ -779,9 +2823,129 @@ mod kvs_builder_tests {
 
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Optional);
         let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().kvs_map.len(), 3);
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().kvs_map.len(), 3);
+    }
+
+    /// Store KVS and defaults files written by an older application version.
+    fn create_old_version_files(backend: &JsonBackend, instance_id: InstanceId) {
+        let kvs_map = KvsMap::from([
+            ("old_key".to_string(), KvsValue::from("value")),
+            ("level".to_string(), KvsValue::from("5")),
+        ]);
+        let snapshot_id = SnapshotId(0);
+        JsonBackend::save(
+            &kvs_map,
+            &backend.kvs_file_path(instance_id, snapshot_id),
+            &backend.hash_file_path(instance_id, snapshot_id),
+        )
+        .unwrap();
+
+        let defaults_map = KvsMap::from([("old_key".to_string(), KvsValue::from("default"))]);
+        JsonBackend::save(
+            &defaults_map,
+            &backend.defaults_file_path(instance_id),
+            &backend.defaults_hash_file_path(instance_id),
+        )
+        .unwrap();
+    }
+
+    fn rename_old_key(kvs_map: &mut KvsMap) -> Result<(), ErrorCode> {
+        if let Some(value) = kvs_map.remove("old_key") {
+            kvs_map.insert("new_key".to_string(), value);
+        }
+        Ok(())
+    }
+
+    fn convert_level(kvs_map: &mut KvsMap) -> Result<(), ErrorCode> {
+        if let Some(KvsValue::String(level)) = kvs_map.get("level") {
+            let level: i32 = level.parse().map_err(|_| ErrorCode::ConversionFailed)?;
+            kvs_map.insert("level".to_string(), KvsValue::I32(level));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_migration_rename_key() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_old_version_files(&backend, instance_id);
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .migration(rename_old_key)
+            .build()
+            .unwrap();
+
+        assert!(!kvs.key_exists("old_key").unwrap());
+        assert_eq!(kvs.get_value_as::<String>("new_key").unwrap(), "value");
+        assert_eq!(kvs.get_default_value("new_key").unwrap(), KvsValue::from("default"));
+        assert!(kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_build_migration_convert_type() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_old_version_files(&backend, instance_id);
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .expect_type("level", KvsValueType::I32)
+            .migration(rename_old_key)
+            .migration(convert_level)
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<i32>("level").unwrap(), 5);
+        assert_eq!(kvs.get_value_as::<String>("new_key").unwrap(), "value");
+    }
+
+    #[test]
+    fn test_build_migration_unchanged() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        create_kvs_files(&dir_path, instance_id, SnapshotId(0)).unwrap();
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .migration(rename_old_key)
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.len().unwrap(), 3);
+        assert!(!kvs.is_dirty().unwrap());
+    }
+
+    #[test]
+    fn test_build_migration_failed() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path).build();
+        create_old_version_files(&backend, instance_id);
+        let result = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend))
+            .migration(|_| Err(ErrorCode::ConversionFailed))
+            .build();
+
+        assert!(result.is_err_and(|e| e == ErrorCode::ConversionFailed));
+        assert!(!KVS_POOL.lock().unwrap().instances.contains_key(&instance_id));
     }
 
     #[test]
@@ -802,7 +2966,6 @@ mod kvs_builder_tests {
     }
 
     #[test]
-    #[ignore = "Not handled properly yet"]
     fn test_build_kvs_load_required_kvs_provided_hash_not_provided() {
         let _lock = lock_and_reset();
 
@@ -818,7 +2981,7 @@ mod kvs_builder_tests {
             .backend(Box::new(backend));
         let result = builder.build();
 
-        assert!(result.is_err_and(|e| e == ErrorCode::KvsHashFileReadError));
+        assert!(result.is_err_and(|e| e == ErrorCode::HashFileNotFound));
     }
 
     #[test]
@@ -858,8 +3021,44 @@ mod kvs_builder_tests {
 
         assert_eq!(kvs.parameters().kvs_load, KvsLoad::Required);
         let kvs_pool = KVS_POOL.lock().unwrap();
-        let kvs_pool_entry = kvs_pool.get(2).unwrap();
-        let kvs_data = kvs_pool_entry.as_ref().unwrap();
-        assert_eq!(kvs_data.data.lock().unwrap().kvs_map.len(), 3);
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        assert_eq!(kvs_data.data.read().unwrap().kvs_map.len(), 3);
+    }
+
+    #[test]
+    fn test_build_kvs_load_expiry_persisted() {
+        let _lock = lock_and_reset();
+
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_path_buf();
+
+        let instance_id = InstanceId(2);
+        let backend = JsonBackendBuilder::new().working_dir(dir_path.clone()).build();
+        let kvs = KvsBuilder::new(instance_id)
+            .backend(Box::new(backend.clone()))
+            .build()
+            .unwrap();
+        kvs.set_value_with_ttl("valid", 1, Duration::from_secs(3600)).unwrap();
+        kvs.set_value_with_ttl("expired", 2, Duration::ZERO).unwrap();
+        kvs.set_value("persistent", 3).unwrap();
+        kvs.flush().unwrap();
+
+        // Drop instance from pool to force reload.
+        let _ = KVS_POOL.lock().unwrap().instances.remove(&instance_id);
+        let kvs = KvsBuilder::new(instance_id)
+            .kvs_load(KvsLoad::Required)
+            .backend(Box::new(backend))
+            .build()
+            .unwrap();
+
+        assert_eq!(kvs.get_value_as::<i32>("valid").unwrap(), 1);
+        assert!(kvs.get_value("expired").is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert_eq!(kvs.get_value_as::<i32>("persistent").unwrap(), 3);
+
+        let kvs_pool = KVS_POOL.lock().unwrap();
+        let kvs_data = kvs_pool.instances.get(&instance_id).unwrap();
+        let expiry_map = &kvs_data.data.read().unwrap().expiry_map;
+        assert!(expiry_map.contains_key("valid"));
+        assert!(!expiry_map.contains_key("persistent"));
     }
 }
@@ -0,0 +1,92 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+//! Operation counters of KVS instances.
+//!
+//! Counters are shared by all handles of an instance and incremented without locking.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Snapshot of operation counters of a KVS instance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KvsMetrics {
+    /// Value reads (`get_value`, `get_value_as`, `get_value_or`, `try_get_value`).
+    pub gets: u64,
+
+    /// Successful value writes (`set_value`, `set_value_with_ttl`, `compare_and_set`).
+    pub sets: u64,
+
+    /// Removed keys (`remove_key`, `remove_prefix`).
+    pub removes: u64,
+
+    /// Successful flushes.
+    pub flushes: u64,
+
+    /// Reads and removals of absent keys.
+    pub key_not_found: u64,
+
+    /// Keys rejected by key validator.
+    pub validation_failures: u64,
+}
+
+/// Operation counters of a KVS instance.
+#[derive(Debug, Default)]
+pub(crate) struct KvsCounters {
+    pub(crate) gets: AtomicU64,
+    pub(crate) sets: AtomicU64,
+    pub(crate) removes: AtomicU64,
+    pub(crate) flushes: AtomicU64,
+    pub(crate) key_not_found: AtomicU64,
+    pub(crate) validation_failures: AtomicU64,
+}
+
+impl KvsCounters {
+    /// Read current counter values.
+    /// Counters are read independently, snapshot is not atomic as a whole.
+    pub(crate) fn snapshot(&self) -> KvsMetrics {
+        KvsMetrics {
+            gets: self.gets.load(Ordering::Relaxed),
+            sets: self.sets.load(Ordering::Relaxed),
+            removes: self.removes.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            key_not_found: self.key_not_found.load(Ordering::Relaxed),
+            validation_failures: self.validation_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod kvs_metrics_tests {
+    use crate::kvs_metrics::{KvsCounters, KvsMetrics};
+    use core::sync::atomic::Ordering;
+
+    #[test]
+    fn test_snapshot_default() {
+        assert_eq!(KvsCounters::default().snapshot(), KvsMetrics::default());
+    }
+
+    #[test]
+    fn test_snapshot_ok() {
+        let counters = KvsCounters::default();
+        counters.gets.fetch_add(3, Ordering::Relaxed);
+        counters.sets.fetch_add(2, Ordering::Relaxed);
+        counters.flushes.fetch_add(1, Ordering::Relaxed);
+
+        let metrics = counters.snapshot();
+        assert_eq!(metrics.gets, 3);
+        assert_eq!(metrics.sets, 2);
+        assert_eq!(metrics.removes, 0);
+        assert_eq!(metrics.flushes, 1);
+        assert_eq!(metrics.key_not_found, 0);
+        assert_eq!(metrics.validation_failures, 0);
+    }
+}
@@ -11,8 +11,11 @@
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
 use crate::error_code::ErrorCode;
-use crate::kvs_api::{KvsApi, SnapshotId};
+use crate::kvs::merge_maps;
+use crate::kvs_api::{DefaultsSource, KvsApi, MergePolicy, SnapshotId};
+use crate::kvs_backend::{ChunkedFlush, CompletedFlush};
 use crate::kvs_value::{KvsMap, KvsValue};
+use core::time::Duration;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
@@ -55,24 +58,85 @@ impl KvsApi for MockKvs {
             Err(ErrorCode::KeyDefaultNotFound)
         }
     }
+    fn reset_to_defaults(&self) -> Result<usize, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(0)
+    }
+    fn materialize_default(&self, _key: &str) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Err(ErrorCode::KeyDefaultNotFound)
+    }
     fn get_all_keys(&self) -> Result<Vec<String>, ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         Ok(self.map.lock().unwrap().keys().cloned().collect())
     }
+    fn keys_with_prefix(&self, prefix: &str) -> Result<Vec<String>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(self
+            .map
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+    fn len(&self) -> Result<usize, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(self.map.lock().unwrap().len())
+    }
+    fn is_empty(&self) -> Result<bool, ErrorCode> {
+        Ok(self.len()? == 0)
+    }
     fn key_exists(&self, key: &str) -> Result<bool, ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         Ok(self.map.lock().unwrap().contains_key(key))
     }
+    fn entries(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(self
+            .map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+    fn entries_with_defaults(&self) -> Result<Vec<(String, KvsValue)>, ErrorCode> {
+        self.entries()
+    }
     fn get_value(&self, key: &str) -> Result<KvsValue, ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         self.map.lock().unwrap().get(key).cloned().ok_or(ErrorCode::KeyNotFound)
     }
+    fn try_get_value(&self, key: &str) -> Result<Option<KvsValue>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(self.map.lock().unwrap().get(key).cloned())
+    }
+    fn with_value<R, F: FnOnce(Option<&KvsValue>) -> R>(&self, key: &str, f: F) -> Result<R, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(f(self.map.lock().unwrap().get(key)))
+    }
     fn get_value_as<T>(&self, key: &str) -> Result<T, ErrorCode>
     where
         for<'a> T: TryFrom<&'a KvsValue> + Clone,
@@ -84,18 +148,46 @@ impl KvsApi for MockKvs {
         let v = self.get_value(key)?;
         T::try_from(&v).map_err(|_| ErrorCode::ConversionFailed)
     }
+    fn get_value_or<T>(&self, key: &str, default: T) -> Result<T, ErrorCode>
+    where
+        for<'a> T: TryFrom<&'a KvsValue> + Clone,
+        for<'a> <T as TryFrom<&'a KvsValue>>::Error: core::fmt::Debug,
+    {
+        match self.try_get_value(key)? {
+            Some(v) => T::try_from(&v).map_err(|_| ErrorCode::ConversionFailed),
+            None => Ok(default),
+        }
+    }
     fn get_default_value(&self, _key: &str) -> Result<KvsValue, ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         Err(ErrorCode::KeyNotFound)
     }
+    fn default_keys(&self) -> Result<Vec<String>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(Vec::new())
+    }
+    fn contains_default(&self, _key: &str) -> Result<bool, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(false)
+    }
     fn is_value_default(&self, _key: &str) -> Result<bool, ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         Ok(false)
     }
+    fn effective_defaults_report(&self) -> Result<Vec<(String, KvsValue, DefaultsSource)>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(Vec::new())
+    }
     fn set_value<S: Into<String>, V: Into<KvsValue>>(&self, key: S, value: V) -> Result<(), ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
@@ -103,6 +195,54 @@ impl KvsApi for MockKvs {
         self.map.lock().unwrap().insert(key.into(), value.into());
         Ok(())
     }
+    fn set_value_with_ttl<S: Into<String>, V: Into<KvsValue>>(
+        &self,
+        key: S,
+        value: V,
+        _ttl: Duration,
+    ) -> Result<(), ErrorCode> {
+        // Expiry is not simulated.
+        self.set_value(key, value)
+    }
+    fn compare_and_set<V: Into<KvsValue>>(
+        &self,
+        key: &str,
+        expected: Option<&KvsValue>,
+        new: V,
+    ) -> Result<bool, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        let mut map = self.map.lock().unwrap();
+        if map.get(key) != expected {
+            return Ok(false);
+        }
+        map.insert(key.to_string(), new.into());
+        Ok(true)
+    }
+    fn swap<V: Into<KvsValue>>(&self, key: &str, value: V) -> Result<Option<KvsValue>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(self.map.lock().unwrap().insert(key.to_string(), value.into()))
+    }
+    fn rename_key(&self, from: &str, to: &str, overwrite: bool) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        let mut map = self.map.lock().unwrap();
+        if !map.contains_key(from) {
+            return Err(ErrorCode::KeyNotFound);
+        }
+        if from != to {
+            if !overwrite && map.contains_key(to) {
+                return Err(ErrorCode::KeyAlreadyExists);
+            }
+            let value = map.remove(from).unwrap();
+            map.insert(to.to_string(), value);
+        }
+        Ok(())
+    }
     fn remove_key(&self, key: &str) -> Result<(), ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
@@ -110,18 +250,62 @@ impl KvsApi for MockKvs {
         self.map.lock().unwrap().remove(key);
         Ok(())
     }
+    fn remove_prefix(&self, prefix: &str) -> Result<usize, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        let mut map = self.map.lock().unwrap();
+        let count = map.len();
+        map.retain(|k, _| !k.starts_with(prefix));
+        Ok(count - map.len())
+    }
+    fn compact<F: Fn(&str, &KvsValue) -> bool>(&self, keep: F) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        self.map.lock().unwrap().retain(|k, v| keep(k, v));
+        Ok(())
+    }
+    fn merge(&self, other: KvsMap, policy: MergePolicy) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        merge_maps(&mut self.map.lock().unwrap(), other, policy);
+        Ok(())
+    }
+    fn is_dirty(&self) -> Result<bool, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(false)
+    }
     fn flush(&self) -> Result<(), ErrorCode> {
         if self.fail {
             return Err(ErrorCode::UnmappedError);
         }
         Ok(())
     }
+    fn flush_with_id(&self) -> Result<SnapshotId, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(SnapshotId(0))
+    }
+    fn flush_chunked(&self) -> Result<Box<dyn ChunkedFlush + '_>, ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(Box::new(CompletedFlush))
+    }
     fn snapshot_count(&self) -> usize {
         if self.fail {
             return 9999;
         }
         0
     }
+    fn snapshots(&self) -> Vec<SnapshotId> {
+        Vec::new()
+    }
     fn snapshot_max_count(&self) -> usize {
         0
     }
@@ -131,13 +315,47 @@ impl KvsApi for MockKvs {
         }
         Ok(())
     }
+    fn snapshot_restore_and_flush(&self, id: SnapshotId) -> Result<(), ErrorCode> {
+        self.snapshot_restore(id)?;
+        self.flush()
+    }
+    fn set_defaults(&self, _defaults: KvsMap) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(())
+    }
+    fn clear_defaults(&self) -> Result<(), ErrorCode> {
+        self.set_defaults(KvsMap::new())
+    }
+    fn snapshot_unrestore(&self) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(())
+    }
+    fn mark_corrupt(&self, _reason: &str) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(())
+    }
+    fn clear_corrupt(&self) -> Result<(), ErrorCode> {
+        if self.fail {
+            return Err(ErrorCode::UnmappedError);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::kvs_api::{KvsApi, SnapshotId};
+    use crate::error_code::ErrorCode;
+    use crate::kvs_api::{KvsApi, MergePolicy, SnapshotId};
+    use crate::kvs_backend::FlushProgress;
     use crate::kvs_mock::MockKvs;
-    use crate::kvs_value::KvsValue;
+    use crate::kvs_value::{KvsMap, KvsValue};
+    use core::time::Duration;
 
     #[test]
     fn test_mock_kvs_pass_and_fail_cases() {
@@ -145,13 +363,64 @@ mod tests {
         let kvs = MockKvs::default();
         assert!(kvs.set_value("a", 1.0).is_ok());
         assert_eq!(kvs.get_value("a").unwrap(), KvsValue::from(1.0));
+        assert_eq!(kvs.try_get_value("a").unwrap(), Some(KvsValue::from(1.0)));
+        assert_eq!(kvs.try_get_value("missing").unwrap(), None);
+        assert_eq!(kvs.with_value("a", |v| v.cloned()).unwrap(), Some(KvsValue::from(1.0)));
+        assert_eq!(kvs.get_value_or("a", 2.0f64).unwrap(), 1.0);
+        assert_eq!(kvs.get_value_or("missing", 2.0f64).unwrap(), 2.0);
+        assert!(kvs
+            .get_value_or("a", true)
+            .is_err_and(|e| e == ErrorCode::ConversionFailed));
         assert_eq!(kvs.get_all_keys().unwrap(), vec!["a".to_string()]);
+        assert_eq!(kvs.entries().unwrap(), vec![("a".to_string(), KvsValue::from(1.0))]);
+        assert_eq!(kvs.entries_with_defaults().unwrap(), kvs.entries().unwrap());
         assert!(kvs.key_exists("a").unwrap());
+        assert_eq!(kvs.len().unwrap(), 1);
+        assert!(!kvs.is_empty().unwrap());
+        assert_eq!(kvs.keys_with_prefix("a").unwrap(), vec!["a".to_string()]);
+        assert!(kvs.keys_with_prefix("b").unwrap().is_empty());
+        assert!(kvs.compare_and_set("a", Some(&KvsValue::from(1.0)), 3.0).unwrap());
+        assert!(!kvs.compare_and_set("a", Some(&KvsValue::from(1.0)), 4.0).unwrap());
+        assert_eq!(kvs.swap("a", 1.0).unwrap(), Some(KvsValue::from(3.0)));
+        assert!(kvs.rename_key("a", "c", false).is_ok());
+        assert!(kvs
+            .rename_key("a", "c", false)
+            .is_err_and(|e| e == ErrorCode::KeyNotFound));
+        assert!(kvs.set_value("d", 5.0).is_ok());
+        assert!(kvs
+            .rename_key("c", "d", false)
+            .is_err_and(|e| e == ErrorCode::KeyAlreadyExists));
+        assert!(kvs.rename_key("d", "c", true).is_ok());
+        assert!(kvs.rename_key("c", "a", false).is_ok());
+        assert_eq!(kvs.get_value("a").unwrap(), KvsValue::from(5.0));
         assert!(kvs.remove_key("a").is_ok());
         assert!(!kvs.key_exists("a").unwrap());
+        assert!(kvs.set_value_with_ttl("b", 2.0, Duration::from_secs(1)).is_ok());
+        assert_eq!(kvs.get_value("b").unwrap(), KvsValue::from(2.0));
         assert_eq!(kvs.snapshot_count(), 0);
+        assert!(kvs.snapshots().is_empty());
         assert!(kvs.flush().is_ok());
+        assert_eq!(kvs.flush_with_id(), Ok(SnapshotId(0)));
+        assert!(kvs
+            .flush_chunked()
+            .is_ok_and(|mut f| f.step() == Ok(FlushProgress::Done)));
+        assert!(kvs.merge(KvsMap::new(), MergePolicy::PreferIncoming).is_ok());
+        assert_eq!(kvs.remove_prefix("b").unwrap(), 1);
+        assert!(kvs.compact(|_, _| true).is_ok());
+        assert_eq!(kvs.reset_to_defaults(), Ok(0));
         assert!(kvs.reset().is_ok());
+        assert!(kvs.is_empty().unwrap());
+        assert!(kvs.set_defaults(KvsMap::new()).is_ok());
+        assert!(kvs.clear_defaults().is_ok());
+        assert!(kvs.default_keys().unwrap().is_empty());
+        assert!(kvs
+            .materialize_default("a")
+            .is_err_and(|e| e == ErrorCode::KeyDefaultNotFound));
+        assert!(!kvs.contains_default("a").unwrap());
+        assert!(kvs.mark_corrupt("reason").is_ok());
+        assert!(kvs.clear_corrupt().is_ok());
+        assert!(kvs.snapshot_restore_and_flush(SnapshotId(1)).is_ok());
+        assert!(kvs.snapshot_unrestore().is_ok());
 
         // Failure case
         let kvs_fail = MockKvs {
@@ -159,16 +428,44 @@ mod tests {
             ..Default::default()
         };
         assert!(kvs_fail.set_value("a", 1.0).is_err());
+        assert!(kvs_fail.set_value_with_ttl("a", 1.0, Duration::from_secs(1)).is_err());
         assert!(kvs_fail.get_value("a").is_err());
+        assert!(kvs_fail.try_get_value("a").is_err());
+        assert!(kvs_fail.with_value("a", |v| v.is_some()).is_err());
+        assert!(kvs_fail.get_value_or("a", 1.0f64).is_err());
         assert!(kvs_fail.get_all_keys().is_err());
+        assert!(kvs_fail.entries().is_err());
+        assert!(kvs_fail.entries_with_defaults().is_err());
         assert!(kvs_fail.key_exists("a").is_err());
+        assert!(kvs_fail.len().is_err());
+        assert!(kvs_fail.is_empty().is_err());
         assert!(kvs_fail.remove_key("a").is_err());
+        assert!(kvs_fail.compare_and_set("a", None, 1.0).is_err());
+        assert!(kvs_fail.swap("a", 1.0).is_err());
+        assert!(kvs_fail.rename_key("a", "b", true).is_err());
+        assert!(kvs_fail.keys_with_prefix("a").is_err());
+        assert!(kvs_fail.remove_prefix("a").is_err());
+        assert!(kvs_fail.compact(|_, _| true).is_err());
         assert_eq!(kvs_fail.snapshot_count(), 9999);
         assert!(kvs_fail.flush().is_err());
+        assert!(kvs_fail.flush_with_id().is_err());
+        assert!(kvs_fail.flush_chunked().is_err());
+        assert!(kvs_fail.merge(KvsMap::new(), MergePolicy::PreferIncoming).is_err());
         assert!(kvs_fail.reset().is_err());
         assert!(kvs_fail.reset_key("a").is_err());
+        assert!(kvs_fail.materialize_default("a").is_err());
+        assert!(kvs_fail.reset_to_defaults().is_err());
         assert!(kvs_fail.get_default_value("a").is_err());
         assert!(kvs_fail.is_value_default("a").is_err());
+        assert!(kvs_fail.default_keys().is_err());
+        assert!(kvs_fail.contains_default("a").is_err());
+        assert!(kvs_fail.effective_defaults_report().is_err());
         assert!(kvs_fail.snapshot_restore(SnapshotId(0)).is_err());
+        assert!(kvs_fail.snapshot_restore_and_flush(SnapshotId(0)).is_err());
+        assert!(kvs_fail.snapshot_unrestore().is_err());
+        assert!(kvs_fail.set_defaults(KvsMap::new()).is_err());
+        assert!(kvs_fail.clear_defaults().is_err());
+        assert!(kvs_fail.mark_corrupt("reason").is_err());
+        assert!(kvs_fail.clear_corrupt().is_err());
     }
 }
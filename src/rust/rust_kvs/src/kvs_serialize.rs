@@ -12,6 +12,8 @@
 // *******************************************************************************
 use crate::error_code::ErrorCode;
 use crate::kvs_value::{KvsMap, KvsValue};
+use core::num::{NonZeroU32, NonZeroU64};
+use std::collections::{BTreeMap, HashMap};
 
 /// `KvsValue` serialization trait.
 /// Allows object to be serialized into `KvsValue`.
@@ -22,18 +24,6 @@ pub trait KvsSerialize {
     fn to_kvs(&self) -> Result<KvsValue, Self::Error>;
 }
 
-macro_rules! impl_kvs_serialize_for_t_unchecked_cast {
-    ($t:ty, $internal_t:ty, $variant:ident) => {
-        impl KvsSerialize for $t {
-            type Error = ErrorCode;
-
-            fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
-                Ok(KvsValue::$variant(self.clone() as $internal_t))
-            }
-        }
-    };
-}
-
 macro_rules! impl_kvs_serialize_for_t_checked_cast {
     ($t:ty, $internal_t:ty, $variant:ident) => {
         impl KvsSerialize for $t {
@@ -64,22 +54,28 @@ macro_rules! impl_kvs_serialize_for_t {
     };
 }
 
-impl_kvs_serialize_for_t_unchecked_cast!(i8, i32, I32);
-impl_kvs_serialize_for_t_unchecked_cast!(i16, i32, I32);
+impl_kvs_serialize_for_t!(i8, I8);
+impl_kvs_serialize_for_t!(i16, I16);
 impl_kvs_serialize_for_t!(i32, I32);
 impl_kvs_serialize_for_t!(i64, I64);
 impl_kvs_serialize_for_t_checked_cast!(isize, i64, I64);
-impl_kvs_serialize_for_t_unchecked_cast!(u8, u32, U32);
-impl_kvs_serialize_for_t_unchecked_cast!(u16, u32, U32);
+impl_kvs_serialize_for_t!(u8, U8);
+impl_kvs_serialize_for_t!(u16, U16);
 impl_kvs_serialize_for_t!(u32, U32);
 impl_kvs_serialize_for_t!(u64, U64);
 impl_kvs_serialize_for_t_checked_cast!(usize, u64, U64);
-impl_kvs_serialize_for_t_unchecked_cast!(f32, f64, F64);
+impl_kvs_serialize_for_t!(f32, F32);
 impl_kvs_serialize_for_t!(f64, F64);
 impl_kvs_serialize_for_t!(bool, Boolean);
 impl_kvs_serialize_for_t!(String, String);
-impl_kvs_serialize_for_t!(Vec<KvsValue>, Array);
-impl_kvs_serialize_for_t!(KvsMap, Object);
+
+impl KvsSerialize for KvsValue {
+    type Error = ErrorCode;
+
+    fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+        Ok(self.clone())
+    }
+}
 
 impl KvsSerialize for &str {
     type Error = ErrorCode;
@@ -97,6 +93,85 @@ impl KvsSerialize for () {
     }
 }
 
+impl KvsSerialize for char {
+    type Error = ErrorCode;
+
+    fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+        Ok(KvsValue::String(self.to_string()))
+    }
+}
+
+macro_rules! impl_kvs_serialize_for_non_zero {
+    ($t:ty, $variant:ident) => {
+        impl KvsSerialize for $t {
+            type Error = ErrorCode;
+
+            fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+                Ok(KvsValue::$variant(self.get()))
+            }
+        }
+    };
+}
+
+impl_kvs_serialize_for_non_zero!(NonZeroU32, U32);
+impl_kvs_serialize_for_non_zero!(NonZeroU64, U64);
+
+impl<T: KvsSerialize<Error = ErrorCode>> KvsSerialize for Vec<T> {
+    type Error = ErrorCode;
+
+    fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+        let array = self.iter().map(T::to_kvs).collect::<Result<Vec<_>, _>>()?;
+        Ok(KvsValue::Array(array))
+    }
+}
+
+impl<T: KvsSerialize<Error = ErrorCode>, const N: usize> KvsSerialize for [T; N] {
+    type Error = ErrorCode;
+
+    fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+        let array = self.iter().map(T::to_kvs).collect::<Result<Vec<_>, _>>()?;
+        Ok(KvsValue::Array(array))
+    }
+}
+
+macro_rules! impl_kvs_serialize_for_tuple {
+    ($($idx:tt $t:ident),+) => {
+        impl<$($t: KvsSerialize<Error = ErrorCode>),+> KvsSerialize for ($($t,)+) {
+            type Error = ErrorCode;
+
+            fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+                Ok(KvsValue::Array(vec![$(self.$idx.to_kvs()?),+]))
+            }
+        }
+    };
+}
+
+impl_kvs_serialize_for_tuple!(0 T0);
+impl_kvs_serialize_for_tuple!(0 T0, 1 T1);
+impl_kvs_serialize_for_tuple!(0 T0, 1 T1, 2 T2);
+impl_kvs_serialize_for_tuple!(0 T0, 1 T1, 2 T2, 3 T3);
+impl_kvs_serialize_for_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4);
+impl_kvs_serialize_for_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5);
+
+macro_rules! impl_kvs_serialize_for_map {
+    ($map:ident) => {
+        impl<T: KvsSerialize<Error = ErrorCode>> KvsSerialize for $map<String, T> {
+            type Error = ErrorCode;
+
+            fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+                let mut map = KvsMap::new();
+                for (key, value) in self {
+                    map.insert(key.clone(), value.to_kvs()?);
+                }
+                Ok(KvsValue::Object(map))
+            }
+        }
+    };
+}
+
+impl_kvs_serialize_for_map!(HashMap);
+impl_kvs_serialize_for_map!(BTreeMap);
+
 /// `KvsValue` deserialization trait.
 /// Allows object to be deserialized from `KvsValue`.
 pub trait KvsDeserialize: Sized {
@@ -107,29 +182,59 @@ pub trait KvsDeserialize: Sized {
 }
 
 macro_rules! impl_kvs_deserialize_for_t_checked_cast {
-    ($t:ty, $variant:ident) => {
+    ($t:ty, $via:ty) => {
         impl KvsDeserialize for $t {
             type Error = ErrorCode;
 
             fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
-                if let KvsValue::$variant(value) = kvs_value {
-                    if let Ok(casted) = <$t>::try_from(value.clone()) {
-                        Ok(casted)
-                    } else {
-                        Err(ErrorCode::DeserializationFailed(
-                            "KvsValue to value cast failed".to_string(),
-                        ))
-                    }
-                } else {
-                    Err(ErrorCode::DeserializationFailed(
+                let value = <$via>::from_kvs(kvs_value)?;
+                <$t>::try_from(value)
+                    .map_err(|_| ErrorCode::DeserializationFailed("KvsValue to value cast failed".to_string()))
+            }
+        }
+    };
+}
+
+/// Integer variants are converted same as by `TryFrom<&KvsValue>`, if value is in range.
+macro_rules! impl_kvs_deserialize_for_integer {
+    ($t:ty) => {
+        impl KvsDeserialize for $t {
+            type Error = ErrorCode;
+
+            fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+                match kvs_value {
+                    KvsValue::I8(_)
+                    | KvsValue::U8(_)
+                    | KvsValue::I16(_)
+                    | KvsValue::U16(_)
+                    | KvsValue::I32(_)
+                    | KvsValue::U32(_)
+                    | KvsValue::I64(_)
+                    | KvsValue::U64(_) => <$t>::try_from(kvs_value)
+                        .map_err(|_| ErrorCode::DeserializationFailed("KvsValue to value cast failed".to_string())),
+                    _ => Err(ErrorCode::DeserializationFailed(
                         "Invalid KvsValue variant provided".to_string(),
-                    ))
+                    )),
                 }
             }
         }
     };
 }
 
+/// Numeric variants are converted same as by `TryFrom<&KvsValue>`, if value is exactly representable.
+macro_rules! impl_kvs_deserialize_for_float {
+    ($t:ty) => {
+        impl KvsDeserialize for $t {
+            type Error = ErrorCode;
+
+            fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+                <$t>::try_from(kvs_value)
+                    .map_err(|_| ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string()))
+            }
+        }
+    };
+}
+
 macro_rules! impl_kvs_deserialize_for_t {
     ($t:ty, $variant:ident) => {
         impl KvsDeserialize for $t {
@@ -148,35 +253,26 @@ macro_rules! impl_kvs_deserialize_for_t {
     };
 }
 
-impl_kvs_deserialize_for_t_checked_cast!(i8, I32);
-impl_kvs_deserialize_for_t_checked_cast!(i16, I32);
-impl_kvs_deserialize_for_t!(i32, I32);
-impl_kvs_deserialize_for_t!(i64, I64);
-impl_kvs_deserialize_for_t_checked_cast!(isize, I64);
-impl_kvs_deserialize_for_t_checked_cast!(u8, U32);
-impl_kvs_deserialize_for_t_checked_cast!(u16, U32);
-impl_kvs_deserialize_for_t!(u32, U32);
-impl_kvs_deserialize_for_t!(u64, U64);
-impl_kvs_deserialize_for_t_checked_cast!(usize, U64);
-impl_kvs_deserialize_for_t!(f64, F64);
+impl_kvs_deserialize_for_integer!(i8);
+impl_kvs_deserialize_for_integer!(i16);
+impl_kvs_deserialize_for_integer!(i32);
+impl_kvs_deserialize_for_integer!(i64);
+impl_kvs_deserialize_for_t_checked_cast!(isize, i64);
+impl_kvs_deserialize_for_integer!(u8);
+impl_kvs_deserialize_for_integer!(u16);
+impl_kvs_deserialize_for_integer!(u32);
+impl_kvs_deserialize_for_integer!(u64);
+impl_kvs_deserialize_for_t_checked_cast!(usize, u64);
+impl_kvs_deserialize_for_float!(f32);
+impl_kvs_deserialize_for_float!(f64);
 impl_kvs_deserialize_for_t!(bool, Boolean);
 impl_kvs_deserialize_for_t!(String, String);
-impl_kvs_deserialize_for_t!(Vec<KvsValue>, Array);
-impl_kvs_deserialize_for_t!(KvsMap, Object);
 
-/// Edge case - `TryFrom` is not implemented for `f32`.
-/// Unchecked `as` conversion must be used.
-impl KvsDeserialize for f32 {
+impl KvsDeserialize for KvsValue {
     type Error = ErrorCode;
 
     fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
-        if let KvsValue::F64(value) = kvs_value {
-            Ok(*value as f32)
-        } else {
-            Err(ErrorCode::DeserializationFailed(
-                "Invalid KvsValue variant provided".to_string(),
-            ))
-        }
+        Ok(kvs_value.clone())
     }
 }
 
@@ -194,23 +290,223 @@ impl KvsDeserialize for () {
     }
 }
 
+impl KvsDeserialize for char {
+    type Error = ErrorCode;
+
+    fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+        let KvsValue::String(value) = kvs_value else {
+            return Err(ErrorCode::DeserializationFailed(
+                "Invalid KvsValue variant provided".to_string(),
+            ));
+        };
+
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(ErrorCode::DeserializationFailed(format!(
+                "Expected single character, got {} characters",
+                value.chars().count()
+            ))),
+        }
+    }
+}
+
+macro_rules! impl_kvs_deserialize_for_non_zero {
+    ($t:ty, $internal_t:ty) => {
+        impl KvsDeserialize for $t {
+            type Error = ErrorCode;
+
+            fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+                <$t>::new(<$internal_t>::from_kvs(kvs_value)?)
+                    .ok_or_else(|| ErrorCode::DeserializationFailed("Zero provided for non-zero value".to_string()))
+            }
+        }
+    };
+}
+
+impl_kvs_deserialize_for_non_zero!(NonZeroU32, u32);
+impl_kvs_deserialize_for_non_zero!(NonZeroU64, u64);
+
+/// Get array elements, checking array length.
+fn array_elements(kvs_value: &KvsValue, len: usize) -> Result<&[KvsValue], ErrorCode> {
+    let KvsValue::Array(array) = kvs_value else {
+        return Err(ErrorCode::DeserializationFailed(
+            "Invalid KvsValue variant provided".to_string(),
+        ));
+    };
+
+    if array.len() != len {
+        return Err(ErrorCode::DeserializationFailed(format!(
+            "Invalid array length: expected {len}, got {}",
+            array.len()
+        )));
+    }
+
+    Ok(array)
+}
+
+/// Deserialize array element, extending error message with element index.
+fn element_from_kvs<T: KvsDeserialize<Error = ErrorCode>>(elements: &[KvsValue], index: usize) -> Result<T, ErrorCode> {
+    T::from_kvs(&elements[index]).map_err(|e| match e {
+        ErrorCode::DeserializationFailed(msg) => ErrorCode::DeserializationFailed(format!("Element {index}: {msg}")),
+        other => other,
+    })
+}
+
+impl<T: KvsDeserialize<Error = ErrorCode>> KvsDeserialize for Vec<T> {
+    type Error = ErrorCode;
+
+    fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+        let KvsValue::Array(elements) = kvs_value else {
+            return Err(ErrorCode::DeserializationFailed(
+                "Invalid KvsValue variant provided".to_string(),
+            ));
+        };
+        (0..elements.len())
+            .map(|index| element_from_kvs(elements, index))
+            .collect()
+    }
+}
+
+impl<T: KvsDeserialize<Error = ErrorCode>, const N: usize> KvsDeserialize for [T; N] {
+    type Error = ErrorCode;
+
+    fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+        let elements = array_elements(kvs_value, N)?;
+        let values = (0..N)
+            .map(|index| element_from_kvs(elements, index))
+            .collect::<Result<Vec<T>, _>>()?;
+        values
+            .try_into()
+            .map_err(|_| ErrorCode::DeserializationFailed(format!("Invalid array length: expected {N}")))
+    }
+}
+
+macro_rules! impl_kvs_deserialize_for_tuple {
+    ($len:literal; $($idx:tt $t:ident),+) => {
+        impl<$($t: KvsDeserialize<Error = ErrorCode>),+> KvsDeserialize for ($($t,)+) {
+            type Error = ErrorCode;
+
+            fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+                let elements = array_elements(kvs_value, $len)?;
+                Ok(($(element_from_kvs::<$t>(elements, $idx)?,)+))
+            }
+        }
+    };
+}
+
+impl_kvs_deserialize_for_tuple!(1; 0 T0);
+impl_kvs_deserialize_for_tuple!(2; 0 T0, 1 T1);
+impl_kvs_deserialize_for_tuple!(3; 0 T0, 1 T1, 2 T2);
+impl_kvs_deserialize_for_tuple!(4; 0 T0, 1 T1, 2 T2, 3 T3);
+impl_kvs_deserialize_for_tuple!(5; 0 T0, 1 T1, 2 T2, 3 T3, 4 T4);
+impl_kvs_deserialize_for_tuple!(6; 0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5);
+
+macro_rules! impl_kvs_deserialize_for_map {
+    ($map:ident) => {
+        impl<T: KvsDeserialize<Error = ErrorCode>> KvsDeserialize for $map<String, T> {
+            type Error = ErrorCode;
+
+            fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+                let KvsValue::Object(map) = kvs_value else {
+                    return Err(ErrorCode::DeserializationFailed(
+                        "Invalid KvsValue variant provided".to_string(),
+                    ));
+                };
+
+                map.iter()
+                    .map(|(key, value)| match T::from_kvs(value) {
+                        Ok(value) => Ok((key.clone(), value)),
+                        Err(ErrorCode::DeserializationFailed(msg)) => {
+                            Err(ErrorCode::DeserializationFailed(format!("Key {key}: {msg}")))
+                        },
+                        Err(e) => Err(e),
+                    })
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_kvs_deserialize_for_map!(HashMap);
+impl_kvs_deserialize_for_map!(BTreeMap);
+
+/// Implement `KvsSerialize` and `KvsDeserialize` for a fieldless enum.
+///
+/// Variants are stored as `KvsValue::String` containing the variant name. All variants must be
+/// listed. Unknown names are reported as `ErrorCode::DeserializationFailed`.
+///
+/// ```
+/// use rust_kvs::kvs_enum;
+/// use rust_kvs::prelude::*;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum LogLevel {
+///     Debug,
+///     Info,
+///     Error,
+/// }
+///
+/// kvs_enum!(LogLevel { Debug, Info, Error });
+///
+/// assert_eq!(LogLevel::Info.to_kvs().unwrap(), KvsValue::from("Info"));
+/// assert_eq!(LogLevel::from_kvs(&KvsValue::from("Error")).unwrap(), LogLevel::Error);
+/// ```
+#[macro_export]
+macro_rules! kvs_enum {
+    // Error type is named explicitly, `Self::Error` is ambiguous for enums with `Error` variant.
+    ($t:ident { $($variant:ident),+ $(,)? }) => {
+        impl $crate::kvs_serialize::KvsSerialize for $t {
+            type Error = $crate::error_code::ErrorCode;
+
+            fn to_kvs(&self) -> ::core::result::Result<$crate::kvs_value::KvsValue, $crate::error_code::ErrorCode> {
+                let name = match self {
+                    $($t::$variant => stringify!($variant),)+
+                };
+                Ok($crate::kvs_value::KvsValue::String(name.to_string()))
+            }
+        }
+
+        impl $crate::kvs_serialize::KvsDeserialize for $t {
+            type Error = $crate::error_code::ErrorCode;
+
+            fn from_kvs(kvs_value: &$crate::kvs_value::KvsValue) -> ::core::result::Result<Self, $crate::error_code::ErrorCode> {
+                let $crate::kvs_value::KvsValue::String(name) = kvs_value else {
+                    return Err($crate::error_code::ErrorCode::DeserializationFailed(
+                        "Invalid KvsValue variant provided".to_string(),
+                    ));
+                };
+
+                match name.as_str() {
+                    $(stringify!($variant) => Ok($t::$variant),)+
+                    _ => Err($crate::error_code::ErrorCode::DeserializationFailed(format!(
+                        "Unknown {} variant: {name}",
+                        stringify!($t)
+                    ))),
+                }
+            }
+        }
+    };
+}
+
 #[cfg(test)]
 mod serialize_tests {
     use crate::kvs_serialize::KvsSerialize;
     use crate::kvs_value::{KvsMap, KvsValue};
+    use core::num::{NonZeroU32, NonZeroU64};
 
     #[test]
     fn test_i8_ok() {
         let value = i8::MIN;
         let kvs_value = value.to_kvs().unwrap();
-        assert_eq!(kvs_value, KvsValue::I32(value as i32));
+        assert_eq!(kvs_value, KvsValue::I8(value));
     }
 
     #[test]
     fn test_i16_ok() {
         let value = i16::MIN;
         let kvs_value = value.to_kvs().unwrap();
-        assert_eq!(kvs_value, KvsValue::I32(value as i32));
+        assert_eq!(kvs_value, KvsValue::I16(value));
     }
 
     #[test]
@@ -238,14 +534,14 @@ mod serialize_tests {
     fn test_u8_ok() {
         let value = u8::MIN;
         let kvs_value = value.to_kvs().unwrap();
-        assert_eq!(kvs_value, KvsValue::U32(value as u32));
+        assert_eq!(kvs_value, KvsValue::U8(value));
     }
 
     #[test]
     fn test_u16_ok() {
         let value = u16::MIN;
         let kvs_value = value.to_kvs().unwrap();
-        assert_eq!(kvs_value, KvsValue::U32(value as u32));
+        assert_eq!(kvs_value, KvsValue::U16(value));
     }
 
     #[test]
@@ -273,7 +569,7 @@ mod serialize_tests {
     fn test_f32_ok() {
         let value = f32::MIN;
         let kvs_value = value.to_kvs().unwrap();
-        assert_eq!(kvs_value, KvsValue::F64(value as f64));
+        assert_eq!(kvs_value, KvsValue::F32(value));
     }
 
     #[test]
@@ -326,19 +622,110 @@ mod serialize_tests {
         assert_eq!(kvs_value, KvsValue::Object(value));
     }
 
+    #[test]
+    fn test_fixed_array_ok() {
+        let value = [1i32, 2, 3];
+        let kvs_value = value.to_kvs().unwrap();
+        assert_eq!(
+            kvs_value,
+            KvsValue::Array(vec![KvsValue::I32(1), KvsValue::I32(2), KvsValue::I32(3)])
+        );
+    }
+
+    #[test]
+    fn test_tuple_ok() {
+        let value = (1u32, "two".to_string(), true);
+        let kvs_value = value.to_kvs().unwrap();
+        assert_eq!(
+            kvs_value,
+            KvsValue::Array(vec![
+                KvsValue::U32(1),
+                KvsValue::String("two".to_string()),
+                KvsValue::Boolean(true)
+            ])
+        );
+    }
+
     #[test]
     fn test_unit_ok() {
         let value = ();
         let kvs_value = value.to_kvs().unwrap();
         assert_eq!(kvs_value, KvsValue::Null);
     }
+
+    #[test]
+    fn test_char_ok() {
+        let value = 'ä';
+        let kvs_value = value.to_kvs().unwrap();
+        assert_eq!(kvs_value, KvsValue::String("ä".to_string()));
+    }
+
+    #[test]
+    fn test_non_zero_ok() {
+        let value = NonZeroU32::new(7).unwrap();
+        assert_eq!(value.to_kvs().unwrap(), KvsValue::U32(7));
+
+        let value = NonZeroU64::new(u64::MAX).unwrap();
+        assert_eq!(value.to_kvs().unwrap(), KvsValue::U64(u64::MAX));
+    }
 }
 
 #[cfg(test)]
 mod deserialize_tests {
     use crate::error_code::ErrorCode;
-    use crate::kvs_serialize::KvsDeserialize;
+    use crate::kvs_serialize::{KvsDeserialize, KvsSerialize};
     use crate::kvs_value::{KvsMap, KvsValue};
+    use core::num::{NonZeroU32, NonZeroU64};
+    use std::collections::{BTreeMap, HashMap};
+
+    /// Nested serializable object, same as in `custom_types` example.
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    impl KvsSerialize for Point {
+        type Error = ErrorCode;
+
+        fn to_kvs(&self) -> Result<KvsValue, Self::Error> {
+            let mut map = KvsMap::new();
+            map.insert("x".to_string(), self.x.to_kvs()?);
+            map.insert("y".to_string(), self.y.to_kvs()?);
+            map.to_kvs()
+        }
+    }
+
+    impl KvsDeserialize for Point {
+        type Error = ErrorCode;
+
+        fn from_kvs(kvs_value: &KvsValue) -> Result<Self, Self::Error> {
+            let field = |name: &str| {
+                let KvsValue::Object(map) = kvs_value else {
+                    return Err(ErrorCode::DeserializationFailed(
+                        "Invalid KvsValue variant provided".to_string(),
+                    ));
+                };
+                f64::from_kvs(
+                    map.get(name)
+                        .ok_or(ErrorCode::DeserializationFailed("Field not found".to_string()))?,
+                )
+            };
+            Ok(Point {
+                x: field("x")?,
+                y: field("y")?,
+            })
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum LogLevel {
+        Debug,
+        Info,
+        Error,
+    }
+
+    crate::kvs_enum!(LogLevel { Debug, Info, Error });
 
     // NOTE: Only internally up-casted types require out of range tests.
     // For other types it's not possible to represent such scenario.
@@ -413,6 +800,14 @@ mod deserialize_tests {
         assert_eq!(value, *kvs_value.get::<i64>().unwrap());
     }
 
+    #[test]
+    fn test_i64_widening() {
+        assert_eq!(i64::from_kvs(&KvsValue::I32(-1)).unwrap(), -1);
+        assert_eq!(i64::from_kvs(&KvsValue::U32(u32::MAX)).unwrap(), i64::from(u32::MAX));
+        assert!(i64::from_kvs(&KvsValue::U64(u64::MAX))
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("KvsValue to value cast failed".to_string())));
+    }
+
     #[test]
     fn test_i64_invalid_variant() {
         let kvs_value = KvsValue::String("invalid string".to_string());
@@ -460,6 +855,14 @@ mod deserialize_tests {
         );
     }
 
+    #[test]
+    fn test_small_int_round_trip() {
+        assert_eq!(u8::from_kvs(&200u8.to_kvs().unwrap()).unwrap(), 200);
+        assert_eq!(i8::from_kvs(&(-8i8).to_kvs().unwrap()).unwrap(), -8);
+        assert_eq!(u16::from_kvs(&60000u16.to_kvs().unwrap()).unwrap(), 60000);
+        assert_eq!(i16::from_kvs(&(-1600i16).to_kvs().unwrap()).unwrap(), -1600);
+    }
+
     #[test]
     fn test_u16_ok() {
         let kvs_value = KvsValue::U32(u16::MIN as u32);
@@ -529,6 +932,21 @@ mod deserialize_tests {
             .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
     }
 
+    #[test]
+    fn test_f32_ok() {
+        let kvs_value = KvsValue::F32(0.1);
+        let value = f32::from_kvs(&kvs_value).unwrap();
+        assert_eq!(value, 0.1f32);
+    }
+
+    #[test]
+    fn test_f32_invalid_variant() {
+        let kvs_value = KvsValue::F64(0.1);
+        let result = f32::from_kvs(&kvs_value);
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
+
     #[test]
     fn test_bool_ok() {
         let kvs_value = KvsValue::Boolean(true);
@@ -566,18 +984,46 @@ mod deserialize_tests {
             KvsValue::String("two".to_string()),
             KvsValue::String("three".to_string()),
         ]);
-        let value = Vec::from_kvs(&kvs_value).unwrap();
+        let value = Vec::<KvsValue>::from_kvs(&kvs_value).unwrap();
         assert_eq!(value, *kvs_value.get::<Vec<KvsValue>>().unwrap());
     }
 
     #[test]
     fn test_array_invalid_variant() {
         let kvs_value = KvsValue::String("invalid string".to_string());
-        let result = Vec::from_kvs(&kvs_value);
+        let result = Vec::<KvsValue>::from_kvs(&kvs_value);
         assert!(result
             .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
     }
 
+    #[test]
+    fn test_typed_array_round_trip() {
+        let value = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+        let kvs_value = value.to_kvs().unwrap();
+        assert_eq!(
+            kvs_value,
+            KvsValue::Array(vec![
+                KvsValue::String("one".to_string()),
+                KvsValue::String("two".to_string()),
+                KvsValue::String("three".to_string()),
+            ])
+        );
+        assert_eq!(Vec::<String>::from_kvs(&kvs_value).unwrap(), value);
+    }
+
+    #[test]
+    fn test_typed_array_mixed_types() {
+        let kvs_value = KvsValue::Array(vec![
+            KvsValue::I32(1),
+            KvsValue::I32(2),
+            KvsValue::String("three".to_string()),
+        ]);
+        let result = Vec::<i32>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Element 2: Invalid KvsValue variant provided".to_string())
+        ));
+    }
+
     #[test]
     fn test_object_ok() {
         let kvs_value = KvsValue::Object(KvsMap::from([
@@ -611,4 +1057,188 @@ mod deserialize_tests {
         assert!(result
             .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
     }
+
+    #[test]
+    fn test_char_round_trip() {
+        for value in ['a', 'ä', '😀'] {
+            let kvs_value = value.to_kvs().unwrap();
+            assert_eq!(char::from_kvs(&kvs_value).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_char_invalid_length() {
+        let result = char::from_kvs(&KvsValue::String("ab".to_string()));
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Expected single character, got 2 characters".to_string())
+        ));
+
+        let result = char::from_kvs(&KvsValue::String(String::new()));
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Expected single character, got 0 characters".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_char_invalid_variant() {
+        let result = char::from_kvs(&KvsValue::U32(97));
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
+
+    #[test]
+    fn test_non_zero_round_trip() {
+        let value = NonZeroU32::new(42).unwrap();
+        assert_eq!(NonZeroU32::from_kvs(&value.to_kvs().unwrap()).unwrap(), value);
+
+        let value = NonZeroU64::new(1 << 40).unwrap();
+        assert_eq!(NonZeroU64::from_kvs(&value.to_kvs().unwrap()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_non_zero_zero() {
+        let result = NonZeroU32::from_kvs(&KvsValue::U32(0));
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Zero provided for non-zero value".to_string())));
+
+        let result = NonZeroU64::from_kvs(&KvsValue::U64(0));
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Zero provided for non-zero value".to_string())));
+    }
+
+    #[test]
+    fn test_non_zero_invalid_variant() {
+        let result = NonZeroU32::from_kvs(&KvsValue::F64(1.0));
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
+
+    #[test]
+    fn test_fixed_array_ok() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::I32(1), KvsValue::I32(2), KvsValue::I32(3)]);
+        let value = <[i32; 3]>::from_kvs(&kvs_value).unwrap();
+        assert_eq!(value, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_fixed_array_invalid_variant() {
+        let kvs_value = KvsValue::String("invalid string".to_string());
+        let result = <[i32; 3]>::from_kvs(&kvs_value);
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
+
+    #[test]
+    fn test_fixed_array_invalid_length() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::I32(1), KvsValue::I32(2)]);
+        let result = <[i32; 3]>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Invalid array length: expected 3, got 2".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_fixed_array_invalid_element() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::I32(1), KvsValue::Boolean(true), KvsValue::I32(3)]);
+        let result = <[i32; 3]>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Element 1: Invalid KvsValue variant provided".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_tuple_ok() {
+        let kvs_value = KvsValue::Array(vec![
+            KvsValue::U32(1),
+            KvsValue::String("two".to_string()),
+            KvsValue::Boolean(true),
+        ]);
+        let value = <(u8, String, bool)>::from_kvs(&kvs_value).unwrap();
+        assert_eq!(value, (1, "two".to_string(), true));
+    }
+
+    #[test]
+    fn test_tuple_invalid_length() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::U32(1), KvsValue::Boolean(true)]);
+        let result = <(u32, String, bool)>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Invalid array length: expected 3, got 2".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_hash_map_point_round_trip() {
+        let value = HashMap::from([
+            ("first".to_string(), Point { x: 1.0, y: 2.0 }),
+            ("second".to_string(), Point { x: -3.5, y: 4.25 }),
+        ]);
+        let kvs_value = value.to_kvs().unwrap();
+        assert!(matches!(kvs_value, KvsValue::Object(ref map) if map.len() == 2));
+
+        let deserialized = HashMap::<String, Point>::from_kvs(&kvs_value).unwrap();
+        assert_eq!(deserialized, value);
+    }
+
+    #[test]
+    fn test_btree_map_ok() {
+        let kvs_value = KvsValue::Object(KvsMap::from([
+            ("b".to_string(), KvsValue::I32(2)),
+            ("a".to_string(), KvsValue::I32(1)),
+        ]));
+        let value = BTreeMap::<String, i32>::from_kvs(&kvs_value).unwrap();
+        assert_eq!(value, BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]));
+        assert_eq!(value.to_kvs().unwrap(), kvs_value);
+    }
+
+    #[test]
+    fn test_map_invalid_variant() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::I32(1)]);
+        let result = HashMap::<String, i32>::from_kvs(&kvs_value);
+        assert!(result
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
+
+    #[test]
+    fn test_map_invalid_value() {
+        let kvs_value = KvsValue::Object(KvsMap::from([
+            ("valid".to_string(), KvsValue::I32(1)),
+            ("invalid".to_string(), KvsValue::String("one".to_string())),
+        ]));
+        let result = BTreeMap::<String, i32>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Key invalid: Invalid KvsValue variant provided".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_tuple_invalid_element() {
+        let kvs_value = KvsValue::Array(vec![KvsValue::U32(1), KvsValue::U32(u32::MAX)]);
+        let result = <(u32, u8)>::from_kvs(&kvs_value);
+        assert!(result.is_err_and(
+            |e| e == ErrorCode::DeserializationFailed("Element 1: KvsValue to value cast failed".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_enum_round_trip() {
+        for level in [LogLevel::Debug, LogLevel::Info, LogLevel::Error] {
+            let kvs_value = level.to_kvs().unwrap();
+            assert_eq!(LogLevel::from_kvs(&kvs_value).unwrap(), level);
+        }
+        assert_eq!(LogLevel::Info.to_kvs().unwrap(), KvsValue::String("Info".to_string()));
+    }
+
+    #[test]
+    fn test_enum_unknown_variant() {
+        let kvs_value = KvsValue::String("Trace".to_string());
+        assert!(LogLevel::from_kvs(&kvs_value)
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Unknown LogLevel variant: Trace".to_string())));
+    }
+
+    #[test]
+    fn test_enum_invalid_variant() {
+        let kvs_value = KvsValue::U32(1);
+        assert!(LogLevel::from_kvs(&kvs_value)
+            .is_err_and(|e| e == ErrorCode::DeserializationFailed("Invalid KvsValue variant provided".to_string())));
+    }
 }
@@ -10,15 +10,36 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 // *******************************************************************************
+use crate::error_code::ErrorCode;
+use core::cmp::Ordering;
 use core::convert::TryFrom;
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 /// Key-value storage map type
 pub type KvsMap = HashMap<String, KvsValue>;
 
+/// Key expiry map type
+///
+/// Expiry is wall-clock based (`SystemTime`), so it remains meaningful across restarts.
+/// Adjustments of the system clock affect expiry accordingly.
+pub type KvsExpiryMap = HashMap<String, SystemTime>;
+
 /// Key-value-storage value
 #[derive(Clone, Debug, PartialEq)]
 pub enum KvsValue {
+    /// 8-bit signed integer
+    I8(i8),
+
+    /// 8-bit unsigned integer
+    U8(u8),
+
+    /// 16-bit signed integer
+    I16(i16),
+
+    /// 16-bit unsigned integer
+    U16(u16),
+
     /// 32-bit signed integer
     I32(i32),
 
@@ -31,6 +52,9 @@ pub enum KvsValue {
     /// 64-bit unsigned integer
     U64(u64),
 
+    /// 32-bit float
+    F32(f32),
+
     /// 64-bit float
     F64(f64),
 
@@ -50,6 +74,86 @@ pub enum KvsValue {
     Object(KvsMap),
 }
 
+/// Type of a `KvsValue`, without the contained value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KvsValueType {
+    /// 8-bit signed integer
+    I8,
+
+    /// 8-bit unsigned integer
+    U8,
+
+    /// 16-bit signed integer
+    I16,
+
+    /// 16-bit unsigned integer
+    U16,
+
+    /// 32-bit signed integer
+    I32,
+
+    /// 32-bit unsigned integer
+    U32,
+
+    /// 64-bit signed integer
+    I64,
+
+    /// 64-bit unsigned integer
+    U64,
+
+    /// 32-bit float
+    F32,
+
+    /// 64-bit float
+    F64,
+
+    /// Boolean
+    Boolean,
+
+    /// String
+    String,
+
+    /// Null
+    Null,
+
+    /// Array
+    Array,
+
+    /// Object
+    Object,
+}
+
+impl KvsValueType {
+    /// Get type of a value, same as [`KvsValue::value_type`].
+    ///
+    /// # Parameters
+    ///   * `value`: Value to get type of
+    pub fn of(value: &KvsValue) -> KvsValueType {
+        value.value_type()
+    }
+
+    /// Get name of the type, same as name of the `KvsValue` variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KvsValueType::I8 => "I8",
+            KvsValueType::U8 => "U8",
+            KvsValueType::I16 => "I16",
+            KvsValueType::U16 => "U16",
+            KvsValueType::I32 => "I32",
+            KvsValueType::U32 => "U32",
+            KvsValueType::I64 => "I64",
+            KvsValueType::U64 => "U64",
+            KvsValueType::F32 => "F32",
+            KvsValueType::F64 => "F64",
+            KvsValueType::Boolean => "Boolean",
+            KvsValueType::String => "String",
+            KvsValueType::Null => "Null",
+            KvsValueType::Array => "Array",
+            KvsValueType::Object => "Object",
+        }
+    }
+}
+
 // Macro to implement From<T> for KvsValue for each supported type/variant.
 // This allows concise and consistent conversion from basic Rust types to KvsValue.
 macro_rules! impl_from_t_for_kvs_value {
@@ -62,10 +166,15 @@ macro_rules! impl_from_t_for_kvs_value {
     };
 }
 
+impl_from_t_for_kvs_value!(i8, I8);
+impl_from_t_for_kvs_value!(u8, U8);
+impl_from_t_for_kvs_value!(i16, I16);
+impl_from_t_for_kvs_value!(u16, U16);
 impl_from_t_for_kvs_value!(i32, I32);
 impl_from_t_for_kvs_value!(u32, U32);
 impl_from_t_for_kvs_value!(i64, I64);
 impl_from_t_for_kvs_value!(u64, U64);
+impl_from_t_for_kvs_value!(f32, F32);
 impl_from_t_for_kvs_value!(f64, F64);
 impl_from_t_for_kvs_value!(bool, Boolean);
 impl_from_t_for_kvs_value!(String, String);
@@ -101,16 +210,85 @@ macro_rules! impl_tryfrom_kvs_value_to_t {
     };
 }
 
-impl_tryfrom_kvs_value_to_t!(i32, I32);
-impl_tryfrom_kvs_value_to_t!(u32, U32);
-impl_tryfrom_kvs_value_to_t!(i64, I64);
-impl_tryfrom_kvs_value_to_t!(u64, U64);
-impl_tryfrom_kvs_value_to_t!(f64, F64);
+// Macro to implement TryFrom<&KvsValue> for integer types.
+//
+// Numeric variants are converted if the value fits losslessly:
+//
+// | Target                  | Accepted variants                                   |
+// |-------------------------|-----------------------------------------------------|
+// | `i8`, `u8`, `i16`,`u16` | Any integer variant with value in range             |
+// | `i32`, `u32`            | Any integer variant with value in range             |
+// | `i64`, `u64`            | Any integer variant with value in range             |
+// | `f32`                   | `F32`, `I8`, `U8`, `I16`, `U16`                     |
+// | `f64`                   | `F32`, `F64`, `I8`, `U8`, `I16`, `U16`, `I32`, `U32` |
+//
+// Floats are never converted to integers.
+macro_rules! impl_tryfrom_kvs_value_to_int {
+    ($to:ty) => {
+        impl TryFrom<&KvsValue> for $to {
+            type Error = String;
+            fn try_from(value: &KvsValue) -> Result<Self, Self::Error> {
+                let converted = match value {
+                    KvsValue::I8(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::U8(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::I16(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::U16(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::I32(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::U32(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::I64(n) => <$to>::try_from(*n).ok(),
+                    KvsValue::U64(n) => <$to>::try_from(*n).ok(),
+                    _ => return Err(format!("KvsValue is not a {}", stringify!($to))),
+                };
+                converted.ok_or_else(|| format!("KvsValue out of range of {}", stringify!($to)))
+            }
+        }
+    };
+}
+
+impl_tryfrom_kvs_value_to_int!(i8);
+impl_tryfrom_kvs_value_to_int!(u8);
+impl_tryfrom_kvs_value_to_int!(i16);
+impl_tryfrom_kvs_value_to_int!(u16);
+impl_tryfrom_kvs_value_to_int!(i32);
+impl_tryfrom_kvs_value_to_int!(u32);
+impl_tryfrom_kvs_value_to_int!(i64);
+impl_tryfrom_kvs_value_to_int!(u64);
 impl_tryfrom_kvs_value_to_t!(bool, Boolean);
 impl_tryfrom_kvs_value_to_t!(String, String);
 impl_tryfrom_kvs_value_to_t!(Vec<KvsValue>, Array);
 impl_tryfrom_kvs_value_to_t!(HashMap<String, KvsValue>, Object);
 
+impl TryFrom<&KvsValue> for f32 {
+    type Error = String;
+    fn try_from(value: &KvsValue) -> Result<Self, Self::Error> {
+        match value {
+            KvsValue::F32(n) => Ok(*n),
+            KvsValue::I8(n) => Ok(f32::from(*n)),
+            KvsValue::U8(n) => Ok(f32::from(*n)),
+            KvsValue::I16(n) => Ok(f32::from(*n)),
+            KvsValue::U16(n) => Ok(f32::from(*n)),
+            _ => Err("KvsValue is not a f32".to_string()),
+        }
+    }
+}
+
+impl TryFrom<&KvsValue> for f64 {
+    type Error = String;
+    fn try_from(value: &KvsValue) -> Result<Self, Self::Error> {
+        match value {
+            KvsValue::F64(n) => Ok(*n),
+            KvsValue::F32(n) => Ok(f64::from(*n)),
+            KvsValue::I8(n) => Ok(f64::from(*n)),
+            KvsValue::U8(n) => Ok(f64::from(*n)),
+            KvsValue::I16(n) => Ok(f64::from(*n)),
+            KvsValue::U16(n) => Ok(f64::from(*n)),
+            KvsValue::I32(n) => Ok(f64::from(*n)),
+            KvsValue::U32(n) => Ok(f64::from(*n)),
+            _ => Err("KvsValue is not a f64".to_string()),
+        }
+    }
+}
+
 impl TryFrom<&KvsValue> for () {
     type Error = &'static str;
     fn try_from(value: &KvsValue) -> Result<Self, Self::Error> {
@@ -130,6 +308,102 @@ impl KvsValue {
     pub fn get<T: KvsValueGet>(&self) -> Option<&T> {
         T::get_inner_value(self)
     }
+
+    /// Get type of the value.
+    pub fn value_type(&self) -> KvsValueType {
+        match self {
+            KvsValue::I8(_) => KvsValueType::I8,
+            KvsValue::U8(_) => KvsValueType::U8,
+            KvsValue::I16(_) => KvsValueType::I16,
+            KvsValue::U16(_) => KvsValueType::U16,
+            KvsValue::I32(_) => KvsValueType::I32,
+            KvsValue::U32(_) => KvsValueType::U32,
+            KvsValue::I64(_) => KvsValueType::I64,
+            KvsValue::U64(_) => KvsValueType::U64,
+            KvsValue::F32(_) => KvsValueType::F32,
+            KvsValue::F64(_) => KvsValueType::F64,
+            KvsValue::Boolean(_) => KvsValueType::Boolean,
+            KvsValue::String(_) => KvsValueType::String,
+            KvsValue::Null => KvsValueType::Null,
+            KvsValue::Array(_) => KvsValueType::Array,
+            KvsValue::Object(_) => KvsValueType::Object,
+        }
+    }
+
+    /// Get name of the value type, e.g. for error messages.
+    pub fn type_name(&self) -> &'static str {
+        self.value_type().name()
+    }
+
+    /// Compare values.
+    ///
+    /// Numeric variants are compared by numeric value, also across variants, e.g. `I32(-1)` is
+    /// less than `U64(0)` and equal to `F64(-1.0)`. Integers are compared with floats exactly,
+    /// without rounding. Strings are compared lexicographically, `false` is less than `true`.
+    ///
+    /// # Parameters
+    ///   * `other`: Value to compare with
+    ///
+    /// # Return Values
+    ///   * Some: Ordering of the values
+    ///   * None: Values are not comparable, e.g. `NaN`, different non-numeric variants or
+    ///     arrays and objects
+    pub fn compare(&self, other: &KvsValue) -> Option<Ordering> {
+        match (self, other) {
+            (KvsValue::String(a), KvsValue::String(b)) => Some(a.cmp(b)),
+            (KvsValue::Boolean(a), KvsValue::Boolean(b)) => Some(a.cmp(b)),
+            (KvsValue::Null, KvsValue::Null) => Some(Ordering::Equal),
+            _ => match (self.as_number()?, other.as_number()?) {
+                (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+                (Number::Float(a), Number::Float(b)) => a.partial_cmp(&b),
+                (Number::Int(a), Number::Float(b)) => compare_int_float(a, b),
+                (Number::Float(a), Number::Int(b)) => compare_int_float(b, a).map(Ordering::reverse),
+            },
+        }
+    }
+
+    /// Get numeric value, `None` for non-numeric variants.
+    fn as_number(&self) -> Option<Number> {
+        match *self {
+            KvsValue::I8(n) => Some(Number::Int(n.into())),
+            KvsValue::U8(n) => Some(Number::Int(n.into())),
+            KvsValue::I16(n) => Some(Number::Int(n.into())),
+            KvsValue::U16(n) => Some(Number::Int(n.into())),
+            KvsValue::I32(n) => Some(Number::Int(n.into())),
+            KvsValue::U32(n) => Some(Number::Int(n.into())),
+            KvsValue::I64(n) => Some(Number::Int(n.into())),
+            KvsValue::U64(n) => Some(Number::Int(n.into())),
+            KvsValue::F32(n) => Some(Number::Float(n.into())),
+            KvsValue::F64(n) => Some(Number::Float(n)),
+            _ => None,
+        }
+    }
+}
+
+/// Numeric value of `KvsValue`, integers of all variants fit into `i128`.
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+/// Compare integer with float exactly, `None` if float is NaN.
+fn compare_int_float(int: i128, float: f64) -> Option<Ordering> {
+    // Bound is 2^127, exactly representable as `f64`.
+    const BOUND: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+    if float.is_nan() {
+        None
+    } else if float >= BOUND {
+        Some(Ordering::Less)
+    } else if float < -BOUND {
+        Some(Ordering::Greater)
+    } else {
+        // Integral part is in range, so cast is exact.
+        let trunc = float.trunc();
+        match int.cmp(&(trunc as i128)) {
+            Ordering::Equal => 0.0.partial_cmp(&(float - trunc)),
+            ordering => Some(ordering),
+        }
+    }
 }
 
 macro_rules! impl_kvs_get_inner_value {
@@ -144,7 +418,12 @@ macro_rules! impl_kvs_get_inner_value {
         }
     };
 }
+impl_kvs_get_inner_value!(f32, F32);
 impl_kvs_get_inner_value!(f64, F64);
+impl_kvs_get_inner_value!(i8, I8);
+impl_kvs_get_inner_value!(u8, U8);
+impl_kvs_get_inner_value!(i16, I16);
+impl_kvs_get_inner_value!(u16, U16);
 impl_kvs_get_inner_value!(i32, I32);
 impl_kvs_get_inner_value!(u32, U32);
 impl_kvs_get_inner_value!(i64, I64);
@@ -163,9 +442,127 @@ impl KvsValueGet for () {
     }
 }
 
+/// Conversion between nested and flat dotted-key maps
+///
+/// Nested `Object` values are walked recursively, `{"config": {"server": {"port": 8080}}}` maps
+/// to `{"config.server.port": 8080}`. Array elements are addressed by index, `arr.0`, `arr.1`.
+/// Empty objects and arrays are kept as leaf values.
+pub trait KvsMapFlatten {
+    /// Flatten nested map into dotted keys with scalar leaves.
+    ///
+    /// # Return Values
+    ///   * Ok: Flat map
+    ///   * `ErrorCode::InvalidKey`: Key contains `.`, so it cannot be flattened unambiguously
+    fn flatten(&self) -> Result<HashMap<String, KvsValue>, ErrorCode>;
+
+    /// Rebuild nesting from dotted keys.
+    ///
+    /// Nodes with children named exactly `0..n` are rebuilt as arrays, other nodes as objects.
+    /// Objects with such keys therefore don't round-trip.
+    ///
+    /// # Return Values
+    ///   * Ok: Nested map
+    ///   * `ErrorCode::InvalidKey`: Key is both a leaf and a prefix of another key
+    fn unflatten(&self) -> Result<KvsMap, ErrorCode>;
+}
+
+/// Intermediate node used by `unflatten`.
+enum FlatNode {
+    Leaf(KvsValue),
+    Branch(HashMap<String, FlatNode>),
+}
+
+impl FlatNode {
+    fn into_value(self) -> KvsValue {
+        match self {
+            FlatNode::Leaf(value) => value,
+            FlatNode::Branch(children) => {
+                let is_array = (0..children.len()).all(|i| children.contains_key(&i.to_string()));
+                if is_array {
+                    let mut children = children;
+                    KvsValue::Array(
+                        (0..children.len())
+                            .filter_map(|i| children.remove(&i.to_string()))
+                            .map(FlatNode::into_value)
+                            .collect(),
+                    )
+                } else {
+                    KvsValue::Object(children.into_iter().map(|(k, v)| (k, v.into_value())).collect())
+                }
+            },
+        }
+    }
+}
+
+fn flatten_value(path: String, value: &KvsValue, out: &mut HashMap<String, KvsValue>) -> Result<(), ErrorCode> {
+    match value {
+        KvsValue::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                if key.contains('.') {
+                    eprintln!("error: key \"{path}.{key}\" cannot be flattened");
+                    return Err(ErrorCode::InvalidKey);
+                }
+                flatten_value(format!("{path}.{key}"), value, out)?;
+            }
+        },
+        KvsValue::Array(arr) if !arr.is_empty() => {
+            for (index, value) in arr.iter().enumerate() {
+                flatten_value(format!("{path}.{index}"), value, out)?;
+            }
+        },
+        _ => {
+            let _ = out.insert(path, value.clone());
+        },
+    }
+    Ok(())
+}
+
+impl KvsMapFlatten for KvsMap {
+    fn flatten(&self) -> Result<HashMap<String, KvsValue>, ErrorCode> {
+        let mut out = HashMap::new();
+        for (key, value) in self {
+            if key.contains('.') {
+                eprintln!("error: key \"{key}\" cannot be flattened");
+                return Err(ErrorCode::InvalidKey);
+            }
+            flatten_value(key.clone(), value, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    fn unflatten(&self) -> Result<KvsMap, ErrorCode> {
+        let mut root: HashMap<String, FlatNode> = HashMap::new();
+        for (key, value) in self {
+            let mut segments: Vec<&str> = key.split('.').collect();
+            let leaf = segments.pop().unwrap_or_default();
+            let mut node = &mut root;
+            for segment in segments {
+                let entry = node
+                    .entry(segment.to_string())
+                    .or_insert_with(|| FlatNode::Branch(HashMap::new()));
+                node = match entry {
+                    FlatNode::Branch(children) => children,
+                    FlatNode::Leaf(_) => {
+                        eprintln!("error: key \"{key}\" conflicts with a leaf value");
+                        return Err(ErrorCode::InvalidKey);
+                    },
+                };
+            }
+            if node.contains_key(leaf) {
+                eprintln!("error: key \"{key}\" conflicts with another key");
+                return Err(ErrorCode::InvalidKey);
+            }
+            let _ = node.insert(leaf.to_string(), FlatNode::Leaf(value.clone()));
+        }
+        Ok(root.into_iter().map(|(k, v)| (k, v.into_value())).collect())
+    }
+}
+
 #[cfg(test)]
 mod kvs_value_tests {
-    use crate::kvs_value::{KvsMap, KvsValue};
+    use crate::error_code::ErrorCode;
+    use crate::kvs_value::{KvsMap, KvsMapFlatten, KvsValue, KvsValueType};
+    use core::cmp::Ordering;
 
     #[test]
     fn test_i32_from_ok() {
@@ -212,11 +609,19 @@ mod kvs_value_tests {
 
     #[test]
     fn test_u32_tryfrom_invalid_type() {
-        let v = KvsValue::from(123i32);
+        let v = KvsValue::from(1.0f32);
         let err = u32::try_from(&v).unwrap_err();
         assert_eq!(err, "KvsValue is not a u32");
     }
 
+    #[test]
+    fn test_u32_tryfrom_out_of_range() {
+        let err = u32::try_from(&KvsValue::from(-1i32)).unwrap_err();
+        assert_eq!(err, "KvsValue out of range of u32");
+        let err = u32::try_from(&KvsValue::from(u64::from(u32::MAX) + 1)).unwrap_err();
+        assert_eq!(err, "KvsValue out of range of u32");
+    }
+
     #[test]
     fn test_u32_get_ok() {
         let v = KvsValue::from(456u32);
@@ -274,11 +679,97 @@ mod kvs_value_tests {
 
     #[test]
     fn test_u64_tryfrom_invalid_type() {
-        let v = KvsValue::from(123i32);
+        let v = KvsValue::from(1.0f64);
         let err = u64::try_from(&v).unwrap_err();
         assert_eq!(err, "KvsValue is not a u64");
     }
 
+    #[test]
+    fn test_integer_tryfrom_widening() {
+        assert_eq!(i64::try_from(&KvsValue::from(-123i32)).unwrap(), -123);
+        assert_eq!(i64::try_from(&KvsValue::from(u32::MAX)).unwrap(), i64::from(u32::MAX));
+        assert_eq!(u64::try_from(&KvsValue::from(u32::MAX)).unwrap(), u64::from(u32::MAX));
+        assert_eq!(u64::try_from(&KvsValue::from(7i64)).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_integer_tryfrom_narrowing() {
+        assert_eq!(i32::try_from(&KvsValue::from(-5i64)).unwrap(), -5);
+        assert_eq!(u32::try_from(&KvsValue::from(5u64)).unwrap(), 5);
+        assert_eq!(
+            i32::try_from(&KvsValue::from(i64::MAX)).unwrap_err(),
+            "KvsValue out of range of i32"
+        );
+        assert_eq!(
+            i64::try_from(&KvsValue::from(u64::MAX)).unwrap_err(),
+            "KvsValue out of range of i64"
+        );
+        assert_eq!(
+            u64::try_from(&KvsValue::from(-1i64)).unwrap_err(),
+            "KvsValue out of range of u64"
+        );
+    }
+
+    #[test]
+    fn test_small_int_from_ok() {
+        assert!(matches!(KvsValue::from(-8i8), KvsValue::I8(-8)));
+        assert!(matches!(KvsValue::from(200u8), KvsValue::U8(200)));
+        assert!(matches!(KvsValue::from(-1600i16), KvsValue::I16(-1600)));
+        assert!(matches!(KvsValue::from(60000u16), KvsValue::U16(60000)));
+    }
+
+    #[test]
+    fn test_small_int_tryfrom() {
+        assert_eq!(u8::try_from(&KvsValue::from(200u8)).unwrap(), 200);
+        assert_eq!(u8::try_from(&KvsValue::from(200u32)).unwrap(), 200);
+        assert_eq!(i64::try_from(&KvsValue::from(-8i8)).unwrap(), -8);
+        assert_eq!(u32::try_from(&KvsValue::from(60000u16)).unwrap(), 60000);
+        assert_eq!(f32::try_from(&KvsValue::from(-1600i16)).unwrap(), -1600.0);
+        assert_eq!(f64::try_from(&KvsValue::from(200u8)).unwrap(), 200.0);
+        assert_eq!(
+            u8::try_from(&KvsValue::from(-8i8)).unwrap_err(),
+            "KvsValue out of range of u8"
+        );
+        assert_eq!(
+            i16::try_from(&KvsValue::from(60000u16)).unwrap_err(),
+            "KvsValue out of range of i16"
+        );
+        assert_eq!(i8::try_from(&KvsValue::from(1.0)).unwrap_err(), "KvsValue is not a i8");
+    }
+
+    #[test]
+    fn test_small_int_get() {
+        let v = KvsValue::from(200u8);
+        assert_eq!(*v.get::<u8>().unwrap(), 200);
+        assert!(v.get::<u32>().is_none());
+    }
+
+    #[test]
+    fn test_f32_from_ok() {
+        let v = KvsValue::from(0.1f32);
+        assert!(matches!(v, KvsValue::F32(x) if x == 0.1f32));
+    }
+
+    #[test]
+    fn test_f32_tryfrom_ok() {
+        let v = KvsValue::from(0.1f32);
+        assert_eq!(f32::try_from(&v).unwrap(), 0.1f32);
+    }
+
+    #[test]
+    fn test_f32_tryfrom_invalid_type() {
+        let v = KvsValue::from(0.1f64);
+        let err = f32::try_from(&v).unwrap_err();
+        assert_eq!(err, "KvsValue is not a f32");
+    }
+
+    #[test]
+    fn test_f32_get_ok() {
+        let v = KvsValue::from(0.1f32);
+        assert_eq!(*v.get::<f32>().unwrap(), 0.1f32);
+        assert!(v.get::<f64>().is_none());
+    }
+
     #[test]
     fn test_f64_from_ok() {
         let v = KvsValue::from(1.23f64);
@@ -296,6 +787,15 @@ mod kvs_value_tests {
         let v = KvsValue::from(true);
         let err = f64::try_from(&v).unwrap_err();
         assert_eq!(err, "KvsValue is not a f64");
+        let err = f64::try_from(&KvsValue::from(1u64)).unwrap_err();
+        assert_eq!(err, "KvsValue is not a f64");
+    }
+
+    #[test]
+    fn test_f64_tryfrom_widening() {
+        assert_eq!(f64::try_from(&KvsValue::from(0.5f32)).unwrap(), 0.5);
+        assert_eq!(f64::try_from(&KvsValue::from(-3i32)).unwrap(), -3.0);
+        assert_eq!(f64::try_from(&KvsValue::from(u32::MAX)).unwrap(), f64::from(u32::MAX));
     }
 
     #[test]
@@ -495,4 +995,200 @@ mod kvs_value_tests {
         let v = KvsValue::from("");
         assert!(v.get::<KvsMap>().is_none());
     }
+
+    #[test]
+    fn test_value_type() {
+        assert_eq!(KvsValue::from(1i8).value_type(), KvsValueType::I8);
+        assert_eq!(KvsValue::from(1u8).value_type(), KvsValueType::U8);
+        assert_eq!(KvsValue::from(1i16).value_type(), KvsValueType::I16);
+        assert_eq!(KvsValue::from(1u16).value_type(), KvsValueType::U16);
+        assert_eq!(KvsValue::from(1i32).value_type(), KvsValueType::I32);
+        assert_eq!(KvsValue::from(1u32).value_type(), KvsValueType::U32);
+        assert_eq!(KvsValue::from(1i64).value_type(), KvsValueType::I64);
+        assert_eq!(KvsValue::from(1u64).value_type(), KvsValueType::U64);
+        assert_eq!(KvsValue::from(1.0f32).value_type(), KvsValueType::F32);
+        assert_eq!(KvsValue::from(1.0).value_type(), KvsValueType::F64);
+        assert_eq!(KvsValue::from(true).value_type(), KvsValueType::Boolean);
+        assert_eq!(KvsValue::from("a").value_type(), KvsValueType::String);
+        assert_eq!(KvsValue::from(()).value_type(), KvsValueType::Null);
+        assert_eq!(KvsValue::from(vec![]).value_type(), KvsValueType::Array);
+        assert_eq!(KvsValue::from(KvsMap::new()).value_type(), KvsValueType::Object);
+    }
+
+    #[test]
+    fn test_type_name() {
+        let values = [
+            (KvsValue::from(1i8), "I8"),
+            (KvsValue::from(1u8), "U8"),
+            (KvsValue::from(1i16), "I16"),
+            (KvsValue::from(1u16), "U16"),
+            (KvsValue::from(1i32), "I32"),
+            (KvsValue::from(1u32), "U32"),
+            (KvsValue::from(1i64), "I64"),
+            (KvsValue::from(1u64), "U64"),
+            (KvsValue::from(1.0f32), "F32"),
+            (KvsValue::from(1.0), "F64"),
+            (KvsValue::from(true), "Boolean"),
+            (KvsValue::from("a"), "String"),
+            (KvsValue::from(()), "Null"),
+            (KvsValue::from(vec![]), "Array"),
+            (KvsValue::from(KvsMap::new()), "Object"),
+        ];
+        for (value, name) in values {
+            assert_eq!(value.type_name(), name);
+            assert_eq!(KvsValueType::of(&value), value.value_type());
+            assert_eq!(KvsValueType::of(&value).name(), name);
+        }
+    }
+
+    fn get_nested_map() -> KvsMap {
+        KvsMap::from([
+            (
+                "config".to_string(),
+                KvsValue::from(KvsMap::from([
+                    (
+                        "server".to_string(),
+                        KvsValue::from(KvsMap::from([
+                            ("port".to_string(), KvsValue::from(8080)),
+                            ("host".to_string(), KvsValue::from("localhost")),
+                        ])),
+                    ),
+                    (
+                        "arr".to_string(),
+                        KvsValue::from(vec![KvsValue::from(true), KvsValue::from(vec![KvsValue::Null])]),
+                    ),
+                    ("empty".to_string(), KvsValue::from(KvsMap::new())),
+                ])),
+            ),
+            ("top".to_string(), KvsValue::from(1.5)),
+        ])
+    }
+
+    #[test]
+    fn test_compare_mixed_numeric() {
+        let mut values = vec![
+            KvsValue::F64(2.5),
+            KvsValue::U64(u64::MAX),
+            KvsValue::I8(-3),
+            KvsValue::F32(-3.5),
+            KvsValue::U8(2),
+            KvsValue::I64(i64::MIN),
+            KvsValue::F64(f64::NEG_INFINITY),
+            KvsValue::U16(3),
+            KvsValue::I32(0),
+            KvsValue::F64(f64::INFINITY),
+        ];
+        values.sort_by(|a, b| a.compare(b).unwrap());
+        assert_eq!(
+            values,
+            vec![
+                KvsValue::F64(f64::NEG_INFINITY),
+                KvsValue::I64(i64::MIN),
+                KvsValue::F32(-3.5),
+                KvsValue::I8(-3),
+                KvsValue::I32(0),
+                KvsValue::U8(2),
+                KvsValue::F64(2.5),
+                KvsValue::U16(3),
+                KvsValue::U64(u64::MAX),
+                KvsValue::F64(f64::INFINITY),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_numeric_exact() {
+        assert_eq!(KvsValue::I32(-1).compare(&KvsValue::F64(-1.0)), Some(Ordering::Equal));
+        assert_eq!(KvsValue::U32(7).compare(&KvsValue::I64(7)), Some(Ordering::Equal));
+        assert_eq!(KvsValue::I32(-1).compare(&KvsValue::F64(-0.5)), Some(Ordering::Less));
+        assert_eq!(KvsValue::F64(-1.5).compare(&KvsValue::I32(-1)), Some(Ordering::Less));
+
+        // Values not distinguishable after conversion of integer to `f64`.
+        let float = 9_007_199_254_740_992.0;
+        assert_eq!(
+            KvsValue::U64(9_007_199_254_740_993).compare(&KvsValue::F64(float)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            KvsValue::F64(float).compare(&KvsValue::U64(9_007_199_254_740_993)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            KvsValue::U64(u64::MAX).compare(&KvsValue::F64(1e30)),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn test_compare_non_numeric() {
+        assert_eq!(KvsValue::from("a").compare(&KvsValue::from("b")), Some(Ordering::Less));
+        assert_eq!(
+            KvsValue::from(true).compare(&KvsValue::from(false)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(KvsValue::Null.compare(&KvsValue::Null), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_compare_incomparable() {
+        assert_eq!(KvsValue::F64(f64::NAN).compare(&KvsValue::F64(1.0)), None);
+        assert_eq!(KvsValue::I32(1).compare(&KvsValue::F64(f64::NAN)), None);
+        assert_eq!(KvsValue::I32(1).compare(&KvsValue::from("1")), None);
+        assert_eq!(KvsValue::from(true).compare(&KvsValue::Null), None);
+        assert_eq!(KvsValue::from(vec![]).compare(&KvsValue::from(vec![])), None);
+        assert_eq!(
+            KvsValue::from(KvsMap::new()).compare(&KvsValue::from(KvsMap::new())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let flat = get_nested_map().flatten().unwrap();
+        assert_eq!(
+            flat,
+            KvsMap::from([
+                ("config.server.port".to_string(), KvsValue::from(8080)),
+                ("config.server.host".to_string(), KvsValue::from("localhost")),
+                ("config.arr.0".to_string(), KvsValue::from(true)),
+                ("config.arr.1.0".to_string(), KvsValue::Null),
+                ("config.empty".to_string(), KvsValue::from(KvsMap::new())),
+                ("top".to_string(), KvsValue::from(1.5)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_flatten_unflatten_round_trip() {
+        let nested = get_nested_map();
+        assert_eq!(nested.flatten().unwrap().unflatten().unwrap(), nested);
+    }
+
+    #[test]
+    fn test_flatten_dotted_key() {
+        let map = KvsMap::from([(
+            "a".to_string(),
+            KvsValue::from(KvsMap::from([("b.c".to_string(), KvsValue::from(1))])),
+        )]);
+        assert!(map.flatten().is_err_and(|e| e == ErrorCode::InvalidKey));
+    }
+
+    #[test]
+    fn test_unflatten_array_gap() {
+        let flat = KvsMap::from([
+            ("arr.0".to_string(), KvsValue::from(1)),
+            ("arr.2".to_string(), KvsValue::from(2)),
+        ]);
+        let nested = flat.unflatten().unwrap();
+        assert_eq!(nested["arr"].value_type(), KvsValueType::Object);
+    }
+
+    #[test]
+    fn test_unflatten_conflict() {
+        let flat = KvsMap::from([
+            ("a".to_string(), KvsValue::from(1)),
+            ("a.b".to_string(), KvsValue::from(2)),
+        ]);
+        assert!(flat.unflatten().is_err_and(|e| e == ErrorCode::InvalidKey));
+    }
 }
@@ -0,0 +1,293 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+//! `serde` support for `KvsValue`.
+//!
+//! `KvsValue` is mapped to natural JSON representation, which is distinct from the t-tagged
+//! storage format used by `JsonBackend`. Type information of integers is not stored, therefore
+//! integers are restored as the smallest fitting type out of `I32`, `I64` and `U64`.
+
+use crate::error_code::ErrorCode;
+use crate::kvs_value::{KvsMap, KvsValue};
+use core::fmt;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Create `KvsValue` from signed integer, using `I32` if value fits.
+fn from_i64(value: i64) -> KvsValue {
+    match i32::try_from(value) {
+        Ok(v) => KvsValue::I32(v),
+        Err(_) => KvsValue::I64(value),
+    }
+}
+
+/// Create `KvsValue` from unsigned integer, using `I32` or `I64` if value fits.
+fn from_u64(value: u64) -> KvsValue {
+    match i64::try_from(value) {
+        Ok(v) => from_i64(v),
+        Err(_) => KvsValue::U64(value),
+    }
+}
+
+impl Serialize for KvsValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            KvsValue::I8(v) => serializer.serialize_i8(*v),
+            KvsValue::U8(v) => serializer.serialize_u8(*v),
+            KvsValue::I16(v) => serializer.serialize_i16(*v),
+            KvsValue::U16(v) => serializer.serialize_u16(*v),
+            KvsValue::I32(v) => serializer.serialize_i32(*v),
+            KvsValue::U32(v) => serializer.serialize_u32(*v),
+            KvsValue::I64(v) => serializer.serialize_i64(*v),
+            KvsValue::U64(v) => serializer.serialize_u64(*v),
+            KvsValue::F32(v) => serializer.serialize_f32(*v),
+            KvsValue::F64(v) => serializer.serialize_f64(*v),
+            KvsValue::Boolean(v) => serializer.serialize_bool(*v),
+            KvsValue::String(v) => serializer.serialize_str(v),
+            KvsValue::Null => serializer.serialize_unit(),
+            KvsValue::Array(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            },
+            KvsValue::Object(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+        }
+    }
+}
+
+/// Visitor creating `KvsValue` from any self-describing format.
+struct KvsValueVisitor;
+
+impl<'de> Visitor<'de> for KvsValueVisitor {
+    type Value = KvsValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid KVS value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<KvsValue, E> {
+        Ok(KvsValue::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<KvsValue, E> {
+        Ok(from_i64(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<KvsValue, E> {
+        Ok(from_u64(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<KvsValue, E> {
+        Ok(KvsValue::F64(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<KvsValue, E> {
+        Ok(KvsValue::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<KvsValue, E> {
+        Ok(KvsValue::String(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<KvsValue, E> {
+        Ok(KvsValue::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<KvsValue, E> {
+        Ok(KvsValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<KvsValue, D::Error> {
+        KvsValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KvsValue, A::Error> {
+        let mut array = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element()? {
+            array.push(element);
+        }
+        Ok(KvsValue::Array(array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KvsValue, A::Error> {
+        let mut object = KvsMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((key, value)) = map.next_entry()? {
+            object.insert(key, value);
+        }
+        Ok(KvsValue::Object(object))
+    }
+}
+
+impl<'de> Deserialize<'de> for KvsValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KvsValueVisitor)
+    }
+}
+
+impl From<serde_json::Value> for KvsValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => KvsValue::Null,
+            serde_json::Value::Bool(v) => KvsValue::Boolean(v),
+            serde_json::Value::Number(v) => {
+                if let Some(n) = v.as_i64() {
+                    from_i64(n)
+                } else if let Some(n) = v.as_u64() {
+                    from_u64(n)
+                } else {
+                    // Numbers which are not integers are always representable as `f64`.
+                    KvsValue::F64(v.as_f64().unwrap_or(f64::NAN))
+                }
+            },
+            serde_json::Value::String(v) => KvsValue::String(v),
+            serde_json::Value::Array(v) => KvsValue::Array(v.into_iter().map(KvsValue::from).collect()),
+            serde_json::Value::Object(v) => {
+                KvsValue::Object(v.into_iter().map(|(k, v)| (k, KvsValue::from(v))).collect())
+            },
+        }
+    }
+}
+
+impl TryFrom<KvsValue> for serde_json::Value {
+    type Error = ErrorCode;
+
+    fn try_from(value: KvsValue) -> Result<Self, Self::Error> {
+        Ok(match value {
+            KvsValue::I8(v) => serde_json::Value::from(v),
+            KvsValue::U8(v) => serde_json::Value::from(v),
+            KvsValue::I16(v) => serde_json::Value::from(v),
+            KvsValue::U16(v) => serde_json::Value::from(v),
+            KvsValue::I32(v) => serde_json::Value::from(v),
+            KvsValue::U32(v) => serde_json::Value::from(v),
+            KvsValue::I64(v) => serde_json::Value::from(v),
+            KvsValue::U64(v) => serde_json::Value::from(v),
+            KvsValue::F32(v) => match serde_json::Number::from_f64(v as f64) {
+                Some(n) => serde_json::Value::Number(n),
+                None => {
+                    eprintln!("error: non-finite number cannot be represented in JSON: {v}");
+                    return Err(ErrorCode::ConversionFailed);
+                },
+            },
+            KvsValue::F64(v) => match serde_json::Number::from_f64(v) {
+                Some(n) => serde_json::Value::Number(n),
+                None => {
+                    eprintln!("error: non-finite number cannot be represented in JSON: {v}");
+                    return Err(ErrorCode::ConversionFailed);
+                },
+            },
+            KvsValue::Boolean(v) => serde_json::Value::Bool(v),
+            KvsValue::String(v) => serde_json::Value::String(v),
+            KvsValue::Null => serde_json::Value::Null,
+            KvsValue::Array(v) => serde_json::Value::Array(
+                v.into_iter()
+                    .map(serde_json::Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            KvsValue::Object(v) => serde_json::Value::Object(
+                v.into_iter()
+                    .map(|(k, v)| Ok((k, serde_json::Value::try_from(v)?)))
+                    .collect::<Result<_, ErrorCode>>()?,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod kvs_value_serde_tests {
+    use crate::error_code::ErrorCode;
+    use crate::kvs_value::{KvsMap, KvsValue};
+
+    fn nested_value() -> KvsValue {
+        KvsValue::from(KvsMap::from([
+            ("i32".to_string(), KvsValue::I32(-321)),
+            ("i64".to_string(), KvsValue::I64(-1 << 40)),
+            ("u64".to_string(), KvsValue::U64(u64::MAX)),
+            ("f64".to_string(), KvsValue::F64(1.5)),
+            ("bool".to_string(), KvsValue::Boolean(true)),
+            ("str".to_string(), KvsValue::String("text".to_string())),
+            ("null".to_string(), KvsValue::Null),
+            (
+                "arr".to_string(),
+                KvsValue::Array(vec![
+                    KvsValue::I32(1),
+                    KvsValue::Array(vec![KvsValue::String("inner".to_string()), KvsValue::Null]),
+                    KvsValue::from(KvsMap::from([("k".to_string(), KvsValue::Boolean(false))])),
+                ]),
+            ),
+            (
+                "obj".to_string(),
+                KvsValue::from(KvsMap::from([(
+                    "sub".to_string(),
+                    KvsValue::from(KvsMap::from([("deep".to_string(), KvsValue::I32(7))])),
+                )])),
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_serialize_natural_json() {
+        let value = KvsValue::Array(vec![
+            KvsValue::I32(1),
+            KvsValue::String("a".to_string()),
+            KvsValue::Null,
+        ]);
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[1,\"a\",null]");
+    }
+
+    #[test]
+    fn test_round_trip_string() {
+        let value = nested_value();
+        let json_str = serde_json::to_string(&value).unwrap();
+        let result: KvsValue = serde_json::from_str(&json_str).unwrap();
+        assert_eq!(result, value);
+    }
+
+    #[test]
+    fn test_round_trip_json_value() {
+        let value = nested_value();
+        let json_value = serde_json::Value::try_from(value.clone()).unwrap();
+        assert_eq!(KvsValue::from(json_value), value);
+    }
+
+    #[test]
+    fn test_integer_types() {
+        let result: KvsValue = serde_json::from_str("[1, -1, 4294967296, 18446744073709551615]").unwrap();
+        assert_eq!(
+            result,
+            KvsValue::Array(vec![
+                KvsValue::I32(1),
+                KvsValue::I32(-1),
+                KvsValue::I64(4294967296),
+                KvsValue::U64(u64::MAX),
+            ])
+        );
+
+        // Type of `U32` is not preserved, value is.
+        let json_value = serde_json::Value::try_from(KvsValue::U32(5)).unwrap();
+        assert_eq!(KvsValue::from(json_value), KvsValue::I32(5));
+    }
+
+    #[test]
+    fn test_non_finite_float() {
+        let result = serde_json::Value::try_from(KvsValue::F64(f64::INFINITY));
+        assert!(result.is_err_and(|e| e == ErrorCode::ConversionFailed));
+    }
+}
@@ -16,11 +16,15 @@
 //!
 //! This crate provides a Key-Value-Store using [TinyJSON](https://crates.io/crates/tinyjson) to
 //! persist the data. To validate the stored data a hash is build and verified using the
-//! [Adler32](https://crates.io/crates/adler32) crate. No other direct dependencies are used
-//! besides the Rust `std` library.
+//! [Adler32](https://crates.io/crates/adler32) crate. Optional compression of the stored files
+//! is provided by the [flate2](https://crates.io/crates/flate2) crate and optional encryption
+//! of the stored files by the [aes-gcm](https://crates.io/crates/aes-gcm) crate. All four crates
+//! are mandatory dependencies. Further dependencies are only added by the optional cargo features
+//! listed below, besides that only the Rust `std` library is used.
 //!
 //! The key-value-storage is opened or initialized with [`KvsBuilder::new`] where various settings
-//! can be applied before the KVS instance is created.
+//! can be applied before the KVS instance is created. For the common case of JSON files in a
+//! directory with default settings, [`open`] can be used instead.
 //!
 //! All `TinyJSON` provided datatypes can be used:
 //!   * `Number`: `f64`
@@ -129,27 +133,54 @@
 //!     defines that `String` and `str` are always valid UTF-8.
 //!   * Feature `FEAT_REQ__KVS__supported_datatypes_values` is matched by using the same types that
 //!     the IPC will use for the Rust implementation.
+//!
+//! Optional cargo features:
+//!   * `serde`: `Serialize` and `Deserialize` for `KvsValue` using natural JSON representation,
+//!     together with conversions from and to `serde_json::Value`.
+//!   * `metrics`: Lock-free operation counters of KVS instances, read with `Kvs::metrics`.
+//!   * `tracing`: `tracing` spans around load, flush and snapshot restore of `JsonBackend`, with
+//!     instance ID, snapshot ID and byte count fields.
 #![forbid(unsafe_code)]
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
+pub mod append_log_backend;
+pub mod audit;
+pub mod clock;
+pub mod encrypted_backend;
 pub mod error_code;
 pub mod json_backend;
 pub mod kvs;
 pub mod kvs_api;
 pub mod kvs_backend;
 pub mod kvs_builder;
+#[cfg(feature = "metrics")]
+pub mod kvs_metrics;
 pub mod kvs_mock;
 pub mod kvs_serialize;
 pub mod kvs_value;
+#[cfg(feature = "serde")]
+pub mod kvs_value_serde;
+pub mod storage_layer;
+
+pub use crate::kvs_builder::open;
 
 /// Prelude module for convenient imports
 pub mod prelude {
+    pub use crate::append_log_backend::{AppendLogBackend, AppendLogBackendBuilder};
+    pub use crate::audit::{AuditEvent, AuditOperation, AuditSink, FileAuditSink};
+    pub use crate::clock::{Clock, MockClock, SystemClock};
+    pub use crate::encrypted_backend::{EncryptedBackend, EncryptedBackendBuilder};
     pub use crate::error_code::ErrorCode;
-    pub use crate::json_backend::{JsonBackend, JsonBackendBuilder};
-    pub use crate::kvs::Kvs;
-    pub use crate::kvs_api::{InstanceId, KvsApi, KvsDefaults, KvsLoad, SnapshotId};
-    pub use crate::kvs_backend::KvsBackend;
-    pub use crate::kvs_builder::KvsBuilder;
+    pub use crate::json_backend::{Compression, JsonBackend, JsonBackendBuilder, RotationStrategy, SyncPolicy};
+    pub use crate::kvs::{FlushHandle, Kvs, ReadOnlyKvs, WatchHandle};
+    pub use crate::kvs_api::{
+        DefaultsPrecedence, DefaultsSource, InstanceId, KvsApi, KvsDefaults, KvsLoad, MergePolicy, SnapshotId,
+    };
+    pub use crate::kvs_backend::{BackendCapabilities, ChunkedFlush, FlushProgress, KvsBackend};
+    pub use crate::kvs_builder::{open, KvsBuilder, KvsMigration};
+    #[cfg(feature = "metrics")]
+    pub use crate::kvs_metrics::KvsMetrics;
     pub use crate::kvs_serialize::{KvsDeserialize, KvsSerialize};
-    pub use crate::kvs_value::{KvsMap, KvsValue};
+    pub use crate::kvs_value::{KvsExpiryMap, KvsMap, KvsMapFlatten, KvsValue, KvsValueType};
+    pub use crate::storage_layer::{EncryptLayer, FileLayer, GzipLayer, StorageLayer};
 }
@@ -0,0 +1,294 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+use crate::error_code::ErrorCode;
+use crate::kvs_backend::DynEq;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+/// Length of AES-GCM nonce, stored in front of encrypted content.
+const NONCE_LEN: usize = 12;
+
+/// Raw file content storage used by `JsonBackend`.
+///
+/// Layers operate on bytes only and can wrap other layers, e.g. `GzipLayer` over `FileLayer`.
+/// Hash files are not passed through layers, hash is computed over content before it is passed
+/// to the layer.
+pub trait StorageLayer: DynEq + Sync + Send {
+    /// Read whole content.
+    ///
+    /// # Parameters
+    ///   * `path`: Path to read from
+    ///
+    /// # Return Values
+    ///   * Ok: Content
+    ///   * `ErrorCode::FileNotFound`: File not found
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn read(&self, path: &Path) -> Result<Vec<u8>, ErrorCode>;
+
+    /// Write whole content, replacing existing content.
+    ///
+    /// # Parameters
+    ///   * `path`: Path to write to
+    ///   * `bytes`: Content
+    ///
+    /// # Return Values
+    ///   * Ok: Content written
+    ///   * `ErrorCode::UnmappedError`: Unmapped error
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), ErrorCode>;
+
+    /// Open content for streamed reading.
+    /// Default implementation reads whole content with [`StorageLayer::read`].
+    fn reader(&self, path: &Path) -> Result<Box<dyn Read + '_>, ErrorCode> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+}
+
+/// Layer storing content in plain files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileLayer;
+
+impl StorageLayer for FileLayer {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, ErrorCode> {
+        Ok(fs::read(path)?)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), ErrorCode> {
+        Ok(fs::write(path, bytes)?)
+    }
+
+    fn reader(&self, path: &Path) -> Result<Box<dyn Read + '_>, ErrorCode> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+}
+
+/// Layer gzip-compressing content stored by inner layer.
+pub struct GzipLayer {
+    inner: Box<dyn StorageLayer>,
+}
+
+impl GzipLayer {
+    /// Create `GzipLayer`.
+    ///
+    /// # Parameters
+    ///   * `inner`: Layer storing compressed content
+    pub fn new(inner: Box<dyn StorageLayer>) -> Self {
+        Self { inner }
+    }
+}
+
+impl PartialEq for GzipLayer {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.dyn_eq(other.inner.as_any())
+    }
+}
+
+impl StorageLayer for GzipLayer {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, ErrorCode> {
+        let mut bytes = Vec::new();
+        self.reader(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), ErrorCode> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes)?;
+        self.inner.write(path, &encoder.finish()?)
+    }
+
+    fn reader(&self, path: &Path) -> Result<Box<dyn Read + '_>, ErrorCode> {
+        Ok(Box::new(GzDecoder::new(self.inner.reader(path)?)))
+    }
+}
+
+/// Layer encrypting content stored by inner layer with AES-256-GCM.
+pub struct EncryptLayer {
+    inner: Box<dyn StorageLayer>,
+    key: [u8; 32],
+}
+
+impl EncryptLayer {
+    /// Create `EncryptLayer`.
+    ///
+    /// # Parameters
+    ///   * `inner`: Layer storing encrypted content
+    ///   * `key`: AES-256 key
+    pub fn new(inner: Box<dyn StorageLayer>, key: [u8; 32]) -> Self {
+        Self { inner, key }
+    }
+}
+
+impl PartialEq for EncryptLayer {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.inner.dyn_eq(other.inner.as_any())
+    }
+}
+
+impl StorageLayer for EncryptLayer {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, ErrorCode> {
+        decrypt(&self.key, &self.inner.read(path)?)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), ErrorCode> {
+        self.inner.write(path, &encrypt(&self.key, bytes)?)
+    }
+}
+
+fn cipher(key: &[u8; 32]) -> Result<Aes256Gcm, ErrorCode> {
+    Aes256Gcm::new_from_slice(key).map_err(|_| ErrorCode::EncryptionFailed)
+}
+
+/// Encrypt content, random nonce is prepended to the ciphertext.
+pub(crate) fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+    let cipher = cipher(key)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext).map_err(|_| {
+        eprintln!("error: encryption failed");
+        ErrorCode::EncryptionFailed
+    })?;
+
+    let mut content = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    content.extend_from_slice(nonce.as_slice());
+    content.extend_from_slice(&ciphertext);
+    Ok(content)
+}
+
+/// Decrypt content produced by `encrypt`.
+pub(crate) fn decrypt(key: &[u8; 32], content: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+    if content.len() < NONCE_LEN {
+        eprintln!("error: encrypted content too short");
+        return Err(ErrorCode::EncryptionFailed);
+    }
+
+    let (nonce, ciphertext) = content.split_at(NONCE_LEN);
+    cipher(key)?.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| {
+        eprintln!("error: decryption failed");
+        ErrorCode::EncryptionFailed
+    })
+}
+
+#[cfg(test)]
+mod storage_layer_tests {
+    use crate::error_code::ErrorCode;
+    use crate::storage_layer::{EncryptLayer, FileLayer, GzipLayer, StorageLayer};
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    const KEY: [u8; 32] = [0x42; 32];
+    const CONTENT: &[u8] = b"{\"key\":{\"t\":\"str\",\"v\":\"secret_value\"}}";
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_file_layer_ok() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+
+        FileLayer.write(&path, CONTENT).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), CONTENT);
+        assert_eq!(FileLayer.read(&path).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_file_layer_not_found() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+
+        assert!(FileLayer.read(&path).is_err_and(|e| e == ErrorCode::FileNotFound));
+        assert!(FileLayer.reader(&path).is_err_and(|e| e == ErrorCode::FileNotFound));
+    }
+
+    #[test]
+    fn test_gzip_layer_ok() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json.gz");
+        let layer = GzipLayer::new(Box::new(FileLayer));
+
+        layer.write(&path, CONTENT).unwrap();
+
+        // Check gzip magic bytes.
+        let stored = std::fs::read(&path).unwrap();
+        assert_eq!(stored[..2], [0x1f, 0x8b]);
+        assert_eq!(layer.read(&path).unwrap(), CONTENT);
+
+        let mut streamed = Vec::new();
+        layer.reader(&path).unwrap().read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, CONTENT);
+    }
+
+    #[test]
+    fn test_encrypt_layer_ok() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+        let layer = EncryptLayer::new(Box::new(FileLayer), KEY);
+
+        layer.write(&path, CONTENT).unwrap();
+
+        assert!(!contains(&std::fs::read(&path).unwrap(), b"secret_value"));
+        assert_eq!(layer.read(&path).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_encrypt_layer_wrong_key() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+        EncryptLayer::new(Box::new(FileLayer), KEY)
+            .write(&path, CONTENT)
+            .unwrap();
+
+        let layer = EncryptLayer::new(Box::new(FileLayer), [0x24; 32]);
+        assert!(layer.read(&path).is_err_and(|e| e == ErrorCode::EncryptionFailed));
+    }
+
+    #[test]
+    fn test_encrypt_layer_content_too_short() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+        FileLayer.write(&path, &[0x00; 4]).unwrap();
+
+        let layer = EncryptLayer::new(Box::new(FileLayer), KEY);
+        assert!(layer.read(&path).is_err_and(|e| e == ErrorCode::EncryptionFailed));
+    }
+
+    #[test]
+    fn test_gzip_over_encrypt_ok() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.json");
+        let layer = GzipLayer::new(Box::new(EncryptLayer::new(Box::new(FileLayer), KEY)));
+
+        layer.write(&path, CONTENT).unwrap();
+
+        let stored = std::fs::read(&path).unwrap();
+        assert!(!contains(&stored, b"secret_value"));
+        assert_ne!(stored[..2], [0x1f, 0x8b]);
+        assert_eq!(layer.read(&path).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_eq() {
+        let gzip = GzipLayer::new(Box::new(FileLayer));
+        assert!(gzip == GzipLayer::new(Box::new(FileLayer)));
+        assert!(gzip != GzipLayer::new(Box::new(GzipLayer::new(Box::new(FileLayer)))));
+
+        let encrypt = EncryptLayer::new(Box::new(FileLayer), KEY);
+        assert!(encrypt == EncryptLayer::new(Box::new(FileLayer), KEY));
+        assert!(encrypt != EncryptLayer::new(Box::new(FileLayer), [0x24; 32]));
+    }
+}
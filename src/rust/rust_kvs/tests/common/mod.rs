@@ -28,10 +28,15 @@ use rust_kvs::kvs_value::KvsValue;
 ///   * `true` if provided values are same.
 pub fn compare_kvs_values(left: &KvsValue, right: &KvsValue) -> bool {
     match (left, right) {
+        (KvsValue::I8(l), KvsValue::I8(r)) => l == r,
+        (KvsValue::U8(l), KvsValue::U8(r)) => l == r,
+        (KvsValue::I16(l), KvsValue::I16(r)) => l == r,
+        (KvsValue::U16(l), KvsValue::U16(r)) => l == r,
         (KvsValue::I32(l), KvsValue::I32(r)) => l == r,
         (KvsValue::U32(l), KvsValue::U32(r)) => l == r,
         (KvsValue::I64(l), KvsValue::I64(r)) => l == r,
         (KvsValue::U64(l), KvsValue::U64(r)) => l == r,
+        (KvsValue::F32(l), KvsValue::F32(r)) => l == r,
         (KvsValue::F64(l), KvsValue::F64(r)) => l == r,
         (KvsValue::Boolean(l), KvsValue::Boolean(r)) => l == r,
         (KvsValue::String(l), KvsValue::String(r)) => l == r,
@@ -0,0 +1,68 @@
+// *******************************************************************************
+// Copyright (c) 2026 Contributors to the Eclipse Foundation
+//
+// See the NOTICE file(s) distributed with this work for additional
+// information regarding copyright ownership.
+//
+// This program and the accompanying materials are made available under the
+// terms of the Apache License Version 2.0 which is available at
+// <https://www.apache.org/licenses/LICENSE-2.0>
+//
+// SPDX-License-Identifier: Apache-2.0
+// *******************************************************************************
+//! Allocation count of JSON serialization.
+//!
+//! Counting allocator is installed for the whole test binary, so this file must contain a single
+//! test. Library forbids unsafe code, so allocator cannot be placed in unit tests.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rust_kvs::prelude::*;
+use std::alloc::System;
+use tempfile::tempdir;
+
+/// System allocator counting allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_flush_allocations() {
+    const ENTRIES: usize = 10_000;
+
+    let dir = tempdir().unwrap();
+    let backend = JsonBackendBuilder::new().working_dir(dir.path().to_path_buf()).build();
+    let kvs_map: KvsMap = (0..ENTRIES)
+        .map(|i| (format!("key{i}"), KvsValue::from(i as i32)))
+        .collect();
+
+    // First flush creates files and checks snapshots, it is not measured.
+    backend.flush(InstanceId(1), &kvs_map).unwrap();
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    backend.flush(InstanceId(1), &kvs_map).unwrap();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    // Scalars and keys were stringified into temporary strings, ~15 allocations per entry.
+    // Now they are generated directly into output buffer, ~9 allocations per entry remain,
+    // mostly owned keys of TinyJSON objects.
+    assert!(
+        allocations < 10 * ENTRIES,
+        "{allocations} allocations for {ENTRIES} entries"
+    );
+    assert_eq!(backend.load_kvs(InstanceId(1), SnapshotId(0)).unwrap(), kvs_map);
+}
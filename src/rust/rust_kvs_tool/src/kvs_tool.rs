@@ -25,9 +25,9 @@
 //!
 //!    Options:
 //!    -h, --help          Show this help message and exit
-//!    -o, --operation     Specify the operation to perform (setkey, getkey, removekey, listkeys, reset, snapshotcount, snapshotmaxcount, snapshotrestore, getkvsfilename, gethashfilename, createtestdata)
+//!    -o, --operation     Specify the operation to perform (setkey, getkey, removekey, listkeys, reset, snapshotcount, snapshotmaxcount, snapshotrestore, verify, getkvsfilename, gethashfilename, savedefaults, createtestdata)
 //!    -k, --key           Specify the key to operate on (for key operations)
-//!    -p, --payload       Specify the value to write (for set operations)
+//!    -p, --payload       Specify the value to write (for set and savedefaults operations)
 //!    -s, --snapshotid    Specify the snapshot ID for Snapshot operations
 //!    -d, --directory     Specify the directory of the Key-Files (default is current directory)
 //!
@@ -60,12 +60,18 @@
 //!    Snapshot Restore:
 //!        kvs_tool -o snapshotrestore -s 1
 //!
+//!    Verify Snapshots:
+//!        kvs_tool -o verify
+//!
 //!    Get KVS Filename:
 //!        kvs_tool -o getkvsfilename -s 1
 //!
 //!    Get Hash Filename:
 //!        kvs_tool -o gethashfilename -s 1
 //!
+//!    Save Defaults (replaces defaults file and its hash):
+//!        kvs_tool -o savedefaults -p '{"MyKey":15,"OtherKey":"Hello World"}'
+//!
 //!    ---------------------------------------
 //!
 //!    Create Test Data:
@@ -90,8 +96,10 @@ enum OperationMode {
     SnapshotCount,
     SnapshotMaxCount,
     SnapshotRestore,
+    Verify,
     GetKvsFilename,
     GetHashFilename,
+    SaveDefaults,
     CreateTestData,
 }
 
@@ -304,13 +312,30 @@ fn _snapshotmaxcount(kvs: Kvs) -> Result<(), ErrorCode> {
     Ok(())
 }
 
+/// Verifies integrity of all snapshots without loading them into the KVS.
+/// Fails if any snapshot is invalid.
+fn _verify(kvs: Kvs) -> Result<(), ErrorCode> {
+    println!("----------------------");
+    println!("Verify Snapshots");
+    let results = kvs.verify()?;
+    for (snapshot_id, valid) in &results {
+        println!("Snapshot {snapshot_id}: {}", if *valid { "valid" } else { "INVALID" });
+    }
+    println!("----------------------");
+    if results.iter().all(|(_, valid)| *valid) {
+        Ok(())
+    } else {
+        Err(ErrorCode::ValidationFailed)
+    }
+}
+
 /// Restores a snapshot in the KVS.
 /// It takes a snapshot ID as an argument and restores the KVS to that snapshot.
 fn _snapshotrestore(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
     println!("----------------------");
     println!("Snapshot Restore");
 
-    let snapshot_id: u32 = match args.opt_value_from_str("--snapshotid") {
+    let snapshot_id: SnapshotId = match args.opt_value_from_str("--snapshotid") {
         Ok(Some(val)) => val,
         Ok(None) | Err(_) => match args.opt_value_from_str("-s") {
             Ok(Some(val)) => val,
@@ -321,7 +346,6 @@ fn _snapshotrestore(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
         },
     };
     println!("Restore Snapshot {}", &snapshot_id);
-    let snapshot_id = SnapshotId(snapshot_id as usize);
     kvs.snapshot_restore(snapshot_id).map_err(|e| {
         eprintln!("KVS restore failed: {e:?}");
         e
@@ -332,9 +356,9 @@ fn _snapshotrestore(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
 }
 
 /// Take backend and downcast it to `JsonBackend`.
-fn _downcast_backend(kvs: &Kvs) -> Result<&JsonBackend, ErrorCode> {
-    match kvs.parameters().backend.as_any().downcast_ref() {
-        Some(backend) => Ok(backend),
+fn _downcast_backend(kvs: &Kvs) -> Result<JsonBackend, ErrorCode> {
+    match kvs.parameters().backend().as_any().downcast_ref::<JsonBackend>() {
+        Some(backend) => Ok(backend.clone()),
         None => {
             eprintln!("Invalid backend type");
             Err(ErrorCode::UnmappedError)
@@ -346,7 +370,7 @@ fn _downcast_backend(kvs: &Kvs) -> Result<&JsonBackend, ErrorCode> {
 fn _getkvsfilename(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
     println!("----------------------");
     println!("Get KVS Filename");
-    let snapshot_id: u32 = match args.opt_value_from_str("--snapshotid") {
+    let snapshot_id: SnapshotId = match args.opt_value_from_str("--snapshotid") {
         Ok(Some(val)) => val,
         Ok(None) | Err(_) => match args.opt_value_from_str("-s") {
             Ok(Some(val)) => val,
@@ -357,7 +381,6 @@ fn _getkvsfilename(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
         },
     };
     let instance_id = kvs.parameters().instance_id;
-    let snapshot_id = SnapshotId(snapshot_id as usize);
     let backend = _downcast_backend(&kvs)?;
     let filename = backend.kvs_file_path(instance_id, snapshot_id);
     println!("KVS Filename: {}", filename.display());
@@ -370,7 +393,7 @@ fn _gethashfilename(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
     println!("----------------------");
     println!("Get Hash Filename");
 
-    let snapshot_id: u32 = match args.opt_value_from_str("--snapshotid") {
+    let snapshot_id: SnapshotId = match args.opt_value_from_str("--snapshotid") {
         Ok(Some(val)) => val,
         Ok(None) | Err(_) => match args.opt_value_from_str("-s") {
             Ok(Some(val)) => val,
@@ -381,7 +404,6 @@ fn _gethashfilename(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
         },
     };
     let instance_id = kvs.parameters().instance_id;
-    let snapshot_id = SnapshotId(snapshot_id as usize);
     let backend = _downcast_backend(&kvs)?;
     let filename = backend.hash_file_path(instance_id, snapshot_id);
     println!("Hash Filename: {}", filename.display());
@@ -389,6 +411,45 @@ fn _gethashfilename(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
     Ok(())
 }
 
+/// Saves the defaults file from a JSON object payload.
+/// Defaults are used by KVS instances opened afterwards.
+fn _savedefaults(kvs: Kvs, mut args: Arguments) -> Result<(), ErrorCode> {
+    println!("----------------------");
+    println!("Save Defaults");
+
+    let value_str: String = match args.opt_value_from_str("-p") {
+        Ok(Some(val)) => val,
+        Ok(None) | Err(_) => match args.opt_value_from_str("--payload") {
+            Ok(Some(val)) => val,
+            _ => {
+                eprintln!("Error: Payload (-p or --payload) needs to be specified!");
+                return Err(ErrorCode::UnmappedError);
+            },
+        },
+    };
+    let defaults_map = match value_str.parse::<JsonValue>().map(|v| from_tinyjson(&v)) {
+        Ok(KvsValue::Object(map)) => map,
+        _ => {
+            eprintln!("Error: Payload needs to be a JSON object!");
+            return Err(ErrorCode::UnmappedError);
+        },
+    };
+
+    let instance_id = kvs.parameters().instance_id;
+    let backend = _downcast_backend(&kvs)?;
+    backend.save_defaults(instance_id, &defaults_map).map_err(|e| {
+        eprintln!("KVS save defaults failed: {e:?}");
+        e
+    })?;
+    println!(
+        "Saved {} default values to {}",
+        defaults_map.len(),
+        backend.defaults_file_path(instance_id).display()
+    );
+    println!("----------------------");
+    Ok(())
+}
+
 /// Creates test data in the KVS based on the example code from the KVS.
 fn _createtestdata(kvs: Kvs) -> Result<(), ErrorCode> {
     println!("----------------------");
@@ -460,9 +521,9 @@ fn main() -> Result<(), ErrorCode> {
         -h, --help          Show this help message and exit
         -o, --operation     Specify the operation to perform (setkey, getkey, removekey, 
                             listkeys, reset, snapshotcount, snapshotmaxcount, snapshotrestore, 
-                            getkvsfilename, gethashfilename, createtestdata)
+                            verify, getkvsfilename, gethashfilename, savedefaults, createtestdata)
         -k, --key           Specify the key to operate on (for key operations)
-        -p, --payload       Specify the value to write (for set operations)
+        -p, --payload       Specify the value to write (for set and savedefaults operations)
         -s, --snapshotid    Specify the snapshot ID for Snapshot operations
         -d, --directory     Specify the directory of the Key-Files (default is current directory)
 
@@ -496,12 +557,18 @@ fn main() -> Result<(), ErrorCode> {
         Snapshot Restore:
             kvs_tool -o snapshotrestore -s 1
 
+        Verify Snapshots:
+            kvs_tool -o verify
+
         Get KVS Filename:
             kvs_tool -o getkvsfilename -s 1
 
         Get Hash Filename:
             kvs_tool -o gethashfilename -s 1
 
+        Save Defaults (replaces defaults file and its hash):
+            kvs_tool -o savedefaults -p '{"MyKey":15,"OtherKey":"Hello World"}'
+
         ---------------------------------------
 
         Create Test Data:
@@ -561,8 +628,10 @@ fn main() -> Result<(), ErrorCode> {
             "snapshotcount" => OperationMode::SnapshotCount,
             "snapshotmaxcount" => OperationMode::SnapshotMaxCount,
             "snapshotrestore" => OperationMode::SnapshotRestore,
+            "verify" => OperationMode::Verify,
             "getkvsfilename" => OperationMode::GetKvsFilename,
             "gethashfilename" => OperationMode::GetHashFilename,
+            "savedefaults" => OperationMode::SaveDefaults,
             _ => OperationMode::Invalid,
         },
         None => OperationMode::Invalid,
@@ -601,6 +670,10 @@ fn main() -> Result<(), ErrorCode> {
             _snapshotrestore(kvs, args)?;
             Ok(())
         },
+        OperationMode::Verify => {
+            _verify(kvs)?;
+            Ok(())
+        },
         OperationMode::GetKvsFilename => {
             _getkvsfilename(kvs, args)?;
             Ok(())
@@ -609,6 +682,10 @@ fn main() -> Result<(), ErrorCode> {
             _gethashfilename(kvs, args)?;
             Ok(())
         },
+        OperationMode::SaveDefaults => {
+            _savedefaults(kvs, args)?;
+            Ok(())
+        },
         OperationMode::CreateTestData => {
             _createtestdata(kvs)?;
             Ok(())
@@ -57,10 +57,15 @@ impl Scenario for SupportedDatatypesValues {
     fn name(&self) -> &str {
         // Set scenario name based on provided value.
         match self.value {
+            KvsValue::I8(_) => "i8",
+            KvsValue::U8(_) => "u8",
+            KvsValue::I16(_) => "i16",
+            KvsValue::U16(_) => "u16",
             KvsValue::I32(_) => "i32",
             KvsValue::U32(_) => "u32",
             KvsValue::I64(_) => "i64",
             KvsValue::U64(_) => "u64",
+            KvsValue::F32(_) => "f32",
             KvsValue::F64(_) => "f64",
             KvsValue::Boolean(_) => "bool",
             KvsValue::String(_) => "str",
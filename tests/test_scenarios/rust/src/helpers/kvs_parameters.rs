@@ -82,6 +82,7 @@ where
             "ignored" => KvsLoad::Ignored,
             "optional" => KvsLoad::Optional,
             "required" => KvsLoad::Required,
+            "latest_valid" => KvsLoad::LatestValid,
             _ => return Err(de::Error::custom("Invalid \"kvs_load\" mode")),
         };
         return Ok(Some(value));